@@ -6,3 +6,4 @@ pub mod message;
 pub mod panel;
 pub mod room_member_list;
 
+pub mod room_settings;
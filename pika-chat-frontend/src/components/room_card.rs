@@ -1,18 +1,35 @@
 use yew::prelude::*;
-use crate::services::room::Room;
+use crate::services::room::{RoomListing, RoomVisibility};
 
 #[derive(Properties, PartialEq)]
 pub struct RoomProps {
-    pub room: Room,
+    pub listing: RoomListing,
 }
 
 #[function_component(RoomCard)]
 pub fn room_card(props: &RoomProps) -> Html {
+    let room = &props.listing.room;
     html! {
         <div class="room-card">
-            <h3 class="room-card-title">{&props.room.room_name}</h3>
-            <p class="room-card-detail">{format!("Room ID: {}", props.room.room_id)}</p>
-            <p class="room-card-detail">{format!("Owner ID: {}", props.room.user_id)}</p>
+            <h3 class="room-card-title">{&room.room_name}</h3>
+            if let Some(topic) = &room.topic {
+                <p class="room-card-detail">{topic}</p>
+            }
+            if room.visibility != RoomVisibility::Public {
+                <p class="room-card-detail">{room.visibility.label()}</p>
+            }
+            if room.archived_at.is_some() {
+                <p class="room-card-detail">{"Archived"}</p>
+            }
+            if props.listing.joined {
+                <p class="room-card-detail">{"Joined"}</p>
+            }
+            <p class="room-card-detail">
+                {format!("{} members, {} online", props.listing.member_count, props.listing.online_count)}
+            </p>
+            <p class="room-card-detail">{format!("Last active: {} UTC", props.listing.last_activity_at)}</p>
+            <p class="room-card-detail">{format!("Room ID: {}", room.room_id)}</p>
+            <p class="room-card-detail">{format!("Owner ID: {}", room.user_id)}</p>
         </div>
     }
 }
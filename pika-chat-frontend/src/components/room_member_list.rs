@@ -1,144 +1,134 @@
 use yew::prelude::*;
-use gloo::timers::callback::Interval;
-use wasm_bindgen_futures::spawn_local;
-use crate::services::room::{RoomMember, UserPresence, get_user_presence};
+use web_sys::HtmlSelectElement;
+use crate::services::room::{Role, RoomMember, UserPresence};
 
 #[derive(Properties, PartialEq)]
 pub struct RoomMembersListProps {
     pub members: Vec<RoomMember>,
-    pub room_id: i64,
-    pub token: Option<String>,
+    pub presence: Vec<UserPresence>, // Kept up to date by the chat room's WebSocket
+    #[prop_or_default]
+    pub my_role: Option<Role>,       // Role of the current user, which decides who they can promote or demote
+    #[prop_or_default]
+    pub on_change_role: Callback<(i64, Role)>,
+    #[prop_or_default]
+    pub on_kick: Callback<i64>,
+    #[prop_or_default]
+    pub on_ban: Callback<String>,    // Username of the member to ban
+    #[prop_or_default]
+    pub on_mute: Callback<i64>,
+    #[prop_or_default]
+    pub on_transfer: Callback<i64>,  // ID of the member to make the owner
 }
 
-#[allow(dead_code)]
-pub enum Msg {
-    FetchUserPresence,
-    FetchUserPresenceSuccess(Vec<UserPresence>),
-    FetchUserPresenceError(String),
-}
-
-pub struct RoomMembersList {
-    user_presence: Vec<UserPresence>,
-    fetch_presence_error: Option<String>,
-    fetch_presence_interval: Option<Interval>,
-}
+#[function_component(RoomMembersList)]
+pub fn room_members_list(props: &RoomMembersListProps) -> Html {
+    html! {
+        <ul style="list-style-type: none; padding: 0; margin: 0;">
+            {
+                for props.members.iter().map(|member| {
+                    let presence = props.presence.iter().find(|presence| presence.user_id == member.user_id);
+                    let status = match presence {
+                        Some(p) if p.in_room => ("In room", "#10B981"), // Green for connected to this room
+                        Some(p) if p.is_online => ("Online", "#F59E0B"), // Amber for connected elsewhere
+                        _ => ("Offline", "#EF4444"), // Red for offline or not found
+                    };
 
-impl Component for RoomMembersList {
-    type Message = Msg;
-    type Properties = RoomMembersListProps;
-
-    fn create(ctx: &Context<Self>) -> Self {
-        let room_id = ctx.props().room_id;
-        let token = ctx.props().token.clone();
-        let link = ctx.link().clone();
-
-        let fetch_presence_interval = Some(Interval::new(1000, move || {
-            if let Some(token) = token.clone() {
-                let link = link.clone(); // Clone the link inside the closure
-                spawn_local(async move {
-                    gloo::console::log!("Fetching user presence...");
-                    match get_user_presence(&token, room_id).await {
-                        Ok(presence) => {
-                            gloo::console::log!(format!("User presence fetched: {:?}", presence));
-                            link.send_message(Msg::FetchUserPresenceSuccess(presence));
-                        }
-                        Err(err) => {
-                            gloo::console::log!(format!("Error fetching user presence: {:?}", err));
-                            link.send_message(Msg::FetchUserPresenceError(err));
+                    // Admins and owners can move members ranked below them to any role below their own
+                    let manager_role = props
+                        .my_role
+                        .filter(|role| role.can_manage_roles() && role.outranks(member.role));
+                    let role_view = match manager_role {
+                        Some(my_role) => {
+                            let user_id = member.user_id;
+                            let onchange = props.on_change_role.reform(move |e: Event| {
+                                let select: HtmlSelectElement = e.target_unchecked_into();
+                                let role = Role::ASSIGNABLE[select.selected_index().max(0) as usize];
+                                (user_id, role)
+                            });
+                            html! {
+                                <select {onchange} style="font-size: 0.75rem; margin-left: 0.5rem;">
+                                    {
+                                        for Role::ASSIGNABLE.iter().map(|&role| html! {
+                                            <option selected={role == member.role} disabled={!my_role.outranks(role)}>
+                                                { role.label() }
+                                            </option>
+                                        })
+                                    }
+                                </select>
+                            }
                         }
-                    }
-                });
-            } else {
-                gloo::console::log!("No token available for fetching user presence.");
-            }
-        }));
-
-        Self {
-            user_presence: vec![],
-            fetch_presence_error: None,
-            fetch_presence_interval,
-        }
-    }
-
-    fn update(&mut self, _ctx: &Context<Self>, msg: Self::Message) -> bool {
-        match msg {
-            Msg::FetchUserPresenceSuccess(presence) => {
-                self.user_presence = presence;
-                true // Re-render with updated presence
-            }
-            Msg::FetchUserPresenceError(err) => {
-                self.fetch_presence_error = Some(err);
-                true // Re-render to display the error
-            }
-            Msg::FetchUserPresence => {
-                false // No state changes to trigger a re-render
-            }
-        }
-    }
+                        None if member.role != Role::Member => html! {
+                            <span style="font-size: 0.75rem; color: #6b7280; margin-left: 0.5rem;">
+                                { member.role.label() }
+                            </span>
+                        },
+                        None => html! {},
+                    };
 
-    fn destroy(&mut self, _ctx: &Context<Self>) {
-        if let Some(interval) = self.fetch_presence_interval.take() {
-            interval.cancel(); // Stop the periodic fetch
-        }
-    }
-
-    fn view(&self, ctx: &Context<Self>) -> Html {
-        html! {
-            <ul style="list-style-type: none; padding: 0; margin: 0;">
-                {
-                    for ctx.props().members.iter().map(|member| {
-                        let presence = self.user_presence.iter().find(|presence| presence.user_id == member.user_id);
-                        let status = match presence {
-                            Some(p) if p.is_online => ("Online", "#10B981"), // Green for online
-                            _ => ("Offline", "#EF4444"), // Red for offline or not found
-                        };
-    
+                    // Moderators and above can mute, kick or ban members ranked below them
+                    let removal_view = if props.my_role.is_some_and(|role| role.can_remove_members() && role.outranks(member.role)) {
+                        let user_id = member.user_id;
+                        let username = member.username.clone();
+                        let on_mute = props.on_mute.reform(move |_: MouseEvent| user_id);
+                        let on_kick = props.on_kick.reform(move |_: MouseEvent| user_id);
+                        let on_ban = props.on_ban.reform(move |_: MouseEvent| username.clone());
                         html! {
-                            <li style="
-                                padding: 0.75rem 0; 
-                                border-bottom: 1px solid #e5e7eb; 
-                                display: flex; 
-                                align-items: center;
-                            ">
-                                // Avatar image
-                                <img 
-                                    src={member.avatar_url.clone()} 
-                                    alt="User Avatar" 
-                                    style="width: 40px; height: 40px; border-radius: 50%; margin-right: 0.75rem;" 
-                                />
-                                // Username and status
-                                <div style="flex: 1;">
-                                    <span style="
-                                        font-size: 1rem; 
-                                        font-weight: 500; 
-                                        color: #374151;
-                                    ">
-                                        { &member.username }
-                                    </span>
-                                    <span style={format!("font-size: 0.875rem; color: {}; margin-left: 0.5rem;", status.1)}>
-                                        { status.0 }
-                                    </span>
-                                </div>
-                            </li>
+                            <>
+                                <button onclick={on_mute} style="font-size: 0.75rem; margin-left: 0.5rem;">{"Mute"}</button>
+                                <button onclick={on_kick} style="font-size: 0.75rem; margin-left: 0.25rem;">{"Kick"}</button>
+                                <button onclick={on_ban} style="font-size: 0.75rem; margin-left: 0.25rem; color: #ef4444;">{"Ban"}</button>
+                            </>
                         }
-                    })
-                }
-                {
-                    if let Some(error) = &self.fetch_presence_error {
+                    } else {
+                        html! {}
+                    };
+
+                    // The owner can hand the room over to any other member
+                    let transfer_view = if props.my_role == Some(Role::Owner) && member.role != Role::Owner {
+                        let user_id = member.user_id;
+                        let on_transfer = props.on_transfer.reform(move |_: MouseEvent| user_id);
                         html! {
-                            <li style="
-                                padding: 0.75rem; 
-                                color: #EF4444;
-                                font-size: 0.875rem;
-                            ">
-                                { format!("Error fetching user presence: {}", error) }
-                            </li>
+                            <button onclick={on_transfer} style="font-size: 0.75rem; margin-left: 0.25rem;">{"Make owner"}</button>
                         }
                     } else {
                         html! {}
+                    };
+
+                    let joined = member.joined_at.as_ref().map(|joined_at| format!("Joined {} UTC", joined_at));
+
+                    html! {
+                        <li title={joined} style="
+                            padding: 0.75rem 0; 
+                            border-bottom: 1px solid #e5e7eb; 
+                            display: flex; 
+                            align-items: center;
+                        ">
+                            // Avatar image
+                            <img 
+                                src={member.avatar_url.clone()} 
+                                alt="User Avatar" 
+                                style="width: 40px; height: 40px; border-radius: 50%; margin-right: 0.75rem;" 
+                            />
+                            // Username and status
+                            <div style="flex: 1;">
+                                <span style="
+                                    font-size: 1rem; 
+                                    font-weight: 500; 
+                                    color: #374151;
+                                ">
+                                    { &member.username }
+                                </span>
+                                <span style={format!("font-size: 0.875rem; color: {}; margin-left: 0.5rem;", status.1)}>
+                                    { status.0 }
+                                </span>
+                                { role_view }
+                                { removal_view }
+                                { transfer_view }
+                            </div>
+                        </li>
                     }
-                }
-            </ul>
-        }
-    }    
+                })
+            }
+        </ul>
+    }
 }
@@ -0,0 +1,78 @@
+use yew::prelude::*;
+use web_sys::HtmlInputElement;
+use crate::services::room::{Room, UpdateRoomRequest};
+
+#[derive(Properties, PartialEq)]
+pub struct RoomSettingsProps {
+    pub room: Room,
+    #[prop_or_default]
+    pub can_delete: bool,                      // Only the owner may delete the room
+    pub on_update: Callback<UpdateRoomRequest>,
+    #[prop_or_default]
+    pub on_delete: Callback<()>,
+}
+
+// Form for admins and the owner to rename the room, set its topic and description, archive or delete it
+#[function_component(RoomSettings)]
+pub fn room_settings(props: &RoomSettingsProps) -> Html {
+    let room_name = use_state(|| props.room.room_name.clone());
+    let topic = use_state(|| props.room.topic.clone().unwrap_or_default());
+    let description = use_state(|| props.room.description.clone().unwrap_or_default());
+
+    // Start over from the saved values whenever the room changes, e.g. after a `room_updated` event
+    {
+        let (room_name, topic, description) = (room_name.clone(), topic.clone(), description.clone());
+        use_effect_with_deps(
+            move |room: &Room| {
+                room_name.set(room.room_name.clone());
+                topic.set(room.topic.clone().unwrap_or_default());
+                description.set(room.description.clone().unwrap_or_default());
+                || ()
+            },
+            props.room.clone(),
+        );
+    }
+
+    let bind = |state: &UseStateHandle<String>| {
+        let state = state.clone();
+        Callback::from(move |e: InputEvent| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            state.set(input.value());
+        })
+    };
+    let on_save = {
+        let (room_name, topic, description) = (room_name.clone(), topic.clone(), description.clone());
+        props.on_update.reform(move |_: MouseEvent| UpdateRoomRequest {
+            room_name: Some((*room_name).clone()),
+            topic: Some((*topic).clone()),
+            description: Some((*description).clone()),
+            archived: None,
+        })
+    };
+    let archived = props.room.archived_at.is_some();
+    let on_archive = props.on_update.reform(move |_: MouseEvent| UpdateRoomRequest {
+        archived: Some(!archived),
+        ..UpdateRoomRequest::default()
+    });
+    let on_delete = props.on_delete.reform(|_: MouseEvent| ());
+    let input_style = "width: 100%; padding: 0.5rem; margin-bottom: 0.5rem; border: 1px solid #d1d5db; border-radius: 0.5rem;";
+    let button_style = "padding: 0.5rem 1rem; margin-right: 0.5rem; color: white; border: none; border-radius: 0.5rem; cursor: pointer;";
+
+    html! {
+        <div style="margin-top: 1.5rem; text-align: left;">
+            <h3 style="font-size: 1.125rem; font-weight: bold; margin-bottom: 0.5rem; color: #1f2937;">{"Room Settings"}</h3>
+            <input type="text" value={(*room_name).clone()} oninput={bind(&room_name)} placeholder="Room name" style={input_style} />
+            <input type="text" value={(*topic).clone()} oninput={bind(&topic)} placeholder="Topic" style={input_style} />
+            <input type="text" value={(*description).clone()} oninput={bind(&description)} placeholder="Description" style={input_style} />
+            <div>
+                <button onclick={on_save} style={format!("{} background-color: #3b82f6;", button_style)}>{"Save"}</button>
+                <button onclick={on_archive} style={format!("{} background-color: #6b7280;", button_style)}>
+                    { if archived { "Unarchive" } else { "Archive" } }
+                </button>
+                if props.can_delete {
+                    <button onclick={on_delete} style={format!("{} background-color: #ef4444;", button_style)}>{"Delete"}</button>
+                }
+            </div>
+        </div>
+    }
+}
@@ -3,7 +3,6 @@ mod routes;
 mod pages;
 mod components;
 mod services;
-use wasm_logger;
 
 fn main() {
     wasm_logger::init(wasm_logger::Config::default().module_prefix("pika_chat"));
@@ -2,23 +2,30 @@ use yew::prelude::*;
 use yew_router::prelude::*;
 use gloo::storage::{LocalStorage, Storage};
 use wasm_bindgen_futures::spawn_local;
-use crate::services::websocket::{WebSocketService, BroadcastMessage};
+use crate::services::websocket::WebSocketService;
+use crate::services::protocol::{ClientCommand, PresenceChange, RemovalCause, ServerEvent};
 use crate::routes::Route;
-use web_sys::HtmlInputElement;
+use web_sys::{HtmlInputElement, HtmlSelectElement};
 use crate::components::footer::Footer;
 use crate::components::header::Header;
-use crate::services::utils::{decode_username, decode_userid};
-use crate::services::auth::logout;
-use crate::services::room::RoomMember;
-use crate::services::room::get_room_members;
+use crate::services::utils::decode_username;
+use crate::services::auth::{clear_tokens, logout};
+use crate::services::room::{Role, Room, RoomKind, RoomMember, UpdateRoomRequest, UserPresence};
+use crate::services::room::{ban_user, delete_room, get_room, get_room_members, get_user_presence, invite_user, kick_member, leave_room, set_member_role, transfer_ownership, update_room};
+use crate::services::room::{mute_member, set_slow_mode};
+use crate::services::invite::{create_invite_link, InviteLink, InviteLinkRequest};
+use crate::services::message::{add_reaction, delete_message, edit_message, get_room_messages, get_thread, remove_reaction};
+use crate::services::message::{MessagesResponse, ReactionSummary, ReactionUser, StoredMessage, ThreadResponse};
+use std::collections::HashMap;
 use crate::components::panel::Panel;
 use crate::components::room_member_list::RoomMembersList;
+use crate::components::room_settings::RoomSettings;
 use crate::components::message::{Message, MessageType};
 
 #[allow(dead_code)]
 pub enum Msg {
     SendMessage,
-    ReceiveMessage(BroadcastMessage),
+    ReceiveEvent(ServerEvent),
     UpdateMessageInput(String),
     WebSocketConnected,
     WebSocketDisconnected,
@@ -26,41 +33,143 @@ pub enum Msg {
     LogoutClicked,
     LogoutSuccess,
     LogoutFailure(String),
+    FetchRoomSuccess(Room),
+    UpdateRoom(UpdateRoomRequest),
+    DeleteRoom,
+    RoomDeleted,
+    LeaveRoom,
+    LeftRoom,
+    KickMember(i64),
+    BanMember(String),
+    MuteMember(i64),
+    TransferOwnership(i64),
+    SetSlowMode(i64),
     FetchRoomMembers,
     FetchRoomMembersSuccess(Vec<RoomMember>),
     FetchRoomMembersError(String),
+    FetchPresence,
+    FetchPresenceSuccess(Vec<UserPresence>),
+    FetchHistory { before: Option<i64> },
+    FetchHistorySuccess(MessagesResponse),
+    FetchHistoryError(String),
+    FetchNewerHistory,
+    FetchNewerHistorySuccess(MessagesResponse),
+    JumpToMessage(i64),
+    JumpToMessageSuccess(ThreadResponse),
+    ChangeRole { user_id: i64, role: Role },
+    UpdateInviteInput(String),
+    InviteUser,
+    InviteUserSuccess(String),
+    UpdateInviteLinkExpiry(Option<i64>),
+    UpdateInviteLinkMaxUses(String),
+    CreateInviteLink,
+    CreateInviteLinkSuccess(InviteLink),
+    StartEdit(i64),
+    UpdateEditInput(String),
+    CancelEdit,
+    SaveEdit,
+    DeleteMessage(i64),
+    MessageActionError(String),
+    ToggleReactionPicker(i64),
+    ToggleReaction { message_id: i64, emoji: String },
+    OpenThread(i64),
+    FetchThreadSuccess(ThreadResponse),
+    CloseThread,
+    UpdateReplyInput(String),
+    SendReply,
+}
+
+// Emoji offered by the reaction picker
+const QUICK_REACTIONS: [&str; 6] = ["👍", "❤️", "😂", "🎉", "😮", "😢"];
+// How often the typing signal is renewed while the user keeps typing; the server drops it after 5 seconds
+const TYPING_RENEW_MS: f64 = 3000.0;
+// Expiry choices for new invite links, in seconds
+const INVITE_LINK_EXPIRIES: [(&str, Option<i64>); 4] = [
+    ("Never expires", None),
+    ("Expires in 1 hour", Some(60 * 60)),
+    ("Expires in 1 day", Some(24 * 60 * 60)),
+    ("Expires in 7 days", Some(7 * 24 * 60 * 60)),
+];
+// Slow mode choices, in seconds between messages
+const SLOW_MODE_CHOICES: [(&str, i64); 5] = [
+    ("Slow mode off", 0),
+    ("One message every 5 seconds", 5),
+    ("One message every 30 seconds", 30),
+    ("One message every minute", 60),
+    ("One message every 5 minutes", 5 * 60),
+];
+
+// An entry in the chat timeline
+#[derive(Clone, PartialEq)]
+pub enum ChatEntry {
+    Message(StoredMessage),
+    System(String),
+}
+
+impl ChatEntry {
+    fn message_id(&self) -> Option<i64> {
+        match self {
+            ChatEntry::Message(message) => Some(message.message_id),
+            ChatEntry::System(_) => None,
+        }
+    }
+}
+
+// The thread shown in the side panel
+pub struct OpenThread {
+    root: StoredMessage,
+    replies: Vec<StoredMessage>, // Oldest first
+    reply_input: String,
 }
 
 #[derive(Clone, Properties, PartialEq)]
 pub struct Props {
     pub room_id: i64,
     pub room_name: Option<String>,
+    #[prop_or_default]
+    pub focus_message_id: Option<i64>, // Message to open the room at instead of the latest messages
 }
 
 pub struct ChatRoom {
     token: Option<String>,
     ws_service: Option<WebSocketService>,
     message_input: String,
-    messages: Vec<BroadcastMessage>, // Store structured messages
+    messages: Vec<ChatEntry>, // Stored messages and system announcements, oldest first
     error: Option<String>,
     username: String,
     avatar_url: Option<String>,
-    userid: String,
+    room: Option<Room>,             // Name, topic and archived state, once loaded
     room_members: Vec<RoomMember>,
     room_members_error: Option<String>,
+    user_presence: Vec<UserPresence>,
+    has_more_history: bool,
+    has_newer_history: bool,        // Set when the room was opened at an older message
+    loading_history: bool,
+    user_id: Option<i64>,           // Learned from the `welcome` event
+    editing: Option<(i64, String)>, // Message being edited and its draft content
+    reactions: HashMap<i64, Vec<ReactionSummary>>, // Reactions by message ID
+    reaction_picker: Option<i64>,   // Message whose reaction picker is open
+    thread: Option<OpenThread>,     // Replies are shown here instead of in the main timeline
+    typing_users: Vec<(i64, String)>, // Other users typing in the room, in the order they started
+    typing_sent_at: Option<f64>,    // When this user last told the room they are typing, in ms
+    invite_input: String,           // Username to invite, for moderators and above
+    invite_link_expiry: Option<i64>, // Settings of the next invite link, for admins and the owner
+    invite_link_max_uses: String,
+    invite_link: Option<InviteLink>, // The last invite link created, to copy and share
 }
 
 impl Component for ChatRoom {
     type Message = Msg;
     type Properties = Props;
 
-    fn create(ctx: &Context<Self>) -> Self {
+    fn create(_ctx: &Context<Self>) -> Self {
         let token = LocalStorage::get::<String>("jwtToken").ok();
         let username = token.as_ref().and_then(|t| decode_username(t)).unwrap_or_default();
-        let userid = token.as_ref().and_then(|t| decode_userid(t)).unwrap_or_default();
         let avatar_url = LocalStorage::get::<String>("avatarUrl").ok(); // Retrieve avatar URL from local storage
 
-        let component = Self {
+        // Room data is loaded once the WebSocket handshake completes (see `load_room`),
+        // since joining over the WebSocket is what makes the user a member
+        Self {
             token,
             ws_service: None,
             message_input: String::new(),
@@ -68,16 +177,25 @@ impl Component for ChatRoom {
             error: None,
             username,
             avatar_url,
-            userid,
+            room: None,
             room_members: vec![],
             room_members_error: None,
-        };
-
-        // Fetch room members on component creation
-        let link = ctx.link().clone();
-        link.send_message(Msg::FetchRoomMembers);
-
-        component
+            user_presence: vec![],
+            has_more_history: false,
+            has_newer_history: false,
+            loading_history: false,
+            user_id: None,
+            editing: None,
+            reactions: HashMap::new(),
+            reaction_picker: None,
+            thread: None,
+            typing_users: vec![],
+            typing_sent_at: None,
+            invite_input: String::new(),
+            invite_link_expiry: None,
+            invite_link_max_uses: String::new(),
+            invite_link: None,
+        }
     }
 
     fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
@@ -85,16 +203,16 @@ impl Component for ChatRoom {
         match msg {
             Msg::SendMessage => {
                 if let Some(ws_service) = &mut self.ws_service {
-                    ws_service.send_message(&self.message_input);
+                    ws_service.send_message(&self.message_input, None);
                     self.message_input.clear();
                 }
+                // The server ends the typing signal once the message is stored
+                self.typing_sent_at = None;
                 true
             }
-            Msg::ReceiveMessage(message) => {
-                self.messages.push(message);
-                true
-            }
+            Msg::ReceiveEvent(event) => self.handle_event(ctx, event),
             Msg::UpdateMessageInput(input) => {
+                self.set_typing(!input.trim().is_empty());
                 self.message_input = input;
                 true
             }
@@ -126,15 +244,168 @@ impl Component for ChatRoom {
                 false
             }
             Msg::LogoutSuccess => {
-                LocalStorage::delete("jwtToken");
+                clear_tokens();
                 navigator.push(&Route::Home);
                 true
             }
             Msg::LogoutFailure(_error) => {
-                LocalStorage::delete("jwtToken");
+                clear_tokens();
                 navigator.push(&Route::Home);
                 true
             }
+            Msg::FetchRoomSuccess(room) => {
+                self.room = Some(room);
+                true
+            }
+            Msg::UpdateRoom(request) => {
+                if let Some(token) = self.token.clone() {
+                    let room_id = ctx.props().room_id;
+                    let link = ctx.link().clone();
+                    spawn_local(async move {
+                        match update_room(&token, room_id, &request).await {
+                            Ok(room) => link.send_message(Msg::FetchRoomSuccess(room)),
+                            Err(err) => link.send_message(Msg::MessageActionError(err)),
+                        }
+                    });
+                }
+                false
+            }
+            Msg::DeleteRoom => {
+                let confirmed = web_sys::window()
+                    .and_then(|window| window.confirm_with_message("Delete this room and all of its messages?").ok())
+                    .unwrap_or(false);
+                if let (true, Some(token)) = (confirmed, self.token.clone()) {
+                    let room_id = ctx.props().room_id;
+                    let link = ctx.link().clone();
+                    spawn_local(async move {
+                        match delete_room(&token, room_id).await {
+                            Ok(()) => link.send_message(Msg::RoomDeleted),
+                            Err(err) => link.send_message(Msg::MessageActionError(err)),
+                        }
+                    });
+                }
+                false
+            }
+            Msg::RoomDeleted => {
+                navigator.push(&Route::Dashboard);
+                false
+            }
+            Msg::LeaveRoom => {
+                let confirmed = web_sys::window()
+                    .and_then(|window| window.confirm_with_message("Leave this room?").ok())
+                    .unwrap_or(false);
+                if let (true, Some(token)) = (confirmed, self.token.clone()) {
+                    let room_id = ctx.props().room_id;
+                    let link = ctx.link().clone();
+                    spawn_local(async move {
+                        match leave_room(&token, room_id).await {
+                            Ok(()) => link.send_message(Msg::LeftRoom),
+                            Err(err) => link.send_message(Msg::MessageActionError(err)),
+                        }
+                    });
+                }
+                false
+            }
+            Msg::LeftRoom => {
+                navigator.push(&Route::Dashboard);
+                false
+            }
+            Msg::KickMember(user_id) => {
+                if let Some(token) = self.token.clone() {
+                    let room_id = ctx.props().room_id;
+                    let link = ctx.link().clone();
+
+                    // The member list follows the `member_removed` event the server sends to the room
+                    spawn_local(async move {
+                        if let Err(err) = kick_member(&token, room_id, user_id).await {
+                            link.send_message(Msg::MessageActionError(err));
+                        }
+                    });
+                }
+                false
+            }
+            Msg::BanMember(username) => {
+                // Cancelling the prompt cancels the ban; an empty answer bans without a reason
+                let reason = web_sys::window()
+                    .and_then(|window| {
+                        window
+                            .prompt_with_message(&format!("Ban {} from this room? Reason (optional):", username))
+                            .ok()
+                    })
+                    .flatten();
+                if let (Some(reason), Some(token)) = (reason, self.token.clone()) {
+                    let room_id = ctx.props().room_id;
+                    let link = ctx.link().clone();
+                    spawn_local(async move {
+                        let reason = Some(reason.trim()).filter(|reason| !reason.is_empty());
+                        if let Err(err) = ban_user(&token, room_id, &username, reason).await {
+                            link.send_message(Msg::MessageActionError(err));
+                        }
+                    });
+                }
+                false
+            }
+            Msg::MuteMember(user_id) => {
+                // Cancelling the prompt or giving anything but a positive number of minutes cancels the mute
+                let minutes = web_sys::window()
+                    .and_then(|window| window.prompt_with_message_and_default("Mute for how many minutes?", "10").ok())
+                    .flatten()
+                    .and_then(|minutes| minutes.trim().parse::<i64>().ok())
+                    .filter(|minutes| *minutes > 0);
+                if let (Some(minutes), Some(token)) = (minutes, self.token.clone()) {
+                    let room_id = ctx.props().room_id;
+                    let link = ctx.link().clone();
+
+                    // The room hears about it through the `member_muted` event
+                    spawn_local(async move {
+                        if let Err(err) = mute_member(&token, room_id, user_id, minutes * 60).await {
+                            link.send_message(Msg::MessageActionError(err));
+                        }
+                    });
+                }
+                false
+            }
+            Msg::TransferOwnership(user_id) => {
+                let username = self
+                    .room_members
+                    .iter()
+                    .find(|member| member.user_id == user_id)
+                    .map(|member| member.username.clone())
+                    .unwrap_or_default();
+                let confirmed = web_sys::window()
+                    .and_then(|window| {
+                        window
+                            .confirm_with_message(&format!("Make {} the owner of this room? You will become an admin.", username))
+                            .ok()
+                    })
+                    .unwrap_or(false);
+                if let (true, Some(token)) = (confirmed, self.token.clone()) {
+                    let room_id = ctx.props().room_id;
+                    let link = ctx.link().clone();
+
+                    // The member list follows the `role_changed` events the server sends to the room
+                    spawn_local(async move {
+                        match transfer_ownership(&token, room_id, user_id).await {
+                            Ok(room) => link.send_message(Msg::FetchRoomSuccess(room)),
+                            Err(err) => link.send_message(Msg::MessageActionError(err)),
+                        }
+                    });
+                }
+                false
+            }
+            Msg::SetSlowMode(seconds) => {
+                if let Some(token) = self.token.clone() {
+                    let room_id = ctx.props().room_id;
+                    let link = ctx.link().clone();
+                    spawn_local(async move {
+                        match set_slow_mode(&token, room_id, seconds).await {
+                            Ok(room) => link.send_message(Msg::FetchRoomSuccess(room)),
+                            Err(err) => link.send_message(Msg::MessageActionError(err)),
+                        }
+                    });
+                }
+                false
+            }
             Msg::FetchRoomMembers => {
                 if let Some(token) = self.token.clone() {
                     let room_id: i64 = ctx.props().room_id;
@@ -158,25 +429,368 @@ impl Component for ChatRoom {
                 self.room_members_error = Some(err);
                 true
             }
+            Msg::FetchPresence => {
+                if let Some(token) = self.token.clone() {
+                    let room_id = ctx.props().room_id;
+                    let link = ctx.link().clone();
+
+                    spawn_local(async move {
+                        // The server answers 404 while nobody has connected to the room yet
+                        match get_user_presence(&token, room_id).await {
+                            Ok(presence) => link.send_message(Msg::FetchPresenceSuccess(presence)),
+                            Err(err) => log::info!("No initial presence for room {}: {}", room_id, err),
+                        }
+                    });
+                }
+                false
+            }
+            Msg::FetchPresenceSuccess(presence) => {
+                // Only fill in the first load; a snapshot from the WebSocket is at least as fresh
+                if self.user_presence.is_empty() {
+                    self.user_presence = presence;
+                    return true;
+                }
+                false
+            }
+            Msg::FetchHistory { before } => {
+                if let Some(token) = self.token.clone() {
+                    let room_id = ctx.props().room_id;
+                    let link = ctx.link().clone();
+                    self.loading_history = true;
+
+                    spawn_local(async move {
+                        match get_room_messages(&token, room_id, before, None).await {
+                            Ok(page) => link.send_message(Msg::FetchHistorySuccess(page)),
+                            Err(err) => link.send_message(Msg::FetchHistoryError(err)),
+                        }
+                    });
+                }
+                true
+            }
+            Msg::FetchHistorySuccess(page) => {
+                // History is older than anything already shown, so prepend it. Live messages
+                // that arrived while the request was in flight may also be in the page.
+                let mut messages: Vec<ChatEntry> = page.messages.into_iter().map(ChatEntry::Message).collect();
+                let loaded_ids: Vec<i64> = messages.iter().filter_map(ChatEntry::message_id).collect();
+                for message_id in &loaded_ids {
+                    self.reactions.remove(message_id);
+                }
+                for summary in page.reactions {
+                    self.reactions.entry(summary.message_id).or_default().push(summary);
+                }
+                messages.extend(
+                    self.messages
+                        .drain(..)
+                        .filter(|m| !matches!(m.message_id(), Some(id) if loaded_ids.contains(&id))),
+                );
+                self.messages = messages;
+                self.has_more_history = page.has_more;
+                self.loading_history = false;
+                true
+            }
+            Msg::FetchHistoryError(err) => {
+                self.error = Some(format!("Failed to load message history: {}", err));
+                self.loading_history = false;
+                true
+            }
+            Msg::FetchNewerHistory => {
+                let newest_message_id = self.messages.iter().rev().find_map(ChatEntry::message_id);
+                if let (Some(token), Some(after)) = (self.token.clone(), newest_message_id) {
+                    let room_id = ctx.props().room_id;
+                    let link = ctx.link().clone();
+                    self.loading_history = true;
+
+                    spawn_local(async move {
+                        match get_room_messages(&token, room_id, None, Some(after)).await {
+                            Ok(page) => link.send_message(Msg::FetchNewerHistorySuccess(page)),
+                            Err(err) => link.send_message(Msg::FetchHistoryError(err)),
+                        }
+                    });
+                }
+                true
+            }
+            Msg::FetchNewerHistorySuccess(page) => {
+                for summary in page.reactions {
+                    let summaries = self.reactions.entry(summary.message_id).or_default();
+                    summaries.retain(|existing| existing.emoji != summary.emoji);
+                    summaries.push(summary);
+                }
+                // Live messages may already be shown after the gap, so insert each one in ID order
+                for message in page.messages {
+                    let message_id = message.message_id;
+                    if self.messages.iter().any(|entry| entry.message_id() == Some(message_id)) {
+                        continue;
+                    }
+                    let position = self
+                        .messages
+                        .iter()
+                        .position(|entry| matches!(entry.message_id(), Some(id) if id > message_id))
+                        .unwrap_or(self.messages.len());
+                    self.messages.insert(position, ChatEntry::Message(message));
+                }
+                self.has_newer_history = page.has_more;
+                self.loading_history = false;
+                true
+            }
+            Msg::JumpToMessage(message_id) => {
+                if let Some(token) = self.token.clone() {
+                    let room_id = ctx.props().room_id;
+                    let link = ctx.link().clone();
+
+                    // The thread endpoint tells whether the message is a reply and, if so, to which root
+                    spawn_local(async move {
+                        match get_thread(&token, room_id, message_id).await {
+                            Ok(thread) => link.send_message(Msg::JumpToMessageSuccess(thread)),
+                            Err(err) => {
+                                link.send_message(Msg::FetchHistoryError(err));
+                                link.send_message(Msg::FetchHistory { before: None });
+                            }
+                        }
+                    });
+                }
+                false
+            }
+            Msg::JumpToMessageSuccess(thread) => {
+                let root_id = thread.root.message_id;
+                let link = ctx.link().clone();
+                // Replies live in the thread panel, so open it next to their root
+                if Some(root_id) != ctx.props().focus_message_id {
+                    link.send_message(Msg::FetchThreadSuccess(thread));
+                }
+                // Load the page of history that ends with the root; newer pages load on request
+                self.has_newer_history = true;
+                link.send_message(Msg::FetchHistory { before: Some(root_id + 1) });
+                false
+            }
+            Msg::ChangeRole { user_id, role } => {
+                if let Some(token) = self.token.clone() {
+                    let room_id = ctx.props().room_id;
+                    let link = ctx.link().clone();
+
+                    // The member list follows the `role_changed` event the server sends to the room
+                    spawn_local(async move {
+                        if let Err(err) = set_member_role(&token, room_id, user_id, role).await {
+                            link.send_message(Msg::MessageActionError(err));
+                            link.send_message(Msg::FetchRoomMembers);
+                        }
+                    });
+                }
+                false
+            }
+            Msg::UpdateInviteInput(username) => {
+                self.invite_input = username;
+                true
+            }
+            Msg::InviteUser => {
+                let username = self.invite_input.trim().to_string();
+                if username.is_empty() {
+                    return false;
+                }
+                if let Some(token) = self.token.clone() {
+                    let room_id = ctx.props().room_id;
+                    let link = ctx.link().clone();
+                    spawn_local(async move {
+                        match invite_user(&token, room_id, &username).await {
+                            Ok(invitation) => link.send_message(Msg::InviteUserSuccess(invitation.username)),
+                            Err(err) => link.send_message(Msg::MessageActionError(err)),
+                        }
+                    });
+                }
+                false
+            }
+            Msg::InviteUserSuccess(username) => {
+                self.invite_input.clear();
+                self.messages.push(ChatEntry::System(format!("{} was invited and can now join this room.", username)));
+                true
+            }
+            Msg::UpdateInviteLinkExpiry(expiry) => {
+                self.invite_link_expiry = expiry;
+                false
+            }
+            Msg::UpdateInviteLinkMaxUses(max_uses) => {
+                self.invite_link_max_uses = max_uses;
+                true
+            }
+            Msg::CreateInviteLink => {
+                let max_uses = match self.invite_link_max_uses.trim() {
+                    "" => None,
+                    value => match value.parse::<i64>() {
+                        Ok(max_uses) if max_uses > 0 => Some(max_uses),
+                        _ => {
+                            self.error = Some("The maximum number of uses must be a positive number".into());
+                            return true;
+                        }
+                    },
+                };
+                if let Some(token) = self.token.clone() {
+                    let room_id = ctx.props().room_id;
+                    let request = InviteLinkRequest {
+                        expires_in_secs: self.invite_link_expiry,
+                        max_uses,
+                        username: None,
+                    };
+                    let link = ctx.link().clone();
+                    spawn_local(async move {
+                        match create_invite_link(&token, room_id, &request).await {
+                            Ok(invite) => link.send_message(Msg::CreateInviteLinkSuccess(invite)),
+                            Err(err) => link.send_message(Msg::MessageActionError(err)),
+                        }
+                    });
+                }
+                false
+            }
+            Msg::CreateInviteLinkSuccess(invite) => {
+                self.invite_link = Some(invite);
+                true
+            }
+            Msg::StartEdit(message_id) => {
+                let content = self.messages.iter().find_map(|entry| match entry {
+                    ChatEntry::Message(message) if message.message_id == message_id => Some(message.content.clone()),
+                    _ => None,
+                });
+                self.editing = content.map(|content| (message_id, content));
+                true
+            }
+            Msg::UpdateEditInput(input) => {
+                if let Some((_, draft)) = &mut self.editing {
+                    *draft = input;
+                }
+                false
+            }
+            Msg::CancelEdit => {
+                self.editing = None;
+                true
+            }
+            Msg::SaveEdit => {
+                if let (Some(token), Some((message_id, content))) = (self.token.clone(), self.editing.take()) {
+                    let room_id = ctx.props().room_id;
+                    let link = ctx.link().clone();
+
+                    // The updated message comes back through the `message_edited` event
+                    spawn_local(async move {
+                        if let Err(err) = edit_message(&token, room_id, message_id, &content).await {
+                            link.send_message(Msg::MessageActionError(err));
+                        }
+                    });
+                }
+                true
+            }
+            Msg::DeleteMessage(message_id) => {
+                if let Some(token) = self.token.clone() {
+                    let room_id = ctx.props().room_id;
+                    let link = ctx.link().clone();
+
+                    // The tombstone comes back through the `message_deleted` event
+                    spawn_local(async move {
+                        if let Err(err) = delete_message(&token, room_id, message_id).await {
+                            link.send_message(Msg::MessageActionError(err));
+                        }
+                    });
+                }
+                false
+            }
+            Msg::MessageActionError(err) => {
+                self.error = Some(err);
+                true
+            }
+            Msg::OpenThread(message_id) => {
+                if let Some(token) = self.token.clone() {
+                    let room_id = ctx.props().room_id;
+                    let link = ctx.link().clone();
+
+                    spawn_local(async move {
+                        match get_thread(&token, room_id, message_id).await {
+                            Ok(thread) => link.send_message(Msg::FetchThreadSuccess(thread)),
+                            Err(err) => link.send_message(Msg::MessageActionError(err)),
+                        }
+                    });
+                }
+                false
+            }
+            Msg::FetchThreadSuccess(response) => {
+                for summary in response.reactions {
+                    let summaries = self.reactions.entry(summary.message_id).or_default();
+                    summaries.retain(|existing| existing.emoji != summary.emoji);
+                    summaries.push(summary);
+                }
+                // Keep the draft when the same thread is reloaded
+                let reply_input = match self.thread.take() {
+                    Some(thread) if thread.root.message_id == response.root.message_id => thread.reply_input,
+                    _ => String::new(),
+                };
+                self.thread = Some(OpenThread {
+                    root: response.root,
+                    replies: response.replies,
+                    reply_input,
+                });
+                true
+            }
+            Msg::CloseThread => {
+                self.thread = None;
+                true
+            }
+            Msg::UpdateReplyInput(input) => {
+                if let Some(thread) = &mut self.thread {
+                    thread.reply_input = input;
+                }
+                true
+            }
+            Msg::SendReply => {
+                if let (Some(ws_service), Some(thread)) = (&self.ws_service, &mut self.thread) {
+                    ws_service.send_message(&thread.reply_input, Some(thread.root.message_id));
+                    thread.reply_input.clear();
+                }
+                true
+            }
+            Msg::ToggleReactionPicker(message_id) => {
+                self.reaction_picker = match self.reaction_picker {
+                    Some(open) if open == message_id => None,
+                    _ => Some(message_id),
+                };
+                true
+            }
+            Msg::ToggleReaction { message_id, emoji } => {
+                self.reaction_picker = None;
+                if let Some(token) = self.token.clone() {
+                    let room_id = ctx.props().room_id;
+                    let link = ctx.link().clone();
+                    let reacted = self.has_reacted(message_id, &emoji);
+
+                    // Counts are updated by the `reaction_added` / `reaction_removed` events
+                    spawn_local(async move {
+                        let result = if reacted {
+                            remove_reaction(&token, room_id, message_id, &emoji).await
+                        } else {
+                            add_reaction(&token, room_id, message_id, &emoji).await
+                        };
+                        if let Err(err) = result {
+                            link.send_message(Msg::MessageActionError(err));
+                        }
+                    });
+                }
+                true
+            }
         }
     }
 
     fn rendered(&mut self, ctx: &Context<Self>, _first_render: bool) {
         if self.ws_service.is_none() {
+            let Some(token) = self.token.clone() else {
+                return;
+            };
             let room_id = ctx.props().room_id;
-            let userid = self.userid.clone();
             let link = ctx.link().clone();
 
-            let on_message = link.callback(Msg::ReceiveMessage);
+            let on_event = link.callback(Msg::ReceiveEvent);
             let on_error = link.callback(Msg::WebSocketError);
-            let on_connect = link.callback(|_| Msg::WebSocketConnected);
+            let on_disconnect = link.callback(|_| Msg::WebSocketDisconnected);
 
             let ws_service = WebSocketService::new(
                 &room_id.to_string(),
-                &userid.to_string(),
-                on_message,
+                &token,
+                on_event,
                 on_error,
-                on_connect,
+                on_disconnect,
             );
 
             self.ws_service = Some(ws_service);
@@ -184,6 +798,21 @@ impl Component for ChatRoom {
     }
 
     fn view(&self, ctx: &Context<Self>) -> Html {
+        // The oldest loaded message is the cursor for the next page of history
+        let oldest_message_id = self.messages.iter().find_map(ChatEntry::message_id);
+        let load_earlier_view = if self.has_more_history {
+            html! {
+                <button
+                    onclick={ctx.link().callback(move |_| Msg::FetchHistory { before: oldest_message_id })}
+                    disabled={self.loading_history}
+                    style="width: 100%; padding: 0.5rem; margin-bottom: 0.5rem; background-color: #f3f4f6; color: #374151; border: 1px solid #e5e7eb; border-radius: 0.5rem; cursor: pointer;"
+                >
+                    { if self.loading_history { "Loading..." } else { "Load earlier messages" } }
+                </button>
+            }
+        } else {
+            html! {}
+        };
         let room_members_view = if !self.room_members.is_empty() {
             html! {
                 <Panel>
@@ -198,9 +827,19 @@ impl Component for ChatRoom {
                     </h2>
                     <RoomMembersList 
                         members={self.room_members.clone()} 
-                        room_id={ctx.props().room_id}
-                        token={self.token.clone()}
+                        presence={self.user_presence.clone()}
+                        my_role={self.my_role()}
+                        on_change_role={ctx.link().callback(|(user_id, role)| Msg::ChangeRole { user_id, role })}
+                        on_kick={ctx.link().callback(Msg::KickMember)}
+                        on_ban={ctx.link().callback(Msg::BanMember)}
+                        on_mute={ctx.link().callback(Msg::MuteMember)}
+                        on_transfer={ctx.link().callback(Msg::TransferOwnership)}
                     />
+                    { self.view_invite(ctx) }
+                    { self.view_invite_link(ctx) }
+                    { self.view_slow_mode(ctx) }
+                    { self.view_room_settings(ctx) }
+                    { self.view_leave(ctx) }
                 </Panel>
             }
         } else if let Some(error) = &self.room_members_error {
@@ -242,29 +881,62 @@ impl Component for ChatRoom {
                     on_logout={ctx.link().callback(|_| Msg::LogoutClicked)}
                 />
                 {room_members_view} // Left panel with room members
+                { self.view_thread(ctx) } // Right panel with the open thread
                 <main
                     style="flex: 1; padding: 2rem; display: flex; flex-direction: column; align-items: center; text-align: center;"
                 >
-                    <h1 style="font-size: 2.5rem; font-weight: bold; color: #1f2937;">{ format!("Chat Room: {}", ctx.props().room_id) }</h1>
+                    <h1 style="font-size: 2.5rem; font-weight: bold; color: #1f2937;">
+                        {
+                            match &self.room {
+                                Some(room) if room.kind == RoomKind::Dm => self.dm_title(),
+                                Some(room) => room.room_name.clone(),
+                                None => format!("Chat Room: {}", ctx.props().room_id),
+                            }
+                        }
+                    </h1>
+                    if let Some(topic) = self.room.as_ref().and_then(|room| room.topic.clone()) {
+                        <p style="font-size: 1.125rem; color: #4b5563; margin-bottom: 0.25rem;">{ topic }</p>
+                    }
+                    if let Some(description) = self.room.as_ref().and_then(|room| room.description.clone()) {
+                        <p style="color: #6b7280; margin-bottom: 1rem;">{ description }</p>
+                    }
+                    if self.is_archived() {
+                        <p style="padding: 0.5rem 1rem; margin-bottom: 1rem; background-color: #fef3c7; color: #92400e; border-radius: 0.5rem;">
+                            {"This room is archived and read-only."}
+                        </p>
+                    }
                     <div style="width: 100%; max-width: 800px; margin-bottom: 2rem; text-align: left;">
                         <div style="border: 1px solid #e5e7eb; border-radius: 0.5rem; padding: 1rem; max-height: 400px; overflow-y: auto; background-color: #ffffff;">
+                            {load_earlier_view}
                             {
-                                for self.messages.iter().map(|msg| {
-                                    html! {
-                                        <p style={
-                                            if msg.is_system {
-                                                "padding: 0.5rem 0; border-bottom: 1px solid #e5e7eb; color: gray;"
-                                            } else {
-                                                "padding: 0.5rem 0; border-bottom: 1px solid #e5e7eb;"
-                                            }
-                                        }>
-                                            { format!("{}: {}", if msg.is_system { "System" } else { &msg.username }, msg.message) }
-                                        </p>
+                                for self.messages.iter().map(|entry| {
+                                    match entry {
+                                        ChatEntry::System(message) => html! {
+                                            <p style="padding: 0.5rem 0; border-bottom: 1px solid #e5e7eb; color: gray;">
+                                                { format!("System: {}", message) }
+                                            </p>
+                                        },
+                                        ChatEntry::Message(message) => self.view_message(ctx, message),
                                     }
                                 })
                             }
+                            if self.has_newer_history {
+                                <button
+                                    onclick={ctx.link().callback(|_| Msg::FetchNewerHistory)}
+                                    disabled={self.loading_history}
+                                    style="width: 100%; padding: 0.5rem; margin-top: 0.5rem; background-color: #f3f4f6; color: #374151; border: 1px solid #e5e7eb; border-radius: 0.5rem; cursor: pointer;"
+                                >
+                                    { if self.loading_history { "Loading..." } else { "Load newer messages" } }
+                                </button>
+                            }
                         </div>
-                        <div style="display: flex; margin-top: 1rem;">
+                        if let Some(error) = &self.error {
+                            <Message message={error.clone()} message_type={MessageType::Error} />
+                        }
+                        <p style="height: 1.25rem; margin: 0.5rem 0 0; color: #6b7280; font-size: 0.875rem; font-style: italic;">
+                            { self.typing_text().unwrap_or_default() }
+                        </p>
+                        <div style="display: flex; margin-top: 0.5rem;">
                             <input
                                 type="text"
                                 value={self.message_input.clone()}
@@ -280,11 +952,13 @@ impl Component for ChatRoom {
                                         None // Do nothing for other keys
                                     }
                                 })}
-                                placeholder="Type your message"
+                                placeholder={ if self.is_archived() { "This room is archived" } else { "Type your message" } }
+                                disabled={self.is_archived()}
                                 style="flex: 1; padding: 0.75rem; border: 1px solid #e5e7eb; border-radius: 0.5rem; margin-right: 0.5rem;"
                             />
                             <button
                                 onclick={ctx.link().callback(|_| Msg::SendMessage)}
+                                disabled={self.is_archived()}
                                 style="padding: 0.75rem 1.5rem; background-color: #1f2937; color: #ffffff; border: none; border-radius: 0.5rem; cursor: pointer;"
                             >
                                 {"Send"}
@@ -298,3 +972,636 @@ impl Component for ChatRoom {
     }
 
 }
+
+impl ChatRoom {
+    // Fetches room members, their presence and the first page of history. Presence is only
+    // fetched once; afterwards the WebSocket pushes every change.
+    fn load_room(&self, ctx: &Context<Self>) {
+        let link = ctx.link();
+        if let Some(token) = self.token.clone() {
+            let room_id = ctx.props().room_id;
+            let link = link.clone();
+            spawn_local(async move {
+                match get_room(&token, room_id).await {
+                    Ok(room) => link.send_message(Msg::FetchRoomSuccess(room)),
+                    Err(err) => log::warn!("Failed to load room {}: {}", room_id, err),
+                }
+            });
+        }
+        link.send_message(Msg::FetchRoomMembers);
+        link.send_message(Msg::FetchPresence);
+        match ctx.props().focus_message_id {
+            Some(message_id) => link.send_message(Msg::JumpToMessage(message_id)),
+            None => link.send_message(Msg::FetchHistory { before: None }),
+        }
+    }
+
+    // Invite-by-username form shown under the member list to moderators and above
+    fn view_invite(&self, ctx: &Context<Self>) -> Html {
+        if !self.my_role().is_some_and(Role::can_invite) {
+            return html! {};
+        }
+        let oninput = ctx.link().callback(|e: InputEvent| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            Msg::UpdateInviteInput(input.value())
+        });
+        let onkeypress = ctx.link().batch_callback(|e: KeyboardEvent| {
+            if e.key() == "Enter" { Some(Msg::InviteUser) } else { None }
+        });
+
+        html! {
+            <div style="display: flex; gap: 0.5rem; margin-top: 1rem;">
+                <input
+                    type="text"
+                    value={self.invite_input.clone()}
+                    {oninput}
+                    {onkeypress}
+                    placeholder="Invite by username"
+                    style="flex: 1; padding: 0.5rem; border: 1px solid #d1d5db; border-radius: 0.5rem;"
+                />
+                <button
+                    onclick={ctx.link().callback(|_| Msg::InviteUser)}
+                    style="padding: 0.5rem 1rem; background-color: #3b82f6; color: white; border: none; border-radius: 0.5rem; cursor: pointer;"
+                >
+                    {"Invite"}
+                </button>
+            </div>
+        }
+    }
+
+    // Invite link form shown to admins and the owner, with the last link created
+    fn view_invite_link(&self, ctx: &Context<Self>) -> Html {
+        if !self.my_role().is_some_and(Role::can_manage_room) {
+            return html! {};
+        }
+        let onchange_expiry = ctx.link().callback(|e: Event| {
+            let select: HtmlSelectElement = e.target_unchecked_into();
+            Msg::UpdateInviteLinkExpiry(INVITE_LINK_EXPIRIES[select.selected_index().max(0) as usize].1)
+        });
+        let oninput_max_uses = ctx.link().callback(|e: InputEvent| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            Msg::UpdateInviteLinkMaxUses(input.value())
+        });
+
+        html! {
+            <div style="margin-top: 1rem;">
+                <div style="display: flex; gap: 0.5rem;">
+                    <select onchange={onchange_expiry} style="padding: 0.5rem; border: 1px solid #d1d5db; border-radius: 0.5rem;">
+                        {
+                            for INVITE_LINK_EXPIRIES.iter().map(|(label, expiry)| html! {
+                                <option selected={self.invite_link_expiry == *expiry}>{ *label }</option>
+                            })
+                        }
+                    </select>
+                    <input
+                        type="number"
+                        min="1"
+                        value={self.invite_link_max_uses.clone()}
+                        oninput={oninput_max_uses}
+                        placeholder="Max uses"
+                        style="width: 6rem; padding: 0.5rem; border: 1px solid #d1d5db; border-radius: 0.5rem;"
+                    />
+                    <button
+                        onclick={ctx.link().callback(|_| Msg::CreateInviteLink)}
+                        style="padding: 0.5rem 1rem; background-color: #3b82f6; color: white; border: none; border-radius: 0.5rem; cursor: pointer;"
+                    >
+                        {"Create link"}
+                    </button>
+                </div>
+                if let Some(invite) = &self.invite_link {
+                    <input
+                        type="text"
+                        readonly=true
+                        value={invite.url()}
+                        style="width: 100%; margin-top: 0.5rem; padding: 0.5rem; border: 1px solid #d1d5db; border-radius: 0.5rem; color: #374151;"
+                    />
+                }
+            </div>
+        }
+    }
+
+    // Room settings shown to admins and the owner
+    fn view_room_settings(&self, ctx: &Context<Self>) -> Html {
+        let my_role = self.my_role();
+        match &self.room {
+            Some(room) if my_role.is_some_and(Role::can_manage_room) => html! {
+                <RoomSettings
+                    room={room.clone()}
+                    can_delete={my_role == Some(Role::Owner)}
+                    on_update={ctx.link().callback(Msg::UpdateRoom)}
+                    on_delete={ctx.link().callback(|_| Msg::DeleteRoom)}
+                />
+            },
+            _ => html! {},
+        }
+    }
+
+    // Lets moderators and above put the room into slow mode, which does not apply to them
+    fn view_slow_mode(&self, ctx: &Context<Self>) -> Html {
+        let slow_mode_secs = match &self.room {
+            Some(room) if self.my_role().is_some_and(Role::can_remove_members) => room.slow_mode_secs,
+            _ => return html! {},
+        };
+        let onchange = ctx.link().callback(|e: Event| {
+            let select: HtmlSelectElement = e.target_unchecked_into();
+            Msg::SetSlowMode(SLOW_MODE_CHOICES[select.selected_index().max(0) as usize].1)
+        });
+
+        html! {
+            <div style="margin-top: 1rem;">
+                <select {onchange} style="padding: 0.5rem; border: 1px solid #d1d5db; border-radius: 0.5rem;">
+                    {
+                        for SLOW_MODE_CHOICES.iter().map(|(label, seconds)| html! {
+                            <option selected={slow_mode_secs == *seconds}>{ *label }</option>
+                        })
+                    }
+                </select>
+            </div>
+        }
+    }
+
+    // The owner cannot leave; they delete the room instead
+    fn view_leave(&self, ctx: &Context<Self>) -> Html {
+        match self.my_role() {
+            Some(role) if role != Role::Owner => html! {
+                <button
+                    onclick={ctx.link().callback(|_| Msg::LeaveRoom)}
+                    style="margin-top: 1rem; padding: 0.5rem 1rem; color: white; background-color: #6b7280; border: none; border-radius: 0.5rem; cursor: pointer;"
+                >
+                    {"Leave room"}
+                </button>
+            },
+            _ => html! {},
+        }
+    }
+
+    // Direct messages are named after the other participants rather than their generated room name
+    fn dm_title(&self) -> String {
+        let others: Vec<&str> = self
+            .room_members
+            .iter()
+            .map(|member| member.username.as_str())
+            .filter(|username| *username != self.username)
+            .collect();
+        format!("Direct message with {}", others.join(", "))
+    }
+
+    // Whether the room is archived, which makes it read-only
+    fn is_archived(&self) -> bool {
+        self.room.as_ref().is_some_and(|room| room.archived_at.is_some())
+    }
+
+    // The current user's role, once both the member list and the `welcome` event have arrived
+    fn my_role(&self) -> Option<Role> {
+        let user_id = self.user_id?;
+        self.room_members.iter().find(|member| member.user_id == user_id).map(|member| member.role)
+    }
+
+    // Tells the room whether this user is typing. While typing, the signal is only renewed every
+    // TYPING_RENEW_MS so that keystrokes do not turn into a flood of frames.
+    fn set_typing(&mut self, is_typing: bool) {
+        let ws_service = match &self.ws_service {
+            Some(ws_service) => ws_service,
+            None => return,
+        };
+        let now = js_sys::Date::now();
+        match (is_typing, self.typing_sent_at) {
+            (true, Some(sent_at)) if now - sent_at < TYPING_RENEW_MS => {}
+            (true, _) => {
+                ws_service.send_command(ClientCommand::Typing { is_typing: true });
+                self.typing_sent_at = Some(now);
+            }
+            (false, Some(_)) => {
+                ws_service.send_command(ClientCommand::Typing { is_typing: false });
+                self.typing_sent_at = None;
+            }
+            (false, None) => {}
+        }
+    }
+
+    // Describes who is typing, e.g. "Pikachu and Eevee are typing…"
+    fn typing_text(&self) -> Option<String> {
+        let names: Vec<&str> = self.typing_users.iter().map(|(_, username)| username.as_str()).collect();
+        match names.as_slice() {
+            [] => None,
+            [name] => Some(format!("{} is typing…", name)),
+            [first, second] => Some(format!("{} and {} are typing…", first, second)),
+            _ => Some("Several people are typing…".to_string()),
+        }
+    }
+
+    // Applies a server event to the component state; returns whether to re-render
+    fn handle_event(&mut self, ctx: &Context<Self>, event: ServerEvent) -> bool {
+        match event {
+            ServerEvent::Welcome { user_id, .. } => {
+                if self.user_id.is_none() {
+                    self.load_room(ctx);
+                }
+                self.user_id = Some(user_id);
+                ctx.link().send_message(Msg::WebSocketConnected);
+                true
+            }
+            ServerEvent::Message { message } => {
+                let message_id = message.message_id;
+                if let Some(ws_service) = &self.ws_service {
+                    ws_service.send_command(ClientCommand::Ack { message_id });
+                }
+
+                // Replies go to the thread panel; the timeline only shows the root's reply count
+                if let Some(root_id) = message.parent_id {
+                    let thread = self.thread.as_mut().filter(|thread| thread.root.message_id == root_id);
+                    if let Some(thread) = thread {
+                        if thread.replies.iter().any(|reply| reply.message_id == message_id) {
+                            return false;
+                        }
+                        thread.replies.push(message);
+                    }
+                    self.update_message(root_id, |root| root.reply_count += 1);
+                    return true;
+                }
+
+                // The same message may already have arrived with a page of history
+                if self.messages.iter().any(|m| m.message_id() == Some(message_id)) {
+                    return false;
+                }
+                self.messages.push(ChatEntry::Message(message));
+                true
+            }
+            ServerEvent::MessageEdited { message } => {
+                let message_id = message.message_id;
+                self.update_message(message_id, |existing| *existing = message.clone())
+            }
+            ServerEvent::MessageDeleted { message_id, parent_id, deleted_at, .. } => {
+                if matches!(&self.editing, Some((id, _)) if *id == message_id) {
+                    self.editing = None;
+                }
+                if let Some(root_id) = parent_id {
+                    self.update_message(root_id, |root| root.reply_count -= 1);
+                }
+                self.update_message(message_id, |existing| {
+                    existing.content.clear();
+                    existing.deleted_at = Some(deleted_at.clone());
+                })
+            }
+            ServerEvent::ReactionAdded { message_id, user_id, username, emoji, .. } => {
+                let summaries = self.reactions.entry(message_id).or_default();
+                match summaries.iter_mut().find(|summary| summary.emoji == emoji) {
+                    Some(summary) if summary.users.iter().any(|user| user.user_id == user_id) => return false,
+                    Some(summary) => {
+                        summary.count += 1;
+                        summary.users.push(ReactionUser { user_id, username });
+                    }
+                    None => summaries.push(ReactionSummary {
+                        message_id,
+                        emoji,
+                        count: 1,
+                        users: vec![ReactionUser { user_id, username }],
+                    }),
+                }
+                true
+            }
+            ServerEvent::ReactionRemoved { message_id, user_id, emoji, .. } => {
+                let Some(summaries) = self.reactions.get_mut(&message_id) else {
+                    return false;
+                };
+                if let Some(summary) = summaries.iter_mut().find(|summary| summary.emoji == emoji) {
+                    summary.users.retain(|user| user.user_id != user_id);
+                    summary.count = summary.users.len() as i64;
+                }
+                summaries.retain(|summary| summary.count > 0);
+                true
+            }
+            ServerEvent::System { message, .. } => {
+                self.messages.push(ChatEntry::System(message));
+                true
+            }
+            ServerEvent::Presence { user_id, change, .. } => {
+                if change == PresenceChange::Left {
+                    self.typing_users.retain(|(id, _)| *id != user_id);
+                }
+                match self.user_presence.iter_mut().find(|p| p.user_id == user_id) {
+                    Some(presence) => presence.apply(change),
+                    None => {
+                        let mut presence = UserPresence { user_id, is_online: false, in_room: false };
+                        presence.apply(change);
+                        self.user_presence.push(presence);
+                    }
+                }
+                // Someone who is not listed yet joined the room, so reload the member list
+                if change == PresenceChange::Joined && !self.room_members.iter().any(|m| m.user_id == user_id) {
+                    ctx.link().send_message(Msg::FetchRoomMembers);
+                }
+                true
+            }
+            ServerEvent::PresenceSnapshot { users, .. } => {
+                self.user_presence = users;
+                true
+            }
+            ServerEvent::RoleChanged { user_id, role, .. } => {
+                if let Some(member) = self.room_members.iter_mut().find(|member| member.user_id == user_id) {
+                    member.role = role;
+                }
+                true
+            }
+            ServerEvent::RoomUpdated { room_name, topic, description, archived, .. } => {
+                if let Some(room) = &mut self.room {
+                    room.room_name = room_name;
+                    room.topic = topic;
+                    room.description = description;
+                    // The exact time is only known to the server; any value marks the room as archived
+                    room.archived_at = if archived { room.archived_at.take().or_else(|| Some(String::new())) } else { None };
+                }
+                true
+            }
+            ServerEvent::RoomDeleted { .. } => {
+                // The server closes the connection right after this event
+                ctx.link().send_message(Msg::RoomDeleted);
+                false
+            }
+            ServerEvent::MemberRemoved { user_id, username, cause, .. } => {
+                if Some(user_id) == self.user_id {
+                    // The server closes the connection right after this event
+                    let notice = match cause {
+                        RemovalCause::Left | RemovalCause::AccountDeleted => None,
+                        RemovalCause::Kicked => Some("You were kicked from this room"),
+                        RemovalCause::Banned => Some("You were banned from this room"),
+                    };
+                    if let (Some(notice), Some(window)) = (notice, web_sys::window()) {
+                        let _ = window.alert_with_message(notice);
+                    }
+                    ctx.link().send_message(Msg::LeftRoom);
+                    return false;
+                }
+                self.room_members.retain(|member| member.user_id != user_id);
+                self.user_presence.retain(|presence| presence.user_id != user_id);
+                let message = match cause {
+                    RemovalCause::Left => format!("{} left the room", username),
+                    RemovalCause::Kicked => format!("{} was kicked from the room", username),
+                    RemovalCause::Banned => format!("{} was banned from the room", username),
+                    RemovalCause::AccountDeleted => format!("{} deleted their account", username),
+                };
+                self.messages.push(ChatEntry::System(message));
+                true
+            }
+            ServerEvent::SessionRevoked => {
+                // The session was revoked, here or on another device; the connection closes next
+                if let Some(window) = web_sys::window() {
+                    let _ = window.alert_with_message("This session was logged out");
+                }
+                ctx.link().send_message(Msg::LogoutSuccess);
+                false
+            }
+            ServerEvent::Typing { user_id, username, is_typing, .. } => {
+                self.typing_users.retain(|(id, _)| *id != user_id);
+                if is_typing && Some(user_id) != self.user_id {
+                    self.typing_users.push((user_id, username));
+                }
+                true
+            }
+            ServerEvent::Ack { .. } => false,
+            ServerEvent::MemberMuted { username, muted_until, .. } => {
+                let message = match muted_until {
+                    Some(muted_until) => format!("{} was muted until {} UTC", username, muted_until),
+                    None => format!("{} is no longer muted", username),
+                };
+                self.messages.push(ChatEntry::System(message));
+                true
+            }
+            ServerEvent::SlowModeChanged { seconds, .. } => {
+                if let Some(room) = &mut self.room {
+                    room.slow_mode_secs = seconds;
+                }
+                let message = match seconds {
+                    0 => "Slow mode is off".to_string(),
+                    seconds => format!("Slow mode is on: one message every {} seconds", seconds),
+                };
+                self.messages.push(ChatEntry::System(message));
+                true
+            }
+            ServerEvent::Error { message, .. } => {
+                self.error = Some(message);
+                true
+            }
+        }
+    }
+
+    fn has_reacted(&self, message_id: i64, emoji: &str) -> bool {
+        self.reactions.get(&message_id).is_some_and(|summaries| {
+            summaries.iter().any(|summary| {
+                summary.emoji == emoji && summary.users.iter().any(|user| Some(user.user_id) == self.user_id)
+            })
+        })
+    }
+
+    // Applies a change to every loaded copy of a message (timeline and thread panel);
+    // returns whether any copy was found
+    fn update_message(&mut self, message_id: i64, update: impl Fn(&mut StoredMessage)) -> bool {
+        let timeline = self.messages.iter_mut().filter_map(|entry| match entry {
+            ChatEntry::Message(message) => Some(message),
+            ChatEntry::System(_) => None,
+        });
+        let thread = self
+            .thread
+            .iter_mut()
+            .flat_map(|thread| std::iter::once(&mut thread.root).chain(thread.replies.iter_mut()));
+
+        let mut found = false;
+        for message in timeline.chain(thread).filter(|message| message.message_id == message_id) {
+            update(message);
+            found = true;
+        }
+        found
+    }
+
+    // Renders the side panel with the open thread and its reply box
+    fn view_thread(&self, ctx: &Context<Self>) -> Html {
+        let Some(thread) = &self.thread else {
+            return html! {};
+        };
+
+        html! {
+            <aside style="
+                position: fixed;
+                top: 68px;
+                right: 0;
+                height: calc(100% - 122px);
+                width: 340px;
+                display: flex;
+                flex-direction: column;
+                background-color: #f9fafb;
+                border-left: 1px solid #e5e7eb;
+                padding: 1rem;
+                box-shadow: -2px 0 5px rgba(0, 0, 0, 0.1);
+                text-align: left;
+            ">
+                <div style="display: flex; justify-content: space-between; align-items: center; margin-bottom: 0.5rem;">
+                    <h2 style="font-size: 1.25rem; font-weight: bold; color: #1f2937;">{"Thread"}</h2>
+                    <button
+                        onclick={ctx.link().callback(|_| Msg::CloseThread)}
+                        style="background: none; border: none; color: #6b7280; cursor: pointer; font-size: 1.25rem;"
+                    >
+                        {"×"}
+                    </button>
+                </div>
+                <div style="flex: 1; overflow-y: auto; background-color: #ffffff; border: 1px solid #e5e7eb; border-radius: 0.5rem; padding: 0.5rem;">
+                    { self.view_message(ctx, &thread.root) }
+                    <p style="font-size: 0.75rem; color: #6b7280; margin: 0.5rem 0;">
+                        { format!("{} replies", thread.root.reply_count) }
+                    </p>
+                    { for thread.replies.iter().map(|reply| self.view_message(ctx, reply)) }
+                </div>
+                <div style="display: flex; margin-top: 0.5rem;">
+                    <input
+                        type="text"
+                        value={thread.reply_input.clone()}
+                        oninput={ctx.link().callback(|e: InputEvent| {
+                            let input: HtmlInputElement = e.target_unchecked_into();
+                            Msg::UpdateReplyInput(input.value())
+                        })}
+                        onkeypress={ctx.link().batch_callback(|e: KeyboardEvent| {
+                            if e.key() == "Enter" {
+                                e.prevent_default();
+                                Some(Msg::SendReply)
+                            } else {
+                                None
+                            }
+                        })}
+                        placeholder="Reply in thread"
+                        style="flex: 1; padding: 0.5rem; border: 1px solid #e5e7eb; border-radius: 0.5rem; margin-right: 0.5rem;"
+                    />
+                    <button
+                        onclick={ctx.link().callback(|_| Msg::SendReply)}
+                        style="padding: 0.5rem 1rem; background-color: #1f2937; color: #ffffff; border: none; border-radius: 0.5rem; cursor: pointer;"
+                    >
+                        {"Reply"}
+                    </button>
+                </div>
+            </aside>
+        }
+    }
+
+    // Renders a chat message, its tombstone or its edit form
+    fn view_message(&self, ctx: &Context<Self>, message: &StoredMessage) -> Html {
+        let message_id = message.message_id;
+        let action_style = "margin-left: 0.5rem; padding: 0 0.25rem; font-size: 0.75rem; background: none; border: none; color: #6b7280; cursor: pointer;";
+
+        if message.deleted_at.is_some() {
+            return html! {
+                <p style="padding: 0.5rem 0; border-bottom: 1px solid #e5e7eb; color: #9ca3af; font-style: italic;">
+                    { format!("{}: This message was deleted", message.username) }
+                </p>
+            };
+        }
+
+        if let Some((_, draft)) = self.editing.as_ref().filter(|(id, _)| *id == message_id) {
+            return html! {
+                <div style="display: flex; padding: 0.5rem 0; border-bottom: 1px solid #e5e7eb;">
+                    <input
+                        type="text"
+                        value={draft.clone()}
+                        oninput={ctx.link().callback(|e: InputEvent| {
+                            let input: HtmlInputElement = e.target_unchecked_into();
+                            Msg::UpdateEditInput(input.value())
+                        })}
+                        onkeypress={ctx.link().batch_callback(|e: KeyboardEvent| {
+                            if e.key() == "Enter" {
+                                e.prevent_default();
+                                Some(Msg::SaveEdit)
+                            } else {
+                                None
+                            }
+                        })}
+                        style="flex: 1; padding: 0.25rem 0.5rem; border: 1px solid #e5e7eb; border-radius: 0.25rem;"
+                    />
+                    <button onclick={ctx.link().callback(|_| Msg::SaveEdit)} style={action_style}>{"Save"}</button>
+                    <button onclick={ctx.link().callback(|_| Msg::CancelEdit)} style={action_style}>{"Cancel"}</button>
+                </div>
+            };
+        }
+
+        // The server enforces the edit window; the buttons are offered to everyone allowed in principle
+        let is_author = self.user_id == Some(message.user_id);
+        let is_moderator = self.my_role().is_some_and(Role::can_moderate);
+        // The message the room was opened at, e.g. from a search result, stands out
+        let background = if ctx.props().focus_message_id == Some(message_id) { "#fef9c3" } else { "transparent" };
+        html! {
+            <div style={format!("padding: 0.5rem 0; border-bottom: 1px solid #e5e7eb; background-color: {};", background)}>
+                { format!("{}: {}", message.username, message.content) }
+                if let Some(edited_at) = &message.edited_at {
+                    <span title={edited_at.clone()} style="margin-left: 0.5rem; font-size: 0.75rem; color: #9ca3af;">{"(edited)"}</span>
+                }
+                if is_author {
+                    <button onclick={ctx.link().callback(move |_| Msg::StartEdit(message_id))} style={action_style}>{"Edit"}</button>
+                }
+                if is_author || is_moderator {
+                    <button onclick={ctx.link().callback(move |_| Msg::DeleteMessage(message_id))} style={action_style}>{"Delete"}</button>
+                }
+                <button onclick={ctx.link().callback(move |_| Msg::ToggleReactionPicker(message_id))} style={action_style}>{"React"}</button>
+                // Only root messages start threads
+                if message.parent_id.is_none() {
+                    <button onclick={ctx.link().callback(move |_| Msg::OpenThread(message_id))} style={action_style}>
+                        {
+                            match message.reply_count {
+                                0 => "Reply".to_string(),
+                                1 => "1 reply".to_string(),
+                                count => format!("{} replies", count),
+                            }
+                        }
+                    </button>
+                }
+                { self.view_reactions(ctx, message_id) }
+            </div>
+        }
+    }
+
+    // Renders the reaction chips under a message, plus the picker when it is open
+    fn view_reactions(&self, ctx: &Context<Self>, message_id: i64) -> Html {
+        let chip_style = |active: bool| {
+            format!(
+                "margin: 0.25rem 0.25rem 0 0; padding: 0.125rem 0.5rem; font-size: 0.875rem; border-radius: 9999px; cursor: pointer; border: 1px solid {}; background-color: {};",
+                if active { "#3b82f6" } else { "#e5e7eb" },
+                if active { "#eff6ff" } else { "#ffffff" },
+            )
+        };
+        let summaries = self.reactions.get(&message_id).cloned().unwrap_or_default();
+
+        html! {
+            <div style="display: flex; flex-wrap: wrap;">
+                {
+                    for summaries.into_iter().map(|summary| {
+                        let who = summary.users.iter().map(|user| user.username.as_str()).collect::<Vec<_>>().join(", ");
+                        let active = self.has_reacted(message_id, &summary.emoji);
+                        let emoji = summary.emoji.clone();
+                        html! {
+                            <button
+                                title={who}
+                                onclick={ctx.link().callback(move |_| Msg::ToggleReaction { message_id, emoji: emoji.clone() })}
+                                style={chip_style(active)}
+                            >
+                                { format!("{} {}", summary.emoji, summary.count) }
+                            </button>
+                        }
+                    })
+                }
+                if self.reaction_picker == Some(message_id) {
+                    {
+                        for QUICK_REACTIONS.iter().map(|emoji| {
+                            let emoji = emoji.to_string();
+                            html! {
+                                <button
+                                    onclick={ctx.link().callback({
+                                        let emoji = emoji.clone();
+                                        move |_| Msg::ToggleReaction { message_id, emoji: emoji.clone() }
+                                    })}
+                                    style={chip_style(false)}
+                                >
+                                    { emoji }
+                                </button>
+                            }
+                        })
+                    }
+                }
+            </div>
+        }
+    }
+}
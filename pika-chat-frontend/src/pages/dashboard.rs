@@ -3,12 +3,13 @@ use yew_router::prelude::*;
 use gloo::storage::{LocalStorage, Storage};
 use wasm_bindgen_futures::spawn_local;
 use crate::routes::Route;
-use crate::services::auth::logout;
-use crate::services::room::{get_rooms, RoomsResponse, Room, RoomInfo, create_room};
+use crate::services::auth::{change_password, clear_tokens, delete_account, get_sessions, logout, revoke_other_sessions, revoke_session, set_email, Session};
+use crate::services::room::{get_rooms, RoomsResponse, Room, RoomInfo, RoomSort, RoomVisibility, create_room};
+use crate::services::dm::{create_dm, get_dms, DirectMessage};
 use crate::components::room_card::RoomCard;
 use crate::components::footer::Footer;
 use crate::components::header::Header;
-use web_sys::HtmlInputElement;
+use web_sys::{HtmlInputElement, HtmlSelectElement};
 use crate::services::utils::decode_username;
 
 pub enum Msg {
@@ -16,12 +17,40 @@ pub enum Msg {
     LogoutSuccess,
     LogoutFailure(String),
     FetchRooms,
-    FetchRoomsSuccess(RoomsResponse),
-    FetchRoomsFailure(String),
+    FetchRoomsSuccess(u32, RoomsResponse),
+    FetchRoomsFailure(u32, String),
+    UpdateSearch(String),
+    UpdateSort(RoomSort),
+    LoadMoreRooms,
     UpdateRoomName(String),
+    UpdateVisibility(RoomVisibility),
     CreateRoom,
     CreateRoomSuccess(Room),
     CreateRoomFailure(String),
+    FetchDmsSuccess(Vec<DirectMessage>),
+    UpdateDmInput(String),
+    CreateDm,
+    CreateDmSuccess(DirectMessage),
+    DmFailure(String),
+    UpdateDeletePassword(String),
+    DeleteAccount,
+    DeleteAccountFailure(String),
+    FetchSessions,
+    FetchSessionsSuccess(Vec<Session>),
+    RevokeSession(String),
+    RevokeOtherSessions,
+    RevokeOtherSessionsSuccess(String),
+    SessionsFailure(String),
+    UpdateCurrentPassword(String),
+    UpdateNewPassword(String),
+    ChangePassword,
+    ChangePasswordSuccess(String),
+    ChangePasswordFailure(String),
+    UpdateEmail(String),
+    UpdateEmailPassword(String),
+    SetEmail,
+    SetEmailSuccess(String),
+    SetEmailFailure(String),
 }
 
 pub struct Dashboard {
@@ -29,9 +58,48 @@ pub struct Dashboard {
     rooms: Option<RoomsResponse>,
     error: Option<String>,
     loading: bool,
+    loading_more: bool,
+    search_input: String, // Only rooms whose name contains this are listed
+    sort: RoomSort,
+    rooms_request: u32,   // Numbers room fetches so that a response to an outdated search is dropped
     room_name_input: String,
+    visibility_input: RoomVisibility,
     username: String,
     avatar_url: Option<String>, // Add avatar_url to state
+    dms: Vec<DirectMessage>,    // Kept apart from the rooms, which never include them
+    dm_input: String,           // Comma-separated usernames to start a direct message with
+    dm_error: Option<String>,
+    delete_password: String,    // Confirms the account deletion
+    delete_error: Option<String>,
+    sessions: Vec<Session>,     // Where the user is logged in, this browser included
+    sessions_notice: Option<String>,
+    sessions_error: Option<String>,
+    current_password: String,   // Confirms the password change
+    new_password: String,
+    password_notice: Option<String>,
+    password_error: Option<String>,
+    email_input: String,        // Where password reset links go; empty removes the address
+    email_password: String,     // Confirms the email change
+    email_notice: Option<String>,
+    email_error: Option<String>,
+}
+
+impl Dashboard {
+    // Fetches the first page of rooms matching the search and sort, or the page after `cursor`
+    fn fetch_rooms(&mut self, ctx: &Context<Self>, cursor: Option<String>) {
+        let Some(token) = self.token.clone() else { return };
+        self.rooms_request += 1;
+        let request = self.rooms_request;
+        let search = Some(self.search_input.trim().to_string()).filter(|search| !search.is_empty());
+        let sort = self.sort;
+        let link = ctx.link().clone();
+        spawn_local(async move {
+            match get_rooms(&token, search.as_deref(), sort, cursor.as_deref()).await {
+                Ok(rooms) => link.send_message(Msg::FetchRoomsSuccess(request, rooms)),
+                Err(err) => link.send_message(Msg::FetchRoomsFailure(request, err)),
+            }
+        });
+    }
 }
 
 impl Component for Dashboard {
@@ -47,10 +115,11 @@ impl Component for Dashboard {
         // Fetch rooms if token exists
         if let Some(token) = token.clone() {
             link.send_message(Msg::FetchRooms);
+            link.send_message(Msg::FetchSessions);
             spawn_local(async move {
-                match get_rooms(&token).await {
-                    Ok(rooms) => link.send_message(Msg::FetchRoomsSuccess(rooms)),
-                    Err(err) => link.send_message(Msg::FetchRoomsFailure(err)),
+                match get_dms(&token).await {
+                    Ok(dms) => link.send_message(Msg::FetchDmsSuccess(dms)),
+                    Err(err) => link.send_message(Msg::DmFailure(err)),
                 }
             });
         }
@@ -60,9 +129,30 @@ impl Component for Dashboard {
             rooms: None,
             error: None,
             loading: true,
+            loading_more: false,
+            search_input: String::new(),
+            sort: RoomSort::default(),
+            rooms_request: 0,
             room_name_input: String::new(),
+            visibility_input: RoomVisibility::default(),
             username,
             avatar_url, // Set the retrieved avatar_url
+            dms: Vec::new(),
+            dm_input: String::new(),
+            dm_error: None,
+            delete_password: String::new(),
+            delete_error: None,
+            sessions: Vec::new(),
+            sessions_notice: None,
+            sessions_error: None,
+            current_password: String::new(),
+            new_password: String::new(),
+            password_notice: None,
+            password_error: None,
+            email_input: String::new(),
+            email_password: String::new(),
+            email_notice: None,
+            email_error: None,
         }
     }     
 
@@ -86,41 +176,81 @@ impl Component for Dashboard {
                 false
             }
             Msg::LogoutSuccess => {
-                LocalStorage::delete("jwtToken");
+                clear_tokens();
                 navigator.push(&Route::Home);
                 true
             }
             Msg::LogoutFailure(_error) => {
-                LocalStorage::delete("jwtToken");
+                clear_tokens();
                 navigator.push(&Route::Home);
                 true
             }
             Msg::FetchRooms => {
                 self.loading = true;
+                self.loading_more = false;
                 self.error = None;
+                self.fetch_rooms(ctx, None);
                 true
             }
-            Msg::FetchRoomsSuccess(rooms) => {
+            Msg::FetchRoomsSuccess(request, page) => {
+                if request != self.rooms_request {
+                    return false;
+                }
+                match (self.loading_more, &mut self.rooms) {
+                    // A further page extends the list already shown
+                    (true, Some(rooms)) => {
+                        rooms.rooms.extend(page.rooms);
+                        rooms.next_cursor = page.next_cursor;
+                    }
+                    _ => self.rooms = Some(page),
+                }
                 self.loading = false;
-                self.rooms = Some(rooms);
+                self.loading_more = false;
                 self.error = None;
                 true
             }
-            Msg::FetchRoomsFailure(err) => {
+            Msg::FetchRoomsFailure(request, err) => {
+                if request != self.rooms_request {
+                    return false;
+                }
                 self.loading = false;
+                self.loading_more = false;
                 self.rooms = None;
                 self.error = Some(err);
                 true
             }
+            Msg::UpdateSearch(search) => {
+                self.search_input = search;
+                ctx.link().send_message(Msg::FetchRooms);
+                true
+            }
+            Msg::UpdateSort(sort) => {
+                self.sort = sort;
+                ctx.link().send_message(Msg::FetchRooms);
+                true
+            }
+            Msg::LoadMoreRooms => {
+                let cursor = self.rooms.as_ref().and_then(|rooms| rooms.next_cursor.clone());
+                if cursor.is_some() && !self.loading_more {
+                    self.loading_more = true;
+                    self.fetch_rooms(ctx, cursor);
+                }
+                true
+            }
             Msg::UpdateRoomName(name) => {
                 self.room_name_input = name;
                 true
             }
+            Msg::UpdateVisibility(visibility) => {
+                self.visibility_input = visibility;
+                true
+            }
             Msg::CreateRoom => {
                 if let Some(token) = self.token.clone() {
                     let link = ctx.link().clone();
                     let room_info = RoomInfo {
                         room_name: self.room_name_input.clone(),
+                        visibility: self.visibility_input,
                     };
                     spawn_local(async move {
                         match create_room(&token, &room_info).await {
@@ -131,17 +261,202 @@ impl Component for Dashboard {
                 }
                 false
             }
-            Msg::CreateRoomSuccess(room) => {
-                if let Some(ref mut rooms) = self.rooms {
-                    rooms.rooms.push(room.clone());
-                }
+            Msg::CreateRoomSuccess(_room) => {
+                // Reload the first page, where the new room shows up with its counts
                 self.room_name_input.clear(); // Clear input on success
+                ctx.link().send_message(Msg::FetchRooms);
                 true
             }
             Msg::CreateRoomFailure(err) => {
                 self.error = Some(err);
                 true
             }
+            Msg::FetchDmsSuccess(dms) => {
+                self.dms = dms;
+                true
+            }
+            Msg::UpdateDmInput(input) => {
+                self.dm_input = input;
+                true
+            }
+            Msg::CreateDm => {
+                let participants: Vec<String> = self
+                    .dm_input
+                    .split(',')
+                    .map(|username| username.trim().to_string())
+                    .filter(|username| !username.is_empty())
+                    .collect();
+                if let (false, Some(token)) = (participants.is_empty(), self.token.clone()) {
+                    let link = ctx.link().clone();
+                    spawn_local(async move {
+                        match create_dm(&token, &participants).await {
+                            Ok(dm) => link.send_message(Msg::CreateDmSuccess(dm)),
+                            Err(err) => link.send_message(Msg::DmFailure(err)),
+                        }
+                    });
+                }
+                false
+            }
+            Msg::CreateDmSuccess(dm) => {
+                // Starting a conversation that already exists opens it too
+                navigator.push(&Route::ChatRoom { room_id: dm.room_id });
+                false
+            }
+            Msg::DmFailure(err) => {
+                self.dm_error = Some(err);
+                true
+            }
+            Msg::UpdateDeletePassword(password) => {
+                self.delete_password = password;
+                true
+            }
+            Msg::DeleteAccount => {
+                let confirmed = web_sys::window()
+                    .and_then(|window| {
+                        window
+                            .confirm_with_message("Delete your account? Rooms you own pass to another member, and your messages stay without your name.")
+                            .ok()
+                    })
+                    .unwrap_or(false);
+                if let (true, Some(token)) = (confirmed, self.token.clone()) {
+                    let link = ctx.link().clone();
+                    let password = self.delete_password.clone();
+                    spawn_local(async move {
+                        match delete_account(&token, &password).await {
+                            // The token is revoked along with the account
+                            Ok(()) => link.send_message(Msg::LogoutSuccess),
+                            Err(err) => link.send_message(Msg::DeleteAccountFailure(err)),
+                        }
+                    });
+                }
+                false
+            }
+            Msg::DeleteAccountFailure(err) => {
+                self.delete_error = Some(err);
+                true
+            }
+            Msg::FetchSessions => {
+                if let Some(token) = self.token.clone() {
+                    let link = ctx.link().clone();
+                    spawn_local(async move {
+                        match get_sessions(&token).await {
+                            Ok(sessions) => link.send_message(Msg::FetchSessionsSuccess(sessions)),
+                            Err(err) => link.send_message(Msg::SessionsFailure(err)),
+                        }
+                    });
+                }
+                false
+            }
+            Msg::FetchSessionsSuccess(sessions) => {
+                self.sessions = sessions;
+                self.sessions_error = None;
+                true
+            }
+            Msg::RevokeSession(session_id) => {
+                // Revoking this browser's own session is logging out
+                let current = self.sessions.iter().any(|session| session.current && session.session_id == session_id);
+                if let Some(token) = self.token.clone() {
+                    let link = ctx.link().clone();
+                    spawn_local(async move {
+                        match revoke_session(&token, &session_id).await {
+                            Ok(()) if current => link.send_message(Msg::LogoutSuccess),
+                            Ok(()) => link.send_message(Msg::FetchSessions),
+                            Err(err) => link.send_message(Msg::SessionsFailure(err)),
+                        }
+                    });
+                }
+                false
+            }
+            Msg::RevokeOtherSessions => {
+                if let Some(token) = self.token.clone() {
+                    let link = ctx.link().clone();
+                    spawn_local(async move {
+                        match revoke_other_sessions(&token).await {
+                            Ok(message) => link.send_message(Msg::RevokeOtherSessionsSuccess(message)),
+                            Err(err) => link.send_message(Msg::SessionsFailure(err)),
+                        }
+                    });
+                }
+                false
+            }
+            Msg::RevokeOtherSessionsSuccess(message) => {
+                self.sessions_notice = Some(message);
+                ctx.link().send_message(Msg::FetchSessions);
+                true
+            }
+            Msg::UpdateCurrentPassword(password) => {
+                self.current_password = password;
+                true
+            }
+            Msg::UpdateNewPassword(password) => {
+                self.new_password = password;
+                true
+            }
+            Msg::ChangePassword => {
+                if let Some(token) = self.token.clone() {
+                    let link = ctx.link().clone();
+                    let current_password = self.current_password.clone();
+                    let new_password = self.new_password.clone();
+                    spawn_local(async move {
+                        match change_password(&token, &current_password, &new_password).await {
+                            Ok(message) => link.send_message(Msg::ChangePasswordSuccess(message)),
+                            Err(err) => link.send_message(Msg::ChangePasswordFailure(err)),
+                        }
+                    });
+                }
+                false
+            }
+            Msg::ChangePasswordSuccess(message) => {
+                self.current_password.clear();
+                self.new_password.clear();
+                self.password_notice = Some(message);
+                self.password_error = None;
+                // The other sessions were logged out with the old password
+                ctx.link().send_message(Msg::FetchSessions);
+                true
+            }
+            Msg::ChangePasswordFailure(err) => {
+                self.password_notice = None;
+                self.password_error = Some(err);
+                true
+            }
+            Msg::UpdateEmail(email) => {
+                self.email_input = email;
+                true
+            }
+            Msg::UpdateEmailPassword(password) => {
+                self.email_password = password;
+                true
+            }
+            Msg::SetEmail => {
+                if let Some(token) = self.token.clone() {
+                    let link = ctx.link().clone();
+                    let email = self.email_input.clone();
+                    let current_password = self.email_password.clone();
+                    spawn_local(async move {
+                        match set_email(&token, &email, &current_password).await {
+                            Ok(message) => link.send_message(Msg::SetEmailSuccess(message)),
+                            Err(err) => link.send_message(Msg::SetEmailFailure(err)),
+                        }
+                    });
+                }
+                false
+            }
+            Msg::SetEmailSuccess(message) => {
+                self.email_password.clear();
+                self.email_notice = Some(message);
+                self.email_error = None;
+                true
+            }
+            Msg::SetEmailFailure(err) => {
+                self.email_notice = None;
+                self.email_error = Some(err);
+                true
+            }
+            Msg::SessionsFailure(err) => {
+                self.sessions_error = Some(err);
+                true
+            }
         }
     }
 
@@ -153,7 +468,23 @@ impl Component for Dashboard {
             let input: HtmlInputElement = e.target_unchecked_into(); // Now works because HtmlInputElement is imported
             Msg::UpdateRoomName(input.value())
         });
+        let onchange_visibility = ctx.link().callback(|e: Event| {
+            let select: HtmlSelectElement = e.target_unchecked_into();
+            Msg::UpdateVisibility(RoomVisibility::ALL[select.selected_index().max(0) as usize])
+        });
         let onclick_create_room = ctx.link().callback(|_| Msg::CreateRoom);
+        let oninput_search = ctx.link().callback(|e: InputEvent| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            Msg::UpdateSearch(input.value())
+        });
+        let onchange_sort = ctx.link().callback(|e: Event| {
+            let select: HtmlSelectElement = e.target_unchecked_into();
+            Msg::UpdateSort(RoomSort::ALL[select.selected_index().max(0) as usize])
+        });
+        let oninput_dm = ctx.link().callback(|e: InputEvent| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            Msg::UpdateDmInput(input.value())
+        });
 
         html! {
             <div class="full-height">
@@ -173,6 +504,15 @@ impl Component for Dashboard {
                             placeholder="Enter room name"
                             class="input-box"
                         />
+                        <select onchange={onchange_visibility} class="input-box">
+                            {
+                                for RoomVisibility::ALL.into_iter().map(|visibility| html! {
+                                    <option selected={self.visibility_input == visibility}>
+                                        { visibility.label() }
+                                    </option>
+                                })
+                            }
+                        </select>
                         <button
                             onclick={onclick_create_room}
                             class="button"
@@ -183,26 +523,214 @@ impl Component for Dashboard {
                     <p class="description">
                         {"Welcome to the Dashboard page! Below is the list of available rooms."}
                     </p>
+                    <Link<Route> to={Route::Search} classes="link">{"Search messages"}</Link<Route>>
+                    <div class="input-group">
+                        <input
+                            type="text"
+                            value={self.search_input.clone()}
+                            oninput={oninput_search}
+                            placeholder="Find rooms by name"
+                            class="input-box"
+                        />
+                        <select onchange={onchange_sort} class="input-box">
+                            {
+                                for RoomSort::ALL.into_iter().map(|sort| html! {
+                                    <option selected={self.sort == sort}>{ sort.label() }</option>
+                                })
+                            }
+                        </select>
+                    </div>
                     if self.loading {
                         <p>{"Loading rooms..."}</p>
                     } else if let Some(error) = &self.error {
                         <p class="error">{format!("Error: {}", error)}</p>
-                    } else if let Some(rooms) = &self.rooms {
+                    } else if let Some(rooms) = self.rooms.as_ref().filter(|rooms| !rooms.rooms.is_empty()) {
                         <div class="room-card-list">
                             {
-                                for rooms.rooms.iter().map(|room| {
+                                for rooms.rooms.iter().map(|listing| {
                                     html! {
                                         // Use Link to pass the `room_id` dynamically
-                                        <Link<Route> to={Route::ChatRoom { room_id: room.room_id }}>
-                                            <RoomCard room={room.clone()} />
+                                        <Link<Route> to={Route::ChatRoom { room_id: listing.room.room_id }}>
+                                            <RoomCard listing={listing.clone()} />
                                         </Link<Route>>
                                     }
                                 })
                             }
                         </div>
+                        if rooms.next_cursor.is_some() {
+                            <button
+                                onclick={ctx.link().callback(|_| Msg::LoadMoreRooms)}
+                                class="button"
+                                disabled={self.loading_more}
+                            >
+                                { if self.loading_more { "Loading..." } else { "Load more rooms" } }
+                            </button>
+                        }
                     } else {
                         <p>{"No rooms available."}</p>
                     }
+
+                    <h2 class="heading">{"Direct messages"}</h2>
+                    <div class="input-group">
+                        <input
+                            type="text"
+                            value={self.dm_input.clone()}
+                            oninput={oninput_dm}
+                            placeholder="Usernames, separated by commas"
+                            class="input-box"
+                        />
+                        <button onclick={ctx.link().callback(|_| Msg::CreateDm)} class="button">
+                            {"Message"}
+                        </button>
+                    </div>
+                    if let Some(error) = &self.dm_error {
+                        <p class="error">{format!("Error: {}", error)}</p>
+                    }
+                    if self.dms.is_empty() {
+                        <p>{"No direct messages yet."}</p>
+                    } else {
+                        <div class="room-card-list">
+                            {
+                                for self.dms.iter().map(|dm| html! {
+                                    <Link<Route> to={Route::ChatRoom { room_id: dm.room_id }}>
+                                        <div class="room-card">
+                                            <h3 class="room-card-title">{ dm.title(&self.username) }</h3>
+                                            if let Some(last_message_at) = &dm.last_message_at {
+                                                <p class="room-card-detail">{format!("Last message: {}", last_message_at)}</p>
+                                            }
+                                        </div>
+                                    </Link<Route>>
+                                })
+                            }
+                        </div>
+                    }
+
+                    <h2 class="heading">{"Sessions"}</h2>
+                    if let Some(error) = &self.sessions_error {
+                        <p class="error">{format!("Error: {}", error)}</p>
+                    }
+                    if let Some(notice) = &self.sessions_notice {
+                        <p>{ notice }</p>
+                    }
+                    <div class="room-card-list">
+                        {
+                            for self.sessions.iter().map(|session| {
+                                let session_id = session.session_id.clone();
+                                html! {
+                                    <div class="room-card">
+                                        <h3 class="room-card-title">
+                                            { session.user_agent.clone().unwrap_or_else(|| "Unknown device".to_string()) }
+                                            if session.current {
+                                                {" (this browser)"}
+                                            }
+                                        </h3>
+                                        if let Some(ip_address) = &session.ip_address {
+                                            <p class="room-card-detail">{format!("Address: {}", ip_address)}</p>
+                                        }
+                                        <p class="room-card-detail">{format!("Signed in: {}", session.created_at)}</p>
+                                        <p class="room-card-detail">{format!("Last seen: {}", session.last_seen_at)}</p>
+                                        <button
+                                            onclick={ctx.link().callback(move |_| Msg::RevokeSession(session_id.clone()))}
+                                            class="button"
+                                        >
+                                            { if session.current { "Log out" } else { "Revoke" } }
+                                        </button>
+                                    </div>
+                                }
+                            })
+                        }
+                    </div>
+                    if self.sessions.iter().any(|session| !session.current) {
+                        <button onclick={ctx.link().callback(|_| Msg::RevokeOtherSessions)} class="button">
+                            {"Log out everywhere else"}
+                        </button>
+                    }
+
+                    <h2 class="heading">{"Change password"}</h2>
+                    <div class="input-group">
+                        <input
+                            type="password"
+                            value={self.current_password.clone()}
+                            oninput={ctx.link().callback(|e: InputEvent| {
+                                let input: HtmlInputElement = e.target_unchecked_into();
+                                Msg::UpdateCurrentPassword(input.value())
+                            })}
+                            placeholder="Current password"
+                            class="input-box"
+                        />
+                        <input
+                            type="password"
+                            value={self.new_password.clone()}
+                            oninput={ctx.link().callback(|e: InputEvent| {
+                                let input: HtmlInputElement = e.target_unchecked_into();
+                                Msg::UpdateNewPassword(input.value())
+                            })}
+                            placeholder="New password"
+                            class="input-box"
+                        />
+                        <button onclick={ctx.link().callback(|_| Msg::ChangePassword)} class="button">
+                            {"Change password"}
+                        </button>
+                    </div>
+                    if let Some(notice) = &self.password_notice {
+                        <p>{ notice }</p>
+                    }
+                    if let Some(error) = &self.password_error {
+                        <p class="error">{format!("Error: {}", error)}</p>
+                    }
+
+                    <h2 class="heading">{"Email address"}</h2>
+                    <div class="input-group">
+                        <input
+                            type="email"
+                            value={self.email_input.clone()}
+                            oninput={ctx.link().callback(|e: InputEvent| {
+                                let input: HtmlInputElement = e.target_unchecked_into();
+                                Msg::UpdateEmail(input.value())
+                            })}
+                            placeholder="Email (leave empty to remove)"
+                            class="input-box"
+                        />
+                        <input
+                            type="password"
+                            value={self.email_password.clone()}
+                            oninput={ctx.link().callback(|e: InputEvent| {
+                                let input: HtmlInputElement = e.target_unchecked_into();
+                                Msg::UpdateEmailPassword(input.value())
+                            })}
+                            placeholder="Current password"
+                            class="input-box"
+                        />
+                        <button onclick={ctx.link().callback(|_| Msg::SetEmail)} class="button">
+                            {"Save email"}
+                        </button>
+                    </div>
+                    if let Some(notice) = &self.email_notice {
+                        <p>{ notice }</p>
+                    }
+                    if let Some(error) = &self.email_error {
+                        <p class="error">{format!("Error: {}", error)}</p>
+                    }
+
+                    <h2 class="heading">{"Delete account"}</h2>
+                    <div class="input-group">
+                        <input
+                            type="password"
+                            value={self.delete_password.clone()}
+                            oninput={ctx.link().callback(|e: InputEvent| {
+                                let input: HtmlInputElement = e.target_unchecked_into();
+                                Msg::UpdateDeletePassword(input.value())
+                            })}
+                            placeholder="Current password"
+                            class="input-box"
+                        />
+                        <button onclick={ctx.link().callback(|_| Msg::DeleteAccount)} class="button">
+                            {"Delete account"}
+                        </button>
+                    </div>
+                    if let Some(error) = &self.delete_error {
+                        <p class="error">{format!("Error: {}", error)}</p>
+                    }
                 </main>
 
                 <Footer />
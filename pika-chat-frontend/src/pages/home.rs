@@ -6,13 +6,14 @@ use crate::components::footer::Footer;
 use crate::components::header::Header;
 use crate::routes::Route;
 use yew_router::prelude::use_navigator;
+use crate::services::auth::clear_tokens;
 use crate::services::utils::decode_username;
 
 #[function_component(Home)]
 pub fn home() -> Html {
-    let username = use_state(|| String::new());
+    let username = use_state(String::new);
     let navigator = use_navigator().expect("Navigator not available");
-    let avatar_url = use_state(|| String::new());
+    let avatar_url = use_state(String::new);
 
     {
         let username = username.clone();
@@ -35,7 +36,7 @@ pub fn home() -> Html {
                             }
                         } else {
                             info!("JWT Token is invalid or expired");
-                            LocalStorage::delete("jwtToken");
+                            clear_tokens();
                             LocalStorage::delete("avatarUrl"); // Remove avatar URL on invalid token
                         }
                     } else {
@@ -56,7 +57,7 @@ pub fn home() -> Html {
         info!("Creating logout callback");
         Callback::from(move |_| {
             gloo::console::log!("Performing logout");
-            LocalStorage::delete("jwtToken");
+            clear_tokens();
             username.set(String::new()); // Clear the username state
             avatar_url.set(String::new()); // Clear the avatar URL state
             navigator.push(&Route::Home); // Navigate back to Home
@@ -0,0 +1,126 @@
+use yew::prelude::*;
+use yew_router::prelude::*;
+use gloo::storage::{LocalStorage, Storage};
+use wasm_bindgen_futures::spawn_local;
+use crate::routes::Route;
+use crate::services::auth::{clear_tokens, logout};
+use crate::services::invite::{accept_invite, PENDING_INVITE_KEY};
+use crate::services::room::Room;
+use crate::services::utils::decode_username;
+use crate::components::footer::Footer;
+use crate::components::header::Header;
+
+pub enum Msg {
+    LogoutClicked,
+    LogoutDone,
+    Redeem,
+    RedeemSuccess(Room),
+    RedeemFailure(String),
+}
+
+#[derive(Clone, Properties, PartialEq)]
+pub struct Props {
+    pub code: String,
+}
+
+// Redeems an invite link and opens the room. Visitors who are not logged in are sent to the
+// login page first; the code is kept in local storage and redeemed once they are back.
+pub struct AcceptInvite {
+    token: Option<String>,
+    username: String,
+    avatar_url: Option<String>,
+    error: Option<String>,
+}
+
+impl Component for AcceptInvite {
+    type Message = Msg;
+    type Properties = Props;
+
+    fn create(ctx: &Context<Self>) -> Self {
+        let token = LocalStorage::get::<String>("jwtToken").ok();
+        let username = token.as_ref().and_then(|t| decode_username(t)).unwrap_or_default();
+        let avatar_url = LocalStorage::get::<String>("avatarUrl").ok();
+        ctx.link().send_message(Msg::Redeem);
+
+        Self {
+            token,
+            username,
+            avatar_url,
+            error: None,
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        let navigator = ctx.link().navigator().expect("No navigator available");
+
+        match msg {
+            Msg::LogoutClicked => {
+                if let Some(token) = self.token.clone() {
+                    let link = ctx.link().clone();
+                    spawn_local(async move {
+                        let _ = logout(&token).await;
+                        link.send_message(Msg::LogoutDone);
+                    });
+                } else {
+                    navigator.push(&Route::Home);
+                }
+                false
+            }
+            Msg::LogoutDone => {
+                clear_tokens();
+                navigator.push(&Route::Home);
+                true
+            }
+            Msg::Redeem => {
+                let token = match self.token.clone() {
+                    Some(token) if !self.username.is_empty() => token,
+                    _ => {
+                        LocalStorage::set(PENDING_INVITE_KEY, ctx.props().code.clone())
+                            .expect("Failed to save invite code");
+                        navigator.push(&Route::Login);
+                        return false;
+                    }
+                };
+                let code = ctx.props().code.clone();
+                let link = ctx.link().clone();
+                spawn_local(async move {
+                    match accept_invite(&token, &code).await {
+                        Ok(room) => link.send_message(Msg::RedeemSuccess(room)),
+                        Err(err) => link.send_message(Msg::RedeemFailure(err)),
+                    }
+                });
+                false
+            }
+            Msg::RedeemSuccess(room) => {
+                navigator.replace(&Route::ChatRoom { room_id: room.room_id });
+                false
+            }
+            Msg::RedeemFailure(err) => {
+                self.error = Some(err);
+                true
+            }
+        }
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        html! {
+            <div class="full-height">
+                <Header
+                    username={Some(self.username.clone())}
+                    avatar_url={self.avatar_url.clone()}
+                    on_logout={ctx.link().callback(|_| Msg::LogoutClicked)}
+                />
+                <main class="main">
+                    <h1 class="heading">{"Room invite"}</h1>
+                    if let Some(error) = &self.error {
+                        <p class="error">{ format!("Error: {}", error) }</p>
+                        <Link<Route> to={Route::Dashboard} classes="link">{"Back to the dashboard"}</Link<Route>>
+                    } else {
+                        <p class="description">{"Joining the room..."}</p>
+                    }
+                </main>
+                <Footer />
+            </div>
+        }
+    }
+}
@@ -5,6 +5,7 @@ use crate::components::form_input::FormInput;
 use crate::components::footer::Footer;
 use crate::components::header::Header;
 use crate::services::auth::{Credentials, login};
+use crate::services::invite::PENDING_INVITE_KEY;
 use crate::routes::Route;
 
 pub struct Login {
@@ -17,7 +18,7 @@ pub enum Msg {
     UpdateUsername(String),
     UpdatePassword(String),
     Submit,
-    LoginSuccess { token: String, username: String, avatar_url: Option<String> },
+    LoginSuccess { token: String, refresh_token: String, username: String, avatar_url: Option<String> },
     LoginFailure(String),
 }
 
@@ -31,6 +32,7 @@ impl Component for Login {
                 username: String::new(),
                 password: String::new(),
                 avatar_url: None, // Only used by register
+                email: None,      // Only used by register
             },
             error: None,
             avatar_url: None, // Initialize avatar_url as None
@@ -55,6 +57,7 @@ impl Component for Login {
                     match login(&credentials).await {
                         Ok(response) => link.send_message(Msg::LoginSuccess {
                             token: response.token,
+                            refresh_token: response.refresh_token,
                             username: credentials.username.clone(),
                             avatar_url: response.avatar_url,
                         }),
@@ -63,9 +66,11 @@ impl Component for Login {
                 });                
                 false
             }
-            Msg::LoginSuccess { token, username, avatar_url } => {
+            Msg::LoginSuccess { token, refresh_token, username, avatar_url } => {
                 // Save the JWT token to local storage
                 LocalStorage::set("jwtToken", token).expect("Failed to save token");
+                // The refresh token renews the short-lived JWT token
+                LocalStorage::set("refreshToken", refresh_token).expect("Failed to save refresh token");
                 
                 // Save avatar_url to local storage (if available)
                 if let Some(url) = avatar_url.clone() {
@@ -79,9 +84,15 @@ impl Component for Login {
                 self.credentials.username = username;
                 self.avatar_url = avatar_url;
             
-                // Redirect to the dashboard
+                // Redirect to the invite link that sent the user here, if any, or to the dashboard
                 let navigator = ctx.link().navigator().unwrap();
-                navigator.push(&Route::Dashboard);
+                match LocalStorage::get::<String>(PENDING_INVITE_KEY) {
+                    Ok(code) => {
+                        LocalStorage::delete(PENDING_INVITE_KEY);
+                        navigator.push(&Route::Invite { code });
+                    }
+                    Err(_) => navigator.push(&Route::Dashboard),
+                }
                 false
             }            
             Msg::LoginFailure(error) => {
@@ -126,6 +137,11 @@ impl Component for Login {
                                 { "Login" }
                             </button>
                         </form>
+                        <p class="register-link">
+                            <a href="/reset-password" class="link">
+                                { "Forgot password?" }
+                            </a>
+                        </p>
                         <p class="register-link">
                             <a href="/register" class="link">
                                 { "Don't have an account? Register" }
@@ -3,3 +3,6 @@ pub mod register;
 pub mod dashboard;
 pub mod home;
 pub mod chatroom;
+pub mod search;
+pub mod invite;
+pub mod reset_password;
@@ -15,6 +15,7 @@ pub struct Register {
 pub enum Msg {
     UpdateUsername(String),
     UpdatePassword(String),
+    UpdateEmail(String),
     SelectAvatar(String),
     Submit,
     RegisterSuccess,
@@ -31,6 +32,7 @@ impl Component for Register {
                 username: String::new(),
                 password: String::new(),
                 avatar_url: None, // Initialize avatar_url as None
+                email: None,
             },
             error: None,
             success: false,
@@ -47,6 +49,10 @@ impl Component for Register {
                 self.credentials.password = password;
                 true
             }
+            Msg::UpdateEmail(email) => {
+                self.credentials.email = Some(email);
+                true
+            }
             Msg::SelectAvatar(avatar) => {
                 self.credentials.avatar_url = Some(avatar); // Directly update avatar_url in credentials
                 true
@@ -83,7 +89,7 @@ impl Component for Register {
     }
 
     fn view(&self, ctx: &Context<Self>) -> Html {
-        let avatar_options = vec![
+        let avatar_options = [
             "/static/avatar1.png".to_string(),
             "/static/avatar2.png".to_string(),
             "/static/avatar3.png".to_string(),
@@ -122,6 +128,13 @@ impl Component for Register {
                                 value={self.credentials.password.clone()}
                                 oninput={ctx.link().callback(Msg::UpdatePassword)}
                             />
+                            <FormInput
+                                label="Email (optional, to reset a forgotten password)"
+                                placeholder="Enter your email"
+                                input_type="email"
+                                value={self.credentials.email.clone().unwrap_or_default()}
+                                oninput={ctx.link().callback(Msg::UpdateEmail)}
+                            />
 
                             <div class="avatar-selection">
                                 <label>{ "Select an Avatar" }</label>
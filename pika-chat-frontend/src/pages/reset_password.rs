@@ -0,0 +1,145 @@
+use yew::prelude::*;
+use wasm_bindgen_futures::spawn_local;
+use crate::components::form_input::FormInput;
+use crate::components::footer::Footer;
+use crate::components::header::Header;
+use crate::services::auth::{forgot_password, reset_password};
+
+pub enum Msg {
+    UpdateEmail(String),
+    UpdatePassword(String),
+    Submit,
+    Done(String),
+    Failure(String),
+}
+
+#[derive(Clone, Properties, PartialEq)]
+pub struct Props {
+    #[prop_or_default]
+    pub token: Option<String>, // From the emailed link; without it the page asks for a link
+}
+
+// Resets a forgotten password in two steps: without a token the page emails a reset link, and
+// the link opens the page again with its token to choose the new password.
+pub struct ResetPassword {
+    email: String,
+    password: String,
+    error: Option<String>,
+    message: Option<String>,
+    done: bool,
+}
+
+impl Component for ResetPassword {
+    type Message = Msg;
+    type Properties = Props;
+
+    fn create(_ctx: &Context<Self>) -> Self {
+        Self {
+            email: String::new(),
+            password: String::new(),
+            error: None,
+            message: None,
+            done: false,
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            Msg::UpdateEmail(email) => {
+                self.email = email;
+                true
+            }
+            Msg::UpdatePassword(password) => {
+                self.password = password;
+                true
+            }
+            Msg::Submit => {
+                let link = ctx.link().clone();
+                let token = ctx.props().token.clone();
+                let email = self.email.clone();
+                let password = self.password.clone();
+
+                spawn_local(async move {
+                    let result = match token {
+                        Some(token) => reset_password(&token, &password).await,
+                        None => forgot_password(&email).await,
+                    };
+                    match result {
+                        Ok(message) => link.send_message(Msg::Done(message)),
+                        Err(error) => link.send_message(Msg::Failure(error)),
+                    }
+                });
+                false
+            }
+            Msg::Done(message) => {
+                self.message = Some(message);
+                self.error = None;
+                self.done = true;
+                true
+            }
+            Msg::Failure(error) => {
+                self.error = Some(error);
+                true
+            }
+        }
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let resetting = ctx.props().token.is_some();
+
+        html! {
+            <div class="full-height">
+                <Header />
+                <main class="main">
+                    <div class="login-card">
+                        <h1 class="login-heading">
+                            { if resetting { "Choose a New Password" } else { "Forgot Password" } }
+                        </h1>
+                        if !self.done {
+                            <form
+                                class="login-form"
+                                onsubmit={ctx.link().callback(|e: SubmitEvent| {
+                                    e.prevent_default();
+                                    Msg::Submit
+                                })}
+                            >
+                                if resetting {
+                                    <FormInput
+                                        label="New password"
+                                        placeholder="Enter your new password"
+                                        input_type="password"
+                                        value={self.password.clone()}
+                                        oninput={ctx.link().callback(Msg::UpdatePassword)}
+                                    />
+                                } else {
+                                    <FormInput
+                                        label="Email"
+                                        placeholder="Enter the email of your account"
+                                        input_type="email"
+                                        value={self.email.clone()}
+                                        oninput={ctx.link().callback(Msg::UpdateEmail)}
+                                    />
+                                }
+                                if let Some(error) = &self.error {
+                                    <p class="error-message">{ error.clone() }</p>
+                                }
+                                <button type="submit" class="button-primary">
+                                    { if resetting { "Reset Password" } else { "Send Reset Link" } }
+                                </button>
+                            </form>
+                        }
+                        if let Some(message) = &self.message {
+                            <p class="success-message">{ message.clone() }</p>
+                        }
+                        <p class="register-link">
+                            <a href="/login" class="link">
+                                { "Back to login" }
+                            </a>
+                        </p>
+                    </div>
+                </main>
+                <Footer />
+            </div>
+        }
+    }
+}
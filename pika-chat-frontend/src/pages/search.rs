@@ -0,0 +1,321 @@
+use yew::prelude::*;
+use yew_router::prelude::*;
+use gloo::storage::{LocalStorage, Storage};
+use wasm_bindgen_futures::spawn_local;
+use web_sys::{HtmlInputElement, HtmlSelectElement};
+use crate::routes::Route;
+use crate::services::auth::{clear_tokens, logout};
+use crate::services::message::StoredMessage;
+use crate::services::room::{get_rooms, Room, RoomSort};
+use crate::services::search::{search_messages, SearchFilters, SearchResponse, SearchResult, SnippetPart};
+use crate::services::utils::decode_username;
+use crate::components::footer::Footer;
+use crate::components::header::Header;
+
+pub enum Msg {
+    LogoutClicked,
+    LogoutDone,
+    FetchRoomsSuccess(Vec<Room>),
+    UpdateQuery(String),
+    UpdateRoom(Option<i64>),
+    UpdateAuthor(String),
+    UpdateFrom(String),
+    UpdateTo(String),
+    Search,
+    LoadMore,
+    SearchSuccess { response: SearchResponse, append: bool },
+    SearchError(String),
+    OpenResult { room_id: i64, message_id: i64 },
+}
+
+pub struct Search {
+    token: Option<String>,
+    username: String,
+    avatar_url: Option<String>,
+    rooms: Vec<Room>,        // Rooms offered in the room filter
+    filters: SearchFilters,  // Filters being edited in the form
+    searched: Option<SearchFilters>, // Filters of the results on screen, used to load more
+    results: Vec<SearchResult>,
+    has_more: bool,
+    loading: bool,
+    error: Option<String>,
+}
+
+impl Search {
+    fn run_search(&mut self, ctx: &Context<Self>, filters: SearchFilters, append: bool) {
+        let token = match self.token.clone() {
+            Some(token) => token,
+            None => return,
+        };
+        let offset = if append { self.results.len() } else { 0 };
+        let link = ctx.link().clone();
+        self.loading = true;
+        self.error = None;
+        self.searched = Some(filters.clone());
+
+        spawn_local(async move {
+            match search_messages(&token, &filters, offset).await {
+                Ok(response) => link.send_message(Msg::SearchSuccess { response, append }),
+                Err(err) => link.send_message(Msg::SearchError(err)),
+            }
+        });
+    }
+
+    fn view_snippet(parts: &[SnippetPart]) -> Html {
+        html! {
+            <p style="margin: 0.25rem 0;">
+                {
+                    for parts.iter().map(|part| {
+                        if part.highlight {
+                            html! { <mark style="background-color: #fde68a;">{ part.text.clone() }</mark> }
+                        } else {
+                            html! { <span>{ part.text.clone() }</span> }
+                        }
+                    })
+                }
+            </p>
+        }
+    }
+
+    fn view_context(message: &Option<StoredMessage>) -> Html {
+        match message {
+            Some(message) if message.deleted_at.is_none() => html! {
+                <p style="margin: 0.25rem 0; color: #9ca3af; font-size: 0.875rem;">
+                    { format!("{}: {}", message.username, message.content) }
+                </p>
+            },
+            _ => html! {},
+        }
+    }
+
+    fn view_result(ctx: &Context<Self>, result: &SearchResult) -> Html {
+        let room_id = result.message.room_id;
+        let message_id = result.message.message_id;
+        let onclick = ctx.link().callback(move |_| Msg::OpenResult { room_id, message_id });
+
+        html! {
+            <div
+                {onclick}
+                style="padding: 0.75rem; margin-bottom: 0.75rem; border: 1px solid #e5e7eb; border-radius: 0.5rem; cursor: pointer;"
+            >
+                <p style="margin: 0; color: #6b7280; font-size: 0.875rem;">
+                    { format!("#{} · {} · {}", result.room_name, result.message.username, result.message.created_at) }
+                    if result.message.parent_id.is_some() {
+                        { " · in thread" }
+                    }
+                </p>
+                { Self::view_context(&result.context_before) }
+                { Self::view_snippet(&result.snippet) }
+                { Self::view_context(&result.context_after) }
+            </div>
+        }
+    }
+}
+
+impl Component for Search {
+    type Message = Msg;
+    type Properties = ();
+
+    fn create(ctx: &Context<Self>) -> Self {
+        let token = LocalStorage::get::<String>("jwtToken").ok();
+        let username = token.as_ref().and_then(|t| decode_username(t)).unwrap_or_default();
+        let avatar_url = LocalStorage::get::<String>("avatarUrl").ok();
+
+        // The room filter only lists rooms the user belongs to, which is also what the search covers
+        if let Some(token) = token.clone() {
+            let link = ctx.link().clone();
+            spawn_local(async move {
+                match get_rooms(&token, None, RoomSort::Activity, None).await {
+                    Ok(response) => link.send_message(Msg::FetchRoomsSuccess(
+                        response
+                            .rooms
+                            .into_iter()
+                            .filter(|listing| listing.joined)
+                            .map(|listing| listing.room)
+                            .collect(),
+                    )),
+                    Err(err) => log::warn!("Failed to load rooms for the search filter: {}", err),
+                }
+            });
+        }
+
+        Self {
+            token,
+            username,
+            avatar_url,
+            rooms: Vec::new(),
+            filters: SearchFilters::default(),
+            searched: None,
+            results: Vec::new(),
+            has_more: false,
+            loading: false,
+            error: None,
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        let navigator = ctx.link().navigator().expect("No navigator available");
+
+        match msg {
+            Msg::LogoutClicked => {
+                if let Some(token) = self.token.clone() {
+                    let link = ctx.link().clone();
+                    spawn_local(async move {
+                        let _ = logout(&token).await;
+                        link.send_message(Msg::LogoutDone);
+                    });
+                } else {
+                    navigator.push(&Route::Home);
+                }
+                false
+            }
+            Msg::LogoutDone => {
+                clear_tokens();
+                navigator.push(&Route::Home);
+                true
+            }
+            Msg::FetchRoomsSuccess(rooms) => {
+                self.rooms = rooms;
+                true
+            }
+            Msg::UpdateQuery(q) => {
+                self.filters.q = q;
+                true
+            }
+            Msg::UpdateRoom(room_id) => {
+                self.filters.room_id = room_id;
+                true
+            }
+            Msg::UpdateAuthor(author) => {
+                self.filters.author = author;
+                true
+            }
+            Msg::UpdateFrom(from) => {
+                self.filters.from = from;
+                true
+            }
+            Msg::UpdateTo(to) => {
+                self.filters.to = to;
+                true
+            }
+            Msg::Search => {
+                if self.filters.q.trim().is_empty() {
+                    self.error = Some("Enter something to search for".into());
+                    return true;
+                }
+                self.run_search(ctx, self.filters.clone(), false);
+                true
+            }
+            Msg::LoadMore => {
+                // Keep paging through the search on screen even if the form has been edited since
+                if let Some(filters) = self.searched.clone() {
+                    self.run_search(ctx, filters, true);
+                }
+                true
+            }
+            Msg::SearchSuccess { response, append } => {
+                if append {
+                    self.results.extend(response.results);
+                } else {
+                    self.results = response.results;
+                }
+                self.has_more = response.has_more;
+                self.loading = false;
+                true
+            }
+            Msg::SearchError(err) => {
+                self.error = Some(err);
+                self.loading = false;
+                true
+            }
+            Msg::OpenResult { room_id, message_id } => {
+                navigator.push(&Route::ChatRoomMessage { room_id, message_id });
+                false
+            }
+        }
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let link = ctx.link();
+        let input_value = |e: InputEvent| -> String {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            input.value()
+        };
+        let oninput_query = link.callback(move |e: InputEvent| Msg::UpdateQuery(input_value(e)));
+        let oninput_author = link.callback(move |e: InputEvent| Msg::UpdateAuthor(input_value(e)));
+        let oninput_from = link.callback(move |e: InputEvent| Msg::UpdateFrom(input_value(e)));
+        let oninput_to = link.callback(move |e: InputEvent| Msg::UpdateTo(input_value(e)));
+        let onchange_room = link.callback(|e: Event| {
+            let select: HtmlSelectElement = e.target_unchecked_into();
+            Msg::UpdateRoom(select.value().parse().ok())
+        });
+        let onkeypress_query = link.batch_callback(|e: KeyboardEvent| {
+            if e.key() == "Enter" { Some(Msg::Search) } else { None }
+        });
+
+        html! {
+            <div class="full-height">
+                <Header
+                    username={Some(self.username.clone())}
+                    avatar_url={self.avatar_url.clone()}
+                    on_logout={link.callback(|_| Msg::LogoutClicked)}
+                />
+                <main class="main">
+                    <h1 class="heading">{"Search messages"}</h1>
+                    <div class="input-group">
+                        <input
+                            type="text"
+                            value={self.filters.q.clone()}
+                            oninput={oninput_query}
+                            onkeypress={onkeypress_query}
+                            placeholder="Search your rooms"
+                            class="input-box"
+                        />
+                        <button onclick={link.callback(|_| Msg::Search)} class="button" disabled={self.loading}>
+                            {"Search"}
+                        </button>
+                    </div>
+                    <div style="display: flex; flex-wrap: wrap; gap: 0.5rem; margin-bottom: 1rem;">
+                        <select onchange={onchange_room} class="input-box">
+                            <option value="" selected={self.filters.room_id.is_none()}>{"All rooms"}</option>
+                            {
+                                for self.rooms.iter().map(|room| html! {
+                                    <option
+                                        value={room.room_id.to_string()}
+                                        selected={self.filters.room_id == Some(room.room_id)}
+                                    >
+                                        { room.room_name.clone() }
+                                    </option>
+                                })
+                            }
+                        </select>
+                        <input
+                            type="text"
+                            value={self.filters.author.clone()}
+                            oninput={oninput_author}
+                            placeholder="Author"
+                            class="input-box"
+                        />
+                        <input type="date" value={self.filters.from.clone()} oninput={oninput_from} class="input-box" />
+                        <input type="date" value={self.filters.to.clone()} oninput={oninput_to} class="input-box" />
+                    </div>
+                    if let Some(error) = &self.error {
+                        <p class="error">{ format!("Error: {}", error) }</p>
+                    }
+                    if self.searched.is_some() && self.results.is_empty() && !self.loading {
+                        <p class="description">{"No messages found."}</p>
+                    }
+                    <div>
+                        { for self.results.iter().map(|result| Self::view_result(ctx, result)) }
+                    </div>
+                    if self.has_more {
+                        <button onclick={link.callback(|_| Msg::LoadMore)} class="button" disabled={self.loading}>
+                            { if self.loading { "Loading..." } else { "Load more" } }
+                        </button>
+                    }
+                </main>
+                <Footer />
+            </div>
+        }
+    }
+}
@@ -5,6 +5,9 @@ use crate::pages::register::Register;
 use crate::pages::dashboard::Dashboard;
 use crate::pages::home::Home;
 use crate::pages::chatroom::ChatRoom;
+use crate::pages::search::Search;
+use crate::pages::invite::AcceptInvite;
+use crate::pages::reset_password::ResetPassword;
 
 // Define your app's routes
 #[derive(Clone, Routable, PartialEq)]
@@ -17,6 +20,16 @@ pub enum Route {
     Register,
     #[at("/chatroom/:room_id")]
     ChatRoom { room_id: i64 },
+    #[at("/chatroom/:room_id/messages/:message_id")]
+    ChatRoomMessage { room_id: i64, message_id: i64 }, // Opens the room at a message, e.g. from search
+    #[at("/search")]
+    Search,
+    #[at("/invite/:code")]
+    Invite { code: String }, // Redeems an invite link, after logging in if needed
+    #[at("/reset-password")]
+    ForgotPassword, // Asks for a password reset link by email
+    #[at("/reset-password/:token")]
+    ResetPassword { token: String }, // Opened from the emailed link to choose a new password
     #[at("/")]
     Home,
 }
@@ -29,5 +42,12 @@ pub fn switch(route: &Route) -> Html {
         Route::Home => html! { <Home /> },
         Route::Dashboard => html! { <Dashboard /> },
         Route::ChatRoom { room_id } => html! { <ChatRoom room_id={*room_id} /> },
+        Route::ChatRoomMessage { room_id, message_id } => html! {
+            <ChatRoom room_id={*room_id} focus_message_id={Some(*message_id)} />
+        },
+        Route::Search => html! { <Search /> },
+        Route::Invite { code } => html! { <AcceptInvite code={code.clone()} /> },
+        Route::ForgotPassword => html! { <ResetPassword /> },
+        Route::ResetPassword { token } => html! { <ResetPassword token={Some(token.clone())} /> },
     }
 }
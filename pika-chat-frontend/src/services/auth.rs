@@ -1,16 +1,31 @@
+use gloo::storage::{LocalStorage, Storage};
 use gloo_net::http::Request;
+use futures::future::{FutureExt, LocalBoxFuture, Shared};
 use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use crate::services::utils::token_expiry;
+
+// Access tokens are refreshed this many seconds before they expire
+const REFRESH_MARGIN_SECS: usize = 30;
+
+thread_local! {
+    // The refresh in progress, awaited by every request that finds the access token about to expire.
+    // A refresh token is good for one use, so two refreshes at once would revoke the session.
+    static REFRESH: RefCell<Option<Shared<LocalBoxFuture<'static, Result<String, String>>>>> = RefCell::new(None);
+}
 
 #[derive(Serialize, Clone, PartialEq)]
 pub struct Credentials {
     pub username: String,
     pub password: String,
     pub avatar_url: Option<String>, // Optional field for avatar URL, only used by register
+    pub email: Option<String>,      // Optional, only used by register; needed to reset a forgotten password
 }
 
 #[derive(Deserialize, Debug)]
 pub struct LoginResponse {
     pub token: String,
+    pub refresh_token: String,
     pub avatar_url: Option<String>,
 }
 
@@ -19,6 +34,90 @@ pub struct ErrorResponse {
     pub error: String,
 }
 
+#[derive(Serialize)]
+struct RefreshRequest<'a> {
+    refresh_token: &'a str,
+}
+
+// Forgets the session, e.g. on logout or when the server no longer accepts its tokens
+pub fn clear_tokens() {
+    LocalStorage::delete("jwtToken");
+    LocalStorage::delete("refreshToken");
+}
+
+// Whether the access token expires within REFRESH_MARGIN_SECS
+fn expires_soon(token: &str) -> bool {
+    let now = js_sys::Date::now() as usize / 1000;
+    token_expiry(token).map_or(true, |exp| exp <= now + REFRESH_MARGIN_SECS)
+}
+
+// The access token to send with a request: the latest one in local storage, refreshed first if
+// it is about to expire. `token` is used if nothing is stored. If the refresh fails the token is
+// returned as is, and the request fails as it would have without refreshing.
+pub async fn access_token(token: &str) -> String {
+    let current = LocalStorage::get::<String>("jwtToken").unwrap_or_else(|_| token.to_string());
+    if !expires_soon(&current) {
+        return current;
+    }
+
+    let refresh = REFRESH.with(|refresh| {
+        refresh
+            .borrow_mut()
+            .get_or_insert_with(|| {
+                async {
+                    let result = refresh_tokens().await;
+                    REFRESH.with(|refresh| refresh.borrow_mut().take());
+                    result
+                }
+                .boxed_local()
+                .shared()
+            })
+            .clone()
+    });
+    match refresh.await {
+        Ok(token) => token,
+        Err(error) => {
+            log::warn!("Failed to refresh access token: {}", error);
+            current
+        }
+    }
+}
+
+// Exchanges the stored refresh token for new tokens and stores them
+async fn refresh_tokens() -> Result<String, String> {
+    // Another tab may have refreshed already
+    if let Ok(token) = LocalStorage::get::<String>("jwtToken") {
+        if !expires_soon(&token) {
+            return Ok(token);
+        }
+    }
+    let refresh_token = LocalStorage::get::<String>("refreshToken").map_err(|_| "Not logged in".to_string())?;
+
+    let response = Request::post("http://127.0.0.1:8080/api/token/refresh")
+        .header("Content-Type", "application/json")
+        .json(&RefreshRequest { refresh_token: &refresh_token })
+        .map_err(|_| "Failed to serialize request".to_string())?
+        .send()
+        .await
+        .map_err(|_| "Failed to connect to the server".to_string())?;
+
+    if (200..300).contains(&response.status()) {
+        let tokens = response.json::<LoginResponse>()
+            .await
+            .map_err(|_| "Invalid server response".to_string())?;
+        LocalStorage::set("jwtToken", &tokens.token).map_err(|_| "Failed to save token".to_string())?;
+        LocalStorage::set("refreshToken", &tokens.refresh_token).map_err(|_| "Failed to save token".to_string())?;
+        Ok(tokens.token)
+    } else {
+        // The session has ended or was revoked
+        clear_tokens();
+        let err: ErrorResponse = response.json::<ErrorResponse>()
+            .await
+            .map_err(|_| "Invalid error response from server".to_string())?;
+        Err(err.error)
+    }
+}
+
 pub async fn login(credentials: &Credentials) -> Result<LoginResponse, String> {
     let response = Request::post("http://127.0.0.1:8080/api/login")
         .header("Content-Type", "application/json")
@@ -65,7 +164,7 @@ pub async fn register(credentials: &Credentials) -> Result<(), String> {
 pub async fn logout(token: &str) -> Result<(), String> {
     let response = Request::post("http://127.0.0.1:8080/api/logout")
         // Include the Bearer token in the Authorization header
-        .header("Authorization", &format!("Bearer {}", token))
+        .header("Authorization", &format!("Bearer {}", access_token(token).await))
         .send()
         .await
         .map_err(|_| "Failed to connect to the server".to_string())?;
@@ -79,3 +178,222 @@ pub async fn logout(token: &str) -> Result<(), String> {
         Err(err.error)
     }
 }
+
+#[derive(Serialize)]
+struct DeleteAccountRequest<'a> {
+    password: &'a str,
+}
+
+// Deletes the current user's account; the password confirms it
+pub async fn delete_account(token: &str, password: &str) -> Result<(), String> {
+    let response = Request::delete("http://127.0.0.1:8080/api/account")
+        .header("Authorization", &format!("Bearer {}", access_token(token).await))
+        .header("Content-Type", "application/json")
+        .json(&DeleteAccountRequest { password })
+        .map_err(|_| "Failed to serialize request".to_string())?
+        .send()
+        .await
+        .map_err(|_| "Failed to connect to the server".to_string())?;
+
+    if (200..300).contains(&response.status()) {
+        Ok(())
+    } else {
+        let err: ErrorResponse = response.json::<ErrorResponse>()
+            .await
+            .map_err(|_| "Invalid error response from server".to_string())?;
+        Err(err.error)
+    }
+}
+
+/// A login session of the current user, on one device
+#[derive(Deserialize, Clone, PartialEq, Debug)]
+pub struct Session {
+    pub session_id: String,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: String,
+    pub last_seen_at: String,
+    pub current: bool, // The session this browser is logged in with
+}
+
+#[derive(Deserialize)]
+struct MessageResponse {
+    message: String,
+}
+
+// Lists the user's live sessions, most recently seen first
+pub async fn get_sessions(token: &str) -> Result<Vec<Session>, String> {
+    let response = Request::get("http://127.0.0.1:8080/api/sessions")
+        .header("Authorization", &format!("Bearer {}", access_token(token).await))
+        .send()
+        .await
+        .map_err(|_| "Failed to connect to the server".to_string())?;
+
+    if (200..300).contains(&response.status()) {
+        response.json::<Vec<Session>>()
+            .await
+            .map_err(|_| "Invalid server response".to_string())
+    } else {
+        let err: ErrorResponse = response.json::<ErrorResponse>()
+            .await
+            .map_err(|_| "Invalid error response from server".to_string())?;
+        Err(err.error)
+    }
+}
+
+// Logs one session out; its device can no longer use its tokens
+pub async fn revoke_session(token: &str, session_id: &str) -> Result<(), String> {
+    let response = Request::delete(&format!("http://127.0.0.1:8080/api/sessions/{}", session_id))
+        .header("Authorization", &format!("Bearer {}", access_token(token).await))
+        .send()
+        .await
+        .map_err(|_| "Failed to connect to the server".to_string())?;
+
+    if (200..300).contains(&response.status()) {
+        Ok(())
+    } else {
+        let err: ErrorResponse = response.json::<ErrorResponse>()
+            .await
+            .map_err(|_| "Invalid error response from server".to_string())?;
+        Err(err.error)
+    }
+}
+
+// Logs out every session but the current one and returns the server's summary
+pub async fn revoke_other_sessions(token: &str) -> Result<String, String> {
+    let response = Request::delete("http://127.0.0.1:8080/api/sessions")
+        .header("Authorization", &format!("Bearer {}", access_token(token).await))
+        .send()
+        .await
+        .map_err(|_| "Failed to connect to the server".to_string())?;
+
+    if (200..300).contains(&response.status()) {
+        response.json::<MessageResponse>()
+            .await
+            .map(|response| response.message)
+            .map_err(|_| "Invalid server response".to_string())
+    } else {
+        let err: ErrorResponse = response.json::<ErrorResponse>()
+            .await
+            .map_err(|_| "Invalid error response from server".to_string())?;
+        Err(err.error)
+    }
+}
+
+#[derive(Serialize)]
+struct ChangePasswordRequest<'a> {
+    current_password: &'a str,
+    new_password: &'a str,
+}
+
+// Changes the current user's password; every other session is logged out
+pub async fn change_password(token: &str, current_password: &str, new_password: &str) -> Result<String, String> {
+    let response = Request::put("http://127.0.0.1:8080/api/account/password")
+        .header("Authorization", &format!("Bearer {}", access_token(token).await))
+        .header("Content-Type", "application/json")
+        .json(&ChangePasswordRequest { current_password, new_password })
+        .map_err(|_| "Failed to serialize request".to_string())?
+        .send()
+        .await
+        .map_err(|_| "Failed to connect to the server".to_string())?;
+
+    if (200..300).contains(&response.status()) {
+        response.json::<MessageResponse>()
+            .await
+            .map(|response| response.message)
+            .map_err(|_| "Invalid server response".to_string())
+    } else {
+        let err: ErrorResponse = response.json::<ErrorResponse>()
+            .await
+            .map_err(|_| "Invalid error response from server".to_string())?;
+        Err(err.error)
+    }
+}
+
+#[derive(Serialize)]
+struct SetEmailRequest<'a> {
+    email: &'a str,
+    current_password: &'a str,
+}
+
+// Sets the address password reset links go to; an empty email removes it
+pub async fn set_email(token: &str, email: &str, current_password: &str) -> Result<String, String> {
+    let response = Request::put("http://127.0.0.1:8080/api/account/email")
+        .header("Authorization", &format!("Bearer {}", access_token(token).await))
+        .header("Content-Type", "application/json")
+        .json(&SetEmailRequest { email, current_password })
+        .map_err(|_| "Failed to serialize request".to_string())?
+        .send()
+        .await
+        .map_err(|_| "Failed to connect to the server".to_string())?;
+
+    if (200..300).contains(&response.status()) {
+        response.json::<MessageResponse>()
+            .await
+            .map(|response| response.message)
+            .map_err(|_| "Invalid server response".to_string())
+    } else {
+        let err: ErrorResponse = response.json::<ErrorResponse>()
+            .await
+            .map_err(|_| "Invalid error response from server".to_string())?;
+        Err(err.error)
+    }
+}
+
+#[derive(Serialize)]
+struct ForgotPasswordRequest<'a> {
+    email: &'a str,
+}
+
+// Asks for a password reset link by email. The server answers the same whether or not the
+// address belongs to an account, so success only means the request was accepted.
+pub async fn forgot_password(email: &str) -> Result<String, String> {
+    let response = Request::post("http://127.0.0.1:8080/api/password/forgot")
+        .header("Content-Type", "application/json")
+        .json(&ForgotPasswordRequest { email })
+        .map_err(|_| "Failed to serialize request".to_string())?
+        .send()
+        .await
+        .map_err(|_| "Failed to connect to the server".to_string())?;
+
+    if (200..300).contains(&response.status()) {
+        response.json::<MessageResponse>()
+            .await
+            .map(|response| response.message)
+            .map_err(|_| "Invalid server response".to_string())
+    } else {
+        let err: ErrorResponse = response.json::<ErrorResponse>()
+            .await
+            .map_err(|_| "Invalid error response from server".to_string())?;
+        Err(err.error)
+    }
+}
+
+#[derive(Serialize)]
+struct ResetPasswordRequest<'a> {
+    token: &'a str,
+    new_password: &'a str,
+}
+
+// Sets a new password with the token from a reset link; every session is logged out
+pub async fn reset_password(reset_token: &str, new_password: &str) -> Result<String, String> {
+    let response = Request::post("http://127.0.0.1:8080/api/password/reset")
+        .header("Content-Type", "application/json")
+        .json(&ResetPasswordRequest { token: reset_token, new_password })
+        .map_err(|_| "Failed to serialize request".to_string())?
+        .send()
+        .await
+        .map_err(|_| "Failed to connect to the server".to_string())?;
+
+    if (200..300).contains(&response.status()) {
+        response.json::<MessageResponse>()
+            .await
+            .map(|response| response.message)
+            .map_err(|_| "Invalid server response".to_string())
+    } else {
+        let err: ErrorResponse = response.json::<ErrorResponse>()
+            .await
+            .map_err(|_| "Invalid error response from server".to_string())?;
+        Err(err.error)
+    }
+}
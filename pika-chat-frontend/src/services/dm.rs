@@ -0,0 +1,77 @@
+use gloo_net::http::Request;
+use serde::{Deserialize, Serialize};
+use crate::services::auth::access_token;
+use crate::services::room::{ErrorResponse, RoomMember};
+
+#[derive(Serialize)]
+struct DmRequest<'a> {
+    participants: &'a [String],
+}
+
+// A direct message conversation, opened like any other room through its `room_id`
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct DirectMessage {
+    pub room_id: i64,
+    pub participants: Vec<RoomMember>, // Includes the current user
+    pub last_message_at: Option<String>,
+}
+
+impl DirectMessage {
+    // Names the conversation after everyone in it but the current user
+    pub fn title(&self, my_username: &str) -> String {
+        let others: Vec<&str> = self
+            .participants
+            .iter()
+            .map(|participant| participant.username.as_str())
+            .filter(|username| *username != my_username)
+            .collect();
+        if others.is_empty() {
+            "Just you".to_string()
+        } else {
+            others.join(", ")
+        }
+    }
+}
+
+// Starts a direct message with the given users, or returns the existing one with exactly them
+pub async fn create_dm(token: &str, participants: &[String]) -> Result<DirectMessage, String> {
+    let response = Request::post("http://127.0.0.1:8080/api/dms")
+        .header("Authorization", &format!("Bearer {}", access_token(token).await))
+        .header("Content-Type", "application/json")
+        .json(&DmRequest { participants })
+        .map_err(|_| "Failed to serialize request".to_string())?
+        .send()
+        .await
+        .map_err(|_| "Failed to connect to the server".to_string())?;
+
+    if response.ok() {
+        response.json::<DirectMessage>()
+            .await
+            .map_err(|_| "Failed to parse server response".to_string())
+    } else {
+        let err: ErrorResponse = response.json::<ErrorResponse>()
+            .await
+            .map_err(|_| "Invalid error response from server".to_string())?;
+        Err(err.error)
+    }
+}
+
+// Lists the user's direct messages, most recently active first
+pub async fn get_dms(token: &str) -> Result<Vec<DirectMessage>, String> {
+    let response = Request::get("http://127.0.0.1:8080/api/dms")
+        .header("Authorization", &format!("Bearer {}", access_token(token).await))
+        .send()
+        .await
+        .map_err(|_| "Failed to connect to the server".to_string())?;
+
+    if response.ok() {
+        response.json::<Vec<DirectMessage>>()
+            .await
+            .map_err(|_| "Failed to parse server response".to_string())
+    } else {
+        let err: ErrorResponse = response.json::<ErrorResponse>()
+            .await
+            .map_err(|_| "Invalid error response from server".to_string())?;
+        Err(err.error)
+    }
+}
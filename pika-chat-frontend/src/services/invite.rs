@@ -0,0 +1,86 @@
+use gloo_net::http::Request;
+use serde::{Deserialize, Serialize};
+use crate::services::auth::access_token;
+use crate::services::room::{ErrorResponse, Room};
+
+// Local storage key holding an invite code to redeem once the user has logged in
+pub const PENDING_INVITE_KEY: &str = "pendingInvite";
+
+#[derive(Serialize, Default)]
+pub struct InviteLinkRequest {
+    pub expires_in_secs: Option<i64>, // Never expires if `None`
+    pub max_uses: Option<i64>,        // Unlimited if `None`
+    pub username: Option<String>,     // Only this user may redeem the link, if set
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[allow(dead_code)]
+pub struct InviteLink {
+    pub code: String,
+    pub room_id: i64,
+    pub created_by: i64,
+    pub user_id: Option<i64>,
+    pub username: Option<String>,
+    pub max_uses: Option<i64>,
+    pub uses: i64,
+    pub expires_at: Option<String>,
+    pub created_at: String,
+}
+
+impl InviteLink {
+    // The address to share, served by the `/invite/:code` route
+    pub fn url(&self) -> String {
+        let origin = web_sys::window()
+            .and_then(|window| window.location().origin().ok())
+            .unwrap_or_default();
+        format!("{}/invite/{}", origin, self.code)
+    }
+}
+
+pub async fn create_invite_link(token: &str, room_id: i64, request: &InviteLinkRequest) -> Result<InviteLink, String> {
+    let response = Request::post(&format!(
+        "http://127.0.0.1:8080/api/rooms/{}/invites",
+        room_id
+    ))
+    .header("Authorization", &format!("Bearer {}", access_token(token).await))
+    .header("Content-Type", "application/json")
+    .json(request)
+    .map_err(|_| "Failed to serialize request".to_string())?
+    .send()
+    .await
+    .map_err(|_| "Failed to connect to the server".to_string())?;
+
+    if response.status() == 201 {
+        response.json::<InviteLink>()
+            .await
+            .map_err(|_| "Failed to parse server response".to_string())
+    } else {
+        let err: ErrorResponse = response.json::<ErrorResponse>()
+            .await
+            .map_err(|_| "Invalid error response from server".to_string())?;
+        Err(err.error)
+    }
+}
+
+// Joins the room behind an invite code; returns the room to open
+pub async fn accept_invite(token: &str, code: &str) -> Result<Room, String> {
+    let response = Request::post(&format!(
+        "http://127.0.0.1:8080/api/invites/{}/accept",
+        js_sys::encode_uri_component(code)
+    ))
+    .header("Authorization", &format!("Bearer {}", access_token(token).await))
+    .send()
+    .await
+    .map_err(|_| "Failed to connect to the server".to_string())?;
+
+    if response.ok() {
+        response.json::<Room>()
+            .await
+            .map_err(|_| "Failed to parse server response".to_string())
+    } else {
+        let err: ErrorResponse = response.json::<ErrorResponse>()
+            .await
+            .map_err(|_| "Invalid error response from server".to_string())?;
+        Err(err.error)
+    }
+}
@@ -1,4 +1,7 @@
+use gloo_net::http::Request;
 use serde::{Deserialize, Serialize};
+use crate::services::auth::access_token;
+use crate::services::room::ErrorResponse;
 
 pub type RoomId = i64;
 
@@ -20,3 +23,215 @@ impl Message {
         Message { room_id, message}
     }
 }
+
+// A message loaded from the room history endpoint
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct StoredMessage {
+    pub message_id: i64,
+    pub room_id: RoomId,
+    pub user_id: i64,
+    pub username: String,
+    pub content: String,
+    pub created_at: String,
+    pub edited_at: Option<String>,
+    pub deleted_at: Option<String>, // Set on tombstones, whose content is empty
+    pub parent_id: Option<i64>,     // Root message of the thread, for replies
+    pub reply_count: i64,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct MessagesResponse {
+    pub messages: Vec<StoredMessage>, // Ordered from oldest to newest
+    pub has_more: bool,
+    pub reactions: Vec<ReactionSummary>, // Reactions to the messages on this page
+}
+
+// A thread's root message with a page of its replies
+#[derive(Deserialize, Debug)]
+pub struct ThreadResponse {
+    pub root: StoredMessage,
+    pub replies: Vec<StoredMessage>, // Ordered from oldest to newest
+    #[allow(dead_code)]
+    pub has_more: bool,
+    pub reactions: Vec<ReactionSummary>,
+}
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct ReactionUser {
+    pub user_id: i64,
+    pub username: String,
+}
+
+// Everyone who reacted to a message with one emoji
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct ReactionSummary {
+    pub message_id: i64,
+    pub emoji: String,
+    pub count: i64,
+    pub users: Vec<ReactionUser>,
+}
+
+// Fetches a page of room history; without `before` or `after` this returns the latest messages
+pub async fn get_room_messages(
+    token: &str,
+    room_id: RoomId,
+    before: Option<i64>,
+    after: Option<i64>,
+) -> Result<MessagesResponse, String> {
+    let mut url = format!("http://127.0.0.1:8080/api/rooms/{}/messages", room_id);
+    if let Some(before) = before {
+        url.push_str(&format!("?before={}", before));
+    } else if let Some(after) = after {
+        url.push_str(&format!("?after={}", after));
+    }
+
+    let response = Request::get(&url)
+        .header("Authorization", &format!("Bearer {}", access_token(token).await))
+        .send()
+        .await
+        .map_err(|_| "Failed to connect to the server".to_string())?;
+
+    if response.ok() {
+        response.json::<MessagesResponse>()
+            .await
+            .map_err(|_| "Failed to parse server response".to_string())
+    } else {
+        let err: ErrorResponse = response.json::<ErrorResponse>()
+            .await
+            .map_err(|_| "Invalid error response from server".to_string())?;
+        Err(err.error)
+    }
+}
+
+// Fetches a thread; `message_id` may be the root or any reply
+pub async fn get_thread(token: &str, room_id: RoomId, message_id: i64) -> Result<ThreadResponse, String> {
+    let response = Request::get(&format!(
+        "http://127.0.0.1:8080/api/rooms/{}/messages/{}/thread?limit=100",
+        room_id, message_id
+    ))
+    .header("Authorization", &format!("Bearer {}", access_token(token).await))
+    .send()
+    .await
+    .map_err(|_| "Failed to connect to the server".to_string())?;
+
+    if response.ok() {
+        response.json::<ThreadResponse>()
+            .await
+            .map_err(|_| "Failed to parse server response".to_string())
+    } else {
+        let err: ErrorResponse = response.json::<ErrorResponse>()
+            .await
+            .map_err(|_| "Invalid error response from server".to_string())?;
+        Err(err.error)
+    }
+}
+
+// Replaces the content of one of the user's messages; the room is told via a `message_edited` event
+pub async fn edit_message(
+    token: &str,
+    room_id: RoomId,
+    message_id: i64,
+    content: &str,
+) -> Result<StoredMessage, String> {
+    let response = Request::put(&format!(
+        "http://127.0.0.1:8080/api/rooms/{}/messages/{}",
+        room_id, message_id
+    ))
+    .header("Authorization", &format!("Bearer {}", access_token(token).await))
+    .header("Content-Type", "application/json")
+    .json(&MessageRequest { content: content.to_string() })
+    .map_err(|_| "Failed to serialize request".to_string())?
+    .send()
+    .await
+    .map_err(|_| "Failed to connect to the server".to_string())?;
+
+    if response.ok() {
+        response.json::<StoredMessage>()
+            .await
+            .map_err(|_| "Failed to parse server response".to_string())
+    } else {
+        let err: ErrorResponse = response.json::<ErrorResponse>()
+            .await
+            .map_err(|_| "Invalid error response from server".to_string())?;
+        Err(err.error)
+    }
+}
+
+// Deletes a message, leaving a tombstone; the room is told via a `message_deleted` event
+pub async fn delete_message(token: &str, room_id: RoomId, message_id: i64) -> Result<(), String> {
+    let response = Request::delete(&format!(
+        "http://127.0.0.1:8080/api/rooms/{}/messages/{}",
+        room_id, message_id
+    ))
+    .header("Authorization", &format!("Bearer {}", access_token(token).await))
+    .send()
+    .await
+    .map_err(|_| "Failed to connect to the server".to_string())?;
+
+    if response.ok() {
+        Ok(())
+    } else {
+        let err: ErrorResponse = response.json::<ErrorResponse>()
+            .await
+            .map_err(|_| "Invalid error response from server".to_string())?;
+        Err(err.error)
+    }
+}
+
+fn reaction_url(room_id: RoomId, message_id: i64, emoji: &str) -> String {
+    let emoji: String = js_sys::encode_uri_component(emoji).into();
+    format!(
+        "http://127.0.0.1:8080/api/rooms/{}/messages/{}/reactions/{}",
+        room_id, message_id, emoji
+    )
+}
+
+// Reacts to a message; the room is told via a `reaction_added` event
+pub async fn add_reaction(
+    token: &str,
+    room_id: RoomId,
+    message_id: i64,
+    emoji: &str,
+) -> Result<Vec<ReactionSummary>, String> {
+    let response = Request::put(&reaction_url(room_id, message_id, emoji))
+        .header("Authorization", &format!("Bearer {}", access_token(token).await))
+        .send()
+        .await
+        .map_err(|_| "Failed to connect to the server".to_string())?;
+
+    if response.ok() {
+        response.json::<Vec<ReactionSummary>>()
+            .await
+            .map_err(|_| "Failed to parse server response".to_string())
+    } else {
+        let err: ErrorResponse = response.json::<ErrorResponse>()
+            .await
+            .map_err(|_| "Invalid error response from server".to_string())?;
+        Err(err.error)
+    }
+}
+
+// Takes back a reaction; the room is told via a `reaction_removed` event
+pub async fn remove_reaction(
+    token: &str,
+    room_id: RoomId,
+    message_id: i64,
+    emoji: &str,
+) -> Result<Vec<ReactionSummary>, String> {
+    let response = Request::delete(&reaction_url(room_id, message_id, emoji))
+        .header("Authorization", &format!("Bearer {}", access_token(token).await))
+        .send()
+        .await
+        .map_err(|_| "Failed to connect to the server".to_string())?;
+
+    if response.ok() {
+        response.json::<Vec<ReactionSummary>>()
+            .await
+            .map_err(|_| "Failed to parse server response".to_string())
+    } else {
+        let err: ErrorResponse = response.json::<ErrorResponse>()
+            .await
+            .map_err(|_| "Invalid error response from server".to_string())?;
+        Err(err.error)
+    }
+}
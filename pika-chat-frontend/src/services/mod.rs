@@ -3,3 +3,7 @@ pub mod room;
 pub mod utils;
 pub mod message;
 pub mod websocket;
+pub mod protocol;
+pub mod search;
+pub mod invite;
+pub mod dm;
@@ -0,0 +1,149 @@
+// Client side of the JSON protocol spoken over `/ws/rooms/{room_id}`.
+// Mirrors `pika-chatroom-server/src/websockets/protocol.rs`; keep the two in sync.
+
+use serde::{Deserialize, Serialize};
+use crate::services::message::StoredMessage;
+use crate::services::room::{Role, UserPresence};
+
+// Version of the protocol this client speaks, sent in the `hello` handshake
+pub const PROTOCOL_VERSION: u32 = 1;
+
+// A command sent to the server, wrapped with its correlation id
+#[derive(Serialize, Debug)]
+pub struct ClientFrame {
+    pub id: String,
+    #[serde(flatten)]
+    pub command: ClientCommand,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+#[allow(dead_code)]
+pub enum ClientCommand {
+    Hello { version: u32 },
+    Send {
+        content: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        parent_id: Option<i64>, // Set to reply in a thread
+    },
+    Typing { is_typing: bool },
+    Ack { message_id: i64 },
+    Ping,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerEvent {
+    Welcome {
+        version: u32,
+        room_id: i64,
+        user_id: i64,
+        username: String,
+    },
+    Message { message: StoredMessage },
+    MessageEdited { message: StoredMessage },
+    MessageDeleted {
+        room_id: i64,
+        message_id: i64,
+        parent_id: Option<i64>,
+        deleted_at: String,
+    },
+    ReactionAdded {
+        room_id: i64,
+        message_id: i64,
+        user_id: i64,
+        username: String,
+        emoji: String,
+    },
+    ReactionRemoved {
+        room_id: i64,
+        message_id: i64,
+        user_id: i64,
+        username: String,
+        emoji: String,
+    },
+    System { room_id: i64, message: String },
+    Presence {
+        room_id: i64,
+        user_id: i64,
+        username: String,
+        change: PresenceChange,
+    },
+    PresenceSnapshot {
+        room_id: i64,
+        users: Vec<UserPresence>,
+    },
+    RoleChanged {
+        room_id: i64,
+        user_id: i64,
+        username: String,
+        role: Role,
+    },
+    RoomUpdated {
+        room_id: i64,
+        room_name: String,
+        topic: Option<String>,
+        description: Option<String>,
+        archived: bool,
+    },
+    RoomDeleted { room_id: i64 },
+    MemberRemoved {
+        room_id: i64,
+        user_id: i64,
+        username: String,
+        cause: RemovalCause,
+    },
+    SessionRevoked, // The server closes the connection right after this event
+    MemberMuted {
+        room_id: i64,
+        user_id: i64,
+        username: String,
+        muted_until: Option<String>, // Absent when the mute was lifted
+    },
+    SlowModeChanged { room_id: i64, seconds: i64 },
+    Typing {
+        room_id: i64,
+        user_id: i64,
+        username: String,
+        is_typing: bool,
+    },
+    Ack { id: String, message_id: Option<i64> },
+    Error {
+        id: Option<String>,
+        code: ErrorCode,
+        message: String,
+        #[serde(default)]
+        retry_after_secs: Option<i64>, // Set when a mute or slow mode cooldown is running
+    },
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PresenceChange {
+    Online,
+    Offline,
+    Joined,
+    Left,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RemovalCause {
+    Left,
+    Kicked,
+    Banned,
+    AccountDeleted,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    MalformedFrame,
+    UnsupportedVersion,
+    HandshakeRequired,
+    InvalidCommand,
+    Forbidden,
+    Muted,
+    SlowMode,
+    InternalError,
+}
@@ -1,10 +1,44 @@
 use gloo_net::http::Request;
 use serde::{Deserialize, Serialize};
+use crate::services::auth::access_token;
+use crate::services::protocol::PresenceChange;
 
 // Room related models
 #[derive(Serialize, Clone, PartialEq)]
 pub struct RoomInfo {
     pub room_name: String,
+    pub visibility: RoomVisibility,
+}
+
+// Who can find and join a room (mirrors the server's models/room.rs)
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum RoomVisibility {
+    #[default]
+    Public,   // Listed for everyone, anyone can join
+    Unlisted, // Not listed, anyone with the room ID can join
+    Private,  // Not listed, only invited users can join
+}
+
+impl RoomVisibility {
+    pub const ALL: [RoomVisibility; 3] = [RoomVisibility::Public, RoomVisibility::Unlisted, RoomVisibility::Private];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            RoomVisibility::Public => "Public",
+            RoomVisibility::Unlisted => "Unlisted",
+            RoomVisibility::Private => "Private",
+        }
+    }
+}
+
+// What a room is for (mirrors the server's models/room.rs)
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum RoomKind {
+    #[default]
+    Channel, // A named room
+    Dm,      // A direct message, listed separately from rooms
 }
 
 #[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
@@ -12,13 +46,131 @@ pub struct Room {
     pub room_id: i64,
     pub room_name: String,
     pub user_id: i64,
+    pub visibility: RoomVisibility,
+    pub kind: RoomKind,
+    pub topic: Option<String>,
+    pub description: Option<String>,
+    pub archived_at: Option<String>, // Set while the room is archived and read-only
+    pub slow_mode_secs: i64,         // Seconds members must wait between messages; 0 when slow mode is off
+}
+
+// Changes to a room; `None` leaves a field as it is and an empty topic or description clears it
+#[derive(Serialize, Default, Clone, PartialEq)]
+pub struct UpdateRoomRequest {
+    pub room_name: Option<String>,
+    pub topic: Option<String>,
+    pub description: Option<String>,
+    pub archived: Option<bool>,
 }
 
 #[derive(Deserialize, Debug)]
 #[allow(dead_code)]
 pub struct RoomsResponse {
     pub req_user_id: i64,
-    pub rooms: Vec<Room>,
+    pub rooms: Vec<RoomListing>,
+    pub next_cursor: Option<String>, // Pass back to `get_rooms` for the next page; absent on the last page
+}
+
+// A room as listed in the room directory
+#[derive(PartialEq, Clone, Debug, Deserialize)]
+pub struct RoomListing {
+    #[serde(flatten)]
+    pub room: Room,
+    pub member_count: i64,
+    pub online_count: i64,
+    pub joined: bool,             // Whether the current user is a member
+    pub last_activity_at: String, // The latest message, or when the room was created (UTC)
+}
+
+// Order of the room directory (mirrors the server's RoomSort)
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum RoomSort {
+    #[default]
+    Created,
+    Activity,
+    Members,
+}
+
+impl RoomSort {
+    pub const ALL: [RoomSort; 3] = [RoomSort::Created, RoomSort::Activity, RoomSort::Members];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            RoomSort::Created => "Newest",
+            RoomSort::Activity => "Most active",
+            RoomSort::Members => "Most members",
+        }
+    }
+
+    fn as_query(self) -> &'static str {
+        match self {
+            RoomSort::Created => "created",
+            RoomSort::Activity => "activity",
+            RoomSort::Members => "members",
+        }
+    }
+}
+
+// A member's role in a room, from most to least privileged (mirrors the server's models/role.rs)
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Owner,
+    Admin,
+    Moderator,
+    Member,
+}
+
+impl Role {
+    // Roles that can be handed out with `set_member_role`; ownership is never assigned this way
+    pub const ASSIGNABLE: [Role; 3] = [Role::Admin, Role::Moderator, Role::Member];
+
+    fn rank(self) -> u8 {
+        match self {
+            Role::Owner => 3,
+            Role::Admin => 2,
+            Role::Moderator => 1,
+            Role::Member => 0,
+        }
+    }
+
+    pub fn outranks(self, other: Role) -> bool {
+        self.rank() > other.rank()
+    }
+
+    // Moderators and above may delete anyone's messages
+    pub fn can_moderate(self) -> bool {
+        self.rank() >= Role::Moderator.rank()
+    }
+
+    // Moderators and above may invite users, which is the only way into a private room
+    pub fn can_invite(self) -> bool {
+        self.rank() >= Role::Moderator.rank()
+    }
+
+    // Moderators and above may kick, ban and mute members ranked below them, and set slow mode
+    pub fn can_remove_members(self) -> bool {
+        self.rank() >= Role::Moderator.rank()
+    }
+
+    // Admins and owners may promote and demote members ranked below them
+    pub fn can_manage_roles(self) -> bool {
+        self.rank() >= Role::Admin.rank()
+    }
+
+    // Admins and owners may change the room's settings and create invite links
+    pub fn can_manage_room(self) -> bool {
+        self.rank() >= Role::Admin.rank()
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Role::Owner => "Owner",
+            Role::Admin => "Admin",
+            Role::Moderator => "Moderator",
+            Role::Member => "Member",
+        }
+    }
 }
 
 #[derive(Deserialize, Debug, Clone, PartialEq)]
@@ -26,6 +178,65 @@ pub struct RoomMember {
     pub user_id: i64,
     pub username: String,
     pub avatar_url: Option<String>,
+    pub role: Role,
+    pub joined_at: Option<String>, // UTC
+}
+
+// A member with their presence, as listed page by page by `GET /api/rooms/{id}/members`
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[allow(dead_code)]
+pub struct MemberListing {
+    #[serde(flatten)]
+    pub member: RoomMember,
+    pub is_online: bool,
+    pub in_room: bool,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RoomMembersResponse {
+    pub members: Vec<MemberListing>, // Ordered by username
+    pub next_cursor: Option<String>, // Username to pass as `after` for the next page
+}
+
+#[derive(Serialize)]
+struct RoleRequest {
+    role: Role,
+}
+
+#[derive(Serialize)]
+struct TransferOwnershipRequest {
+    user_id: i64,
+}
+
+#[derive(Serialize)]
+struct InvitationRequest<'a> {
+    username: &'a str,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[allow(dead_code)]
+pub struct RoomInvitation {
+    pub room_id: i64,
+    pub user_id: i64,
+    pub username: String,
+    pub invited_by: i64,
+    pub created_at: String,
+}
+
+#[derive(Serialize)]
+struct MuteRequest {
+    duration_secs: i64,
+}
+
+#[derive(Serialize)]
+struct SlowModeRequest {
+    seconds: i64,
+}
+
+#[derive(Serialize)]
+struct BanRequest<'a> {
+    username: &'a str,
+    reason: Option<&'a str>,
 }
 
 #[derive(Deserialize)]
@@ -34,9 +245,23 @@ pub struct ErrorResponse {
 }
 
 // Service functions for room-related backend interactions
-pub async fn get_rooms(token: &str) -> Result<RoomsResponse, String> {
-    let response = Request::get("http://127.0.0.1:8080/api/rooms")
-        .header("Authorization", &format!("Bearer {}", token))
+pub async fn get_rooms(
+    token: &str,
+    q: Option<&str>,
+    sort: RoomSort,
+    cursor: Option<&str>,
+) -> Result<RoomsResponse, String> {
+    let encode = |value: &str| -> String { js_sys::encode_uri_component(value).into() };
+    let mut url = format!("http://127.0.0.1:8080/api/rooms?sort={}", sort.as_query());
+    if let Some(q) = q {
+        url.push_str(&format!("&q={}", encode(q)));
+    }
+    if let Some(cursor) = cursor {
+        url.push_str(&format!("&cursor={}", encode(cursor)));
+    }
+
+    let response = Request::get(&url)
+        .header("Authorization", &format!("Bearer {}", access_token(token).await))
         .send()
         .await
         .map_err(|_| "Failed to connect to the server".to_string())?;
@@ -55,7 +280,7 @@ pub async fn get_rooms(token: &str) -> Result<RoomsResponse, String> {
 
 pub async fn create_room(token: &str, room_info: &RoomInfo) -> Result<Room, String> {
     let response = Request::post("http://127.0.0.1:8080/api/rooms")
-        .header("Authorization", &format!("Bearer {}", token))
+        .header("Authorization", &format!("Bearer {}", access_token(token).await))
         .header("Content-Type", "application/json")
         .json(room_info)
         .map_err(|_| "Failed to serialize request".to_string())?
@@ -75,18 +300,150 @@ pub async fn create_room(token: &str, room_info: &RoomInfo) -> Result<Room, Stri
     }
 }
 
+pub async fn get_room(token: &str, room_id: i64) -> Result<Room, String> {
+    let response = Request::get(&format!("http://127.0.0.1:8080/api/rooms/{}", room_id))
+        .header("Authorization", &format!("Bearer {}", access_token(token).await))
+        .send()
+        .await
+        .map_err(|_| "Failed to connect to the server".to_string())?;
+
+    if response.ok() {
+        response.json::<Room>()
+            .await
+            .map_err(|_| "Failed to parse server response".to_string())
+    } else {
+        let err: ErrorResponse = response.json::<ErrorResponse>()
+            .await
+            .map_err(|_| "Invalid error response from server".to_string())?;
+        Err(err.error)
+    }
+}
+
+// Renames the room, sets its topic and description, or archives it; returns the updated room
+pub async fn update_room(token: &str, room_id: i64, request: &UpdateRoomRequest) -> Result<Room, String> {
+    let response = Request::patch(&format!("http://127.0.0.1:8080/api/rooms/{}", room_id))
+        .header("Authorization", &format!("Bearer {}", access_token(token).await))
+        .header("Content-Type", "application/json")
+        .json(request)
+        .map_err(|_| "Failed to serialize request".to_string())?
+        .send()
+        .await
+        .map_err(|_| "Failed to connect to the server".to_string())?;
+
+    if response.ok() {
+        response.json::<Room>()
+            .await
+            .map_err(|_| "Failed to parse server response".to_string())
+    } else {
+        let err: ErrorResponse = response.json::<ErrorResponse>()
+            .await
+            .map_err(|_| "Invalid error response from server".to_string())?;
+        Err(err.error)
+    }
+}
+
+pub async fn delete_room(token: &str, room_id: i64) -> Result<(), String> {
+    let response = Request::delete(&format!("http://127.0.0.1:8080/api/rooms/{}", room_id))
+        .header("Authorization", &format!("Bearer {}", access_token(token).await))
+        .send()
+        .await
+        .map_err(|_| "Failed to connect to the server".to_string())?;
+
+    if response.ok() {
+        Ok(())
+    } else {
+        let err: ErrorResponse = response.json::<ErrorResponse>()
+            .await
+            .map_err(|_| "Invalid error response from server".to_string())?;
+        Err(err.error)
+    }
+}
+
+// Fetches one page of a room's members, optionally only those whose username contains `q`
+pub async fn get_room_members_page(
+    token: &str,
+    room_id: i64,
+    after: Option<&str>,
+    q: Option<&str>,
+) -> Result<RoomMembersResponse, String> {
+    let encode = |value: &str| -> String { js_sys::encode_uri_component(value).into() };
+    let mut url = format!("http://127.0.0.1:8080/api/rooms/{}/members?limit=100", room_id);
+    if let Some(after) = after {
+        url.push_str(&format!("&after={}", encode(after)));
+    }
+    if let Some(q) = q {
+        url.push_str(&format!("&q={}", encode(q)));
+    }
+
+    let response = Request::get(&url)
+        .header("Authorization", &format!("Bearer {}", access_token(token).await))
+        .send()
+        .await
+        .map_err(|_| "Failed to connect to the server".to_string())?;
+
+    if response.ok() {
+        response.json::<RoomMembersResponse>()
+            .await
+            .map_err(|_| "Failed to parse server response".to_string())
+    } else {
+        let err: ErrorResponse = response.json::<ErrorResponse>()
+            .await
+            .map_err(|_| "Invalid error response from server".to_string())?;
+        Err(err.error)
+    }
+}
+
+// Fetches every member of a room, following the pages to the end
 pub async fn get_room_members(token: &str, room_id: i64) -> Result<Vec<RoomMember>, String> {
-    let response = Request::get(&format!(
+    let mut members = Vec::new();
+    let mut after: Option<String> = None;
+    loop {
+        let page = get_room_members_page(token, room_id, after.as_deref(), None).await?;
+        members.extend(page.members.into_iter().map(|listing| listing.member));
+        match page.next_cursor {
+            Some(cursor) => after = Some(cursor),
+            None => return Ok(members),
+        }
+    }
+}
+
+#[allow(dead_code)]
+pub async fn add_room_member(token: &str, room_id: i64) -> Result<(), String> {
+    let response = Request::post(&format!(
         "http://127.0.0.1:8080/api/rooms/{}/members",
         room_id
     ))
-    .header("Authorization", &format!("Bearer {}", token))
+    .header("Authorization", &format!("Bearer {}", access_token(token).await))
+    .send()
+    .await
+    .map_err(|_| "Failed to connect to the server".to_string())?;
+
+    if response.ok() {
+        Ok(())
+    } else {
+        let err: ErrorResponse = response.json::<ErrorResponse>()
+            .await
+            .map_err(|_| "Invalid error response from server".to_string())?;
+        Err(err.error)
+    }
+}
+
+// Promotes or demotes a member; returns the member with their new role
+pub async fn set_member_role(token: &str, room_id: i64, user_id: i64, role: Role) -> Result<RoomMember, String> {
+    let response = Request::put(&format!(
+        "http://127.0.0.1:8080/api/rooms/{}/members/{}/role",
+        room_id, user_id
+    ))
+    .header("Authorization", &format!("Bearer {}", access_token(token).await))
+    .header("Content-Type", "application/json")
+    .json(&RoleRequest { role })
+    .map_err(|_| "Failed to serialize request".to_string())?
     .send()
     .await
     .map_err(|_| "Failed to connect to the server".to_string())?;
 
     if response.ok() {
-        response.json::<Vec<RoomMember>>()
+        response.json::<RoomMember>()
             .await
             .map_err(|_| "Failed to parse server response".to_string())
     } else {
@@ -97,19 +454,50 @@ pub async fn get_room_members(token: &str, room_id: i64) -> Result<Vec<RoomMembe
     }
 }
 
-#[allow(dead_code)]
-pub async fn add_room_member(token: &str, room_id: i64) -> Result<(), String> {
-    let response = Request::post(&format!(
-        "http://127.0.0.1:8080/api/rooms/{}/members",
+// Makes another member the owner of the room; the current owner becomes an admin
+pub async fn transfer_ownership(token: &str, room_id: i64, user_id: i64) -> Result<Room, String> {
+    let response = Request::put(&format!(
+        "http://127.0.0.1:8080/api/rooms/{}/owner",
         room_id
     ))
-    .header("Authorization", &format!("Bearer {}", token))
+    .header("Authorization", &format!("Bearer {}", access_token(token).await))
+    .header("Content-Type", "application/json")
+    .json(&TransferOwnershipRequest { user_id })
+    .map_err(|_| "Failed to serialize request".to_string())?
     .send()
     .await
     .map_err(|_| "Failed to connect to the server".to_string())?;
 
     if response.ok() {
-        Ok(())
+        response.json::<Room>()
+            .await
+            .map_err(|_| "Failed to parse server response".to_string())
+    } else {
+        let err: ErrorResponse = response.json::<ErrorResponse>()
+            .await
+            .map_err(|_| "Invalid error response from server".to_string())?;
+        Err(err.error)
+    }
+}
+
+// Invites a user by username; they can then join the room even when it is private
+pub async fn invite_user(token: &str, room_id: i64, username: &str) -> Result<RoomInvitation, String> {
+    let response = Request::post(&format!(
+        "http://127.0.0.1:8080/api/rooms/{}/invitations",
+        room_id
+    ))
+    .header("Authorization", &format!("Bearer {}", access_token(token).await))
+    .header("Content-Type", "application/json")
+    .json(&InvitationRequest { username })
+    .map_err(|_| "Failed to serialize request".to_string())?
+    .send()
+    .await
+    .map_err(|_| "Failed to connect to the server".to_string())?;
+
+    if response.status() == 201 {
+        response.json::<RoomInvitation>()
+            .await
+            .map_err(|_| "Failed to parse server response".to_string())
     } else {
         let err: ErrorResponse = response.json::<ErrorResponse>()
             .await
@@ -121,14 +509,34 @@ pub async fn add_room_member(token: &str, room_id: i64) -> Result<(), String> {
 #[derive(Deserialize, Debug, Clone, PartialEq)]
 pub struct UserPresence {
     pub user_id: i64,
-    pub is_online: bool,
+    pub is_online: bool, // Connected anywhere
+    pub in_room: bool,   // Connected to this room
 }
+
+impl UserPresence {
+    // Applies a presence change pushed over the WebSocket
+    pub fn apply(&mut self, change: PresenceChange) {
+        match change {
+            PresenceChange::Online => self.is_online = true,
+            PresenceChange::Offline => {
+                self.is_online = false;
+                self.in_room = false;
+            }
+            PresenceChange::Joined => {
+                self.is_online = true;
+                self.in_room = true;
+            }
+            PresenceChange::Left => self.in_room = false,
+        }
+    }
+}
+
 pub async fn get_user_presence(token: &str, room_id: i64) -> Result<Vec<UserPresence>, String> {
     let response = Request::get(&format!(
         "http://127.0.0.1:8080/api/users/presence/{}",
         room_id
     ))
-    .header("Authorization", &format!("Bearer {}", token))
+    .header("Authorization", &format!("Bearer {}", access_token(token).await))
     .send()
     .await
     .map_err(|_| "Failed to connect to the server".to_string())?;
@@ -156,3 +564,113 @@ pub async fn get_user_presence(token: &str, room_id: i64) -> Result<Vec<UserPres
         Err(err.error)
     }
 }
+
+// Leaves a room; the owner cannot leave
+pub async fn leave_room(token: &str, room_id: i64) -> Result<(), String> {
+    let response = Request::delete(&format!("http://127.0.0.1:8080/api/rooms/{}/members/me", room_id))
+        .header("Authorization", &format!("Bearer {}", access_token(token).await))
+        .send()
+        .await
+        .map_err(|_| "Failed to connect to the server".to_string())?;
+
+    if response.ok() {
+        Ok(())
+    } else {
+        let err: ErrorResponse = response.json::<ErrorResponse>()
+            .await
+            .map_err(|_| "Invalid error response from server".to_string())?;
+        Err(err.error)
+    }
+}
+
+// Removes a member from a room; they may join again
+pub async fn kick_member(token: &str, room_id: i64, user_id: i64) -> Result<(), String> {
+    let response = Request::delete(&format!(
+        "http://127.0.0.1:8080/api/rooms/{}/members/{}",
+        room_id, user_id
+    ))
+    .header("Authorization", &format!("Bearer {}", access_token(token).await))
+    .send()
+    .await
+    .map_err(|_| "Failed to connect to the server".to_string())?;
+
+    if response.ok() {
+        Ok(())
+    } else {
+        let err: ErrorResponse = response.json::<ErrorResponse>()
+            .await
+            .map_err(|_| "Invalid error response from server".to_string())?;
+        Err(err.error)
+    }
+}
+
+// Bans a user permanently, removing them from the room if they are a member
+pub async fn ban_user(token: &str, room_id: i64, username: &str, reason: Option<&str>) -> Result<(), String> {
+    let response = Request::post(&format!(
+        "http://127.0.0.1:8080/api/rooms/{}/bans",
+        room_id
+    ))
+    .header("Authorization", &format!("Bearer {}", access_token(token).await))
+    .header("Content-Type", "application/json")
+    .json(&BanRequest { username, reason })
+    .map_err(|_| "Failed to serialize request".to_string())?
+    .send()
+    .await
+    .map_err(|_| "Failed to connect to the server".to_string())?;
+
+    if response.status() == 201 {
+        Ok(())
+    } else {
+        let err: ErrorResponse = response.json::<ErrorResponse>()
+            .await
+            .map_err(|_| "Invalid error response from server".to_string())?;
+        Err(err.error)
+    }
+}
+
+// Mutes a member for a while; muted members cannot post until it runs out
+pub async fn mute_member(token: &str, room_id: i64, user_id: i64, duration_secs: i64) -> Result<(), String> {
+    let response = Request::put(&format!(
+        "http://127.0.0.1:8080/api/rooms/{}/members/{}/mute",
+        room_id, user_id
+    ))
+    .header("Authorization", &format!("Bearer {}", access_token(token).await))
+    .header("Content-Type", "application/json")
+    .json(&MuteRequest { duration_secs })
+    .map_err(|_| "Failed to serialize request".to_string())?
+    .send()
+    .await
+    .map_err(|_| "Failed to connect to the server".to_string())?;
+
+    if response.ok() {
+        Ok(())
+    } else {
+        let err: ErrorResponse = response.json::<ErrorResponse>()
+            .await
+            .map_err(|_| "Invalid error response from server".to_string())?;
+        Err(err.error)
+    }
+}
+
+// Sets how many seconds members must wait between messages; 0 turns slow mode off
+pub async fn set_slow_mode(token: &str, room_id: i64, seconds: i64) -> Result<Room, String> {
+    let response = Request::put(&format!("http://127.0.0.1:8080/api/rooms/{}/slow_mode", room_id))
+        .header("Authorization", &format!("Bearer {}", access_token(token).await))
+        .header("Content-Type", "application/json")
+        .json(&SlowModeRequest { seconds })
+        .map_err(|_| "Failed to serialize request".to_string())?
+        .send()
+        .await
+        .map_err(|_| "Failed to connect to the server".to_string())?;
+
+    if response.ok() {
+        response.json::<Room>()
+            .await
+            .map_err(|_| "Failed to parse server response".to_string())
+    } else {
+        let err: ErrorResponse = response.json::<ErrorResponse>()
+            .await
+            .map_err(|_| "Invalid error response from server".to_string())?;
+        Err(err.error)
+    }
+}
@@ -0,0 +1,75 @@
+use gloo_net::http::Request;
+use serde::Deserialize;
+use crate::services::auth::access_token;
+use crate::services::message::StoredMessage;
+use crate::services::room::ErrorResponse;
+
+// Filters for a message search; empty fields are left out of the request
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SearchFilters {
+    pub q: String,
+    pub room_id: Option<i64>,
+    pub author: String,
+    pub from: String, // YYYY-MM-DD
+    pub to: String,   // YYYY-MM-DD
+}
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct SnippetPart {
+    pub text: String,
+    pub highlight: bool, // Text that matched the query
+}
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct SearchResult {
+    pub message: StoredMessage,
+    pub room_name: String,
+    pub snippet: Vec<SnippetPart>,
+    pub context_before: Option<StoredMessage>,
+    pub context_after: Option<StoredMessage>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SearchResponse {
+    pub results: Vec<SearchResult>, // Best matches first
+    pub has_more: bool,
+}
+
+// Searches the messages of every room the user is a member of
+pub async fn search_messages(
+    token: &str,
+    filters: &SearchFilters,
+    offset: usize,
+) -> Result<SearchResponse, String> {
+    let encode = |value: &str| -> String { js_sys::encode_uri_component(value).into() };
+    let mut url = format!(
+        "http://127.0.0.1:8080/api/search/messages?q={}&offset={}",
+        encode(&filters.q),
+        offset
+    );
+    if let Some(room_id) = filters.room_id {
+        url.push_str(&format!("&room_id={}", room_id));
+    }
+    for (name, value) in [("author", &filters.author), ("from", &filters.from), ("to", &filters.to)] {
+        if !value.trim().is_empty() {
+            url.push_str(&format!("&{}={}", name, encode(value.trim())));
+        }
+    }
+
+    let response = Request::get(&url)
+        .header("Authorization", &format!("Bearer {}", access_token(token).await))
+        .send()
+        .await
+        .map_err(|_| "Failed to connect to the server".to_string())?;
+
+    if response.ok() {
+        response.json::<SearchResponse>()
+            .await
+            .map_err(|_| "Failed to parse server response".to_string())
+    } else {
+        let err: ErrorResponse = response.json::<ErrorResponse>()
+            .await
+            .map_err(|_| "Invalid error response from server".to_string())?;
+        Err(err.error)
+    }
+}
@@ -1,6 +1,7 @@
 use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use serde::Deserialize;
 use js_sys::Date;
+use gloo::storage::{LocalStorage, Storage};
 
 #[derive(Deserialize)]
 #[allow(dead_code)]
@@ -22,13 +23,15 @@ pub fn decode_username(token: &str) -> Option<String> {
     let claims: Claims = serde_json::from_slice(&decoded_payload).ok()?;
     let current_time = Date::new_0().get_time() as usize / 1000; // current time in seconds
 
-    if current_time < claims.exp {
+    // An expired access token still names the user while the session can be refreshed
+    if current_time < claims.exp || LocalStorage::get::<String>("refreshToken").is_ok() {
         Some(claims.username)
     } else {
         None
     }
 }
 
+#[allow(dead_code)]
 pub fn decode_userid(token: &str) -> Option<String> {
     let parts: Vec<&str> = token.split('.').collect();
     if parts.len() != 3 {
@@ -40,9 +43,18 @@ pub fn decode_userid(token: &str) -> Option<String> {
     let claims: Claims = serde_json::from_slice(&decoded_payload).ok()?;
     let current_time = Date::new_0().get_time() as usize / 1000; // current time in seconds
 
-    if current_time < claims.exp {
+    // An expired access token still names the user while the session can be refreshed
+    if current_time < claims.exp || LocalStorage::get::<String>("refreshToken").is_ok() {
         Some(claims.sub)
     } else {
         None
     }
 }
+
+// The expiry of a token, as a Unix timestamp in seconds
+pub fn token_expiry(token: &str) -> Option<usize> {
+    let payload = token.split('.').nth(1)?;
+    let decoded_payload = URL_SAFE_NO_PAD.decode(payload).ok()?;
+    let claims: Claims = serde_json::from_slice(&decoded_payload).ok()?;
+    Some(claims.exp)
+}
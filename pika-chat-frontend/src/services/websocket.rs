@@ -1,90 +1,124 @@
 use gloo::net::websocket::{futures::WebSocket, Message};
 use wasm_bindgen_futures::spawn_local;
 use yew::prelude::*;
+use futures::channel::mpsc::{unbounded, UnboundedSender};
 use futures::{StreamExt, SinkExt};
-use std::rc::Rc;
-use std::cell::RefCell;
-use serde::Deserialize;
-
-// Define the structure for incoming messages
-#[derive(Deserialize)]
-#[allow(dead_code)]
-pub struct BroadcastMessage {
-    pub room_id: i64,
-    pub message: String,
-    pub is_system: bool,
-    pub username: String,
-}
+use std::cell::Cell;
+use crate::services::auth::access_token;
+use crate::services::protocol::{ClientCommand, ClientFrame, ServerEvent, PROTOCOL_VERSION};
 
 #[allow(dead_code)]
 pub struct WebSocketService {
-    write: Option<Rc<RefCell<futures::stream::SplitSink<WebSocket, Message>>>>,
-    sender: Rc<Callback<BroadcastMessage>>,
+    write: Option<UnboundedSender<Message>>,
+    next_id: Cell<u64>, // Counter used to generate correlation ids for outgoing commands
     room_id: String,
 }
 
 impl WebSocketService {
     pub fn new(
         room_id: &str,
-        userid: &str,
-        sender: Callback<BroadcastMessage>,
-        _on_error: Callback<String>,
-        on_connect: Callback<()>,
+        token: &str,
+        on_event: Callback<ServerEvent>,
+        on_error: Callback<String>,
+        on_disconnect: Callback<()>,
     ) -> Self {
-        let ws_url = format!(
-            "ws://127.0.0.1:8080/ws/rooms/{}?user_id={}",
-            room_id, userid
-        );
-    
-        let ws = WebSocket::open(&ws_url).expect("Failed to open WebSocket");
-    
-        let (write, mut read) = ws.split();
-    
-        let sender = Rc::new(sender);
-    
-        // Spawn a task to handle incoming messages
-        let sender_clone = sender.clone();
+        let ws_url = format!("ws://127.0.0.1:8080/ws/rooms/{}", room_id);
+
+        // Outgoing frames go through a channel so that concurrent sends never share the sink.
+        // Frames sent before the socket opens wait in the channel.
+        let (tx, mut rx) = unbounded::<Message>();
+        let token = token.to_string();
         spawn_local(async move {
-            while let Some(Ok(Message::Text(msg))) = read.next().await {
-                match serde_json::from_str::<BroadcastMessage>(&msg) {
-                    Ok(parsed_msg) => sender_clone.emit(parsed_msg),
-                    Err(_) => log::warn!("Failed to parse incoming message: {}", msg),
+            // The access token is checked when the socket opens, so it must not be about to expire
+            let token = access_token(&token).await;
+
+            // Browsers cannot set an Authorization header on WebSocket requests, so the JWT
+            // travels as a sub-protocol next to "bearer" and stays out of the URL
+            let ws = WebSocket::open_with_protocols(&ws_url, &["bearer", &token])
+                .expect("Failed to open WebSocket");
+
+            let (mut write, mut read) = ws.split();
+
+            spawn_local(async move {
+                while let Some(msg) = rx.next().await {
+                    if let Err(e) = write.send(msg).await {
+                        log::error!("Failed to send message: {:?}", e);
+                    }
+                }
+                // The sender was dropped, so close the connection
+                write.close().await.ok();
+            });
+
+            // Handle incoming events
+            while let Some(msg) = read.next().await {
+                match msg {
+                    Ok(Message::Text(text)) => match serde_json::from_str::<ServerEvent>(&text) {
+                        Ok(event) => on_event.emit(event),
+                        Err(_) => log::warn!("Failed to parse incoming event: {}", text),
+                    },
+                    Ok(Message::Bytes(_)) => log::warn!("Ignoring unexpected binary frame"),
+                    Err(e) => {
+                        on_error.emit(format!("WebSocket error: {:?}", e));
+                        break;
+                    }
                 }
             }
+            on_disconnect.emit(());
         });
-    
-        // Emit connection success
-        on_connect.emit(());
-    
-        Self {
-            write: Some(Rc::new(RefCell::new(write))),
-            sender,
+
+        let service = Self {
+            write: Some(tx),
+            next_id: Cell::new(0),
             room_id: room_id.to_string(),
-        }
+        };
+
+        // The handshake must be the first frame; the server answers with a `welcome` event
+        service.send_command(ClientCommand::Hello { version: PROTOCOL_VERSION });
+        service
     }
 
-    pub fn send_message(&self, message: &str) {
-        if let Some(write) = &self.write {
-            let msg = message.to_string();
-            let write = write.clone();
-            spawn_local(async move {
-                let mut write = write.borrow_mut();
-                if let Err(e) = write.send(Message::Text(msg)).await {
-                    log::error!("Failed to send message: {:?}", e);
-                }
-            });
-        } else {
-            log::warn!("WebSocket write sink is not available. Message not sent.");
+    // Sends a command and returns the correlation id the server will echo in its ack or error
+    pub fn send_command(&self, command: ClientCommand) -> Option<String> {
+        let write = match &self.write {
+            Some(write) => write,
+            None => {
+                log::warn!("WebSocket write sink is not available. Command not sent.");
+                return None;
+            }
+        };
+
+        let id = self.next_id.get() + 1;
+        self.next_id.set(id);
+        let frame = ClientFrame {
+            id: id.to_string(),
+            command,
+        };
+
+        let text = match serde_json::to_string(&frame) {
+            Ok(text) => text,
+            Err(e) => {
+                log::error!("Failed to serialize command: {:?}", e);
+                return None;
+            }
+        };
+        if write.unbounded_send(Message::Text(text)).is_err() {
+            log::error!("Failed to send message: WebSocket writer has stopped");
+            return None;
         }
+        Some(frame.id)
+    }
+
+    // Posts a message to the room, or a reply when `parent_id` names a thread's root
+    pub fn send_message(&self, content: &str, parent_id: Option<i64>) -> Option<String> {
+        self.send_command(ClientCommand::Send {
+            content: content.to_string(),
+            parent_id,
+        })
     }
 
     pub fn close(&mut self) {
-        if let Some(write) = self.write.take() {
-            spawn_local(async move {
-                let mut write = write.borrow_mut();
-                write.close().await.ok();
-            });
-        }
+        // Dropping the sender ends the writer task, which closes the socket
+        self.write.take();
     }
 }
 
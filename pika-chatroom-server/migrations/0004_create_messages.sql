@@ -0,0 +1,13 @@
+-- Migration script to persist chat messages sent over WebSocket
+CREATE TABLE messages (
+    message_id INTEGER PRIMARY KEY AUTOINCREMENT,
+    room_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL, -- The ID of the user who sent the message
+    content TEXT NOT NULL,
+    created_at TEXT DEFAULT (datetime('now')),
+    FOREIGN KEY (room_id) REFERENCES rooms(room_id),
+    FOREIGN KEY (user_id) REFERENCES users(user_id)
+);
+
+-- History is always read per room, ordered by message_id (used as the pagination cursor)
+CREATE INDEX idx_messages_room_id_message_id ON messages (room_id, message_id);
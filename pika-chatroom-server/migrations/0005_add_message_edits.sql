@@ -0,0 +1,14 @@
+-- Migration script to support editing and deleting chat messages
+ALTER TABLE messages ADD COLUMN edited_at TEXT DEFAULT NULL;  -- Set on the latest edit
+ALTER TABLE messages ADD COLUMN deleted_at TEXT DEFAULT NULL; -- Set when the message becomes a tombstone
+
+-- Every earlier version of a message, saved before each edit or deletion
+CREATE TABLE message_revisions (
+    revision_id INTEGER PRIMARY KEY AUTOINCREMENT,
+    message_id INTEGER NOT NULL,
+    content TEXT NOT NULL,       -- The content as it was before the change
+    revised_at TEXT NOT NULL,    -- When this content was replaced
+    FOREIGN KEY (message_id) REFERENCES messages(message_id)
+);
+
+CREATE INDEX idx_message_revisions_message_id ON message_revisions (message_id);
@@ -0,0 +1,10 @@
+-- Migration script for emoji reactions on chat messages
+CREATE TABLE reactions (
+    message_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,    -- The ID of the user who reacted
+    emoji TEXT NOT NULL,
+    created_at TEXT DEFAULT (datetime('now')),
+    PRIMARY KEY (message_id, user_id, emoji), -- A user reacts with each emoji at most once per message
+    FOREIGN KEY (message_id) REFERENCES messages(message_id),
+    FOREIGN KEY (user_id) REFERENCES users(user_id)
+);
@@ -0,0 +1,5 @@
+-- Migration script for threaded replies: a reply points at the root message of its thread
+ALTER TABLE messages ADD COLUMN parent_id INTEGER DEFAULT NULL REFERENCES messages(message_id);
+
+-- Threads are read per root message, ordered by message_id
+CREATE INDEX idx_messages_parent_id_message_id ON messages (parent_id, message_id);
@@ -0,0 +1,25 @@
+-- Migration script for full-text search over chat messages.
+-- messages_fts is an external-content FTS5 index of messages.content, kept in sync by triggers.
+CREATE VIRTUAL TABLE messages_fts USING fts5(
+    content,
+    content = 'messages',
+    content_rowid = 'message_id',
+    tokenize = 'unicode61 remove_diacritics 2'
+);
+
+CREATE TRIGGER messages_fts_insert AFTER INSERT ON messages BEGIN
+    INSERT INTO messages_fts (rowid, content) VALUES (new.message_id, new.content);
+END;
+
+CREATE TRIGGER messages_fts_delete AFTER DELETE ON messages BEGIN
+    INSERT INTO messages_fts (messages_fts, rowid, content) VALUES ('delete', old.message_id, old.content);
+END;
+
+-- Edits and deletions (which empty the content) replace the indexed text
+CREATE TRIGGER messages_fts_update AFTER UPDATE OF content ON messages BEGIN
+    INSERT INTO messages_fts (messages_fts, rowid, content) VALUES ('delete', old.message_id, old.content);
+    INSERT INTO messages_fts (rowid, content) VALUES (new.message_id, new.content);
+END;
+
+-- Index the messages stored before this migration
+INSERT INTO messages_fts (rowid, content) SELECT message_id, content FROM messages;
@@ -0,0 +1,13 @@
+-- Migration script for per-membership roles: owner, admin, moderator or member
+ALTER TABLE user_rooms ADD COLUMN role TEXT NOT NULL DEFAULT 'member'
+    CHECK (role IN ('owner', 'admin', 'moderator', 'member'));
+
+-- Room creators were not always members of their rooms; make them members, then owners
+INSERT OR IGNORE INTO user_rooms (user_id, room_id) SELECT user_id, room_id FROM rooms;
+UPDATE user_rooms SET role = 'owner'
+WHERE EXISTS (
+    SELECT 1 FROM rooms r WHERE r.room_id = user_rooms.room_id AND r.user_id = user_rooms.user_id
+);
+
+-- Looking up one member's role is the permission check run before privileged actions
+CREATE INDEX idx_user_rooms_room_id_role ON user_rooms (room_id, role);
@@ -0,0 +1,18 @@
+-- Migration script for room visibility: public rooms are listed and open to everyone, unlisted rooms
+-- are open to anyone who knows their ID, and private rooms only admit invited users
+ALTER TABLE rooms ADD COLUMN visibility TEXT NOT NULL DEFAULT 'public'
+    CHECK (visibility IN ('public', 'unlisted', 'private'));
+
+-- Pending invitations to join a room; removed once the user joins
+CREATE TABLE room_invitations (
+    room_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,   -- The invited user
+    invited_by INTEGER NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    PRIMARY KEY (room_id, user_id),
+    FOREIGN KEY (room_id) REFERENCES rooms(room_id),
+    FOREIGN KEY (user_id) REFERENCES users(user_id),
+    FOREIGN KEY (invited_by) REFERENCES users(user_id)
+);
+
+CREATE INDEX idx_room_invitations_user_id ON room_invitations (user_id);
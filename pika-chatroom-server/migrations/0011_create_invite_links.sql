@@ -0,0 +1,17 @@
+-- Migration script for shareable invite links: anyone holding the code can join the room, unless
+-- the link is bound to one user, has expired or has been used up
+CREATE TABLE invite_links (
+    code TEXT PRIMARY KEY,
+    room_id INTEGER NOT NULL,
+    created_by INTEGER NOT NULL,
+    user_id INTEGER,            -- Only this user may redeem the link, if set
+    max_uses INTEGER,           -- Unlimited if NULL
+    uses INTEGER NOT NULL DEFAULT 0,
+    expires_at TEXT,            -- Never expires if NULL
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    FOREIGN KEY (room_id) REFERENCES rooms(room_id),
+    FOREIGN KEY (created_by) REFERENCES users(user_id),
+    FOREIGN KEY (user_id) REFERENCES users(user_id)
+);
+
+CREATE INDEX idx_invite_links_room_id ON invite_links (room_id);
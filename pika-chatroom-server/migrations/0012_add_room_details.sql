@@ -0,0 +1,5 @@
+-- Migration script for room details and archiving: a topic and description shown with the room,
+-- and the time the room was archived, after which it is read-only
+ALTER TABLE rooms ADD COLUMN topic TEXT;
+ALTER TABLE rooms ADD COLUMN description TEXT;
+ALTER TABLE rooms ADD COLUMN archived_at TEXT;
@@ -0,0 +1,14 @@
+-- Migration script for room bans: banned users are removed from the room and cannot rejoin it,
+-- whether through the members endpoint, the WebSocket or an invite, until the ban expires or is lifted
+CREATE TABLE room_bans (
+    room_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,   -- The banned user
+    banned_by INTEGER NOT NULL,
+    reason TEXT,
+    expires_at TEXT,            -- Permanent if NULL
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    PRIMARY KEY (room_id, user_id),
+    FOREIGN KEY (room_id) REFERENCES rooms(room_id),
+    FOREIGN KEY (user_id) REFERENCES users(user_id),
+    FOREIGN KEY (banned_by) REFERENCES users(user_id)
+);
@@ -0,0 +1,15 @@
+-- Migration script for mutes and slow mode: muted members cannot post in the room until their mute
+-- runs out, and in slow mode each member may post one message every `slow_mode_secs` seconds
+ALTER TABLE rooms ADD COLUMN slow_mode_secs INTEGER NOT NULL DEFAULT 0; -- Slow mode is off at 0
+
+CREATE TABLE room_mutes (
+    room_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,   -- The muted member
+    muted_by INTEGER NOT NULL,
+    expires_at TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    PRIMARY KEY (room_id, user_id),
+    FOREIGN KEY (room_id) REFERENCES rooms(room_id),
+    FOREIGN KEY (user_id) REFERENCES users(user_id),
+    FOREIGN KEY (muted_by) REFERENCES users(user_id)
+);
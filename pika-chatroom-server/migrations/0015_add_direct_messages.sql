@@ -0,0 +1,8 @@
+-- Migration script for direct messages: private rooms of kind 'dm' between a fixed set of participants,
+-- kept out of the room list. `dm_key` holds the sorted participant IDs so that starting a conversation
+-- with the same people again finds the existing room.
+ALTER TABLE rooms ADD COLUMN kind TEXT NOT NULL DEFAULT 'channel'
+    CHECK (kind IN ('channel', 'dm'));
+ALTER TABLE rooms ADD COLUMN dm_key TEXT;
+
+CREATE UNIQUE INDEX idx_rooms_dm_key ON rooms (dm_key);
@@ -0,0 +1,4 @@
+-- Migration script for account deletion: deleted accounts keep their row, renamed to
+-- 'deleted-user-<user_id>' and without a password, so that their messages stay in the history
+-- without naming them. Deleted accounts cannot log in and their tokens are rejected.
+ALTER TABLE users ADD COLUMN deleted_at TEXT;
@@ -0,0 +1,12 @@
+-- Migration script for persistent token revocation: tokens revoked by logging out are recorded by
+-- their `jti` claim until they expire, after which the server deletes the row
+CREATE TABLE revoked_tokens (
+    jti TEXT PRIMARY KEY,
+    user_id INTEGER,             -- The user the token was issued to
+    expires_at TEXT NOT NULL,    -- The token's `exp`; the row is pruned after this time
+    revoked_at TEXT NOT NULL DEFAULT (datetime('now')),
+    FOREIGN KEY (user_id) REFERENCES users(user_id)
+);
+
+-- Pruning deletes the rows whose tokens have expired
+CREATE INDEX idx_revoked_tokens_expires_at ON revoked_tokens (expires_at);
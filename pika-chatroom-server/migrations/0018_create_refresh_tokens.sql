@@ -0,0 +1,17 @@
+-- Migration script for refresh tokens: opaque tokens exchanged for new access tokens, stored only as
+-- their SHA-256 hash. Every refresh rotates the token; the tokens rotated from one login form a family,
+-- and presenting a token that was already used revokes its whole family.
+CREATE TABLE refresh_tokens (
+    token_id INTEGER PRIMARY KEY AUTOINCREMENT,
+    token_hash TEXT UNIQUE NOT NULL,  -- Hex SHA-256 of the token; the token itself is never stored
+    family_id TEXT NOT NULL,          -- Shared by every token rotated from the same login
+    user_id INTEGER NOT NULL,
+    expires_at TEXT NOT NULL,
+    used_at TEXT,                     -- Set when the token is exchanged for the next one
+    revoked_at TEXT,                  -- Set on every token of the family when it is revoked
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    FOREIGN KEY (user_id) REFERENCES users(user_id)
+);
+
+CREATE INDEX idx_refresh_tokens_family_id ON refresh_tokens (family_id);
+CREATE INDEX idx_refresh_tokens_expires_at ON refresh_tokens (expires_at);
@@ -0,0 +1,21 @@
+-- Migration script for sessions: one per login, on one device. The session ID is the family ID of
+-- the session's refresh tokens and the `sid` claim of its access tokens.
+CREATE TABLE sessions (
+    session_id TEXT PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    user_agent TEXT,                  -- User-Agent header of the latest login or refresh
+    ip_address TEXT,                  -- Client address of the latest login or refresh
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    last_seen_at TEXT NOT NULL DEFAULT (datetime('now')), -- Latest login or refresh
+    expires_at TEXT NOT NULL,         -- When the latest refresh token expires
+    revoked_at TEXT,
+    FOREIGN KEY (user_id) REFERENCES users(user_id)
+);
+
+CREATE INDEX idx_sessions_user_id ON sessions (user_id);
+
+-- Sessions started before this migration, known only through their refresh tokens
+INSERT INTO sessions (session_id, user_id, created_at, last_seen_at, expires_at, revoked_at)
+SELECT family_id, user_id, MIN(created_at), MAX(created_at), MAX(expires_at), MAX(revoked_at)
+FROM refresh_tokens
+GROUP BY family_id, user_id;
@@ -0,0 +1,17 @@
+-- Migration script for password resets: users may give an email address, where single-use reset
+-- links are sent. Reset tokens are stored only as their SHA-256 hash, like refresh tokens.
+ALTER TABLE users ADD COLUMN email TEXT; -- Lowercased; optional, but required to reset a forgotten password
+
+CREATE UNIQUE INDEX idx_users_email ON users (email) WHERE email IS NOT NULL;
+
+CREATE TABLE password_reset_tokens (
+    token_id INTEGER PRIMARY KEY AUTOINCREMENT,
+    token_hash TEXT UNIQUE NOT NULL,  -- Hex SHA-256 of the token; the token itself is only in the email
+    user_id INTEGER NOT NULL,
+    expires_at TEXT NOT NULL,
+    used_at TEXT,                     -- Set when this or another token of the user resets the password, or the email changes
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    FOREIGN KEY (user_id) REFERENCES users(user_id)
+);
+
+CREATE INDEX idx_password_reset_tokens_user_id ON password_reset_tokens (user_id);
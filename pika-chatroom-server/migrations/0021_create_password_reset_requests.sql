@@ -0,0 +1,11 @@
+-- Migration script for rate limiting password resets: each accepted request is recorded with the
+-- address it named and the client it came from, until it falls out of the rate limit window.
+CREATE TABLE password_reset_requests (
+    request_id INTEGER PRIMARY KEY AUTOINCREMENT,
+    email TEXT NOT NULL,  -- Lowercased; recorded whether or not an account uses it
+    ip_address TEXT,      -- The client address, as recorded for sessions
+    requested_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE INDEX idx_password_reset_requests_email ON password_reset_requests (email, requested_at);
+CREATE INDEX idx_password_reset_requests_ip_address ON password_reset_requests (ip_address, requested_at);
@@ -0,0 +1,178 @@
+use jsonwebtoken::errors::{Error as JwtError, ErrorKind};
+use jsonwebtoken::{decode, decode_header, encode, Algorithm, DecodingKey, EncodingKey, Header, TokenData, Validation};
+use lazy_static::lazy_static;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+// Key ID given to the single key configured through SECRET_KEY
+const DEFAULT_KID: &str = "default";
+
+// The keys used to sign and verify JWTs, read from the environment (or the .env file) the first
+// time they are used; main loads them at startup so that a bad configuration stops the server.
+// JWT_KEYS_FILE names a key set file, which allows rotation and asymmetric keys; without it,
+// SECRET_KEY is the one HS256 secret.
+lazy_static! {
+    pub static ref JWT_KEYS: JwtKeys = JwtKeys::from_env().unwrap_or_else(|e| panic!("Invalid JWT key configuration: {}", e));
+}
+
+// A key set file, e.g.
+// {
+//   "active": "2024-11",
+//   "keys": [
+//     { "kid": "2024-11", "algorithm": "EdDSA", "private_key_file": "ed25519.pem", "public_key_file": "ed25519.pub.pem" },
+//     { "kid": "2024-06", "algorithm": "RS256", "public_key_file": "rsa.pub.pem" },
+//     { "kid": "2024-01", "algorithm": "HS256", "secret": "..." }
+//   ]
+// }
+// New tokens are signed with the active key; the others only verify tokens issued before the
+// rotation. Key file paths are relative to the key set file.
+#[derive(Deserialize)]
+struct KeySetFile {
+    active: String,
+    keys: Vec<KeyEntry>,
+}
+
+#[derive(Deserialize)]
+struct KeyEntry {
+    kid: String,
+    algorithm: Algorithm,
+    secret: Option<String>,             // HS256 only
+    private_key_file: Option<PathBuf>,  // RS256 and EdDSA; only needed by the active key
+    public_key_file: Option<PathBuf>,   // RS256 and EdDSA
+}
+
+// A key able to verify tokens, and to sign them if it is the active key
+struct JwtKey {
+    algorithm: Algorithm,
+    encoding: Option<EncodingKey>,
+    decoding: DecodingKey,
+}
+
+/// The signing key set: the active key signs new tokens, tagged with its `kid`, and every key
+/// in the set verifies the tokens tagged with its own `kid`
+pub struct JwtKeys {
+    active_kid: String,
+    keys: HashMap<String, JwtKey>,
+}
+
+impl JwtKeys {
+    // Loads the key set named by JWT_KEYS_FILE, or else the HS256 secret in SECRET_KEY
+    fn from_env() -> Result<Self, String> {
+        if let Ok(path) = std::env::var("JWT_KEYS_FILE") {
+            return Self::from_file(Path::new(&path));
+        }
+        match std::env::var("SECRET_KEY") {
+            Ok(secret) if !secret.is_empty() => Ok(JwtKeys {
+                active_kid: DEFAULT_KID.to_string(),
+                keys: HashMap::from([(
+                    DEFAULT_KID.to_string(),
+                    JwtKey {
+                        algorithm: Algorithm::HS256,
+                        encoding: Some(EncodingKey::from_secret(secret.as_bytes())),
+                        decoding: DecodingKey::from_secret(secret.as_bytes()),
+                    },
+                )]),
+            }),
+            _ => Err("set JWT_KEYS_FILE or SECRET_KEY".into()),
+        }
+    }
+
+    fn from_file(path: &Path) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+        let file: KeySetFile =
+            serde_json::from_str(&contents).map_err(|e| format!("cannot parse {}: {}", path.display(), e))?;
+        let base_dir = path.parent().unwrap_or_else(|| Path::new("."));
+
+        let mut keys = HashMap::new();
+        for entry in file.keys {
+            let kid = entry.kid.clone();
+            let key = JwtKey::load(entry, base_dir).map_err(|e| format!("key '{}': {}", kid, e))?;
+            if keys.insert(kid.clone(), key).is_some() {
+                return Err(format!("key '{}' is listed twice", kid));
+            }
+        }
+        match keys.get(&file.active) {
+            Some(key) if key.encoding.is_some() => {}
+            Some(_) => return Err(format!("the active key '{}' has no private key", file.active)),
+            None => return Err(format!("the active key '{}' is not in the key set", file.active)),
+        }
+        Ok(JwtKeys {
+            active_kid: file.active,
+            keys,
+        })
+    }
+
+    /// The `kid` of the key that signs new tokens
+    pub fn active_kid(&self) -> &str {
+        &self.active_kid
+    }
+
+    /// Signs claims with the active key
+    pub fn sign<T: Serialize>(&self, claims: &T) -> Result<String, JwtError> {
+        let key = &self.keys[&self.active_kid];
+        let mut header = Header::new(key.algorithm);
+        header.kid = Some(self.active_kid.clone());
+        // The active key always has an encoding key; loading the key set checks it
+        encode(&header, claims, key.encoding.as_ref().expect("active key can sign"))
+    }
+
+    /// Verifies a token with the key named by its `kid`, accepting only that key's algorithm.
+    /// Tokens without a `kid` were issued before key IDs existed and are checked against the
+    /// active key; tokens naming a key that is not in the set are invalid.
+    pub fn verify<T: DeserializeOwned>(&self, token: &str) -> Result<TokenData<T>, JwtError> {
+        let kid = decode_header(token)?.kid.unwrap_or_else(|| self.active_kid.clone());
+        let key = self.keys.get(&kid).ok_or(ErrorKind::InvalidToken)?;
+        decode::<T>(token, &key.decoding, &Validation::new(key.algorithm))
+    }
+}
+
+impl JwtKey {
+    fn load(entry: KeyEntry, base_dir: &Path) -> Result<Self, String> {
+        let read = |file: &Path| {
+            let path = base_dir.join(file);
+            std::fs::read(&path).map_err(|e| format!("cannot read {}: {}", path.display(), e))
+        };
+        let invalid = |e: JwtError| format!("invalid key: {}", e);
+
+        match entry.algorithm {
+            Algorithm::HS256 => {
+                let secret = entry.secret.filter(|secret| !secret.is_empty()).ok_or("HS256 keys need a secret")?;
+                Ok(JwtKey {
+                    algorithm: Algorithm::HS256,
+                    encoding: Some(EncodingKey::from_secret(secret.as_bytes())),
+                    decoding: DecodingKey::from_secret(secret.as_bytes()),
+                })
+            }
+            Algorithm::RS256 | Algorithm::EdDSA => {
+                let rsa = entry.algorithm == Algorithm::RS256;
+                let public_key = read(entry.public_key_file.as_deref().ok_or("public_key_file is required")?)?;
+                let decoding = if rsa {
+                    DecodingKey::from_rsa_pem(&public_key)
+                } else {
+                    DecodingKey::from_ed_pem(&public_key)
+                }
+                .map_err(invalid)?;
+                let encoding = match entry.private_key_file.as_deref() {
+                    Some(file) => {
+                        let private_key = read(file)?;
+                        let encoding = if rsa {
+                            EncodingKey::from_rsa_pem(&private_key)
+                        } else {
+                            EncodingKey::from_ed_pem(&private_key)
+                        };
+                        Some(encoding.map_err(invalid)?)
+                    }
+                    None => None,
+                };
+                Ok(JwtKey {
+                    algorithm: entry.algorithm,
+                    encoding,
+                    decoding,
+                })
+            }
+            other => Err(format!("unsupported algorithm {:?}; use HS256, RS256 or EdDSA", other)),
+        }
+    }
+}
@@ -1 +1,2 @@
-pub mod state;
+pub mod settings;
+pub mod keys;
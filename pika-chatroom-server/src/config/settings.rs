@@ -0,0 +1,42 @@
+use chrono::Duration;
+use lazy_static::lazy_static;
+
+// Default for MESSAGE_EDIT_WINDOW_SECS: 15 minutes
+const DEFAULT_MESSAGE_EDIT_WINDOW_SECS: i64 = 15 * 60;
+// Default for ACCESS_TOKEN_TTL_SECS: 5 minutes
+const DEFAULT_ACCESS_TOKEN_TTL_SECS: i64 = 5 * 60;
+// Default for REFRESH_TOKEN_TTL_SECS: 30 days
+const DEFAULT_REFRESH_TOKEN_TTL_SECS: i64 = 30 * 24 * 60 * 60;
+// Default for PASSWORD_RESET_TTL_SECS: 1 hour
+const DEFAULT_PASSWORD_RESET_TTL_SECS: i64 = 60 * 60;
+// Default for PASSWORD_RESET_RATE_WINDOW_SECS: 1 hour
+const DEFAULT_PASSWORD_RESET_RATE_WINDOW_SECS: i64 = 60 * 60;
+// Default for PASSWORD_RESET_URL: the reset page of the frontend served by `trunk serve`
+const DEFAULT_PASSWORD_RESET_URL: &str = "http://127.0.0.1:3000/reset-password";
+
+// Reads a number of seconds from the environment
+fn seconds_from_env(name: &str, default: i64) -> Duration {
+    Duration::seconds(
+        std::env::var(name)
+            .ok()
+            .and_then(|secs| secs.parse().ok())
+            .unwrap_or(default)
+    )
+}
+
+// Settings read from the environment (or the .env file) the first time they are used
+lazy_static! {
+    // How long after sending a message its author may still edit or delete it
+    pub static ref MESSAGE_EDIT_WINDOW: Duration = seconds_from_env("MESSAGE_EDIT_WINDOW_SECS", DEFAULT_MESSAGE_EDIT_WINDOW_SECS);
+    // How long an access token is valid; clients refresh it with their refresh token
+    pub static ref ACCESS_TOKEN_TTL: Duration = seconds_from_env("ACCESS_TOKEN_TTL_SECS", DEFAULT_ACCESS_TOKEN_TTL_SECS);
+    // How long a refresh token can be exchanged; each refresh issues a new one with a fresh lifetime
+    pub static ref REFRESH_TOKEN_TTL: Duration = seconds_from_env("REFRESH_TOKEN_TTL_SECS", DEFAULT_REFRESH_TOKEN_TTL_SECS);
+    // How long a password reset link can be used
+    pub static ref PASSWORD_RESET_TTL: Duration = seconds_from_env("PASSWORD_RESET_TTL_SECS", DEFAULT_PASSWORD_RESET_TTL_SECS);
+    // Period over which password reset requests are counted towards their rate limits
+    pub static ref PASSWORD_RESET_RATE_WINDOW: Duration = seconds_from_env("PASSWORD_RESET_RATE_WINDOW_SECS", DEFAULT_PASSWORD_RESET_RATE_WINDOW_SECS);
+    // Page that reset links point to; the reset token is appended as a path segment
+    pub static ref PASSWORD_RESET_URL: String = std::env::var("PASSWORD_RESET_URL")
+        .unwrap_or_else(|_| DEFAULT_PASSWORD_RESET_URL.to_string());
+}
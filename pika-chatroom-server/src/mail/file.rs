@@ -0,0 +1,46 @@
+use crate::mail::{Email, MailError, Mailer};
+use chrono::Utc;
+use futures_util::future::BoxFuture;
+use std::path::PathBuf;
+use tokio::io::AsyncWriteExt;
+
+// Default for MAIL_FILE
+const DEFAULT_MAIL_FILE: &str = "mail.log";
+
+/// Appends every email to the file named by MAIL_FILE instead of sending it; meant for
+/// development, where reset links can be copied from the file
+pub struct FileMailer {
+    path: PathBuf,
+}
+
+impl FileMailer {
+    pub fn from_env() -> Self {
+        FileMailer {
+            path: std::env::var("MAIL_FILE").unwrap_or_else(|_| DEFAULT_MAIL_FILE.to_string()).into(),
+        }
+    }
+}
+
+impl Mailer for FileMailer {
+    fn send(&self, email: Email) -> BoxFuture<'_, Result<(), MailError>> {
+        Box::pin(async move {
+            let entry = format!(
+                "Date: {}\nTo: {}\nSubject: {}\n\n{}\n\n",
+                Utc::now().to_rfc2822(),
+                email.to,
+                email.subject,
+                email.body
+            );
+            let fail = |e: std::io::Error| MailError(format!("cannot write to {}: {}", self.path.display(), e));
+            let mut file = tokio::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)
+                .await
+                .map_err(fail)?;
+            // One write per email, so that concurrent emails do not interleave
+            file.write_all(entry.as_bytes()).await.map_err(fail)?;
+            Ok(())
+        })
+    }
+}
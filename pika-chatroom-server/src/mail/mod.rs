@@ -0,0 +1,48 @@
+//! Outgoing email, such as password reset links.
+//!
+//! Handlers send through the [`Mailer`] registered as application data, so the backend is chosen
+//! once at startup: `MAILER=smtp` (the default) delivers through an SMTP server, and `MAILER=file`
+//! appends every email to a file, for development only. Tests keep emails in memory instead.
+
+pub mod file;
+#[cfg(test)]
+pub mod memory;
+pub mod smtp;
+
+use futures_util::future::BoxFuture;
+use std::fmt;
+use std::sync::Arc;
+
+/// A plain-text email
+#[derive(Clone, Debug)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Why an email could not be sent
+#[derive(Debug)]
+pub struct MailError(pub String);
+
+impl fmt::Display for MailError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Delivers emails
+pub trait Mailer: Send + Sync {
+    fn send(&self, email: Email) -> BoxFuture<'_, Result<(), MailError>>;
+}
+
+/// Builds the mailer named by MAILER; main calls this at startup, so that a bad configuration
+/// stops the server
+pub fn from_env() -> Result<Arc<dyn Mailer>, String> {
+    match std::env::var("MAILER").as_deref().unwrap_or("smtp") {
+        "smtp" => Ok(Arc::new(smtp::SmtpMailer::from_env()?)),
+        // Reset links are written out in plain text, so this is never the default
+        "file" => Ok(Arc::new(file::FileMailer::from_env())),
+        other => Err(format!("unknown mailer '{}'; use smtp, or file for development", other)),
+    }
+}
@@ -0,0 +1,67 @@
+use crate::mail::{Email, MailError, Mailer};
+use futures_util::future::BoxFuture;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+
+/// Sends emails through an SMTP server, configured by
+/// - SMTP_HOST: the server, required
+/// - SMTP_PORT: defaults to the standard port of the TLS mode
+/// - SMTP_TLS: `starttls` (the default), `tls`, or `none` for a local sink such as MailHog
+/// - SMTP_USERNAME and SMTP_PASSWORD: optional credentials
+/// - MAIL_FROM: the sender, defaulting to `Pika Chat <no-reply@SMTP_HOST>`
+pub struct SmtpMailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl SmtpMailer {
+    pub fn from_env() -> Result<Self, String> {
+        let host = std::env::var("SMTP_HOST").map_err(|_| "SMTP_HOST is required by the smtp mailer".to_string())?;
+        let from = std::env::var("MAIL_FROM")
+            .unwrap_or_else(|_| format!("Pika Chat <no-reply@{}>", host))
+            .parse::<Mailbox>()
+            .map_err(|e| format!("invalid MAIL_FROM: {}", e))?;
+
+        let mut builder = match std::env::var("SMTP_TLS").as_deref().unwrap_or("starttls") {
+            "starttls" => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&host),
+            "tls" => AsyncSmtpTransport::<Tokio1Executor>::relay(&host),
+            "none" => Ok(AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&host)),
+            other => return Err(format!("invalid SMTP_TLS '{}'; use starttls, tls or none", other)),
+        }
+        .map_err(|e| format!("invalid SMTP_HOST '{}': {}", host, e))?;
+        if let Ok(port) = std::env::var("SMTP_PORT") {
+            builder = builder.port(port.parse().map_err(|_| format!("invalid SMTP_PORT '{}'", port))?);
+        }
+        if let (Ok(username), Ok(password)) = (std::env::var("SMTP_USERNAME"), std::env::var("SMTP_PASSWORD")) {
+            builder = builder.credentials(Credentials::new(username, password));
+        }
+
+        Ok(SmtpMailer {
+            transport: builder.build(),
+            from,
+        })
+    }
+}
+
+impl Mailer for SmtpMailer {
+    fn send(&self, email: Email) -> BoxFuture<'_, Result<(), MailError>> {
+        Box::pin(async move {
+            let to = email
+                .to
+                .parse::<Mailbox>()
+                .map_err(|e| MailError(format!("invalid recipient '{}': {}", email.to, e)))?;
+            let message = Message::builder()
+                .from(self.from.clone())
+                .to(to)
+                .subject(email.subject)
+                .body(email.body)
+                .map_err(|e| MailError(format!("cannot build email: {}", e)))?;
+            self.transport
+                .send(message)
+                .await
+                .map_err(|e| MailError(format!("SMTP delivery failed: {}", e)))?;
+            Ok(())
+        })
+    }
+}
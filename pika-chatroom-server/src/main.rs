@@ -1,4 +1,5 @@
 mod config;
+mod mail;
 mod middleware;
 mod models;
 mod routes;
@@ -8,8 +9,25 @@ use actix::Actor;
 use actix_cors::Cors;
 use actix_web::{web, App, HttpServer};
 use middleware::auth_middleware::AuthMiddleware;
-use routes::auth::{register_user, login_user, logout_user, AuthData};
-use routes::room::{create_room, add_room_member, get_rooms, get_room_members, join_room_ws, get_user_presence, RoomMember, Room, RoomInfo, RoomsResponse};
+use routes::auth::{register_user, login_user, refresh_access_token, logout_user, AuthData, RefreshRequest};
+use routes::room::{create_room, add_room_member, get_rooms, get_room_members, join_room_ws, get_user_presence, set_room_visibility, get_room, update_room, delete_room, RoomMember, MemberListing, RoomMembersResponse, Room, RoomInfo, RoomListing, RoomSort, RoomsResponse, UpdateRoomRequest, VisibilityRequest};
+use routes::invitation::{get_invitations, invite_user, revoke_invitation, InvitationRequest, RoomInvitation};
+use routes::invite_link::{accept_invite, create_invite_link, get_invite_links, revoke_invite_link, InviteLink, InviteLinkRequest};
+use routes::message::{get_room_messages, get_thread, edit_message, delete_message, get_message_revisions, EditMessageRequest, MessagesResponse, ThreadResponse};
+use models::message::{MessageRevision, StoredMessage};
+use models::reaction::{ReactionSummary, ReactionUser};
+use routes::reaction::{add_reaction, get_message_reactions, remove_reaction};
+use routes::search::{search_messages, SearchResponse, SearchResult, SnippetPart};
+use routes::role::{set_member_role, transfer_ownership, RoleRequest, TransferOwnershipRequest};
+use routes::account::{delete_account, set_email, DeleteAccountRequest, SetEmailRequest};
+use routes::session::{delete_other_sessions, delete_session, get_sessions, SessionInfo};
+use routes::password::{change_password, forgot_password, reset_password, ChangePasswordRequest, ForgotPasswordRequest, ResetPasswordRequest};
+use routes::moderation::{ban_user, get_bans, kick_member, leave_room, mute_member, set_slow_mode, unban_user, unmute_member};
+use routes::moderation::{BanRequest, MuteRequest, RoomBan, RoomMute, SlowModeRequest};
+use routes::dm::{create_dm, get_dms, DirectMessage, DmRequest};
+use models::role::Role;
+use models::room::{RoomKind, RoomVisibility};
+use models::presence::{PresenceChange, UserPresence};
 use models::response::{ErrorResponse, MessageResponse, TokenResponse};
 use routes::test_routes::test_protected_route;
 use sqlx::SqlitePool;
@@ -24,16 +42,57 @@ use websockets::chat_session::RoomServer;
     paths(
         crate::routes::auth::register_user,
         crate::routes::auth::login_user,
+        crate::routes::auth::refresh_access_token,
         crate::routes::auth::logout_user,
         crate::routes::room::get_rooms,
         crate::routes::room::create_room,
         crate::routes::room::add_room_member,
         crate::routes::room::get_room_members,
+        crate::routes::role::set_member_role,
+        crate::routes::role::transfer_ownership,
+        crate::routes::account::delete_account,
+        crate::routes::account::set_email,
+        crate::routes::session::get_sessions,
+        crate::routes::session::delete_session,
+        crate::routes::session::delete_other_sessions,
+        crate::routes::password::change_password,
+        crate::routes::password::forgot_password,
+        crate::routes::password::reset_password,
+        crate::routes::moderation::leave_room,
+        crate::routes::moderation::kick_member,
+        crate::routes::moderation::ban_user,
+        crate::routes::moderation::get_bans,
+        crate::routes::moderation::unban_user,
+        crate::routes::moderation::mute_member,
+        crate::routes::moderation::unmute_member,
+        crate::routes::moderation::set_slow_mode,
+        crate::routes::dm::create_dm,
+        crate::routes::dm::get_dms,
+        crate::routes::room::set_room_visibility,
+        crate::routes::room::get_room,
+        crate::routes::room::update_room,
+        crate::routes::room::delete_room,
+        crate::routes::invitation::invite_user,
+        crate::routes::invitation::get_invitations,
+        crate::routes::invitation::revoke_invitation,
+        crate::routes::invite_link::create_invite_link,
+        crate::routes::invite_link::get_invite_links,
+        crate::routes::invite_link::revoke_invite_link,
+        crate::routes::invite_link::accept_invite,
         crate::routes::room::join_room_ws,
-        crate::routes::room::get_user_presence
+        crate::routes::room::get_user_presence,
+        crate::routes::message::get_room_messages,
+        crate::routes::message::get_thread,
+        crate::routes::message::edit_message,
+        crate::routes::message::delete_message,
+        crate::routes::message::get_message_revisions,
+        crate::routes::reaction::get_message_reactions,
+        crate::routes::reaction::add_reaction,
+        crate::routes::reaction::remove_reaction,
+        crate::routes::search::search_messages
     ),
     // Define all the schemas (data structures) that will be used in the API documentation.
-    components(schemas(RoomMember, Room, RoomInfo, RoomsResponse, AuthData, MessageResponse, TokenResponse, ErrorResponse))
+    components(schemas(RoomMember, MemberListing, RoomMembersResponse, Room, RoomInfo, RoomListing, RoomSort, RoomsResponse, AuthData, RefreshRequest, MessageResponse, TokenResponse, ErrorResponse, StoredMessage, MessagesResponse, ThreadResponse, EditMessageRequest, MessageRevision, ReactionSummary, ReactionUser, SearchResponse, SearchResult, SnippetPart, UserPresence, PresenceChange, Role, RoleRequest, TransferOwnershipRequest, DeleteAccountRequest, SetEmailRequest, SessionInfo, ChangePasswordRequest, ForgotPasswordRequest, ResetPasswordRequest, RoomVisibility, VisibilityRequest, UpdateRoomRequest, InvitationRequest, RoomInvitation, InviteLinkRequest, InviteLink, BanRequest, RoomBan, MuteRequest, RoomMute, SlowModeRequest, RoomKind, DmRequest, DirectMessage))
 )]
 // Empty struct ApiDoc serves as the root for the OpenAPI spec.
 // #[openapi(...)] generates a full OpenAPI spec, including all paths and schemas.
@@ -49,16 +108,30 @@ async fn main() -> std::io::Result<()> {
     // Load environment variables from a .env file, if present
     dotenvy::dotenv().ok();
 
+    // Load the JWT signing keys now, so that a bad key configuration stops the server at startup
+    lazy_static::initialize(&config::keys::JWT_KEYS);
+    log::info!("Signing tokens with key '{}'", config::keys::JWT_KEYS.active_kid());
+
     // Establish a connection pool to the SQLite database using SQLx
     let pool = SqlitePool::connect(&std::env::var("DATABASE_URL").unwrap())
         .await
         .unwrap();
+    // Load the tokens revoked before the last restart, then prune expired records in the background
+    let revoked = middleware::revocation::load_revocations(&pool)
+        .await
+        .expect("Failed to load token revocations");
+    log::info!("Loaded {} token revocations", revoked);
+    actix_web::rt::spawn(middleware::revocation::prune_expired_records_periodically(pool.clone()));
+
     // Initialize a new instance of RoomServer (managing chat rooms) and start it as an Actor.
     // This actor will handle WebSocket communication for room sessions.
     // Calling start() on RoomServer here starts the actor and calls its `started` method (if implemented),
     // signaling the actor is ready to receive and process messages.
     let room_server = RoomServer::new().start();
 
+    // Pick the mail backend now, so that a bad mail configuration stops the server at startup
+    let mailer = mail::from_env().unwrap_or_else(|e| panic!("Invalid mailer configuration: {}", e));
+
     // Configure and run the Actix Web HTTP server
     HttpServer::new(move || {
         // The closure passed to HttpServer::new is used to create a new instance of App
@@ -76,7 +149,7 @@ async fn main() -> std::io::Result<()> {
             .wrap(
                 Cors::default()
                     .allow_any_origin() // Allow frontend origin
-                    .allowed_methods(vec!["GET", "POST", "OPTIONS"]) // Allow specific methods
+                    .allowed_methods(vec!["GET", "POST", "PUT", "PATCH", "DELETE", "OPTIONS"]) // Allow specific methods
                     .allow_any_header() // Allow any custom headers if required
                     .supports_credentials() // Support cookies and credentials
                     .max_age(3600), // Cache preflight response for 1 hour
@@ -88,6 +161,8 @@ async fn main() -> std::io::Result<()> {
             )
             // Register the RoomServer actor, shared across threads for managing chat room sessions.
             .app_data(web::Data::new(room_server.clone()))
+            // Register the mailer that delivers password reset links
+            .app_data(web::Data::from(mailer.clone()))
             .service(
                 web::resource("/ws/rooms/{room_id}")
                     .route(web::get().to(join_room_ws)),
@@ -98,12 +173,47 @@ async fn main() -> std::io::Result<()> {
                     // Register public routes that don't require authentication
                     .service(register_user)
                     .service(login_user)
+                    .service(refresh_access_token)
+                    .service(
+                        web::resource("/password/forgot")
+                            .route(web::post().to(forgot_password)), // POST to email a password reset link
+                    )
+                    .service(
+                        web::resource("/password/reset")
+                            .route(web::post().to(reset_password)), // POST a reset token with the new password
+                    )
                     // Register the logout route with AuthMiddleware to protect it
                     .service(
                         web::resource("/logout")
                             .wrap(AuthMiddleware)
                             .route(web::post().to(logout_user)),
                     )
+                    .service(
+                        web::resource("/account")
+                            .wrap(AuthMiddleware)
+                            .route(web::delete().to(delete_account)), // DELETE the user's own account
+                    )
+                    .service(
+                        web::resource("/account/email")
+                            .wrap(AuthMiddleware)
+                            .route(web::put().to(set_email)), // PUT to set, change or remove the email address
+                    )
+                    .service(
+                        web::resource("/account/password")
+                            .wrap(AuthMiddleware)
+                            .route(web::put().to(change_password)), // PUT to change the password
+                    )
+                    .service(
+                        web::resource("/sessions")
+                            .wrap(AuthMiddleware)
+                            .route(web::get().to(get_sessions)) // GET the user's live sessions
+                            .route(web::delete().to(delete_other_sessions)), // DELETE every session but the current one
+                    )
+                    .service(
+                        web::resource("/sessions/{session_id}")
+                            .wrap(AuthMiddleware)
+                            .route(web::delete().to(delete_session)), // DELETE to revoke one session
+                    )
                     // Register the test route with AuthMiddleware for testing
                     .service(
                         web::resource("/test-protected")
@@ -117,12 +227,137 @@ async fn main() -> std::io::Result<()> {
                             .route(web::post().to(create_room)) // Handle POST requests to create a room
                             .route(web::get().to(get_rooms)), // Handle GET requests to retrieve rooms
                     )
+                    .service(
+                        web::resource("/dms")
+                            .wrap(AuthMiddleware)
+                            .route(web::post().to(create_dm)) // POST to start or reopen a direct message
+                            .route(web::get().to(get_dms)), // GET the user's direct messages
+                    )
                     .service(
                         web::resource("/rooms/{room_id}/members")
                             .wrap(AuthMiddleware)
                             .route(web::post().to(add_room_member)) // POST to add a member
                             .route(web::get().to(get_room_members)), // GET to retrieve members
                     )
+                    .service(
+                        web::resource("/rooms/{room_id}")
+                            .wrap(AuthMiddleware)
+                            .route(web::get().to(get_room)) // GET a room's details
+                            .route(web::patch().to(update_room)) // PATCH to rename, set the topic and description, or archive
+                            .route(web::delete().to(delete_room)), // DELETE a room and its history
+                    )
+                    .service(
+                        web::resource("/rooms/{room_id}/visibility")
+                            .wrap(AuthMiddleware)
+                            .route(web::put().to(set_room_visibility)), // PUT to make a room public, unlisted or private
+                    )
+                    .service(
+                        web::resource("/rooms/{room_id}/invitations")
+                            .wrap(AuthMiddleware)
+                            .route(web::post().to(invite_user)) // POST to invite a user by username
+                            .route(web::get().to(get_invitations)), // GET pending invitations
+                    )
+                    .service(
+                        web::resource("/rooms/{room_id}/invitations/{user_id}")
+                            .wrap(AuthMiddleware)
+                            .route(web::delete().to(revoke_invitation)), // DELETE to revoke an invitation
+                    )
+                    .service(
+                        web::resource("/rooms/{room_id}/invites")
+                            .wrap(AuthMiddleware)
+                            .route(web::post().to(create_invite_link)) // POST to create a shareable invite link
+                            .route(web::get().to(get_invite_links)), // GET the room's invite links
+                    )
+                    .service(
+                        web::resource("/rooms/{room_id}/invites/{code}")
+                            .wrap(AuthMiddleware)
+                            .route(web::delete().to(revoke_invite_link)), // DELETE to revoke an invite link
+                    )
+                    .service(
+                        web::resource("/invites/{code}/accept")
+                            .wrap(AuthMiddleware)
+                            .route(web::post().to(accept_invite)), // POST to join a room through an invite link
+                    )
+                    // Registered before `/members/{user_id}` so that `me` is not taken for a user ID
+                    .service(
+                        web::resource("/rooms/{room_id}/members/me")
+                            .wrap(AuthMiddleware)
+                            .route(web::delete().to(leave_room)), // DELETE to leave a room
+                    )
+                    .service(
+                        web::resource("/rooms/{room_id}/members/{user_id}")
+                            .wrap(AuthMiddleware)
+                            .route(web::delete().to(kick_member)), // DELETE to kick a member
+                    )
+                    .service(
+                        web::resource("/rooms/{room_id}/bans")
+                            .wrap(AuthMiddleware)
+                            .route(web::post().to(ban_user)) // POST to ban a user by username
+                            .route(web::get().to(get_bans)), // GET the bans in force
+                    )
+                    .service(
+                        web::resource("/rooms/{room_id}/bans/{user_id}")
+                            .wrap(AuthMiddleware)
+                            .route(web::delete().to(unban_user)), // DELETE to lift a ban
+                    )
+                    .service(
+                        web::resource("/rooms/{room_id}/members/{user_id}/mute")
+                            .wrap(AuthMiddleware)
+                            .route(web::put().to(mute_member)) // PUT to mute a member for a while
+                            .route(web::delete().to(unmute_member)), // DELETE to lift a mute
+                    )
+                    .service(
+                        web::resource("/rooms/{room_id}/slow_mode")
+                            .wrap(AuthMiddleware)
+                            .route(web::put().to(set_slow_mode)), // PUT to set or turn off slow mode
+                    )
+                    .service(
+                        web::resource("/rooms/{room_id}/members/{user_id}/role")
+                            .wrap(AuthMiddleware)
+                            .route(web::put().to(set_member_role)), // PUT to promote or demote a member
+                    )
+                    .service(
+                        web::resource("/rooms/{room_id}/owner")
+                            .wrap(AuthMiddleware)
+                            .route(web::put().to(transfer_ownership)), // PUT to hand the room over to another member
+                    )
+                    .service(
+                        web::resource("/rooms/{room_id}/messages")
+                            .wrap(AuthMiddleware)
+                            .route(web::get().to(get_room_messages)), // GET a page of message history
+                    )
+                    .service(
+                        web::resource("/rooms/{room_id}/messages/{message_id}")
+                            .wrap(AuthMiddleware)
+                            .route(web::put().to(edit_message)) // PUT to edit a message
+                            .route(web::delete().to(delete_message)), // DELETE to leave a tombstone
+                    )
+                    .service(
+                        web::resource("/rooms/{room_id}/messages/{message_id}/thread")
+                            .wrap(AuthMiddleware)
+                            .route(web::get().to(get_thread)), // GET a thread's root and replies
+                    )
+                    .service(
+                        web::resource("/rooms/{room_id}/messages/{message_id}/revisions")
+                            .wrap(AuthMiddleware)
+                            .route(web::get().to(get_message_revisions)),
+                    )
+                    .service(
+                        web::resource("/rooms/{room_id}/messages/{message_id}/reactions")
+                            .wrap(AuthMiddleware)
+                            .route(web::get().to(get_message_reactions)),
+                    )
+                    .service(
+                        web::resource("/rooms/{room_id}/messages/{message_id}/reactions/{emoji}")
+                            .wrap(AuthMiddleware)
+                            .route(web::put().to(add_reaction)) // PUT to react with an emoji
+                            .route(web::delete().to(remove_reaction)), // DELETE to take the reaction back
+                    )
+                    .service(
+                        web::resource("/search/messages")
+                            .wrap(AuthMiddleware)
+                            .route(web::get().to(search_messages)), // GET full-text search over the user's rooms
+                    )
                     .service(
                         web::resource("/users/presence/{room_id}")
                             .wrap(AuthMiddleware)
@@ -1,19 +1,24 @@
 use actix_service::{Service, Transform};
 use actix_web::{
     dev::{ServiceRequest, ServiceResponse},
-    Error, HttpResponse, body::BoxBody, web::Data, HttpMessage
+    Error, HttpRequest, HttpResponse, body::BoxBody, web::Data, HttpMessage
 }; // Import essential components for HTTP handling and request/response types
 // Import future types for async operations in middleware
 use futures_util::future::{ok, LocalBoxFuture, Ready};
-// Import functions and structs for JWT decoding
-use jsonwebtoken::{decode, DecodingKey, Validation, Algorithm};
+// Import the configured key set for JWT verification
+use crate::config::keys::JWT_KEYS;
 use std::rc::Rc;
 use log::info;
 // Ensure Claims struct is imported for token validation
 use crate::models::claim::Claims;
 use sqlx::SqlitePool;
-// Import the global token blacklist for token revocation
-use crate::config::state::TOKEN_BLACKLIST;
+// Import the revocation checks for tokens of users who logged out and for revoked sessions
+use crate::middleware::revocation::{is_revoked, is_session_revoked};
+
+// The session a request was authenticated in; AuthMiddleware puts it in the request extensions
+// next to the user ID
+#[derive(Clone, Debug)]
+pub struct SessionId(pub String);
 
 // Define the AuthMiddleware struct for implementing middleware behavior
 pub struct AuthMiddleware;
@@ -74,86 +79,102 @@ where
         let service = self.service.clone();
         let pool = req.app_data::<Data<SqlitePool>>().cloned(); // Retrieve and clone the database pool if available
 
-        // Check if the "Authorization" header is present
-        if let Some(auth_header) = req.headers().get("Authorization") {
-            if let Ok(header_str) = auth_header.to_str() {
-                // Check if the header starts with "Bearer "
-                if header_str.starts_with("Bearer ") {
-                    // Extract the token from the header
-                    let token = &header_str[7..];
-
-                    // Check if the token is in the blacklist for revocation
-                    {
-                        let blacklist = TOKEN_BLACKLIST.lock().unwrap();
-                        info!("blacklist: {:?}, token: {}", *blacklist, token);
-                        if blacklist.contains(token) {
-                            info!("Token found in blacklist, blocking access");
-                            // Return an unauthorized response if the token is in the blacklist
-                            return Box::pin(async move {
-                                let (req, _payload) = req.into_parts();
-                                let response = HttpResponse::Unauthorized().body("Token is invalid");
-                                Ok(ServiceResponse::new(req, response.map_into_boxed_body()))
-                            });
-                        }
-                    }
+        // Check if the "Authorization" header carries a bearer token
+        let token = bearer_token(req.request()).map(str::to_string);
 
-                    // Decode the JWT token using a secret key for verification
-                    let decoding_key = DecodingKey::from_secret("secret_key_for_jwt".as_ref());
-                    if let Ok(decoded_token) = decode::<Claims>(
-                        token,
-                        &decoding_key,
-                        &Validation::new(Algorithm::HS256),
-                    ) {
-                        // Check if the user ID (sub) in the token exists in the database
-                        if let Some(pool) = pool {
-                            let user_id = decoded_token.claims.sub.parse::<i64>().ok();
-                            if let Some(user_id) = user_id {
-                                let future = async move {
-                                    // Query the database to check if the user ID exists
-                                    if let Ok(mut conn) = pool.acquire().await {
-                                        let user_exists = sqlx::query!(
-                                            "SELECT 1 AS exists_flag FROM users WHERE user_id = ?",
-                                            user_id
-                                        )
-                                        .fetch_optional(&mut conn)
-                                        .await
-                                        .map(|row| row.is_some())
-                                        .unwrap_or(false);
-
-                                        // If the user exists, proceed with the service call
-                                        if user_exists {
-                                            info!(
-                                                "Token validated successfully for user ID: {}, username: {}",
-                                                decoded_token.claims.sub, decoded_token.claims.username
-                                            );
-
-                                            // Insert user_id into req.extensions()
-                                            req.extensions_mut().insert(user_id);
-
-                                            // Proceed with the service call
-                                            return service.call(req).await;
-                                        }
-                                    }
-
-                                    // Log and return an unauthorized response if the user ID is not found
-                                    info!("Token's user ID not found in the database.");
-                                    let (req, _payload) = req.into_parts();
-                                    let response = HttpResponse::Unauthorized().body("Invalid user");
-                                    Ok(ServiceResponse::new(req, response.map_into_boxed_body()))
-                                };
-                                return Box::pin(future);
-                            }
-                        }
+        Box::pin(async move {
+            if let (Some(token), Some(pool)) = (token, pool) {
+                match authenticate_token(pool.get_ref(), &token).await {
+                    Ok((user_id, session_id)) => {
+                        // Insert user_id and the session into req.extensions() and proceed with the service call
+                        req.extensions_mut().insert(user_id);
+                        req.extensions_mut().insert(session_id);
+                        return service.call(req).await;
+                    }
+                    Err(auth_error) => {
+                        let (req, _payload) = req.into_parts();
+                        let response = HttpResponse::Unauthorized().body(auth_error.message());
+                        return Ok(ServiceResponse::new(req, response.map_into_boxed_body()));
                     }
                 }
             }
-        }
 
-        // Return an unauthorized response if no valid token is provided
-        Box::pin(async move {
+            // Return an unauthorized response if no valid token is provided
             let (req, _payload) = req.into_parts();
             let response = HttpResponse::Unauthorized().body("Unauthorized");
             Ok(ServiceResponse::new(req, response.map_into_boxed_body()))
         })
     }
 }
+
+// Reasons a bearer token can be rejected
+#[derive(Debug)]
+pub enum AuthError {
+    Revoked,     // The token was revoked by logging out, or its session was revoked
+    Invalid,     // The token could not be decoded or has expired
+    UnknownUser, // The token is valid but its user does not exist
+}
+
+impl AuthError {
+    // Message returned to the client in the 401 response
+    pub fn message(&self) -> &'static str {
+        match self {
+            AuthError::Revoked => "Token is invalid",
+            AuthError::Invalid => "Unauthorized",
+            AuthError::UnknownUser => "Invalid user",
+        }
+    }
+}
+
+// Extracts the token from an "Authorization: Bearer <token>" header, if present
+pub fn bearer_token(req: &HttpRequest) -> Option<&str> {
+    req.headers()
+        .get("Authorization")
+        .and_then(|header| header.to_str().ok())
+        .and_then(|header_str| header_str.strip_prefix("Bearer "))
+}
+
+// Validates a raw JWT and returns the ID of the user it belongs to, with its session.
+// Shared by AuthMiddleware and the WebSocket upgrade, which cannot carry an Authorization header from browsers.
+pub async fn authenticate_token(pool: &SqlitePool, token: &str) -> Result<(i64, SessionId), AuthError> {
+    // Decode the JWT token with the key named by its `kid`
+    let decoded_token = JWT_KEYS.verify::<Claims>(token).map_err(|_| AuthError::Invalid)?;
+
+    // Reject tokens revoked by logging out
+    if is_revoked(&decoded_token.claims.jti) {
+        info!("Token {} was revoked, blocking access", decoded_token.claims.jti);
+        return Err(AuthError::Revoked);
+    }
+    // Reject tokens of sessions revoked from another device
+    if is_session_revoked(&decoded_token.claims.sid) {
+        info!("Session {} was revoked, blocking access", decoded_token.claims.sid);
+        return Err(AuthError::Revoked);
+    }
+    let user_id = decoded_token
+        .claims
+        .sub
+        .parse::<i64>()
+        .map_err(|_| AuthError::Invalid)?;
+
+    // Query the database to check if the user ID (sub) in the token exists
+    let user_exists = sqlx::query!(
+        "SELECT 1 AS exists_flag FROM users WHERE user_id = ? AND deleted_at IS NULL",
+        user_id
+    )
+    .fetch_optional(pool)
+    .await
+    .map(|row| row.is_some())
+    .unwrap_or(false);
+
+    if !user_exists {
+        // Log and reject the token if the user ID is not found
+        info!("Token's user ID not found in the database.");
+        return Err(AuthError::UnknownUser);
+    }
+
+    info!(
+        "Token validated successfully for user ID: {}, username: {}",
+        decoded_token.claims.sub, decoded_token.claims.username
+    );
+    Ok((user_id, SessionId(decoded_token.claims.sid)))
+}
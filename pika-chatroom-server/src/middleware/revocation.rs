@@ -0,0 +1,141 @@
+use crate::config::settings::{ACCESS_TOKEN_TTL, PASSWORD_RESET_RATE_WINDOW};
+use crate::models::claim::Claims;
+use crate::routes::message::TIMESTAMP_FORMAT;
+use chrono::{TimeZone, Utc};
+use lazy_static::lazy_static;
+use log::{error, info};
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+// How often revocations of expired tokens, expired refresh tokens, ended sessions and old reset requests are deleted
+const PRUNE_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+// Revoked token IDs (`jti`) with their expiry, as Unix timestamps. The revoked_tokens table is
+// the record; this cache mirrors it so that checking a token does not touch the database.
+// Both only hold tokens that have not expired yet, since expired tokens are rejected anyway.
+lazy_static! {
+    static ref REVOKED_TOKENS: Mutex<HashMap<String, i64>> = Mutex::new(HashMap::new());
+}
+
+// Revoked session IDs (`sid`) with the time, as a Unix timestamp, by which every access token
+// issued to them has expired. The sessions table is the record.
+lazy_static! {
+    static ref REVOKED_SESSIONS: Mutex<HashMap<String, i64>> = Mutex::new(HashMap::new());
+}
+
+// Fills the cache from the table; run once at startup, before any request is served
+pub async fn load_revocations(pool: &SqlitePool) -> Result<usize, sqlx::Error> {
+    let rows = sqlx::query!(
+        "SELECT jti as `jti!`, CAST(strftime('%s', expires_at) AS INTEGER) as `exp!: i64` FROM revoked_tokens \
+        WHERE expires_at > datetime('now')"
+    )
+    .fetch_all(pool)
+    .await?;
+
+    // Access tokens outlive the revocation of their session by at most ACCESS_TOKEN_TTL
+    let ttl = ACCESS_TOKEN_TTL.num_seconds();
+    let sessions = sqlx::query!(
+        "SELECT session_id as `session_id!`, CAST(strftime('%s', revoked_at) AS INTEGER) + ? as `until!: i64` FROM sessions \
+        WHERE revoked_at IS NOT NULL AND CAST(strftime('%s', revoked_at) AS INTEGER) + ? > CAST(strftime('%s', 'now') AS INTEGER)",
+        ttl,
+        ttl
+    )
+    .fetch_all(pool)
+    .await?;
+    REVOKED_SESSIONS
+        .lock()
+        .unwrap()
+        .extend(sessions.into_iter().map(|row| (row.session_id, row.until)));
+
+    let mut revoked = REVOKED_TOKENS.lock().unwrap();
+    revoked.extend(rows.into_iter().map(|row| (row.jti, row.exp)));
+    Ok(revoked.len())
+}
+
+// Revokes a token until it expires
+pub async fn revoke_token(pool: &SqlitePool, claims: &Claims) -> Result<(), sqlx::Error> {
+    let exp = claims.exp as i64;
+    let expires_at = Utc
+        .timestamp_opt(exp, 0)
+        .single()
+        .unwrap_or_else(Utc::now)
+        .format(TIMESTAMP_FORMAT)
+        .to_string();
+    let user_id = claims.sub.parse::<i64>().ok();
+
+    sqlx::query!(
+        "INSERT OR IGNORE INTO revoked_tokens (jti, user_id, expires_at) VALUES (?, ?, ?)",
+        claims.jti,
+        user_id,
+        expires_at
+    )
+    .execute(pool)
+    .await?;
+
+    REVOKED_TOKENS.lock().unwrap().insert(claims.jti.clone(), exp);
+    Ok(())
+}
+
+// Whether the token with this ID has been revoked
+pub fn is_revoked(jti: &str) -> bool {
+    REVOKED_TOKENS.lock().unwrap().contains_key(jti)
+}
+
+// Refuses the access tokens of sessions that were just revoked in the sessions table
+pub fn revoke_session_tokens(session_ids: &[String]) {
+    let until = (Utc::now() + *ACCESS_TOKEN_TTL).timestamp();
+    let mut revoked = REVOKED_SESSIONS.lock().unwrap();
+    for session_id in session_ids {
+        revoked.insert(session_id.clone(), until);
+    }
+}
+
+// Whether the session with this ID has been revoked
+pub fn is_session_revoked(sid: &str) -> bool {
+    REVOKED_SESSIONS.lock().unwrap().contains_key(sid)
+}
+
+// Forgets revocations of tokens that have expired, expired refresh tokens, ended sessions and
+// password reset requests that no longer count towards the rate limit
+async fn prune_expired_records(pool: &SqlitePool) -> Result<u64, sqlx::Error> {
+    let now = Utc::now().timestamp();
+    REVOKED_TOKENS.lock().unwrap().retain(|_, exp| *exp > now);
+    REVOKED_SESSIONS.lock().unwrap().retain(|_, until| *until > now);
+
+    let deleted = sqlx::query!("DELETE FROM revoked_tokens WHERE expires_at <= datetime('now')")
+        .execute(pool)
+        .await?
+        .rows_affected();
+    // Expired refresh tokens are refused anyway, so they can go as well, and so can the sessions
+    // whose last refresh token expired
+    let expired = sqlx::query!("DELETE FROM refresh_tokens WHERE expires_at <= datetime('now')")
+        .execute(pool)
+        .await?
+        .rows_affected();
+    let ended = sqlx::query!("DELETE FROM sessions WHERE expires_at <= datetime('now')")
+        .execute(pool)
+        .await?
+        .rows_affected();
+    // Password reset requests only count towards the rate limit within its window
+    let window = format!("-{} seconds", PASSWORD_RESET_RATE_WINDOW.num_seconds());
+    let requests = sqlx::query!("DELETE FROM password_reset_requests WHERE requested_at <= datetime('now', ?)", window)
+        .execute(pool)
+        .await?
+        .rows_affected();
+    Ok(deleted + expired + ended + requests)
+}
+
+// Background task that prunes expired revocations, refresh tokens, sessions and reset requests every PRUNE_INTERVAL
+pub async fn prune_expired_records_periodically(pool: SqlitePool) {
+    let mut interval = actix_web::rt::time::interval(PRUNE_INTERVAL);
+    loop {
+        interval.tick().await;
+        match prune_expired_records(&pool).await {
+            Ok(0) => {}
+            Ok(deleted) => info!("Pruned {} expired token revocations, refresh tokens, sessions and reset requests", deleted),
+            Err(e) => error!("Failed to prune expired records: {}", e),
+        }
+    }
+}
@@ -6,4 +6,6 @@ pub struct Claims {
     pub username: String, // Username
     pub iat: usize,    // Issued at time
     pub exp: usize,    // Expiration time
+    pub jti: String,   // Unique token ID, by which the token is revoked
+    pub sid: String,   // Session ID: the refresh token family the token was issued from
 }
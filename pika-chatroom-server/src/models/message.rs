@@ -0,0 +1,28 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// A chat message as stored in the `messages` table, joined with the sender's username.
+/// Deleted messages are kept as tombstones: `deleted_at` is set and `content` is empty.
+/// Thread replies carry the ID of their root message in `parent_id`; roots count their replies.
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct StoredMessage {
+    pub message_id: i64,
+    pub room_id: i64,
+    pub user_id: i64,
+    pub username: String,
+    pub content: String,
+    pub created_at: String,
+    pub edited_at: Option<String>,
+    pub deleted_at: Option<String>,
+    pub parent_id: Option<i64>,
+    pub reply_count: i64, // Replies that have not been deleted
+}
+
+/// An earlier version of a message, saved before it was edited or deleted
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct MessageRevision {
+    pub revision_id: i64,
+    pub message_id: i64,
+    pub content: String,
+    pub revised_at: String,
+}
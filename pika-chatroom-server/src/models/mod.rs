@@ -1,5 +1,7 @@
 pub mod claim;
 pub mod room;
-pub mod user_room;
 pub mod response;
 pub mod presence;
+pub mod message;
+pub mod reaction;
+pub mod role;
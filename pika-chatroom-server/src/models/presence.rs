@@ -12,15 +12,45 @@ impl Message for GetRoomPresence {
     type Result = Vec<UserPresence>;
 }
 
+/// Message to request the presence status of specific users, in the order given, as seen from a room
+pub struct GetUsersPresence {
+    pub room_id: RoomId,
+    pub user_ids: Vec<UserId>,
+}
+
+impl Message for GetUsersPresence {
+    type Result = Vec<UserPresence>;
+}
+
+/// Message to request how many users are connected to each of the given rooms, in the order given
+pub struct GetOnlineCounts {
+    pub room_ids: Vec<RoomId>,
+}
+
+impl Message for GetOnlineCounts {
+    type Result = Vec<usize>;
+}
+
 /// Structure representing the presence status of a user
-#[derive(Serialize, Deserialize, Debug, ToSchema)] // Derive ToSchema for OpenAPI support
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)] // Derive ToSchema for OpenAPI support
 pub struct UserPresence {
     pub user_id: UserId,
-    pub is_online: bool,
+    pub is_online: bool, // Has at least one open connection anywhere
+    pub in_room: bool,   // Has at least one open connection to this room
 }
 
 impl UserPresence {
-    pub fn new(user_id: UserId, is_online: bool) -> Self {
-        UserPresence { user_id, is_online }
+    pub fn new(user_id: UserId, is_online: bool, in_room: bool) -> Self {
+        UserPresence { user_id, is_online, in_room }
     }
 }
+
+/// Kinds of presence change pushed to a room over WebSocket
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PresenceChange {
+    Online,  // The user opened their first connection anywhere
+    Offline, // The user closed their last connection anywhere
+    Joined,  // The user opened their first connection to this room
+    Left,    // The user closed their last connection to this room
+}
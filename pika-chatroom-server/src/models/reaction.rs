@@ -0,0 +1,57 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// A user who reacted to a message
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct ReactionUser {
+    pub user_id: i64,
+    pub username: String,
+}
+
+/// All reactions to a message with one emoji, in the order users reacted
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct ReactionSummary {
+    pub message_id: i64,
+    pub emoji: String,
+    pub count: i64,
+    pub users: Vec<ReactionUser>,
+}
+
+/// A row of the `reactions` table, joined with the username
+pub struct ReactionRow {
+    pub message_id: i64,
+    pub emoji: String,
+    pub user_id: i64,
+    pub username: String,
+}
+
+impl ReactionSummary {
+    /// Groups rows ordered by message into one summary per (message, emoji)
+    pub fn from_rows(rows: Vec<ReactionRow>) -> Vec<ReactionSummary> {
+        let mut summaries: Vec<ReactionSummary> = Vec::new();
+        for row in rows {
+            let user = ReactionUser {
+                user_id: row.user_id,
+                username: row.username,
+            };
+            match summaries
+                .iter_mut()
+                .rev()
+                .take_while(|summary| summary.message_id == row.message_id)
+                .find(|summary| summary.emoji == row.emoji)
+            {
+                Some(summary) => {
+                    summary.count += 1;
+                    summary.users.push(user);
+                }
+                None => summaries.push(ReactionSummary {
+                    message_id: row.message_id,
+                    emoji: row.emoji,
+                    count: 1,
+                    users: vec![user],
+                }),
+            }
+        }
+        summaries
+    }
+}
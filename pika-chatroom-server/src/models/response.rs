@@ -13,7 +13,9 @@ pub struct ErrorResponse {
 
 #[derive(serde::Serialize, ToSchema)]
 pub struct TokenResponse {
-    pub token: String,             // JWT token
+    pub token: String,             // Short-lived JWT access token
+    pub expires_in: i64,           // Seconds until the access token expires
+    pub refresh_token: String,     // Opaque token for POST /api/token/refresh; valid for one use
     pub username: String,          // Username of the logged-in user
     pub avatar_url: Option<String>, // Optional avatar URL of the user
 }
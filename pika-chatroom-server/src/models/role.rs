@@ -0,0 +1,71 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Role a member holds in a room, stored in `user_rooms.role`.
+/// Every channel has exactly one owner; everyone who joins starts as a member. Direct messages have
+/// no owner: all their participants are members.
+#[derive(Serialize, Deserialize, sqlx::Type, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum Role {
+    Owner,
+    Admin,
+    Moderator,
+    Member,
+}
+
+/// Actions in a room that depend on the member's role
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    ViewRoom,             // Read the history, threads, reactions, members and presence
+    PostMessages,         // Send messages and replies, edit and delete own messages, react
+    DeleteOthersMessages, // Replace anyone's message with a tombstone
+    ViewOthersRevisions,  // Read the edit history of anyone's message
+    ManageRoles,          // Promote and demote members ranked below oneself
+    InviteMembers,        // Invite users to the room, which private rooms require
+    RemoveMembers,        // Kick and ban members ranked below oneself
+    ModerateChat,         // Mute members ranked below oneself and set slow mode, which does not apply to them
+    ManageRoom,           // Change the room's name, topic, visibility and invite links, and archive it
+    DeleteRoom,           // Delete the room with its history
+    TransferOwnership,    // Hand the room over to another member
+}
+
+impl Permission {
+    /// Whether the action changes the room's content, which archived rooms no longer allow
+    pub fn changes_content(self) -> bool {
+        matches!(self, Permission::PostMessages | Permission::DeleteOthersMessages)
+    }
+}
+
+impl Role {
+    // Higher ranks outrank lower ones
+    fn rank(self) -> u8 {
+        match self {
+            Role::Owner => 3,
+            Role::Admin => 2,
+            Role::Moderator => 1,
+            Role::Member => 0,
+        }
+    }
+
+    /// Whether this role ranks strictly above `other`
+    pub fn outranks(self, other: Role) -> bool {
+        self.rank() > other.rank()
+    }
+
+    /// The permission table: which roles may perform which actions
+    pub fn can(self, permission: Permission) -> bool {
+        match permission {
+            Permission::ViewRoom | Permission::PostMessages => true,
+            Permission::DeleteOthersMessages
+            | Permission::ViewOthersRevisions
+            | Permission::InviteMembers
+            | Permission::RemoveMembers
+            | Permission::ModerateChat => {
+                self.rank() >= Role::Moderator.rank()
+            }
+            Permission::ManageRoles | Permission::ManageRoom => self.rank() >= Role::Admin.rank(),
+            Permission::DeleteRoom | Permission::TransferOwnership => self == Role::Owner,
+        }
+    }
+}
@@ -1,9 +1,23 @@
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
-// TODO: Check if necessary
-#[derive(Serialize, Deserialize, Debug)]
-pub struct Room {
-    pub room_id: i32,
-    pub room_name: String,
-    pub created_at: String,
+/// Who can find and join a room, stored in `rooms.visibility`
+#[derive(Serialize, Deserialize, sqlx::Type, Debug, Clone, Copy, PartialEq, Eq, Default, ToSchema)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum RoomVisibility {
+    #[default]
+    Public,   // Listed for everyone; anyone can join
+    Unlisted, // Only listed for members; anyone who knows the room ID can join
+    Private,  // Only listed for members and invited users; joining needs an invitation
+}
+
+/// What a room is for, stored in `rooms.kind`
+#[derive(Serialize, Deserialize, sqlx::Type, Debug, Clone, Copy, PartialEq, Eq, Default, ToSchema)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum RoomKind {
+    #[default]
+    Channel, // A named room, listed according to its visibility
+    Dm,      // A private conversation between a fixed set of participants, never listed as a room
 }
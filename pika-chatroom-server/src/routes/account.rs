@@ -0,0 +1,349 @@
+use crate::config::keys::JWT_KEYS;
+use crate::middleware::auth_middleware::bearer_token;
+use crate::middleware::revocation::revoke_token;
+use crate::models::claim::Claims;
+use crate::models::response::{ErrorResponse, MessageResponse};
+use crate::models::role::Role;
+use crate::routes::auth::normalize_email;
+use crate::routes::message::TIMESTAMP_FORMAT;
+use crate::routes::role::set_owner;
+use crate::routes::room::is_unique_violation;
+use crate::routes::session::{end_sessions, revoke_session, user_session_ids};
+use crate::websockets::chat_session::{BroadcastMessage, RemoveMember, RoomServer};
+use crate::websockets::protocol::{RemovalCause, ServerEvent};
+use actix::Addr;
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse, Responder};
+use bcrypt::verify;
+use chrono::Utc;
+use log::{error, info};
+use serde::Deserialize;
+use sqlx::SqlitePool;
+use utoipa::ToSchema;
+
+// Deleted accounts are renamed to this prefix followed by their user ID, which frees their
+// username; registering a name with this prefix is refused
+pub(crate) const DELETED_USERNAME_PREFIX: &str = "deleted-user-";
+
+#[derive(Deserialize, ToSchema)]
+pub struct DeleteAccountRequest {
+    pub password: String, // The account's current password, to confirm the deletion
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct SetEmailRequest {
+    pub email: String,            // The new address; empty to remove it
+    pub current_password: String, // Confirms the change, since the address can reset the password
+}
+
+// What became of a channel the deleted user owned
+enum Handover {
+    Transferred { room_id: i64, user_id: i64, username: String },
+    Archived { room_id: i64 },
+}
+
+// What deleting an account changed, announced once the deletion is committed
+struct Deletion {
+    handovers: Vec<Handover>,
+    memberships: Vec<i64>,    // Rooms the user was in
+    session_ids: Vec<String>, // Sessions revoked along with the account
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/account/email",
+    request_body = SetEmailRequest,
+    params(
+        ("Authorization" = String, Header, description = "Bearer <JWT Token>")
+    ),
+    responses(
+        (status = 200, description = "Email address set, changed or removed; reset links sent to the previous address stop working", body = MessageResponse),
+        (status = 400, description = "Invalid email address, or another account uses it", body = ErrorResponse),
+        (status = 401, description = "User ID missing in token, or wrong current password", body = ErrorResponse),
+        (status = 500, description = "Failed to update email", body = ErrorResponse)
+    )
+)]
+pub async fn set_email(
+    pool: web::Data<SqlitePool>,
+    body: web::Json<SetEmailRequest>,
+    req: HttpRequest,
+) -> impl Responder {
+    let user_id = match req.extensions().get::<i64>().copied() {
+        Some(user_id) => user_id,
+        None => {
+            return HttpResponse::Unauthorized().json(ErrorResponse {
+                error: "User ID missing in token".into(),
+            })
+        }
+    };
+    let email = match normalize_email(&body.email) {
+        Ok(email) => email,
+        Err(response) => return response,
+    };
+
+    match sqlx::query_scalar!(
+        "SELECT password_hash FROM users WHERE user_id = ? AND deleted_at IS NULL",
+        user_id
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    {
+        Ok(Some(password_hash)) if verify(&body.current_password, &password_hash).unwrap_or(false) => {}
+        Ok(_) => {
+            return HttpResponse::Unauthorized().json(ErrorResponse {
+                error: "Unauthorized: Invalid password".into(),
+            })
+        }
+        Err(e) => {
+            error!("Failed to load user {}: {}", user_id, e);
+            return HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to update email".into(),
+            });
+        }
+    }
+
+    // Links already mailed went to the previous address, which may no longer be the user's
+    let result = async {
+        let mut tx = pool.begin().await?;
+        sqlx::query!("UPDATE users SET email = ? WHERE user_id = ?", email, user_id)
+            .execute(&mut tx)
+            .await?;
+        sqlx::query!(
+            "UPDATE password_reset_tokens SET used_at = datetime('now') WHERE user_id = ? AND used_at IS NULL",
+            user_id
+        )
+        .execute(&mut tx)
+        .await?;
+        tx.commit().await
+    }
+    .await;
+
+    match result {
+        Ok(()) => {
+            info!("User {} {} their email address", user_id, if email.is_some() { "set" } else { "removed" });
+            HttpResponse::Ok().json(MessageResponse {
+                message: match email {
+                    Some(_) => "Email address saved".into(),
+                    None => "Email address removed".into(),
+                },
+            })
+        }
+        Err(e) if is_unique_violation(&e) => HttpResponse::BadRequest().json(ErrorResponse {
+            error: "Another account uses this email address".into(),
+        }),
+        Err(e) => {
+            error!("Failed to update email of user {}: {}", user_id, e);
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to update email".into(),
+            })
+        }
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/account",
+    request_body = DeleteAccountRequest,
+    params(
+        ("Authorization" = String, Header, description = "Bearer <JWT Token>")
+    ),
+    responses(
+        (status = 200, description = "Account deleted: owned channels passed on or archived, memberships removed, messages anonymized, tokens revoked and live connections closed", body = MessageResponse),
+        (status = 401, description = "User ID missing in token, or wrong password", body = ErrorResponse),
+        (status = 500, description = "Failed to delete account", body = ErrorResponse)
+    )
+)]
+pub async fn delete_account(
+    pool: web::Data<SqlitePool>,
+    room_server: web::Data<Addr<RoomServer>>,
+    body: web::Json<DeleteAccountRequest>,
+    req: HttpRequest,
+) -> impl Responder {
+    let user_id = match req.extensions().get::<i64>().copied() {
+        Some(user_id) => user_id,
+        None => {
+            return HttpResponse::Unauthorized().json(ErrorResponse {
+                error: "User ID missing in token".into(),
+            })
+        }
+    };
+
+    let user = match sqlx::query!(
+        "SELECT username, password_hash FROM users WHERE user_id = ? AND deleted_at IS NULL",
+        user_id
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    {
+        Ok(Some(user)) if verify(&body.password, &user.password_hash).unwrap_or(false) => user,
+        Ok(_) => {
+            return HttpResponse::Unauthorized().json(ErrorResponse {
+                error: "Unauthorized: Invalid password".into(),
+            })
+        }
+        Err(e) => {
+            error!("Failed to load user {}: {}", user_id, e);
+            return HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to delete account".into(),
+            });
+        }
+    };
+
+    let deleted_at = Utc::now().format(TIMESTAMP_FORMAT).to_string();
+    let deleted_username = format!("{}{}", DELETED_USERNAME_PREFIX, user_id);
+
+    let result: Result<Deletion, sqlx::Error> = async {
+        let mut tx = pool.begin().await?;
+
+        // Each channel the user owns goes to its highest-ranked remaining member, the longest-standing
+        // one among equals, and is archived if nobody else is left. Direct messages have no owner.
+        let owned_rooms = sqlx::query_scalar!(
+            "SELECT room_id as `room_id!: i64` FROM rooms WHERE user_id = ? AND kind = 'channel'",
+            user_id
+        )
+        .fetch_all(&mut tx)
+        .await?;
+        let mut handovers = Vec::with_capacity(owned_rooms.len());
+        for room_id in owned_rooms {
+            let successor = sqlx::query!(
+                "SELECT u.user_id as `user_id!: i64`, u.username as `username!` FROM user_rooms ur \
+                INNER JOIN users u ON u.user_id = ur.user_id \
+                WHERE ur.room_id = ? AND ur.user_id != ? \
+                ORDER BY CASE ur.role WHEN 'admin' THEN 0 WHEN 'moderator' THEN 1 ELSE 2 END, ur.joined_at, ur.user_id \
+                LIMIT 1",
+                room_id,
+                user_id
+            )
+            .fetch_optional(&mut tx)
+            .await?;
+            match successor {
+                Some(successor) => {
+                    set_owner(&mut tx, room_id, successor.user_id).await?;
+                    handovers.push(Handover::Transferred {
+                        room_id,
+                        user_id: successor.user_id,
+                        username: successor.username,
+                    });
+                }
+                None => {
+                    sqlx::query!(
+                        "UPDATE rooms SET archived_at = COALESCE(archived_at, ?) WHERE room_id = ?",
+                        deleted_at,
+                        room_id
+                    )
+                    .execute(&mut tx)
+                    .await?;
+                    handovers.push(Handover::Archived { room_id });
+                }
+            }
+        }
+
+        let memberships = sqlx::query_scalar!(
+            "SELECT room_id as `room_id!: i64` FROM user_rooms WHERE user_id = ?",
+            user_id
+        )
+        .fetch_all(&mut tx)
+        .await?;
+        sqlx::query!("DELETE FROM user_rooms WHERE user_id = ?", user_id)
+            .execute(&mut tx)
+            .await?;
+        sqlx::query!("DELETE FROM room_invitations WHERE user_id = ?", user_id)
+            .execute(&mut tx)
+            .await?;
+        // Links that only this user could redeem are of no use to anyone else
+        sqlx::query!("DELETE FROM invite_links WHERE user_id = ?", user_id)
+            .execute(&mut tx)
+            .await?;
+        sqlx::query!("DELETE FROM room_mutes WHERE user_id = ?", user_id)
+            .execute(&mut tx)
+            .await?;
+        sqlx::query!("DELETE FROM room_bans WHERE user_id = ?", user_id)
+            .execute(&mut tx)
+            .await?;
+        sqlx::query!("DELETE FROM password_reset_tokens WHERE user_id = ?", user_id)
+            .execute(&mut tx)
+            .await?;
+        // None of the account's sessions can be refreshed anymore
+        let session_ids = user_session_ids(&mut tx, user_id, None).await?;
+        for session_id in &session_ids {
+            revoke_session(&mut tx, session_id).await?;
+        }
+
+        // Messages and reactions name their author through the users table, so renaming the
+        // account anonymizes them. Without a password hash the account can no longer log in, and
+        // without an email address it cannot be reset.
+        sqlx::query!(
+            "UPDATE users SET username = ?, password_hash = '', avatar_url = NULL, email = NULL, deleted_at = ? WHERE user_id = ?",
+            deleted_username,
+            deleted_at,
+            user_id
+        )
+        .execute(&mut tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(Deletion {
+            handovers,
+            memberships,
+            session_ids,
+        })
+    }
+    .await;
+
+    let Deletion { handovers, memberships, session_ids } = match result {
+        Ok(deletion) => deletion,
+        Err(e) => {
+            error!("Failed to delete account of user {}: {}", user_id, e);
+            return HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to delete account".into(),
+            });
+        }
+    };
+
+    // Tokens of deleted accounts are rejected from now on; the one used here is also revoked
+    if let Some(token_data) = bearer_token(&req).and_then(|token| JWT_KEYS.verify::<Claims>(token).ok()) {
+        if let Err(e) = revoke_token(pool.get_ref(), &token_data.claims).await {
+            error!("Failed to revoke token {}: {}", token_data.claims.jti, e);
+        }
+    }
+    // Access tokens of every session are refused right away, and their connections close
+    end_sessions(room_server.get_ref(), session_ids);
+
+    for handover in handovers {
+        match handover {
+            Handover::Transferred { room_id, user_id: owner_id, username } => {
+                info!("Room {} passed from deleted user {} to user {}", room_id, user_id, owner_id);
+                room_server.do_send(BroadcastMessage {
+                    room_id,
+                    event: ServerEvent::RoleChanged {
+                        room_id,
+                        user_id: owner_id,
+                        username,
+                        role: Role::Owner,
+                    },
+                });
+            }
+            Handover::Archived { room_id } => {
+                // Nobody else was in the room, so there is no one to tell
+                info!("Room {} archived after its owner, user {}, deleted their account", room_id, user_id);
+            }
+        }
+    }
+    // Rooms learn under the old name who left; the account's own connections close
+    for room_id in memberships {
+        room_server.do_send(RemoveMember {
+            room_id,
+            user_id,
+            event: ServerEvent::MemberRemoved {
+                room_id,
+                user_id,
+                username: user.username.clone(),
+                cause: RemovalCause::AccountDeleted,
+            },
+        });
+    }
+
+    info!("User {} ('{}') deleted their account", user_id, user.username);
+    HttpResponse::Ok().json(MessageResponse {
+        message: "Account deleted".into(),
+    })
+}
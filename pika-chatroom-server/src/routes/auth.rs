@@ -1,20 +1,93 @@
 use actix_web::{post, HttpResponse, web, Responder, HttpRequest};
 use bcrypt::{hash, verify, DEFAULT_COST};
-use jsonwebtoken::{encode, Header, EncodingKey};
 use chrono::Utc;
-use sqlx::SqlitePool;
-use log::{info, error};
+use sqlx::{Executor, Sqlite, SqlitePool};
+use log::{info, error, warn};
+use sha2::{Digest, Sha256};
 use crate::models::claim::Claims;
 use serde::Deserialize;
-use crate::config::state::TOKEN_BLACKLIST;
+use crate::config::keys::JWT_KEYS;
+use crate::middleware::auth_middleware::bearer_token;
+use crate::middleware::revocation::revoke_token;
+use rand::distributions::{Alphanumeric, DistString};
 use utoipa::ToSchema;
 use crate::models::response::{MessageResponse, ErrorResponse, TokenResponse};
+use crate::routes::account::DELETED_USERNAME_PREFIX;
+use crate::routes::message::TIMESTAMP_FORMAT;
+use crate::config::settings::{ACCESS_TOKEN_TTL, REFRESH_TOKEN_TTL};
+use crate::routes::session::{end_sessions, revoke_session, start_session, touch_session, ClientInfo};
+use crate::websockets::chat_session::RoomServer;
+use actix::Addr;
+
+// Length of token and session IDs (`jti`, `sid`); 62^22 IDs make collisions practically impossible
+const TOKEN_ID_LENGTH: usize = 22;
+// Length of refresh tokens; 62^43 values cannot be guessed, so an unsalted hash stores them safely
+const REFRESH_TOKEN_LENGTH: usize = 43;
+
+#[derive(Deserialize, ToSchema)]
+pub struct RefreshRequest {
+    refresh_token: String,
+}
+
+// Refresh and password reset tokens are stored as their SHA-256 hash, so the tables alone cannot be used to log in
+pub(crate) fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+// Signs a short-lived access token for a session
+fn issue_access_token(user_id: i64, username: &str, session_id: &str) -> Result<String, jsonwebtoken::errors::Error> {
+    let now = Utc::now();
+    let claims = Claims {
+        sub: user_id.to_string(), // Use user_id for sub
+        username: username.to_string(),
+        iat: now.timestamp() as usize, // Issued at time
+        exp: (now + *ACCESS_TOKEN_TTL).timestamp() as usize, // Expiration time
+        jti: Alphanumeric.sample_string(&mut rand::thread_rng(), TOKEN_ID_LENGTH), // Identifies the token for revocation
+        sid: session_id.to_string(), // The session the token was issued to
+    };
+    // Sign with the active key, which tags the token with its `kid`
+    JWT_KEYS.sign(&claims)
+}
+
+// Stores a new refresh token in a session's family and returns the token
+async fn issue_refresh_token<'e, E: Executor<'e, Database = Sqlite>>(
+    executor: E,
+    user_id: i64,
+    session_id: &str,
+) -> Result<String, sqlx::Error> {
+    let token = Alphanumeric.sample_string(&mut rand::thread_rng(), REFRESH_TOKEN_LENGTH);
+    let token_hash = hash_token(&token);
+    let expires_at = (Utc::now() + *REFRESH_TOKEN_TTL).format(TIMESTAMP_FORMAT).to_string();
+    sqlx::query!(
+        "INSERT INTO refresh_tokens (token_hash, family_id, user_id, expires_at) VALUES (?, ?, ?, ?)",
+        token_hash,
+        session_id,
+        user_id,
+        expires_at
+    )
+    .execute(executor)
+    .await?;
+    Ok(token)
+}
+
+// Addresses are stored trimmed and lowercased, and compared that way; a blank one counts as none
+pub(crate) fn normalize_email(email: &str) -> Result<Option<String>, HttpResponse> {
+    let email = email.trim().to_lowercase();
+    if email.is_empty() {
+        return Ok(None);
+    }
+    if !email.contains('@') {
+        return Err(HttpResponse::BadRequest().json(ErrorResponse { error: "Invalid email address".into() }));
+    }
+    Ok(Some(email))
+}
 
 #[derive(Deserialize, ToSchema)]
 pub struct AuthData {
     username: String,
     password: String,
     avatar_url: Option<String>,  // Optional avatar URL field
+    email: Option<String>,       // Optional, only used by register; needed to reset a forgotten password
 }
 
 #[utoipa::path(
@@ -23,7 +96,7 @@ pub struct AuthData {
     request_body = AuthData,
     responses(
         (status = 201, description = "User created successfully", body = MessageResponse),
-        (status = 400, description = "User could not be created", body = ErrorResponse)
+        (status = 400, description = "User could not be created, e.g. the username or email is taken, or the email is invalid", body = ErrorResponse)
     )
 )]
 #[post("/register")]
@@ -31,16 +104,28 @@ async fn register_user(
     pool: web::Data<SqlitePool>,
     user_data: web::Json<AuthData>,
 ) -> HttpResponse {
+    // Deleted accounts are renamed with this prefix
+    if user_data.username.starts_with(DELETED_USERNAME_PREFIX) {
+        return HttpResponse::BadRequest().json(ErrorResponse {
+            error: format!("Usernames cannot start with '{}'", DELETED_USERNAME_PREFIX),
+        });
+    }
+
     let hashed_password = hash(&user_data.password, DEFAULT_COST).unwrap();
 
     // Optional avatar_url handling
-    let avatar_url = user_data.avatar_url.clone().unwrap_or_else(|| "".to_string());
+    let avatar_url = user_data.avatar_url.clone().unwrap_or_default();
+    let email = match normalize_email(user_data.email.as_deref().unwrap_or_default()) {
+        Ok(email) => email,
+        Err(response) => return response,
+    };
 
     let result = sqlx::query!(
-        "INSERT INTO users (username, password_hash, avatar_url) VALUES (?, ?, ?)",
+        "INSERT INTO users (username, password_hash, avatar_url, email) VALUES (?, ?, ?, ?)",
         user_data.username,
         hashed_password,
-        avatar_url
+        avatar_url,
+        email
     )
     .execute(pool.get_ref())
     .await;
@@ -64,17 +149,19 @@ async fn register_user(
     responses(
         (status = 200, description = "User logged in successfully", body = TokenResponse),
         (status = 401, description = "Unauthorized: Invalid username or password or User ID missing in token", body = ErrorResponse),
-        (status = 401, description = "Unauthorized: User ID missing in token", body = ErrorResponse)
+        (status = 401, description = "Unauthorized: User ID missing in token", body = ErrorResponse),
+        (status = 500, description = "Failed to log in", body = ErrorResponse)
     )
 )]
 #[post("/login")]
 async fn login_user(
     pool: web::Data<SqlitePool>,
     login_data: web::Json<AuthData>,
+    req: HttpRequest,
 ) -> HttpResponse {
     // Fetch user from the database based on the provided username
     let user = sqlx::query!(
-        "SELECT user_id, username, password_hash, avatar_url, created_at FROM users WHERE username = ?",
+        "SELECT user_id, username, password_hash, avatar_url, created_at FROM users WHERE username = ? AND deleted_at IS NULL",
         login_data.username
     )
     .fetch_optional(pool.get_ref())
@@ -84,24 +171,32 @@ async fn login_user(
         let is_valid = verify(&login_data.password, &user.password_hash).unwrap();
 
         if is_valid {
-            let now = Utc::now().timestamp() as usize;
-            let expiration = Utc::now()
-                .checked_add_signed(chrono::Duration::hours(24))
-                .expect("valid timestamp")
-                .timestamp() as usize;
-
-            let claims = Claims {
-                sub: user.user_id.expect("User ID should not be None").to_string(), // Use user_id for sub
-                username: user.username.clone(), // Added username to claims
-                iat: now, // Issued at time
-                exp: expiration, // Expiration time
+            let user_id = user.user_id.expect("User ID should not be None");
+            // Each login starts a session: a family of refresh tokens, each exchanged for the next
+            let session_id = Alphanumeric.sample_string(&mut rand::thread_rng(), TOKEN_ID_LENGTH);
+            let client = ClientInfo::from_request(&req);
+            let started = async {
+                let mut tx = pool.begin().await?;
+                start_session(&mut tx, &session_id, user_id, &client).await?;
+                let refresh_token = issue_refresh_token(&mut tx, user_id, &session_id).await?;
+                tx.commit().await?;
+                Ok::<String, sqlx::Error>(refresh_token)
+            }
+            .await;
+            let refresh_token = match started {
+                Ok(refresh_token) => refresh_token,
+                Err(e) => {
+                    error!("Failed to start a session for user '{}': {}", user.username, e);
+                    return HttpResponse::InternalServerError().json(ErrorResponse { error: "Failed to log in".into() });
+                }
+            };
+            let token = match issue_access_token(user_id, &user.username, &session_id) {
+                Ok(token) => token,
+                Err(e) => {
+                    error!("Failed to sign token for user '{}': {}", user.username, e);
+                    return HttpResponse::InternalServerError().json(ErrorResponse { error: "Failed to log in".into() });
+                }
             };
-
-            let token = encode(
-                &Header::default(),
-                &claims,
-                &EncodingKey::from_secret("secret_key_for_jwt".as_ref()),
-            ).unwrap();
 
             info!(
                 "User '{}' logged in successfully. Avatar URL: {:?}",
@@ -109,14 +204,16 @@ async fn login_user(
             );
 
             // Include avatar_url in the response
-            return HttpResponse::Ok().json(TokenResponse {
+            HttpResponse::Ok().json(TokenResponse {
                 token,
+                expires_in: ACCESS_TOKEN_TTL.num_seconds(),
+                refresh_token,
                 username: user.username,
                 avatar_url: user.avatar_url,
-            });
+            })
         } else {
             info!("User '{}' failed to log in due to incorrect password.", login_data.username);
-            return HttpResponse::Unauthorized().json(ErrorResponse { error: "Unauthorized: Invalid username or password".into() });
+            HttpResponse::Unauthorized().json(ErrorResponse { error: "Unauthorized: Invalid username or password".into() })
         }
     } else {
         info!("Login attempt failed: user '{}' not found.", login_data.username);
@@ -124,30 +221,153 @@ async fn login_user(
     }
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/token/refresh",
+    request_body = RefreshRequest,
+    responses(
+        (status = 200, description = "A new access token and a new refresh token; the refresh token sent can no longer be used", body = TokenResponse),
+        (status = 401, description = "Unauthorized: the refresh token is unknown, expired or revoked, or was already used, which revokes its whole session", body = ErrorResponse),
+        (status = 500, description = "Failed to refresh token", body = ErrorResponse)
+    )
+)]
+#[post("/token/refresh")]
+async fn refresh_access_token(
+    pool: web::Data<SqlitePool>,
+    room_server: web::Data<Addr<RoomServer>>,
+    body: web::Json<RefreshRequest>,
+    req: HttpRequest,
+) -> HttpResponse {
+    let invalid = || HttpResponse::Unauthorized().json(ErrorResponse { error: "Unauthorized: Invalid refresh token".into() });
+    let failed = |e: sqlx::Error| {
+        error!("Failed to refresh token: {}", e);
+        HttpResponse::InternalServerError().json(ErrorResponse { error: "Failed to refresh token".into() })
+    };
+    let token_hash = hash_token(&body.refresh_token);
+
+    let mut tx = match pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => return failed(e),
+    };
+    let stored = match sqlx::query!(
+        "SELECT rt.token_id as `token_id!: i64`, rt.family_id, rt.user_id as `user_id!: i64`, u.username, u.avatar_url, \
+        rt.used_at IS NOT NULL as `used!: bool`, rt.revoked_at IS NOT NULL as `revoked!: bool`, \
+        (rt.expires_at > datetime('now') AND u.deleted_at IS NULL) as `live!: bool` \
+        FROM refresh_tokens rt INNER JOIN users u ON u.user_id = rt.user_id \
+        WHERE rt.token_hash = ?",
+        token_hash
+    )
+    .fetch_optional(&mut tx)
+    .await
+    {
+        Ok(Some(stored)) => stored,
+        Ok(None) => return invalid(),
+        Err(e) => return failed(e),
+    };
+    if stored.revoked || !stored.live {
+        return invalid();
+    }
+
+    // Each refresh token is good for one refresh. One that comes back after being used was
+    // copied, so nothing issued to its session can be trusted anymore.
+    let rotated = !stored.used
+        && match sqlx::query!(
+            "UPDATE refresh_tokens SET used_at = datetime('now') WHERE token_id = ? AND used_at IS NULL",
+            stored.token_id
+        )
+        .execute(&mut tx)
+        .await
+        {
+            Ok(result) => result.rows_affected() == 1,
+            Err(e) => return failed(e),
+        };
+    if !rotated {
+        warn!(
+            "Refresh token of session {} of user {} was used twice; revoking the session",
+            stored.family_id, stored.user_id
+        );
+        let revoked = async {
+            revoke_session(&mut tx, &stored.family_id).await?;
+            tx.commit().await
+        }
+        .await;
+        return match revoked {
+            Ok(()) => {
+                end_sessions(room_server.get_ref(), vec![stored.family_id]);
+                invalid()
+            }
+            Err(e) => failed(e),
+        };
+    }
+
+    let client = ClientInfo::from_request(&req);
+    let rotated = async {
+        touch_session(&mut tx, &stored.family_id, &client).await?;
+        issue_refresh_token(&mut tx, stored.user_id, &stored.family_id).await
+    }
+    .await;
+    let refresh_token = match rotated {
+        Ok(refresh_token) => refresh_token,
+        Err(e) => return failed(e),
+    };
+    if let Err(e) = tx.commit().await {
+        return failed(e);
+    }
+    let token = match issue_access_token(stored.user_id, &stored.username, &stored.family_id) {
+        Ok(token) => token,
+        Err(e) => {
+            error!("Failed to sign token for user '{}': {}", stored.username, e);
+            return HttpResponse::InternalServerError().json(ErrorResponse { error: "Failed to refresh token".into() });
+        }
+    };
+
+    HttpResponse::Ok().json(TokenResponse {
+        token,
+        expires_in: ACCESS_TOKEN_TTL.num_seconds(),
+        refresh_token,
+        username: stored.username,
+        avatar_url: stored.avatar_url,
+    })
+}
+
 #[utoipa::path(
     post,
     path = "/api/logout",
     responses(
-        (status = 200, description = "Logged out successfully", body = MessageResponse),
-        (status = 400, description = "Invalid or missing token", body = ErrorResponse)
+        (status = 200, description = "Logged out successfully; the access token is revoked until it expires, the session can no longer be refreshed and its WebSockets are closed", body = MessageResponse),
+        (status = 400, description = "Invalid or missing token", body = ErrorResponse),
+        (status = 500, description = "Failed to log out", body = ErrorResponse)
     ),
     params(
         ("Authorization" = String, Header, description = "Bearer <JWT Token>")
     )
 )]
-pub async fn logout_user(req: HttpRequest) -> impl Responder {
-    if let Some(auth_header) = req.headers().get("Authorization") {
-        if let Ok(header_str) = auth_header.to_str() {
-            if header_str.starts_with("Bearer ") {
-                let token = header_str[7..].to_string();
-                {
-                    let mut blacklist = TOKEN_BLACKLIST.lock().unwrap();
-                    blacklist.insert(token.clone());
-                    info!("Token '{}' added to blacklist. Current blacklist: {:?}", token, *blacklist);
-                }
-                return HttpResponse::Ok().json(MessageResponse { message: "Logged out successfully".into() });
-            }
+pub async fn logout_user(
+    pool: web::Data<SqlitePool>,
+    room_server: web::Data<Addr<RoomServer>>,
+    req: HttpRequest,
+) -> impl Responder {
+    let claims = match bearer_token(&req).and_then(|token| JWT_KEYS.verify::<Claims>(token).ok()) {
+        Some(token_data) => token_data.claims,
+        None => return HttpResponse::BadRequest().json(ErrorResponse { error: "Invalid or missing token".into() }),
+    };
+
+    let revoked = async {
+        revoke_token(pool.get_ref(), &claims).await?;
+        let mut tx = pool.begin().await?;
+        revoke_session(&mut tx, &claims.sid).await?;
+        tx.commit().await
+    }
+    .await;
+    match revoked {
+        Ok(()) => {
+            end_sessions(room_server.get_ref(), vec![claims.sid.clone()]);
+            info!("User {} logged out; token {} and session {} revoked", claims.sub, claims.jti, claims.sid);
+            HttpResponse::Ok().json(MessageResponse { message: "Logged out successfully".into() })
+        }
+        Err(e) => {
+            error!("Failed to revoke token {}: {}", claims.jti, e);
+            HttpResponse::InternalServerError().json(ErrorResponse { error: "Failed to log out".into() })
         }
     }
-    HttpResponse::BadRequest().json(ErrorResponse { error: "Invalid or missing token".into() })
 }
@@ -0,0 +1,255 @@
+use crate::models::response::ErrorResponse;
+use crate::models::role::Role;
+use crate::routes::room::{RoomMember, DM_NAME_PREFIX};
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse, Responder};
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::collections::BTreeSet;
+use utoipa::ToSchema;
+
+// Most participants a direct message can have, including the user who starts it
+const MAX_DM_PARTICIPANTS: usize = 8;
+
+#[derive(Deserialize, ToSchema)]
+pub struct DmRequest {
+    pub participants: Vec<String>, // Usernames of the other participants; the requesting user is always included
+}
+
+/// A direct message conversation: a private room of kind `dm` that is reached through its `room_id`
+/// like any other room, over the same WebSocket and history endpoints
+#[derive(Serialize, ToSchema)]
+pub struct DirectMessage {
+    pub room_id: i64,
+    pub participants: Vec<RoomMember>, // Everyone still in the conversation, including the requesting user
+    pub last_message_at: Option<String>,
+}
+
+// Loads the current participants of a direct message
+async fn load_participants(pool: &SqlitePool, room_id: i64) -> Result<Vec<RoomMember>, sqlx::Error> {
+    sqlx::query_as!(
+        RoomMember,
+        "SELECT u.user_id as `user_id!: i64`, u.username as `username!`, u.avatar_url, ur.role as `role!: Role`, \
+        ur.joined_at FROM users u \
+        INNER JOIN user_rooms ur ON u.user_id = ur.user_id \
+        WHERE ur.room_id = ? ORDER BY u.username",
+        room_id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/dms",
+    request_body = DmRequest,
+    params(
+        ("Authorization" = String, Header, description = "Bearer <JWT Token>")
+    ),
+    responses(
+        (status = 201, description = "Direct message created with the requesting user and the participants as members", body = DirectMessage),
+        (status = 200, description = "A direct message between exactly these users already exists; anyone who left it is added back", body = DirectMessage),
+        (status = 400, description = "No other participants, or too many", body = ErrorResponse),
+        (status = 401, description = "User ID missing in token", body = ErrorResponse),
+        (status = 404, description = "A participant does not exist", body = ErrorResponse),
+        (status = 500, description = "Failed to create direct message", body = ErrorResponse)
+    )
+)]
+pub async fn create_dm(
+    pool: web::Data<SqlitePool>,
+    body: web::Json<DmRequest>,
+    req: HttpRequest,
+) -> impl Responder {
+    let user_id = match req.extensions().get::<i64>().copied() {
+        Some(user_id) => user_id,
+        None => {
+            return HttpResponse::Unauthorized().json(ErrorResponse {
+                error: "User ID missing in token".into(),
+            })
+        }
+    };
+
+    // The participant set decides which conversation this is, whatever order the names came in
+    let mut participant_ids = BTreeSet::from([user_id]);
+    let usernames: BTreeSet<&str> = body
+        .participants
+        .iter()
+        .map(|username| username.trim())
+        .filter(|username| !username.is_empty())
+        .collect();
+    let too_many = || {
+        HttpResponse::BadRequest().json(ErrorResponse {
+            error: format!("Direct messages can have at most {} participants", MAX_DM_PARTICIPANTS),
+        })
+    };
+    // The list may name the requesting user too, so it can be one longer than the limit
+    if usernames.len() > MAX_DM_PARTICIPANTS {
+        return too_many();
+    }
+    for username in usernames {
+        match sqlx::query_scalar!(
+            "SELECT user_id as `user_id!: i64` FROM users WHERE username = ? AND deleted_at IS NULL",
+            username
+        )
+        .fetch_optional(pool.get_ref())
+        .await
+        {
+            Ok(Some(participant_id)) => {
+                participant_ids.insert(participant_id);
+            }
+            Ok(None) => {
+                return HttpResponse::NotFound().json(ErrorResponse {
+                    error: format!("User '{}' not found", username),
+                })
+            }
+            Err(e) => {
+                error!("Failed to look up user '{}': {}", username, e);
+                return HttpResponse::InternalServerError().json(ErrorResponse {
+                    error: "Failed to create direct message".into(),
+                });
+            }
+        }
+    }
+    if participant_ids.len() > MAX_DM_PARTICIPANTS {
+        return too_many();
+    }
+    if participant_ids.len() < 2 {
+        return HttpResponse::BadRequest().json(ErrorResponse {
+            error: "A direct message needs at least one other participant".into(),
+        });
+    }
+
+    let dm_key = participant_ids.iter().map(i64::to_string).collect::<Vec<_>>().join(",");
+    let room_name = format!("{}{}", DM_NAME_PREFIX, dm_key);
+
+    // Finding or creating the room and seating its participants happen together. Everyone joins
+    // as a plain member, so nobody can invite others, moderate or delete the conversation.
+    let result: Result<(i64, bool), sqlx::Error> = async {
+        let mut tx = pool.begin().await?;
+        let created = sqlx::query!(
+            "INSERT INTO rooms (room_name, user_id, visibility, kind, dm_key) VALUES (?, ?, 'private', 'dm', ?) \
+            ON CONFLICT (dm_key) DO NOTHING",
+            room_name,
+            user_id,
+            dm_key
+        )
+        .execute(&mut tx)
+        .await?
+        .rows_affected()
+            > 0;
+        let room_id = sqlx::query_scalar!(
+            "SELECT room_id as `room_id!: i64` FROM rooms WHERE dm_key = ?",
+            dm_key
+        )
+        .fetch_one(&mut tx)
+        .await?;
+        for participant_id in &participant_ids {
+            sqlx::query!(
+                "INSERT OR IGNORE INTO user_rooms (user_id, room_id, role) VALUES (?, ?, 'member')",
+                participant_id,
+                room_id
+            )
+            .execute(&mut tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok((room_id, created))
+    }
+    .await;
+
+    let (room_id, created) = match result {
+        Ok(found) => found,
+        Err(e) => {
+            error!("Failed to create direct message between users {}: {}", dm_key, e);
+            return HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to create direct message".into(),
+            });
+        }
+    };
+
+    let last_message_at = sqlx::query_scalar!(
+        "SELECT MAX(created_at) as `last_message_at?: String` FROM messages WHERE room_id = ?",
+        room_id
+    )
+    .fetch_one(pool.get_ref())
+    .await;
+    let dm = match (load_participants(pool.get_ref(), room_id).await, last_message_at) {
+        (Ok(participants), Ok(last_message_at)) => DirectMessage {
+            room_id,
+            participants,
+            last_message_at,
+        },
+        (Err(e), _) | (_, Err(e)) => {
+            error!("Failed to load direct message {}: {}", room_id, e);
+            return HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to create direct message".into(),
+            });
+        }
+    };
+
+    if created {
+        info!("User {} started direct message {} with users {}", user_id, room_id, dm_key);
+        HttpResponse::Created().json(dm)
+    } else {
+        HttpResponse::Ok().json(dm)
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/dms",
+    params(
+        ("Authorization" = String, Header, description = "Bearer <JWT Token>")
+    ),
+    responses(
+        (status = 200, description = "The user's direct messages, most recently active first", body = [DirectMessage]),
+        (status = 401, description = "User ID missing in token", body = ErrorResponse),
+        (status = 500, description = "Failed to retrieve direct messages", body = ErrorResponse)
+    )
+)]
+pub async fn get_dms(pool: web::Data<SqlitePool>, req: HttpRequest) -> impl Responder {
+    let user_id = match req.extensions().get::<i64>().copied() {
+        Some(user_id) => user_id,
+        None => {
+            return HttpResponse::Unauthorized().json(ErrorResponse {
+                error: "User ID missing in token".into(),
+            })
+        }
+    };
+
+    let result: Result<Vec<DirectMessage>, sqlx::Error> = async {
+        // Conversations without messages yet sort by when they were started
+        let rooms = sqlx::query!(
+            "SELECT room_id as `room_id!: i64`, last_message_at as `last_message_at?: String` FROM ( \
+                SELECT r.room_id, r.created_at, \
+                (SELECT MAX(m.created_at) FROM messages m WHERE m.room_id = r.room_id) as last_message_at \
+                FROM rooms r INNER JOIN user_rooms ur ON ur.room_id = r.room_id \
+                WHERE r.kind = 'dm' AND ur.user_id = ? \
+            ) ORDER BY COALESCE(last_message_at, created_at) DESC, room_id DESC",
+            user_id
+        )
+        .fetch_all(pool.get_ref())
+        .await?;
+
+        let mut dms = Vec::with_capacity(rooms.len());
+        for room in rooms {
+            dms.push(DirectMessage {
+                room_id: room.room_id,
+                participants: load_participants(pool.get_ref(), room.room_id).await?,
+                last_message_at: room.last_message_at,
+            });
+        }
+        Ok(dms)
+    }
+    .await;
+
+    match result {
+        Ok(dms) => HttpResponse::Ok().json(dms),
+        Err(e) => {
+            error!("Failed to retrieve direct messages of user {}: {}", user_id, e);
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to retrieve direct messages".into(),
+            })
+        }
+    }
+}
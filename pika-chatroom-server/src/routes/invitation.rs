@@ -0,0 +1,250 @@
+use crate::models::response::{ErrorResponse, MessageResponse};
+use crate::models::role::Permission;
+use crate::routes::role::{member_role, require_permission};
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse, Responder};
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use utoipa::ToSchema;
+
+#[derive(Deserialize, ToSchema)]
+pub struct InvitationRequest {
+    pub username: String, // The user to invite
+}
+
+/// A pending invitation; it is used up when the user joins the room
+#[derive(Serialize, ToSchema)]
+pub struct RoomInvitation {
+    pub room_id: i64,
+    pub user_id: i64,
+    pub username: String,
+    pub invited_by: i64,
+    pub created_at: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/rooms/{room_id}/invitations",
+    request_body = InvitationRequest,
+    params(
+        ("room_id" = i64, Path, description = "ID of the room"),
+        ("Authorization" = String, Header, description = "Bearer <JWT Token>")
+    ),
+    responses(
+        (status = 201, description = "User invited (or already invited); they can now join the room even if it is private", body = RoomInvitation),
+        (status = 400, description = "The user is already a member", body = ErrorResponse),
+        (status = 401, description = "User ID missing in token", body = ErrorResponse),
+        (status = 403, description = "Only moderators, admins and the owner can invite users", body = ErrorResponse),
+        (status = 404, description = "User not found", body = ErrorResponse),
+        (status = 500, description = "Failed to invite user", body = ErrorResponse)
+    )
+)]
+pub async fn invite_user(
+    pool: web::Data<SqlitePool>,
+    path: web::Path<i64>,
+    body: web::Json<InvitationRequest>,
+    req: HttpRequest,
+) -> impl Responder {
+    let room_id = path.into_inner();
+    let user_id = match req.extensions().get::<i64>().copied() {
+        Some(user_id) => user_id,
+        None => {
+            return HttpResponse::Unauthorized().json(ErrorResponse {
+                error: "User ID missing in token".into(),
+            })
+        }
+    };
+
+    if let Err(e) = require_permission(pool.get_ref(), room_id, user_id, Permission::InviteMembers).await {
+        return e.to_response();
+    }
+
+    let invitee_id = match sqlx::query_scalar!(
+        "SELECT user_id as `user_id!: i64` FROM users WHERE username = ? AND deleted_at IS NULL",
+        body.username
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    {
+        Ok(Some(invitee_id)) => invitee_id,
+        Ok(None) => {
+            return HttpResponse::NotFound().json(ErrorResponse {
+                error: "User not found".into(),
+            })
+        }
+        Err(e) => {
+            error!("Failed to look up user '{}': {}", body.username, e);
+            return HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to invite user".into(),
+            });
+        }
+    };
+
+    match member_role(pool.get_ref(), room_id, invitee_id).await {
+        Ok(None) => {}
+        Ok(Some(_)) => {
+            return HttpResponse::BadRequest().json(ErrorResponse {
+                error: "User is already a member of this room".into(),
+            })
+        }
+        Err(e) => {
+            error!("Failed to check membership of user {} in room {}: {}", invitee_id, room_id, e);
+            return HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to invite user".into(),
+            });
+        }
+    }
+
+    // Inviting someone twice keeps the first invitation
+    let result = sqlx::query!(
+        "INSERT OR IGNORE INTO room_invitations (room_id, user_id, invited_by) VALUES (?, ?, ?)",
+        room_id,
+        invitee_id,
+        user_id
+    )
+    .execute(pool.get_ref())
+    .await;
+    if let Err(e) = result {
+        error!("Failed to invite user {} to room {}: {}", invitee_id, room_id, e);
+        return HttpResponse::InternalServerError().json(ErrorResponse {
+            error: "Failed to invite user".into(),
+        });
+    }
+
+    match sqlx::query_as!(
+        RoomInvitation,
+        "SELECT ri.room_id, ri.user_id, u.username, ri.invited_by, ri.created_at FROM room_invitations ri \
+        INNER JOIN users u ON u.user_id = ri.user_id \
+        WHERE ri.room_id = ? AND ri.user_id = ?",
+        room_id,
+        invitee_id
+    )
+    .fetch_one(pool.get_ref())
+    .await
+    {
+        Ok(invitation) => {
+            info!("User {} invited user {} to room {}", user_id, invitee_id, room_id);
+            HttpResponse::Created().json(invitation)
+        }
+        Err(e) => {
+            error!("Failed to load invitation of user {} to room {}: {}", invitee_id, room_id, e);
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to invite user".into(),
+            })
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/rooms/{room_id}/invitations",
+    params(
+        ("room_id" = i64, Path, description = "ID of the room"),
+        ("Authorization" = String, Header, description = "Bearer <JWT Token>")
+    ),
+    responses(
+        (status = 200, description = "Pending invitations, oldest first", body = [RoomInvitation]),
+        (status = 401, description = "User ID missing in token", body = ErrorResponse),
+        (status = 403, description = "Only moderators, admins and the owner can see invitations", body = ErrorResponse),
+        (status = 500, description = "Failed to retrieve invitations", body = ErrorResponse)
+    )
+)]
+pub async fn get_invitations(
+    pool: web::Data<SqlitePool>,
+    path: web::Path<i64>,
+    req: HttpRequest,
+) -> impl Responder {
+    let room_id = path.into_inner();
+    let user_id = match req.extensions().get::<i64>().copied() {
+        Some(user_id) => user_id,
+        None => {
+            return HttpResponse::Unauthorized().json(ErrorResponse {
+                error: "User ID missing in token".into(),
+            })
+        }
+    };
+
+    if let Err(e) = require_permission(pool.get_ref(), room_id, user_id, Permission::InviteMembers).await {
+        return e.to_response();
+    }
+
+    match sqlx::query_as!(
+        RoomInvitation,
+        "SELECT ri.room_id, ri.user_id, u.username, ri.invited_by, ri.created_at FROM room_invitations ri \
+        INNER JOIN users u ON u.user_id = ri.user_id \
+        WHERE ri.room_id = ? ORDER BY ri.created_at, ri.user_id",
+        room_id
+    )
+    .fetch_all(pool.get_ref())
+    .await
+    {
+        Ok(invitations) => HttpResponse::Ok().json(invitations),
+        Err(e) => {
+            error!("Failed to retrieve invitations of room {}: {}", room_id, e);
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to retrieve invitations".into(),
+            })
+        }
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/rooms/{room_id}/invitations/{user_id}",
+    params(
+        ("room_id" = i64, Path, description = "ID of the room"),
+        ("user_id" = i64, Path, description = "ID of the invited user"),
+        ("Authorization" = String, Header, description = "Bearer <JWT Token>")
+    ),
+    responses(
+        (status = 200, description = "Invitation revoked", body = MessageResponse),
+        (status = 401, description = "User ID missing in token", body = ErrorResponse),
+        (status = 403, description = "Only moderators, admins and the owner can revoke invitations", body = ErrorResponse),
+        (status = 404, description = "Invitation not found", body = ErrorResponse),
+        (status = 500, description = "Failed to revoke invitation", body = ErrorResponse)
+    )
+)]
+pub async fn revoke_invitation(
+    pool: web::Data<SqlitePool>,
+    path: web::Path<(i64, i64)>,
+    req: HttpRequest,
+) -> impl Responder {
+    let (room_id, invitee_id) = path.into_inner();
+    let user_id = match req.extensions().get::<i64>().copied() {
+        Some(user_id) => user_id,
+        None => {
+            return HttpResponse::Unauthorized().json(ErrorResponse {
+                error: "User ID missing in token".into(),
+            })
+        }
+    };
+
+    if let Err(e) = require_permission(pool.get_ref(), room_id, user_id, Permission::InviteMembers).await {
+        return e.to_response();
+    }
+
+    match sqlx::query!(
+        "DELETE FROM room_invitations WHERE room_id = ? AND user_id = ?",
+        room_id,
+        invitee_id
+    )
+    .execute(pool.get_ref())
+    .await
+    {
+        Ok(result) if result.rows_affected() > 0 => {
+            info!("User {} revoked the invitation of user {} to room {}", user_id, invitee_id, room_id);
+            HttpResponse::Ok().json(MessageResponse {
+                message: "Invitation revoked".into(),
+            })
+        }
+        Ok(_) => HttpResponse::NotFound().json(ErrorResponse {
+            error: "Invitation not found".into(),
+        }),
+        Err(e) => {
+            error!("Failed to revoke invitation of user {} to room {}: {}", invitee_id, room_id, e);
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to revoke invitation".into(),
+            })
+        }
+    }
+}
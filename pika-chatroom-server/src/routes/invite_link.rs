@@ -0,0 +1,419 @@
+use crate::models::response::{ErrorResponse, MessageResponse};
+use crate::models::role::Permission;
+use crate::routes::message::TIMESTAMP_FORMAT;
+use crate::routes::moderation::is_banned;
+use crate::routes::role::{member_role, require_permission};
+use crate::routes::room::{fetch_room, JoinError, Room};
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse, Responder};
+use chrono::{Duration, Utc};
+use log::{error, info};
+use rand::distributions::{Alphanumeric, DistString};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use utoipa::ToSchema;
+
+// Length of generated invite codes; 62^10 codes are far too many to guess
+const INVITE_CODE_LENGTH: usize = 10;
+
+#[derive(Deserialize, ToSchema)]
+pub struct InviteLinkRequest {
+    pub expires_in_secs: Option<i64>, // The link never expires if omitted
+    pub max_uses: Option<i64>,        // The link can be used any number of times if omitted
+    pub username: Option<String>,     // Only this user may redeem the link, if given
+}
+
+/// A shareable invite link; redeeming its code makes the user a member of the room
+#[derive(Serialize, ToSchema)]
+pub struct InviteLink {
+    pub code: String,
+    pub room_id: i64,
+    pub created_by: i64,
+    pub user_id: Option<i64>,       // The only user who may redeem the link, if bound
+    pub username: Option<String>,
+    pub max_uses: Option<i64>,
+    pub uses: i64,
+    pub expires_at: Option<String>, // UTC
+    pub created_at: String,
+}
+
+async fn load_invite_link(pool: &SqlitePool, code: &str) -> Result<Option<InviteLink>, sqlx::Error> {
+    sqlx::query_as!(
+        InviteLink,
+        "SELECT il.code as `code!`, il.room_id, il.created_by, il.user_id, u.username as `username?`, il.max_uses, il.uses, \
+        il.expires_at, il.created_at FROM invite_links il \
+        LEFT JOIN users u ON u.user_id = il.user_id \
+        WHERE il.code = ?",
+        code
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/rooms/{room_id}/invites",
+    request_body = InviteLinkRequest,
+    params(
+        ("room_id" = i64, Path, description = "ID of the room"),
+        ("Authorization" = String, Header, description = "Bearer <JWT Token>")
+    ),
+    responses(
+        (status = 201, description = "Invite link created", body = InviteLink),
+        (status = 400, description = "The expiry or maximum number of uses is not positive", body = ErrorResponse),
+        (status = 401, description = "User ID missing in token", body = ErrorResponse),
+        (status = 403, description = "Only admins and the owner can create invite links", body = ErrorResponse),
+        (status = 404, description = "User not found", body = ErrorResponse),
+        (status = 500, description = "Failed to create invite link", body = ErrorResponse)
+    )
+)]
+pub async fn create_invite_link(
+    pool: web::Data<SqlitePool>,
+    path: web::Path<i64>,
+    body: web::Json<InviteLinkRequest>,
+    req: HttpRequest,
+) -> impl Responder {
+    let room_id = path.into_inner();
+    let user_id = match req.extensions().get::<i64>().copied() {
+        Some(user_id) => user_id,
+        None => {
+            return HttpResponse::Unauthorized().json(ErrorResponse {
+                error: "User ID missing in token".into(),
+            })
+        }
+    };
+    let body = body.into_inner();
+
+    if body.expires_in_secs.is_some_and(|secs| secs <= 0) || body.max_uses.is_some_and(|uses| uses <= 0) {
+        return HttpResponse::BadRequest().json(ErrorResponse {
+            error: "The expiry and the maximum number of uses must be positive".into(),
+        });
+    }
+
+    if let Err(e) = require_permission(pool.get_ref(), room_id, user_id, Permission::ManageRoom).await {
+        return e.to_response();
+    }
+
+    let bound_user_id = match &body.username {
+        Some(username) => match sqlx::query_scalar!(
+            "SELECT user_id as `user_id!: i64` FROM users WHERE username = ? AND deleted_at IS NULL",
+            username
+        )
+        .fetch_optional(pool.get_ref())
+        .await
+        {
+            Ok(Some(bound_user_id)) => Some(bound_user_id),
+            Ok(None) => {
+                return HttpResponse::NotFound().json(ErrorResponse {
+                    error: "User not found".into(),
+                })
+            }
+            Err(e) => {
+                error!("Failed to look up user '{}': {}", username, e);
+                return HttpResponse::InternalServerError().json(ErrorResponse {
+                    error: "Failed to create invite link".into(),
+                });
+            }
+        },
+        None => None,
+    };
+
+    let code = Alphanumeric.sample_string(&mut rand::thread_rng(), INVITE_CODE_LENGTH);
+    let expires_at = body
+        .expires_in_secs
+        .map(|secs| (Utc::now() + Duration::seconds(secs)).format(TIMESTAMP_FORMAT).to_string());
+
+    if let Err(e) = sqlx::query!(
+        "INSERT INTO invite_links (code, room_id, created_by, user_id, max_uses, expires_at) VALUES (?, ?, ?, ?, ?, ?)",
+        code,
+        room_id,
+        user_id,
+        bound_user_id,
+        body.max_uses,
+        expires_at
+    )
+    .execute(pool.get_ref())
+    .await
+    {
+        error!("Failed to create invite link for room {}: {}", room_id, e);
+        return HttpResponse::InternalServerError().json(ErrorResponse {
+            error: "Failed to create invite link".into(),
+        });
+    }
+
+    match load_invite_link(pool.get_ref(), &code).await {
+        Ok(Some(invite)) => {
+            info!("User {} created an invite link for room {}", user_id, room_id);
+            HttpResponse::Created().json(invite)
+        }
+        Ok(None) => HttpResponse::InternalServerError().json(ErrorResponse {
+            error: "Failed to create invite link".into(),
+        }),
+        Err(e) => {
+            error!("Failed to load invite link of room {}: {}", room_id, e);
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to create invite link".into(),
+            })
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/rooms/{room_id}/invites",
+    params(
+        ("room_id" = i64, Path, description = "ID of the room"),
+        ("Authorization" = String, Header, description = "Bearer <JWT Token>")
+    ),
+    responses(
+        (status = 200, description = "The room's invite links, newest first, including expired and used up ones", body = [InviteLink]),
+        (status = 401, description = "User ID missing in token", body = ErrorResponse),
+        (status = 403, description = "Only admins and the owner can see invite links", body = ErrorResponse),
+        (status = 500, description = "Failed to retrieve invite links", body = ErrorResponse)
+    )
+)]
+pub async fn get_invite_links(
+    pool: web::Data<SqlitePool>,
+    path: web::Path<i64>,
+    req: HttpRequest,
+) -> impl Responder {
+    let room_id = path.into_inner();
+    let user_id = match req.extensions().get::<i64>().copied() {
+        Some(user_id) => user_id,
+        None => {
+            return HttpResponse::Unauthorized().json(ErrorResponse {
+                error: "User ID missing in token".into(),
+            })
+        }
+    };
+
+    if let Err(e) = require_permission(pool.get_ref(), room_id, user_id, Permission::ManageRoom).await {
+        return e.to_response();
+    }
+
+    match sqlx::query_as!(
+        InviteLink,
+        "SELECT il.code as `code!`, il.room_id, il.created_by, il.user_id, u.username as `username?`, il.max_uses, il.uses, \
+        il.expires_at, il.created_at FROM invite_links il \
+        LEFT JOIN users u ON u.user_id = il.user_id \
+        WHERE il.room_id = ? ORDER BY il.created_at DESC, il.rowid DESC",
+        room_id
+    )
+    .fetch_all(pool.get_ref())
+    .await
+    {
+        Ok(invites) => HttpResponse::Ok().json(invites),
+        Err(e) => {
+            error!("Failed to retrieve invite links of room {}: {}", room_id, e);
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to retrieve invite links".into(),
+            })
+        }
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/rooms/{room_id}/invites/{code}",
+    params(
+        ("room_id" = i64, Path, description = "ID of the room"),
+        ("code" = String, Path, description = "Code of the invite link"),
+        ("Authorization" = String, Header, description = "Bearer <JWT Token>")
+    ),
+    responses(
+        (status = 200, description = "Invite link revoked; the code can no longer be redeemed", body = MessageResponse),
+        (status = 401, description = "User ID missing in token", body = ErrorResponse),
+        (status = 403, description = "Only admins and the owner can revoke invite links", body = ErrorResponse),
+        (status = 404, description = "Invite link not found", body = ErrorResponse),
+        (status = 500, description = "Failed to revoke invite link", body = ErrorResponse)
+    )
+)]
+pub async fn revoke_invite_link(
+    pool: web::Data<SqlitePool>,
+    path: web::Path<(i64, String)>,
+    req: HttpRequest,
+) -> impl Responder {
+    let (room_id, code) = path.into_inner();
+    let user_id = match req.extensions().get::<i64>().copied() {
+        Some(user_id) => user_id,
+        None => {
+            return HttpResponse::Unauthorized().json(ErrorResponse {
+                error: "User ID missing in token".into(),
+            })
+        }
+    };
+
+    if let Err(e) = require_permission(pool.get_ref(), room_id, user_id, Permission::ManageRoom).await {
+        return e.to_response();
+    }
+
+    match sqlx::query!(
+        "DELETE FROM invite_links WHERE room_id = ? AND code = ?",
+        room_id,
+        code
+    )
+    .execute(pool.get_ref())
+    .await
+    {
+        Ok(result) if result.rows_affected() > 0 => {
+            info!("User {} revoked an invite link of room {}", user_id, room_id);
+            HttpResponse::Ok().json(MessageResponse {
+                message: "Invite link revoked".into(),
+            })
+        }
+        Ok(_) => HttpResponse::NotFound().json(ErrorResponse {
+            error: "Invite link not found".into(),
+        }),
+        Err(e) => {
+            error!("Failed to revoke invite link of room {}: {}", room_id, e);
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to revoke invite link".into(),
+            })
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/invites/{code}/accept",
+    params(
+        ("code" = String, Path, description = "Code of the invite link"),
+        ("Authorization" = String, Header, description = "Bearer <JWT Token>")
+    ),
+    responses(
+        (status = 200, description = "The user is now a member of the room, even if it is private; already being a member is not an error", body = Room),
+        (status = 401, description = "User ID missing in token", body = ErrorResponse),
+        (status = 403, description = "The invite link is meant for another user, or the user is banned from the room", body = ErrorResponse),
+        (status = 404, description = "Invite link not found", body = ErrorResponse),
+        (status = 410, description = "The invite link has expired or has been used up", body = ErrorResponse),
+        (status = 500, description = "Failed to accept invite", body = ErrorResponse)
+    )
+)]
+pub async fn accept_invite(
+    pool: web::Data<SqlitePool>,
+    path: web::Path<String>,
+    req: HttpRequest,
+) -> impl Responder {
+    let code = path.into_inner();
+    let user_id = match req.extensions().get::<i64>().copied() {
+        Some(user_id) => user_id,
+        None => {
+            return HttpResponse::Unauthorized().json(ErrorResponse {
+                error: "User ID missing in token".into(),
+            })
+        }
+    };
+
+    let invite = match load_invite_link(pool.get_ref(), &code).await {
+        Ok(Some(invite)) => invite,
+        Ok(None) => {
+            return HttpResponse::NotFound().json(ErrorResponse {
+                error: "Invite link not found".into(),
+            })
+        }
+        Err(e) => {
+            error!("Failed to load invite link: {}", e);
+            return HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to accept invite".into(),
+            });
+        }
+    };
+    let room_id = invite.room_id;
+
+    if invite.user_id.is_some_and(|bound_user_id| bound_user_id != user_id) {
+        return HttpResponse::Forbidden().json(ErrorResponse {
+            error: "This invite link is meant for another user".into(),
+        });
+    }
+
+    let room = match fetch_room(pool.get_ref(), room_id).await {
+        Ok(Some(room)) => room,
+        Ok(None) => {
+            return HttpResponse::NotFound().json(ErrorResponse {
+                error: "Invite link not found".into(),
+            })
+        }
+        Err(e) => {
+            error!("Failed to load room {}: {}", room_id, e);
+            return HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to accept invite".into(),
+            });
+        }
+    };
+
+    // Members following the link again are let through without using it up
+    match member_role(pool.get_ref(), room_id, user_id).await {
+        Ok(Some(_)) => return HttpResponse::Ok().json(room),
+        Ok(None) => {}
+        Err(e) => {
+            error!("Failed to check membership of user {} in room {}: {}", user_id, room_id, e);
+            return HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to accept invite".into(),
+            });
+        }
+    }
+
+    // A ban outweighs any invite link
+    match is_banned(pool.get_ref(), room_id, user_id).await {
+        Ok(false) => {}
+        Ok(true) => return JoinError::Banned.to_response(),
+        Err(e) => {
+            error!("Failed to check bans of user {} in room {}: {}", user_id, room_id, e);
+            return HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to accept invite".into(),
+            });
+        }
+    }
+
+    let result: Result<bool, sqlx::Error> = async {
+        let mut tx = pool.begin().await?;
+        // Counting the use and checking the limits in one statement keeps concurrent redemptions
+        // from going over `max_uses`
+        let redeemed = sqlx::query!(
+            "UPDATE invite_links SET uses = uses + 1 WHERE code = ? \
+            AND (max_uses IS NULL OR uses < max_uses) \
+            AND (expires_at IS NULL OR expires_at > datetime('now'))",
+            code
+        )
+        .execute(&mut tx)
+        .await?
+        .rows_affected()
+            > 0;
+        if !redeemed {
+            return Ok(false);
+        }
+
+        sqlx::query!(
+            "INSERT OR IGNORE INTO user_rooms (user_id, room_id) VALUES (?, ?)",
+            user_id,
+            room_id
+        )
+        .execute(&mut tx)
+        .await?;
+        sqlx::query!(
+            "DELETE FROM room_invitations WHERE room_id = ? AND user_id = ?",
+            room_id,
+            user_id
+        )
+        .execute(&mut tx)
+        .await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+    .await;
+
+    match result {
+        Ok(true) => {
+            info!("User {} joined room {} through an invite link", user_id, room_id);
+            HttpResponse::Ok().json(room)
+        }
+        Ok(false) => HttpResponse::Gone().json(ErrorResponse {
+            error: "This invite link has expired or has been used up".into(),
+        }),
+        Err(e) => {
+            error!("Failed to redeem invite link for room {}: {}", room_id, e);
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to accept invite".into(),
+            })
+        }
+    }
+}
@@ -0,0 +1,639 @@
+use crate::config::settings::MESSAGE_EDIT_WINDOW;
+use crate::models::message::{MessageRevision, StoredMessage};
+use crate::models::reaction::ReactionSummary;
+use crate::models::response::{ErrorResponse, MessageResponse};
+use crate::models::role::Permission;
+use crate::routes::moderation::{mute_remaining, muted_response};
+use crate::routes::reaction::fetch_reactions;
+use crate::routes::role::require_permission;
+use crate::websockets::chat_session::{BroadcastMessage, RoomServer, MAX_MESSAGE_LENGTH};
+use crate::websockets::protocol::ServerEvent;
+use actix::Addr;
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse, Responder};
+use chrono::{NaiveDateTime, Utc};
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use utoipa::ToSchema;
+
+// Number of messages returned when the client does not ask for a specific page size
+const DEFAULT_PAGE_SIZE: i64 = 50;
+// Upper bound on the page size to keep a single request cheap
+const MAX_PAGE_SIZE: i64 = 100;
+// Format of every timestamp stored in the messages tables (UTC, same as SQLite's datetime('now'))
+pub(crate) const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+#[derive(Deserialize)]
+pub struct MessageHistoryQuery {
+    pub before: Option<i64>, // Only return messages with an ID lower than this one
+    pub after: Option<i64>,  // Only return messages with an ID higher than this one
+    pub limit: Option<i64>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct MessagesResponse {
+    pub messages: Vec<StoredMessage>, // Root messages only, always ordered from oldest to newest
+    pub has_more: bool,               // Whether more messages exist beyond this page in the paging direction
+    pub reactions: Vec<ReactionSummary>, // Reactions to the messages on this page
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/rooms/{room_id}/messages",
+    params(
+        ("room_id" = i64, Path, description = "ID of the room"),
+        ("before" = Option<i64>, Query, description = "Return messages older than this message ID"),
+        ("after" = Option<i64>, Query, description = "Return messages newer than this message ID"),
+        ("limit" = Option<i64>, Query, description = "Maximum number of messages to return (default 50, max 100)"),
+        ("Authorization" = String, Header, description = "Bearer <JWT Token>")
+    ),
+    responses(
+        (status = 200, description = "A page of the room's message history", body = MessagesResponse),
+        (status = 401, description = "User ID missing in token", body = ErrorResponse),
+        (status = 403, description = "User is not a member of the room", body = ErrorResponse),
+        (status = 404, description = "Room not found", body = ErrorResponse),
+        (status = 500, description = "Failed to retrieve messages", body = ErrorResponse)
+    )
+)]
+pub async fn get_room_messages(
+    pool: web::Data<SqlitePool>,
+    path: web::Path<i64>,
+    query: web::Query<MessageHistoryQuery>,
+    req: HttpRequest,
+) -> impl Responder {
+    let room_id = path.into_inner();
+    let user_id = match req.extensions().get::<i64>().copied() {
+        Some(user_id) => user_id,
+        None => {
+            return HttpResponse::Unauthorized().json(ErrorResponse {
+                error: "User ID missing in token".into(),
+            })
+        }
+    };
+
+    let room_exists = sqlx::query!(
+        "SELECT 1 AS exists_flag FROM rooms WHERE room_id = ?",
+        room_id
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .map(|row| row.is_some())
+    .unwrap_or(false);
+
+    if !room_exists {
+        return HttpResponse::NotFound().json(ErrorResponse {
+            error: "Room not found".into(),
+        });
+    }
+    if let Err(e) = require_permission(pool.get_ref(), room_id, user_id, Permission::ViewRoom).await {
+        return e.to_response();
+    }
+
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    // Fetch one extra row to find out whether another page exists
+    let fetch_limit = limit + 1;
+
+    // Paging forward with `after` reads in ascending order; every other case pages backwards
+    // from the newest message (or from `before`) and is reversed afterwards.
+    let forward = query.after.is_some() && query.before.is_none();
+    let result = if forward {
+        sqlx::query_as!(
+            StoredMessage,
+            "SELECT m.message_id as `message_id!`, m.room_id, m.user_id, u.username, m.content, m.created_at as `created_at!`, m.edited_at, m.deleted_at, m.parent_id, \
+            (SELECT COUNT(*) FROM messages r WHERE r.parent_id = m.message_id AND r.deleted_at IS NULL) as `reply_count!: i64` \
+            FROM messages m INNER JOIN users u ON u.user_id = m.user_id \
+            WHERE m.room_id = ? AND m.parent_id IS NULL AND m.message_id > ? \
+            ORDER BY m.message_id ASC LIMIT ?",
+            room_id,
+            query.after,
+            fetch_limit
+        )
+        .fetch_all(pool.get_ref())
+        .await
+    } else {
+        sqlx::query_as!(
+            StoredMessage,
+            "SELECT m.message_id as `message_id!`, m.room_id, m.user_id, u.username, m.content, m.created_at as `created_at!`, m.edited_at, m.deleted_at, m.parent_id, \
+            (SELECT COUNT(*) FROM messages r WHERE r.parent_id = m.message_id AND r.deleted_at IS NULL) as `reply_count!: i64` \
+            FROM messages m INNER JOIN users u ON u.user_id = m.user_id \
+            WHERE m.room_id = ? AND m.parent_id IS NULL AND (? IS NULL OR m.message_id < ?) AND (? IS NULL OR m.message_id > ?) \
+            ORDER BY m.message_id DESC LIMIT ?",
+            room_id,
+            query.before,
+            query.before,
+            query.after,
+            query.after,
+            fetch_limit
+        )
+        .fetch_all(pool.get_ref())
+        .await
+    };
+
+    match result {
+        Ok(mut messages) => {
+            let has_more = messages.len() as i64 > limit;
+            messages.truncate(limit as usize);
+            if !forward {
+                messages.reverse();
+            }
+            info!("Retrieved {} messages for room {}", messages.len(), room_id);
+
+            let reactions = match (messages.first(), messages.last()) {
+                (Some(first), Some(last)) => {
+                    match fetch_reactions(pool.get_ref(), room_id, first.message_id, last.message_id).await {
+                        Ok(reactions) => reactions,
+                        Err(e) => {
+                            error!("Failed to retrieve reactions for room {}: {}", room_id, e);
+                            return HttpResponse::InternalServerError().json(ErrorResponse {
+                                error: "Failed to retrieve messages".into(),
+                            });
+                        }
+                    }
+                }
+                _ => Vec::new(),
+            };
+            HttpResponse::Ok().json(MessagesResponse { messages, has_more, reactions })
+        }
+        Err(e) => {
+            error!("Failed to retrieve messages for room {}: {}", room_id, e);
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to retrieve messages".into(),
+            })
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ThreadQuery {
+    pub after: Option<i64>, // Only return replies with an ID higher than this one
+    pub limit: Option<i64>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ThreadResponse {
+    pub root: StoredMessage,
+    pub replies: Vec<StoredMessage>,     // Ordered from oldest to newest
+    pub has_more: bool,                  // Whether newer replies exist beyond this page
+    pub reactions: Vec<ReactionSummary>, // Reactions to the root and the replies on this page
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/rooms/{room_id}/messages/{message_id}/thread",
+    params(
+        ("room_id" = i64, Path, description = "ID of the room"),
+        ("message_id" = i64, Path, description = "ID of the root message, or of any reply in the thread"),
+        ("after" = Option<i64>, Query, description = "Return replies newer than this message ID"),
+        ("limit" = Option<i64>, Query, description = "Maximum number of replies to return (default 50, max 100)"),
+        ("Authorization" = String, Header, description = "Bearer <JWT Token>")
+    ),
+    responses(
+        (status = 200, description = "The root message and a page of its replies", body = ThreadResponse),
+        (status = 404, description = "Message not found", body = ErrorResponse),
+        (status = 500, description = "Failed to retrieve thread", body = ErrorResponse)
+    )
+)]
+pub async fn get_thread(
+    pool: web::Data<SqlitePool>,
+    path: web::Path<(i64, i64)>,
+    query: web::Query<ThreadQuery>,
+    req: HttpRequest,
+) -> impl Responder {
+    let (room_id, message_id) = path.into_inner();
+    let user_id = match req.extensions().get::<i64>().copied() {
+        Some(user_id) => user_id,
+        None => {
+            return HttpResponse::Unauthorized().json(ErrorResponse {
+                error: "User ID missing in token".into(),
+            })
+        }
+    };
+
+    if let Err(e) = require_permission(pool.get_ref(), room_id, user_id, Permission::ViewRoom).await {
+        return e.to_response();
+    }
+
+    // Asking for a reply returns the whole thread it belongs to
+    let root = match fetch_message(pool.get_ref(), room_id, message_id).await {
+        Ok(Some(message)) => match message.parent_id {
+            Some(root_id) => fetch_message(pool.get_ref(), room_id, root_id).await,
+            None => Ok(Some(message)),
+        },
+        other => other,
+    };
+    let root = match root {
+        Ok(Some(root)) => root,
+        Ok(None) => {
+            return HttpResponse::NotFound().json(ErrorResponse {
+                error: "Message not found".into(),
+            })
+        }
+        Err(e) => {
+            error!("Failed to load thread of message {}: {}", message_id, e);
+            return HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to retrieve thread".into(),
+            });
+        }
+    };
+
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    let fetch_limit = limit + 1;
+    let replies = sqlx::query_as!(
+        StoredMessage,
+        "SELECT m.message_id as `message_id!`, m.room_id, m.user_id, u.username, m.content, m.created_at as `created_at!`, m.edited_at, m.deleted_at, m.parent_id, \
+            0 as `reply_count!: i64` \
+        FROM messages m INNER JOIN users u ON u.user_id = m.user_id \
+        WHERE m.parent_id = ? AND (? IS NULL OR m.message_id > ?) \
+        ORDER BY m.message_id ASC LIMIT ?",
+        root.message_id,
+        query.after,
+        query.after,
+        fetch_limit
+    )
+    .fetch_all(pool.get_ref())
+    .await;
+
+    let mut replies = match replies {
+        Ok(replies) => replies,
+        Err(e) => {
+            error!("Failed to retrieve replies to message {}: {}", root.message_id, e);
+            return HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to retrieve thread".into(),
+            });
+        }
+    };
+    let has_more = replies.len() as i64 > limit;
+    replies.truncate(limit as usize);
+
+    let last_message_id = replies.last().map_or(root.message_id, |reply| reply.message_id);
+    let reactions = match fetch_reactions(pool.get_ref(), room_id, root.message_id, last_message_id).await {
+        // The ID range also covers messages outside the thread; keep only the thread's own
+        Ok(reactions) => reactions
+            .into_iter()
+            .filter(|reaction| {
+                reaction.message_id == root.message_id
+                    || replies.iter().any(|reply| reply.message_id == reaction.message_id)
+            })
+            .collect(),
+        Err(e) => {
+            error!("Failed to retrieve reactions for thread {}: {}", root.message_id, e);
+            return HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to retrieve thread".into(),
+            });
+        }
+    };
+
+    HttpResponse::Ok().json(ThreadResponse {
+        root,
+        replies,
+        has_more,
+        reactions,
+    })
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct EditMessageRequest {
+    pub content: String,
+}
+
+// Loads a message of a room, including tombstones
+pub(crate) async fn fetch_message(
+    pool: &SqlitePool,
+    room_id: i64,
+    message_id: i64,
+) -> Result<Option<StoredMessage>, sqlx::Error> {
+    sqlx::query_as!(
+        StoredMessage,
+        "SELECT m.message_id as `message_id!`, m.room_id, m.user_id, u.username, m.content, m.created_at as `created_at!`, m.edited_at, m.deleted_at, m.parent_id, \
+            (SELECT COUNT(*) FROM messages r WHERE r.parent_id = m.message_id AND r.deleted_at IS NULL) as `reply_count!: i64` \
+        FROM messages m INNER JOIN users u ON u.user_id = m.user_id \
+        WHERE m.room_id = ? AND m.message_id = ?",
+        room_id,
+        message_id
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+// Whether the author of a message sent at `created_at` may still change it
+fn within_edit_window(created_at: &str) -> bool {
+    match NaiveDateTime::parse_from_str(created_at, TIMESTAMP_FORMAT) {
+        Ok(created_at) => Utc::now().naive_utc() - created_at <= *MESSAGE_EDIT_WINDOW,
+        Err(e) => {
+            error!("Unreadable message timestamp '{}': {}", created_at, e);
+            false
+        }
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/rooms/{room_id}/messages/{message_id}",
+    request_body = EditMessageRequest,
+    params(
+        ("room_id" = i64, Path, description = "ID of the room"),
+        ("message_id" = i64, Path, description = "ID of the message to edit"),
+        ("Authorization" = String, Header, description = "Bearer <JWT Token>")
+    ),
+    responses(
+        (status = 200, description = "Message edited; the room receives a message_edited event", body = StoredMessage),
+        (status = 400, description = "Invalid message content", body = ErrorResponse),
+        (status = 401, description = "User ID missing in token", body = ErrorResponse),
+        (status = 403, description = "Not a member, not the author, muted in the room, the edit window has expired, or the room is archived", body = ErrorResponse),
+        (status = 404, description = "Message not found or deleted", body = ErrorResponse),
+        (status = 500, description = "Failed to edit message", body = ErrorResponse)
+    )
+)]
+pub async fn edit_message(
+    pool: web::Data<SqlitePool>,
+    room_server: web::Data<Addr<RoomServer>>,
+    path: web::Path<(i64, i64)>,
+    body: web::Json<EditMessageRequest>,
+    req: HttpRequest,
+) -> impl Responder {
+    let (room_id, message_id) = path.into_inner();
+    let user_id = match req.extensions().get::<i64>().copied() {
+        Some(user_id) => user_id,
+        None => {
+            return HttpResponse::Unauthorized().json(ErrorResponse {
+                error: "User ID missing in token".into(),
+            })
+        }
+    };
+
+    let content = body.into_inner().content;
+    if content.trim().is_empty() || content.chars().count() > MAX_MESSAGE_LENGTH {
+        return HttpResponse::BadRequest().json(ErrorResponse {
+            error: format!("Messages must contain between 1 and {} characters", MAX_MESSAGE_LENGTH),
+        });
+    }
+    if let Err(e) = require_permission(pool.get_ref(), room_id, user_id, Permission::PostMessages).await {
+        return e.to_response();
+    }
+    // An edit is broadcast like a new message, so a mute holds edits back too
+    match mute_remaining(pool.get_ref(), room_id, user_id).await {
+        Ok(None) => {}
+        Ok(Some(remaining)) => return muted_response(remaining),
+        Err(e) => {
+            error!("Failed to check mute of user {} in room {}: {}", user_id, room_id, e);
+            return HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to edit message".into(),
+            });
+        }
+    }
+
+    let mut message = match fetch_message(pool.get_ref(), room_id, message_id).await {
+        Ok(Some(message)) if message.deleted_at.is_none() => message,
+        Ok(_) => {
+            return HttpResponse::NotFound().json(ErrorResponse {
+                error: "Message not found".into(),
+            })
+        }
+        Err(e) => {
+            error!("Failed to load message {}: {}", message_id, e);
+            return HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to edit message".into(),
+            });
+        }
+    };
+
+    if message.user_id != user_id {
+        return HttpResponse::Forbidden().json(ErrorResponse {
+            error: "You can only edit your own messages".into(),
+        });
+    }
+    if !within_edit_window(&message.created_at) {
+        return HttpResponse::Forbidden().json(ErrorResponse {
+            error: "The edit window for this message has expired".into(),
+        });
+    }
+
+    // Keep the current content as a revision, then replace it
+    let edited_at = Utc::now().format(TIMESTAMP_FORMAT).to_string();
+    let result: Result<(), sqlx::Error> = async {
+        let mut tx = pool.begin().await?;
+        sqlx::query!(
+            "INSERT INTO message_revisions (message_id, content, revised_at) VALUES (?, ?, ?)",
+            message_id,
+            message.content,
+            edited_at
+        )
+        .execute(&mut tx)
+        .await?;
+        sqlx::query!(
+            "UPDATE messages SET content = ?, edited_at = ? WHERE message_id = ?",
+            content,
+            edited_at,
+            message_id
+        )
+        .execute(&mut tx)
+        .await?;
+        tx.commit().await
+    }
+    .await;
+
+    if let Err(e) = result {
+        error!("Failed to edit message {}: {}", message_id, e);
+        return HttpResponse::InternalServerError().json(ErrorResponse {
+            error: "Failed to edit message".into(),
+        });
+    }
+
+    message.content = content;
+    message.edited_at = Some(edited_at);
+    info!("User {} edited message {} in room {}", user_id, message_id, room_id);
+
+    room_server.do_send(BroadcastMessage {
+        room_id,
+        event: ServerEvent::MessageEdited { message: message.clone() },
+    });
+    HttpResponse::Ok().json(message)
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/rooms/{room_id}/messages/{message_id}",
+    params(
+        ("room_id" = i64, Path, description = "ID of the room"),
+        ("message_id" = i64, Path, description = "ID of the message to delete"),
+        ("Authorization" = String, Header, description = "Bearer <JWT Token>")
+    ),
+    responses(
+        (status = 200, description = "Message replaced by a tombstone; the room receives a message_deleted event", body = MessageResponse),
+        (status = 401, description = "User ID missing in token", body = ErrorResponse),
+        (status = 403, description = "Neither a moderator nor the author within the edit window, or the room is archived", body = ErrorResponse),
+        (status = 404, description = "Message not found or already deleted", body = ErrorResponse),
+        (status = 500, description = "Failed to delete message", body = ErrorResponse)
+    )
+)]
+pub async fn delete_message(
+    pool: web::Data<SqlitePool>,
+    room_server: web::Data<Addr<RoomServer>>,
+    path: web::Path<(i64, i64)>,
+    req: HttpRequest,
+) -> impl Responder {
+    let (room_id, message_id) = path.into_inner();
+    let user_id = match req.extensions().get::<i64>().copied() {
+        Some(user_id) => user_id,
+        None => {
+            return HttpResponse::Unauthorized().json(ErrorResponse {
+                error: "User ID missing in token".into(),
+            })
+        }
+    };
+
+    let role = match require_permission(pool.get_ref(), room_id, user_id, Permission::PostMessages).await {
+        Ok(role) => role,
+        Err(e) => return e.to_response(),
+    };
+
+    let message = match fetch_message(pool.get_ref(), room_id, message_id).await {
+        Ok(Some(message)) if message.deleted_at.is_none() => message,
+        Ok(_) => {
+            return HttpResponse::NotFound().json(ErrorResponse {
+                error: "Message not found".into(),
+            })
+        }
+        Err(e) => {
+            error!("Failed to load message {}: {}", message_id, e);
+            return HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to delete message".into(),
+            });
+        }
+    };
+
+    // Moderators and above may delete any message; authors only their own, within the edit window
+    if !role.can(Permission::DeleteOthersMessages) {
+        if message.user_id != user_id {
+            return HttpResponse::Forbidden().json(ErrorResponse {
+                error: "Only the author or a moderator can delete this message".into(),
+            });
+        }
+        if !within_edit_window(&message.created_at) {
+            return HttpResponse::Forbidden().json(ErrorResponse {
+                error: "The edit window for this message has expired".into(),
+            });
+        }
+    }
+
+    // Keep the content as a revision and leave a tombstone in the history
+    let deleted_at = Utc::now().format(TIMESTAMP_FORMAT).to_string();
+    let result: Result<(), sqlx::Error> = async {
+        let mut tx = pool.begin().await?;
+        sqlx::query!(
+            "INSERT INTO message_revisions (message_id, content, revised_at) VALUES (?, ?, ?)",
+            message_id,
+            message.content,
+            deleted_at
+        )
+        .execute(&mut tx)
+        .await?;
+        sqlx::query!(
+            "UPDATE messages SET content = '', deleted_at = ? WHERE message_id = ?",
+            deleted_at,
+            message_id
+        )
+        .execute(&mut tx)
+        .await?;
+        tx.commit().await
+    }
+    .await;
+
+    if let Err(e) = result {
+        error!("Failed to delete message {}: {}", message_id, e);
+        return HttpResponse::InternalServerError().json(ErrorResponse {
+            error: "Failed to delete message".into(),
+        });
+    }
+
+    info!("User {} deleted message {} in room {}", user_id, message_id, room_id);
+    room_server.do_send(BroadcastMessage {
+        room_id,
+        event: ServerEvent::MessageDeleted {
+            room_id,
+            message_id,
+            parent_id: message.parent_id,
+            deleted_at,
+        },
+    });
+    HttpResponse::Ok().json(MessageResponse {
+        message: "Message deleted".into(),
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/rooms/{room_id}/messages/{message_id}/revisions",
+    params(
+        ("room_id" = i64, Path, description = "ID of the room"),
+        ("message_id" = i64, Path, description = "ID of the message"),
+        ("Authorization" = String, Header, description = "Bearer <JWT Token>")
+    ),
+    responses(
+        (status = 200, description = "Earlier versions of the message, oldest first", body = [MessageRevision]),
+        (status = 401, description = "User ID missing in token", body = ErrorResponse),
+        (status = 403, description = "Only the author or a moderator can see the edit history", body = ErrorResponse),
+        (status = 404, description = "Message not found", body = ErrorResponse),
+        (status = 500, description = "Failed to retrieve revisions", body = ErrorResponse)
+    )
+)]
+pub async fn get_message_revisions(
+    pool: web::Data<SqlitePool>,
+    path: web::Path<(i64, i64)>,
+    req: HttpRequest,
+) -> impl Responder {
+    let (room_id, message_id) = path.into_inner();
+    let user_id = match req.extensions().get::<i64>().copied() {
+        Some(user_id) => user_id,
+        None => {
+            return HttpResponse::Unauthorized().json(ErrorResponse {
+                error: "User ID missing in token".into(),
+            })
+        }
+    };
+
+    let role = match require_permission(pool.get_ref(), room_id, user_id, Permission::ViewRoom).await {
+        Ok(role) => role,
+        Err(e) => return e.to_response(),
+    };
+
+    let message = match fetch_message(pool.get_ref(), room_id, message_id).await {
+        Ok(Some(message)) => message,
+        Ok(None) => {
+            return HttpResponse::NotFound().json(ErrorResponse {
+                error: "Message not found".into(),
+            })
+        }
+        Err(e) => {
+            error!("Failed to load message {}: {}", message_id, e);
+            return HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to retrieve revisions".into(),
+            });
+        }
+    };
+
+    // Revisions include the content of deleted messages, so they are not public to the room
+    if message.user_id != user_id && !role.can(Permission::ViewOthersRevisions) {
+        return HttpResponse::Forbidden().json(ErrorResponse {
+            error: "Only the author or a moderator can see the edit history".into(),
+        });
+    }
+
+    match sqlx::query_as!(
+        MessageRevision,
+        "SELECT revision_id as `revision_id!`, message_id, content, revised_at \
+        FROM message_revisions WHERE message_id = ? ORDER BY revision_id ASC",
+        message_id
+    )
+    .fetch_all(pool.get_ref())
+    .await
+    {
+        Ok(revisions) => HttpResponse::Ok().json(revisions),
+        Err(e) => {
+            error!("Failed to retrieve revisions of message {}: {}", message_id, e);
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to retrieve revisions".into(),
+            })
+        }
+    }
+}
@@ -1,3 +1,16 @@
 pub mod auth;         // Declare the auth module
 pub mod test_routes;  // Include the test routes module
 pub mod room;
+pub mod message;
+pub mod reaction;
+pub mod search;
+pub mod role;
+pub mod invitation;
+pub mod invite_link;
+pub mod moderation;
+pub mod dm;
+pub mod account;
+pub mod session;
+pub mod password;
+#[cfg(test)]
+pub mod test_support; // Shared setup for the route tests
@@ -0,0 +1,853 @@
+use crate::models::response::{ErrorResponse, MessageResponse};
+use crate::models::role::{Permission, Role};
+use crate::routes::message::TIMESTAMP_FORMAT;
+use crate::routes::role::{member_role, require_permission, AccessError};
+use crate::routes::room::{fetch_room, Room};
+use crate::websockets::chat_session::{BroadcastMessage, RemoveMember, RoomServer};
+use crate::websockets::protocol::{RemovalCause, ServerEvent};
+use actix::Addr;
+use actix_web::http::header::RETRY_AFTER;
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse, Responder};
+use chrono::{Duration, Utc};
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use utoipa::ToSchema;
+
+// Longest ban reason accepted, in characters
+const MAX_BAN_REASON_LENGTH: usize = 500;
+// Longest mute accepted: 30 days
+const MAX_MUTE_SECS: i64 = 30 * 24 * 60 * 60;
+// Longest slow mode interval accepted: 6 hours
+const MAX_SLOW_MODE_SECS: i64 = 6 * 60 * 60;
+
+#[derive(Deserialize, ToSchema)]
+pub struct BanRequest {
+    pub username: String,             // The user to ban; they do not have to be a member
+    pub reason: Option<String>,
+    pub expires_in_secs: Option<i64>, // The ban is permanent if omitted
+}
+
+/// A ban in force; the user cannot join the room until it expires or is lifted
+#[derive(Serialize, ToSchema)]
+pub struct RoomBan {
+    pub room_id: i64,
+    pub user_id: i64,
+    pub username: String,
+    pub banned_by: i64,
+    pub reason: Option<String>,
+    pub expires_at: Option<String>, // UTC
+    pub created_at: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct MuteRequest {
+    pub duration_secs: i64,
+}
+
+/// A mute in force; the member cannot post in the room until it expires or is lifted
+#[derive(Serialize, ToSchema)]
+pub struct RoomMute {
+    pub room_id: i64,
+    pub user_id: i64,
+    pub username: String,
+    pub muted_by: i64,
+    pub expires_at: String, // UTC
+    pub created_at: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct SlowModeRequest {
+    pub seconds: i64, // 0 turns slow mode off
+}
+
+// Whether the user is banned from the room by a ban that has not expired
+pub(crate) async fn is_banned(pool: &SqlitePool, room_id: i64, user_id: i64) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar!(
+        "SELECT EXISTS (SELECT 1 FROM room_bans WHERE room_id = ? AND user_id = ? \
+        AND (expires_at IS NULL OR expires_at > datetime('now'))) as `banned!: bool`",
+        room_id,
+        user_id
+    )
+    .fetch_one(pool)
+    .await
+}
+
+// Seconds left until the user's mute in the room ends; `None` when they are not muted
+pub(crate) async fn mute_remaining(pool: &SqlitePool, room_id: i64, user_id: i64) -> Result<Option<i64>, sqlx::Error> {
+    sqlx::query_scalar!(
+        "SELECT CAST(strftime('%s', expires_at) - strftime('%s', 'now') AS INTEGER) as `remaining!: i64` \
+        FROM room_mutes WHERE room_id = ? AND user_id = ? AND expires_at > datetime('now')",
+        room_id,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+// The response to a muted member's REST write, carrying the same message as the WebSocket's
+// `muted` error and the seconds left in a Retry-After header
+pub(crate) fn muted_response(remaining: i64) -> HttpResponse {
+    HttpResponse::Forbidden()
+        .insert_header((RETRY_AFTER, remaining.to_string()))
+        .json(ErrorResponse {
+            error: format!("You are muted in this room for {} more seconds", remaining),
+        })
+}
+
+// Seconds left until slow mode lets the user post in the room again, counted from their latest
+// message; `None` when slow mode is off or they may post now
+pub(crate) async fn slow_mode_remaining(pool: &SqlitePool, room_id: i64, user_id: i64) -> Result<Option<i64>, sqlx::Error> {
+    let remaining = sqlx::query_scalar!(
+        "SELECT CAST(r.slow_mode_secs - (strftime('%s', 'now') - strftime('%s', MAX(m.created_at))) AS INTEGER) as `remaining?: i64` \
+        FROM rooms r LEFT JOIN messages m ON m.room_id = r.room_id AND m.user_id = ? \
+        WHERE r.room_id = ? AND r.slow_mode_secs > 0",
+        user_id,
+        room_id
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(remaining.flatten().filter(|remaining| *remaining > 0))
+}
+
+// Loads the username of a member along with their role
+async fn load_member(pool: &SqlitePool, room_id: i64, user_id: i64) -> Result<Option<(String, Role)>, sqlx::Error> {
+    let member = sqlx::query!(
+        "SELECT u.username, ur.role as `role: Role` FROM users u \
+        INNER JOIN user_rooms ur ON u.user_id = ur.user_id \
+        WHERE ur.room_id = ? AND ur.user_id = ?",
+        room_id,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(member.map(|member| (member.username, member.role)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/rooms/{room_id}/members/me",
+    params(
+        ("room_id" = i64, Path, description = "ID of the room to leave"),
+        ("Authorization" = String, Header, description = "Bearer <JWT Token>")
+    ),
+    responses(
+        (status = 200, description = "Left the room; the room receives a member_removed event and the user's connections to it close", body = MessageResponse),
+        (status = 400, description = "The owner cannot leave the room", body = ErrorResponse),
+        (status = 401, description = "User ID missing in token", body = ErrorResponse),
+        (status = 403, description = "User is not a member of the room", body = ErrorResponse),
+        (status = 500, description = "Failed to leave room", body = ErrorResponse)
+    )
+)]
+pub async fn leave_room(
+    pool: web::Data<SqlitePool>,
+    room_server: web::Data<Addr<RoomServer>>,
+    path: web::Path<i64>,
+    req: HttpRequest,
+) -> impl Responder {
+    let room_id = path.into_inner();
+    let user_id = match req.extensions().get::<i64>().copied() {
+        Some(user_id) => user_id,
+        None => {
+            return HttpResponse::Unauthorized().json(ErrorResponse {
+                error: "User ID missing in token".into(),
+            })
+        }
+    };
+
+    let username = match load_member(pool.get_ref(), room_id, user_id).await {
+        Ok(Some((_, Role::Owner))) => {
+            return HttpResponse::BadRequest().json(ErrorResponse {
+                error: "The owner cannot leave the room; transfer it to another member or delete it instead".into(),
+            })
+        }
+        Ok(Some((username, _))) => username,
+        Ok(None) => return AccessError::NotMember.to_response(),
+        Err(e) => {
+            error!("Failed to load member {} of room {}: {}", user_id, room_id, e);
+            return HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to leave room".into(),
+            });
+        }
+    };
+
+    if let Err(e) = sqlx::query!(
+        "DELETE FROM user_rooms WHERE room_id = ? AND user_id = ?",
+        room_id,
+        user_id
+    )
+    .execute(pool.get_ref())
+    .await
+    {
+        error!("Failed to remove user {} from room {}: {}", user_id, room_id, e);
+        return HttpResponse::InternalServerError().json(ErrorResponse {
+            error: "Failed to leave room".into(),
+        });
+    }
+
+    info!("User {} left room {}", user_id, room_id);
+    room_server.do_send(RemoveMember {
+        room_id,
+        user_id,
+        event: ServerEvent::MemberRemoved {
+            room_id,
+            user_id,
+            username,
+            cause: RemovalCause::Left,
+        },
+    });
+    HttpResponse::Ok().json(MessageResponse {
+        message: "You left the room".into(),
+    })
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/rooms/{room_id}/members/{user_id}",
+    params(
+        ("room_id" = i64, Path, description = "ID of the room"),
+        ("user_id" = i64, Path, description = "ID of the member to kick"),
+        ("Authorization" = String, Header, description = "Bearer <JWT Token>")
+    ),
+    responses(
+        (status = 200, description = "Member kicked; the room receives a member_removed event and the member's connections to it close. They may join again", body = MessageResponse),
+        (status = 400, description = "Users cannot kick themselves", body = ErrorResponse),
+        (status = 401, description = "User ID missing in token", body = ErrorResponse),
+        (status = 403, description = "Not allowed to remove members, or the member ranks too high", body = ErrorResponse),
+        (status = 404, description = "Member not found", body = ErrorResponse),
+        (status = 500, description = "Failed to kick member", body = ErrorResponse)
+    )
+)]
+pub async fn kick_member(
+    pool: web::Data<SqlitePool>,
+    room_server: web::Data<Addr<RoomServer>>,
+    path: web::Path<(i64, i64)>,
+    req: HttpRequest,
+) -> impl Responder {
+    let (room_id, member_id) = path.into_inner();
+    let user_id = match req.extensions().get::<i64>().copied() {
+        Some(user_id) => user_id,
+        None => {
+            return HttpResponse::Unauthorized().json(ErrorResponse {
+                error: "User ID missing in token".into(),
+            })
+        }
+    };
+
+    if member_id == user_id {
+        return HttpResponse::BadRequest().json(ErrorResponse {
+            error: "You cannot kick yourself; leave the room instead".into(),
+        });
+    }
+
+    let actor_role = match require_permission(pool.get_ref(), room_id, user_id, Permission::RemoveMembers).await {
+        Ok(role) => role,
+        Err(e) => return e.to_response(),
+    };
+
+    let (username, member_role) = match load_member(pool.get_ref(), room_id, member_id).await {
+        Ok(Some(member)) => member,
+        Ok(None) => {
+            return HttpResponse::NotFound().json(ErrorResponse {
+                error: "Member not found".into(),
+            })
+        }
+        Err(e) => {
+            error!("Failed to load member {} of room {}: {}", member_id, room_id, e);
+            return HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to kick member".into(),
+            });
+        }
+    };
+
+    if !actor_role.outranks(member_role) {
+        return HttpResponse::Forbidden().json(ErrorResponse {
+            error: "You can only remove members ranked below you".into(),
+        });
+    }
+
+    if let Err(e) = sqlx::query!(
+        "DELETE FROM user_rooms WHERE room_id = ? AND user_id = ?",
+        room_id,
+        member_id
+    )
+    .execute(pool.get_ref())
+    .await
+    {
+        error!("Failed to kick user {} from room {}: {}", member_id, room_id, e);
+        return HttpResponse::InternalServerError().json(ErrorResponse {
+            error: "Failed to kick member".into(),
+        });
+    }
+
+    info!("User {} kicked user {} from room {}", user_id, member_id, room_id);
+    room_server.do_send(RemoveMember {
+        room_id,
+        user_id: member_id,
+        event: ServerEvent::MemberRemoved {
+            room_id,
+            user_id: member_id,
+            username,
+            cause: RemovalCause::Kicked,
+        },
+    });
+    HttpResponse::Ok().json(MessageResponse {
+        message: "Member kicked".into(),
+    })
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/rooms/{room_id}/bans",
+    request_body = BanRequest,
+    params(
+        ("room_id" = i64, Path, description = "ID of the room"),
+        ("Authorization" = String, Header, description = "Bearer <JWT Token>")
+    ),
+    responses(
+        (status = 201, description = "User banned, replacing any earlier ban; a member is removed from the room like a kick and cannot rejoin while the ban lasts", body = RoomBan),
+        (status = 400, description = "Users cannot ban themselves, the reason is too long or the expiry is not positive", body = ErrorResponse),
+        (status = 401, description = "User ID missing in token", body = ErrorResponse),
+        (status = 403, description = "Not allowed to remove members, or the member ranks too high", body = ErrorResponse),
+        (status = 404, description = "User not found", body = ErrorResponse),
+        (status = 500, description = "Failed to ban user", body = ErrorResponse)
+    )
+)]
+pub async fn ban_user(
+    pool: web::Data<SqlitePool>,
+    room_server: web::Data<Addr<RoomServer>>,
+    path: web::Path<i64>,
+    body: web::Json<BanRequest>,
+    req: HttpRequest,
+) -> impl Responder {
+    let room_id = path.into_inner();
+    let user_id = match req.extensions().get::<i64>().copied() {
+        Some(user_id) => user_id,
+        None => {
+            return HttpResponse::Unauthorized().json(ErrorResponse {
+                error: "User ID missing in token".into(),
+            })
+        }
+    };
+    let body = body.into_inner();
+
+    // Blank reasons are dropped rather than stored
+    let reason = body.reason.map(|reason| reason.trim().to_string()).filter(|reason| !reason.is_empty());
+    if reason.as_ref().is_some_and(|reason| reason.chars().count() > MAX_BAN_REASON_LENGTH) {
+        return HttpResponse::BadRequest().json(ErrorResponse {
+            error: format!("The reason cannot be longer than {} characters", MAX_BAN_REASON_LENGTH),
+        });
+    }
+    if body.expires_in_secs.is_some_and(|secs| secs <= 0) {
+        return HttpResponse::BadRequest().json(ErrorResponse {
+            error: "The expiry must be a positive number of seconds".into(),
+        });
+    }
+
+    let actor_role = match require_permission(pool.get_ref(), room_id, user_id, Permission::RemoveMembers).await {
+        Ok(role) => role,
+        Err(e) => return e.to_response(),
+    };
+
+    let banned_id = match sqlx::query_scalar!(
+        "SELECT user_id as `user_id!: i64` FROM users WHERE username = ? AND deleted_at IS NULL",
+        body.username
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    {
+        Ok(Some(banned_id)) => banned_id,
+        Ok(None) => {
+            return HttpResponse::NotFound().json(ErrorResponse {
+                error: "User not found".into(),
+            })
+        }
+        Err(e) => {
+            error!("Failed to look up user '{}': {}", body.username, e);
+            return HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to ban user".into(),
+            });
+        }
+    };
+
+    if banned_id == user_id {
+        return HttpResponse::BadRequest().json(ErrorResponse {
+            error: "You cannot ban yourself".into(),
+        });
+    }
+
+    // Members are subject to the same rank rule as kicks; anyone else can be banned pre-emptively
+    let was_member = match member_role(pool.get_ref(), room_id, banned_id).await {
+        Ok(Some(role)) if !actor_role.outranks(role) => {
+            return HttpResponse::Forbidden().json(ErrorResponse {
+                error: "You can only remove members ranked below you".into(),
+            })
+        }
+        Ok(role) => role.is_some(),
+        Err(e) => {
+            error!("Failed to check membership of user {} in room {}: {}", banned_id, room_id, e);
+            return HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to ban user".into(),
+            });
+        }
+    };
+
+    let expires_at = body
+        .expires_in_secs
+        .map(|secs| (Utc::now() + Duration::seconds(secs)).format(TIMESTAMP_FORMAT).to_string());
+
+    // The ban, the membership and any pending invitation change together
+    let result: Result<RoomBan, sqlx::Error> = async {
+        let mut tx = pool.begin().await?;
+        sqlx::query!(
+            "INSERT OR REPLACE INTO room_bans (room_id, user_id, banned_by, reason, expires_at) VALUES (?, ?, ?, ?, ?)",
+            room_id,
+            banned_id,
+            user_id,
+            reason,
+            expires_at
+        )
+        .execute(&mut tx)
+        .await?;
+        sqlx::query!(
+            "DELETE FROM user_rooms WHERE room_id = ? AND user_id = ?",
+            room_id,
+            banned_id
+        )
+        .execute(&mut tx)
+        .await?;
+        sqlx::query!(
+            "DELETE FROM room_invitations WHERE room_id = ? AND user_id = ?",
+            room_id,
+            banned_id
+        )
+        .execute(&mut tx)
+        .await?;
+        let ban = sqlx::query_as!(
+            RoomBan,
+            "SELECT rb.room_id, rb.user_id, u.username, rb.banned_by, rb.reason, rb.expires_at, rb.created_at \
+            FROM room_bans rb INNER JOIN users u ON u.user_id = rb.user_id \
+            WHERE rb.room_id = ? AND rb.user_id = ?",
+            room_id,
+            banned_id
+        )
+        .fetch_one(&mut tx)
+        .await?;
+        tx.commit().await?;
+        Ok(ban)
+    }
+    .await;
+
+    match result {
+        Ok(ban) => {
+            info!("User {} banned user {} from room {}", user_id, banned_id, room_id);
+            if was_member {
+                room_server.do_send(RemoveMember {
+                    room_id,
+                    user_id: banned_id,
+                    event: ServerEvent::MemberRemoved {
+                        room_id,
+                        user_id: banned_id,
+                        username: ban.username.clone(),
+                        cause: RemovalCause::Banned,
+                    },
+                });
+            }
+            HttpResponse::Created().json(ban)
+        }
+        Err(e) => {
+            error!("Failed to ban user {} from room {}: {}", banned_id, room_id, e);
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to ban user".into(),
+            })
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/rooms/{room_id}/bans",
+    params(
+        ("room_id" = i64, Path, description = "ID of the room"),
+        ("Authorization" = String, Header, description = "Bearer <JWT Token>")
+    ),
+    responses(
+        (status = 200, description = "Bans in force, newest first; expired bans are left out", body = [RoomBan]),
+        (status = 401, description = "User ID missing in token", body = ErrorResponse),
+        (status = 403, description = "Only moderators, admins and the owner can see bans", body = ErrorResponse),
+        (status = 500, description = "Failed to retrieve bans", body = ErrorResponse)
+    )
+)]
+pub async fn get_bans(
+    pool: web::Data<SqlitePool>,
+    path: web::Path<i64>,
+    req: HttpRequest,
+) -> impl Responder {
+    let room_id = path.into_inner();
+    let user_id = match req.extensions().get::<i64>().copied() {
+        Some(user_id) => user_id,
+        None => {
+            return HttpResponse::Unauthorized().json(ErrorResponse {
+                error: "User ID missing in token".into(),
+            })
+        }
+    };
+
+    if let Err(e) = require_permission(pool.get_ref(), room_id, user_id, Permission::RemoveMembers).await {
+        return e.to_response();
+    }
+
+    match sqlx::query_as!(
+        RoomBan,
+        "SELECT rb.room_id, rb.user_id, u.username, rb.banned_by, rb.reason, rb.expires_at, rb.created_at \
+        FROM room_bans rb INNER JOIN users u ON u.user_id = rb.user_id \
+        WHERE rb.room_id = ? AND (rb.expires_at IS NULL OR rb.expires_at > datetime('now')) \
+        ORDER BY rb.created_at DESC, rb.user_id",
+        room_id
+    )
+    .fetch_all(pool.get_ref())
+    .await
+    {
+        Ok(bans) => HttpResponse::Ok().json(bans),
+        Err(e) => {
+            error!("Failed to retrieve bans of room {}: {}", room_id, e);
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to retrieve bans".into(),
+            })
+        }
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/rooms/{room_id}/bans/{user_id}",
+    params(
+        ("room_id" = i64, Path, description = "ID of the room"),
+        ("user_id" = i64, Path, description = "ID of the banned user"),
+        ("Authorization" = String, Header, description = "Bearer <JWT Token>")
+    ),
+    responses(
+        (status = 200, description = "Ban lifted; the user can join the room again", body = MessageResponse),
+        (status = 401, description = "User ID missing in token", body = ErrorResponse),
+        (status = 403, description = "Only moderators, admins and the owner can lift bans", body = ErrorResponse),
+        (status = 404, description = "Ban not found", body = ErrorResponse),
+        (status = 500, description = "Failed to lift ban", body = ErrorResponse)
+    )
+)]
+pub async fn unban_user(
+    pool: web::Data<SqlitePool>,
+    path: web::Path<(i64, i64)>,
+    req: HttpRequest,
+) -> impl Responder {
+    let (room_id, banned_id) = path.into_inner();
+    let user_id = match req.extensions().get::<i64>().copied() {
+        Some(user_id) => user_id,
+        None => {
+            return HttpResponse::Unauthorized().json(ErrorResponse {
+                error: "User ID missing in token".into(),
+            })
+        }
+    };
+
+    if let Err(e) = require_permission(pool.get_ref(), room_id, user_id, Permission::RemoveMembers).await {
+        return e.to_response();
+    }
+
+    match sqlx::query!(
+        "DELETE FROM room_bans WHERE room_id = ? AND user_id = ?",
+        room_id,
+        banned_id
+    )
+    .execute(pool.get_ref())
+    .await
+    {
+        Ok(result) if result.rows_affected() > 0 => {
+            info!("User {} lifted the ban of user {} from room {}", user_id, banned_id, room_id);
+            HttpResponse::Ok().json(MessageResponse {
+                message: "Ban lifted".into(),
+            })
+        }
+        Ok(_) => HttpResponse::NotFound().json(ErrorResponse {
+            error: "Ban not found".into(),
+        }),
+        Err(e) => {
+            error!("Failed to lift ban of user {} from room {}: {}", banned_id, room_id, e);
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to lift ban".into(),
+            })
+        }
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/rooms/{room_id}/members/{user_id}/mute",
+    request_body = MuteRequest,
+    params(
+        ("room_id" = i64, Path, description = "ID of the room"),
+        ("user_id" = i64, Path, description = "ID of the member to mute"),
+        ("Authorization" = String, Header, description = "Bearer <JWT Token>")
+    ),
+    responses(
+        (status = 200, description = "Member muted, replacing any earlier mute; the room receives a member_muted event", body = RoomMute),
+        (status = 400, description = "Users cannot mute themselves, or the duration is out of range", body = ErrorResponse),
+        (status = 401, description = "User ID missing in token", body = ErrorResponse),
+        (status = 403, description = "Not allowed to mute members, or the member ranks too high", body = ErrorResponse),
+        (status = 404, description = "Member not found", body = ErrorResponse),
+        (status = 500, description = "Failed to mute member", body = ErrorResponse)
+    )
+)]
+pub async fn mute_member(
+    pool: web::Data<SqlitePool>,
+    room_server: web::Data<Addr<RoomServer>>,
+    path: web::Path<(i64, i64)>,
+    body: web::Json<MuteRequest>,
+    req: HttpRequest,
+) -> impl Responder {
+    let (room_id, member_id) = path.into_inner();
+    let user_id = match req.extensions().get::<i64>().copied() {
+        Some(user_id) => user_id,
+        None => {
+            return HttpResponse::Unauthorized().json(ErrorResponse {
+                error: "User ID missing in token".into(),
+            })
+        }
+    };
+
+    if member_id == user_id {
+        return HttpResponse::BadRequest().json(ErrorResponse {
+            error: "You cannot mute yourself".into(),
+        });
+    }
+    if !(1..=MAX_MUTE_SECS).contains(&body.duration_secs) {
+        return HttpResponse::BadRequest().json(ErrorResponse {
+            error: format!("The duration must be between 1 and {} seconds", MAX_MUTE_SECS),
+        });
+    }
+
+    let actor_role = match require_permission(pool.get_ref(), room_id, user_id, Permission::ModerateChat).await {
+        Ok(role) => role,
+        Err(e) => return e.to_response(),
+    };
+
+    let (username, member_role) = match load_member(pool.get_ref(), room_id, member_id).await {
+        Ok(Some(member)) => member,
+        Ok(None) => {
+            return HttpResponse::NotFound().json(ErrorResponse {
+                error: "Member not found".into(),
+            })
+        }
+        Err(e) => {
+            error!("Failed to load member {} of room {}: {}", member_id, room_id, e);
+            return HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to mute member".into(),
+            });
+        }
+    };
+
+    if !actor_role.outranks(member_role) {
+        return HttpResponse::Forbidden().json(ErrorResponse {
+            error: "You can only mute members ranked below you".into(),
+        });
+    }
+
+    let expires_at = (Utc::now() + Duration::seconds(body.duration_secs)).format(TIMESTAMP_FORMAT).to_string();
+    if let Err(e) = sqlx::query!(
+        "INSERT OR REPLACE INTO room_mutes (room_id, user_id, muted_by, expires_at) VALUES (?, ?, ?, ?)",
+        room_id,
+        member_id,
+        user_id,
+        expires_at
+    )
+    .execute(pool.get_ref())
+    .await
+    {
+        error!("Failed to mute user {} in room {}: {}", member_id, room_id, e);
+        return HttpResponse::InternalServerError().json(ErrorResponse {
+            error: "Failed to mute member".into(),
+        });
+    }
+
+    info!("User {} muted user {} in room {} until {}", user_id, member_id, room_id, expires_at);
+    room_server.do_send(BroadcastMessage {
+        room_id,
+        event: ServerEvent::MemberMuted {
+            room_id,
+            user_id: member_id,
+            username: username.clone(),
+            muted_until: Some(expires_at.clone()),
+        },
+    });
+    HttpResponse::Ok().json(RoomMute {
+        room_id,
+        user_id: member_id,
+        username,
+        muted_by: user_id,
+        expires_at,
+        created_at: Utc::now().format(TIMESTAMP_FORMAT).to_string(),
+    })
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/rooms/{room_id}/members/{user_id}/mute",
+    params(
+        ("room_id" = i64, Path, description = "ID of the room"),
+        ("user_id" = i64, Path, description = "ID of the muted member"),
+        ("Authorization" = String, Header, description = "Bearer <JWT Token>")
+    ),
+    responses(
+        (status = 200, description = "Mute lifted; the room receives a member_muted event without muted_until", body = MessageResponse),
+        (status = 401, description = "User ID missing in token", body = ErrorResponse),
+        (status = 403, description = "Only moderators, admins and the owner can lift mutes", body = ErrorResponse),
+        (status = 404, description = "Mute not found", body = ErrorResponse),
+        (status = 500, description = "Failed to lift mute", body = ErrorResponse)
+    )
+)]
+pub async fn unmute_member(
+    pool: web::Data<SqlitePool>,
+    room_server: web::Data<Addr<RoomServer>>,
+    path: web::Path<(i64, i64)>,
+    req: HttpRequest,
+) -> impl Responder {
+    let (room_id, member_id) = path.into_inner();
+    let user_id = match req.extensions().get::<i64>().copied() {
+        Some(user_id) => user_id,
+        None => {
+            return HttpResponse::Unauthorized().json(ErrorResponse {
+                error: "User ID missing in token".into(),
+            })
+        }
+    };
+
+    if let Err(e) = require_permission(pool.get_ref(), room_id, user_id, Permission::ModerateChat).await {
+        return e.to_response();
+    }
+
+    // Expired mutes count as not found
+    let result: Result<Option<String>, sqlx::Error> = async {
+        let deleted = sqlx::query!(
+            "DELETE FROM room_mutes WHERE room_id = ? AND user_id = ? AND expires_at > datetime('now')",
+            room_id,
+            member_id
+        )
+        .execute(pool.get_ref())
+        .await?;
+        if deleted.rows_affected() == 0 {
+            return Ok(None);
+        }
+        sqlx::query_scalar!("SELECT username FROM users WHERE user_id = ?", member_id)
+            .fetch_optional(pool.get_ref())
+            .await
+    }
+    .await;
+
+    let username = match result {
+        Ok(Some(username)) => username,
+        Ok(None) => {
+            return HttpResponse::NotFound().json(ErrorResponse {
+                error: "Mute not found".into(),
+            })
+        }
+        Err(e) => {
+            error!("Failed to lift mute of user {} in room {}: {}", member_id, room_id, e);
+            return HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to lift mute".into(),
+            });
+        }
+    };
+
+    info!("User {} lifted the mute of user {} in room {}", user_id, member_id, room_id);
+    room_server.do_send(BroadcastMessage {
+        room_id,
+        event: ServerEvent::MemberMuted {
+            room_id,
+            user_id: member_id,
+            username,
+            muted_until: None,
+        },
+    });
+    HttpResponse::Ok().json(MessageResponse {
+        message: "Mute lifted".into(),
+    })
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/rooms/{room_id}/slow_mode",
+    request_body = SlowModeRequest,
+    params(
+        ("room_id" = i64, Path, description = "ID of the room"),
+        ("Authorization" = String, Header, description = "Bearer <JWT Token>")
+    ),
+    responses(
+        (status = 200, description = "Slow mode set, or turned off at 0 seconds; the room receives a slow_mode_changed event. Moderators, admins and the owner are exempt", body = Room),
+        (status = 400, description = "The interval is out of range", body = ErrorResponse),
+        (status = 401, description = "User ID missing in token", body = ErrorResponse),
+        (status = 403, description = "Only moderators, admins and the owner can set slow mode", body = ErrorResponse),
+        (status = 404, description = "Room not found", body = ErrorResponse),
+        (status = 500, description = "Failed to set slow mode", body = ErrorResponse)
+    )
+)]
+pub async fn set_slow_mode(
+    pool: web::Data<SqlitePool>,
+    room_server: web::Data<Addr<RoomServer>>,
+    path: web::Path<i64>,
+    body: web::Json<SlowModeRequest>,
+    req: HttpRequest,
+) -> impl Responder {
+    let room_id = path.into_inner();
+    let user_id = match req.extensions().get::<i64>().copied() {
+        Some(user_id) => user_id,
+        None => {
+            return HttpResponse::Unauthorized().json(ErrorResponse {
+                error: "User ID missing in token".into(),
+            })
+        }
+    };
+
+    if !(0..=MAX_SLOW_MODE_SECS).contains(&body.seconds) {
+        return HttpResponse::BadRequest().json(ErrorResponse {
+            error: format!("Slow mode must be between 0 and {} seconds", MAX_SLOW_MODE_SECS),
+        });
+    }
+
+    if let Err(e) = require_permission(pool.get_ref(), room_id, user_id, Permission::ModerateChat).await {
+        return e.to_response();
+    }
+
+    let result: Result<Option<Room>, sqlx::Error> = async {
+        sqlx::query!(
+            "UPDATE rooms SET slow_mode_secs = ? WHERE room_id = ?",
+            body.seconds,
+            room_id
+        )
+        .execute(pool.get_ref())
+        .await?;
+        fetch_room(pool.get_ref(), room_id).await
+    }
+    .await;
+
+    match result {
+        Ok(Some(room)) => {
+            info!("User {} set slow mode of room {} to {} seconds", user_id, room_id, body.seconds);
+            room_server.do_send(BroadcastMessage {
+                room_id,
+                event: ServerEvent::SlowModeChanged {
+                    room_id,
+                    seconds: room.slow_mode_secs,
+                },
+            });
+            HttpResponse::Ok().json(room)
+        }
+        Ok(None) => HttpResponse::NotFound().json(ErrorResponse {
+            error: "Room not found".into(),
+        }),
+        Err(e) => {
+            error!("Failed to set slow mode of room {}: {}", room_id, e);
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to set slow mode".into(),
+            })
+        }
+    }
+}
@@ -0,0 +1,550 @@
+use crate::config::settings::{PASSWORD_RESET_RATE_WINDOW, PASSWORD_RESET_TTL, PASSWORD_RESET_URL};
+use crate::mail::{Email, Mailer};
+use crate::middleware::auth_middleware::SessionId;
+use crate::models::response::{ErrorResponse, MessageResponse};
+use crate::routes::auth::hash_token;
+use crate::routes::message::TIMESTAMP_FORMAT;
+use crate::routes::session::{end_sessions, revoke_session, user_session_ids};
+use crate::websockets::chat_session::RoomServer;
+use actix::Addr;
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse, Responder};
+use bcrypt::{hash, verify, DEFAULT_COST};
+use chrono::Utc;
+use log::{error, info};
+use rand::distributions::{Alphanumeric, DistString};
+use serde::Deserialize;
+use sqlx::SqlitePool;
+use std::sync::Arc;
+use utoipa::ToSchema;
+
+// Length of password reset tokens; like refresh tokens, too long to guess
+const RESET_TOKEN_LENGTH: usize = 43;
+// Reset requests acted on per address, and per client address, within PASSWORD_RESET_RATE_WINDOW.
+// A new link does not void the ones sent before it, so requests made by someone else cannot keep
+// a user from resetting; the limits only cap how many emails anyone can have sent to an address.
+const MAX_RESET_REQUESTS_PER_EMAIL: i64 = 3;
+const MAX_RESET_REQUESTS_PER_IP: i64 = 10;
+
+#[derive(Deserialize, ToSchema)]
+pub struct ChangePasswordRequest {
+    pub current_password: String,
+    pub new_password: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ForgotPasswordRequest {
+    pub email: String, // The address given at registration
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ResetPasswordRequest {
+    pub token: String, // From the reset link
+    pub new_password: String,
+}
+
+// Hashes a new password, refusing empty ones
+fn hash_new_password(password: &str) -> Result<String, HttpResponse> {
+    if password.is_empty() {
+        return Err(HttpResponse::BadRequest().json(ErrorResponse {
+            error: "The new password cannot be empty".into(),
+        }));
+    }
+    hash(password, DEFAULT_COST).map_err(|e| {
+        error!("Failed to hash password: {}", e);
+        HttpResponse::InternalServerError().json(ErrorResponse {
+            error: "Failed to update password".into(),
+        })
+    })
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/account/password",
+    request_body = ChangePasswordRequest,
+    params(
+        ("Authorization" = String, Header, description = "Bearer <JWT Token>")
+    ),
+    responses(
+        (status = 200, description = "Password changed; every other session is revoked, and the current one stays logged in", body = MessageResponse),
+        (status = 400, description = "The new password is empty", body = ErrorResponse),
+        (status = 401, description = "User ID missing in token, or wrong current password", body = ErrorResponse),
+        (status = 500, description = "Failed to update password", body = ErrorResponse)
+    )
+)]
+pub async fn change_password(
+    pool: web::Data<SqlitePool>,
+    room_server: web::Data<Addr<RoomServer>>,
+    body: web::Json<ChangePasswordRequest>,
+    req: HttpRequest,
+) -> impl Responder {
+    let (user_id, current) = match (req.extensions().get::<i64>().copied(), req.extensions().get::<SessionId>().cloned()) {
+        (Some(user_id), Some(session_id)) => (user_id, session_id.0),
+        _ => {
+            return HttpResponse::Unauthorized().json(ErrorResponse {
+                error: "User ID missing in token".into(),
+            })
+        }
+    };
+
+    match sqlx::query_scalar!(
+        "SELECT password_hash FROM users WHERE user_id = ? AND deleted_at IS NULL",
+        user_id
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    {
+        Ok(Some(password_hash)) if verify(&body.current_password, &password_hash).unwrap_or(false) => {}
+        Ok(_) => {
+            return HttpResponse::Unauthorized().json(ErrorResponse {
+                error: "Unauthorized: Invalid password".into(),
+            })
+        }
+        Err(e) => {
+            error!("Failed to load user {}: {}", user_id, e);
+            return HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to update password".into(),
+            });
+        }
+    }
+    let password_hash = match hash_new_password(&body.new_password) {
+        Ok(password_hash) => password_hash,
+        Err(response) => return response,
+    };
+
+    // Whoever else knew the old password is logged out along with it
+    let result = async {
+        let mut tx = pool.begin().await?;
+        sqlx::query!("UPDATE users SET password_hash = ? WHERE user_id = ?", password_hash, user_id)
+            .execute(&mut tx)
+            .await?;
+        let revoked = user_session_ids(&mut tx, user_id, Some(&current)).await?;
+        for session_id in &revoked {
+            revoke_session(&mut tx, session_id).await?;
+        }
+        tx.commit().await?;
+        Ok::<Vec<String>, sqlx::Error>(revoked)
+    }
+    .await;
+
+    match result {
+        Ok(revoked) => {
+            info!("User {} changed their password; {} other sessions revoked", user_id, revoked.len());
+            end_sessions(room_server.get_ref(), revoked);
+            HttpResponse::Ok().json(MessageResponse {
+                message: "Password changed; other sessions were logged out".into(),
+            })
+        }
+        Err(e) => {
+            error!("Failed to change password of user {}: {}", user_id, e);
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to update password".into(),
+            })
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/password/forgot",
+    request_body = ForgotPasswordRequest,
+    responses(
+        (status = 200, description = "If an account uses this address, a reset link valid for PASSWORD_RESET_TTL_SECS is sent to it, unless the address or the client already made too many requests within PASSWORD_RESET_RATE_WINDOW_SECS. The response is the same in every case, and is sent before the account is looked up, so neither its content nor its timing tells which addresses have accounts", body = MessageResponse),
+        (status = 500, description = "Failed to start the password reset", body = ErrorResponse)
+    )
+)]
+pub async fn forgot_password(
+    pool: web::Data<SqlitePool>,
+    mailer: web::Data<dyn Mailer>,
+    body: web::Json<ForgotPasswordRequest>,
+    req: HttpRequest,
+) -> impl Responder {
+    let email = body.email.trim().to_lowercase();
+    // The address of the connection itself: forwarding headers come from the client, which could
+    // put a different address in each request
+    let ip_address = req.peer_addr().map(|addr| addr.ip().to_string());
+
+    // The limits count requests, not accounts, so hitting one does not tell whether the address has
+    // an account either. Refused requests are not recorded, so retrying does not extend the wait.
+    let window = format!("-{} seconds", PASSWORD_RESET_RATE_WINDOW.num_seconds());
+    let accepted = async {
+        let mut tx = pool.begin().await?;
+        let recent = sqlx::query!(
+            "SELECT (SELECT COUNT(*) FROM password_reset_requests WHERE email = ? AND requested_at > datetime('now', ?)) as `by_email!: i64`, \
+            (SELECT COUNT(*) FROM password_reset_requests WHERE ip_address = ? AND requested_at > datetime('now', ?)) as `by_ip!: i64`",
+            email,
+            window,
+            ip_address,
+            window
+        )
+        .fetch_one(&mut tx)
+        .await?;
+        if recent.by_email >= MAX_RESET_REQUESTS_PER_EMAIL || recent.by_ip >= MAX_RESET_REQUESTS_PER_IP {
+            return Ok(false);
+        }
+        sqlx::query!(
+            "INSERT INTO password_reset_requests (email, ip_address) VALUES (?, ?)",
+            email,
+            ip_address
+        )
+        .execute(&mut tx)
+        .await?;
+        tx.commit().await?;
+        Ok::<bool, sqlx::Error>(true)
+    }
+    .await;
+    match accepted {
+        // Looking up the account, storing a token and talking to the mail server all happen after
+        // responding, so that known and unknown addresses take the same time to answer
+        Ok(true) => {
+            actix_web::rt::spawn(send_reset_link(pool.get_ref().clone(), mailer.into_inner(), email));
+        }
+        // Refused requests get the same answer, but send nothing and leave earlier links working
+        Ok(false) => info!("Ignored a password reset request from {:?}: rate limit reached", ip_address),
+        Err(e) => {
+            error!("Failed to record password reset request: {}", e);
+            return HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to start the password reset".into(),
+            });
+        }
+    }
+    HttpResponse::Ok().json(MessageResponse {
+        message: "If an account uses this address, a reset link was sent to it".into(),
+    })
+}
+
+// Emails a reset link to the account using this address, if any. Failures are only logged:
+// telling the client would tell it that the account exists.
+async fn send_reset_link(pool: SqlitePool, mailer: Arc<dyn Mailer>, email: String) {
+    let user = match sqlx::query!(
+        "SELECT user_id as `user_id!: i64`, username FROM users WHERE email = ? AND deleted_at IS NULL",
+        email
+    )
+    .fetch_optional(&pool)
+    .await
+    {
+        Ok(Some(user)) => user,
+        Ok(None) => {
+            info!("Password reset requested for unknown address");
+            return;
+        }
+        Err(e) => {
+            error!("Failed to look up user for a password reset: {}", e);
+            return;
+        }
+    };
+
+    // Links sent earlier keep working until they expire or one of them is used
+    let token = Alphanumeric.sample_string(&mut rand::thread_rng(), RESET_TOKEN_LENGTH);
+    let token_hash = hash_token(&token);
+    let expires_at = (Utc::now() + *PASSWORD_RESET_TTL).format(TIMESTAMP_FORMAT).to_string();
+    let stored = sqlx::query!(
+        "INSERT INTO password_reset_tokens (token_hash, user_id, expires_at) VALUES (?, ?, ?)",
+        token_hash,
+        user.user_id,
+        expires_at
+    )
+    .execute(&pool)
+    .await;
+    if let Err(e) = stored {
+        error!("Failed to store password reset token for user {}: {}", user.user_id, e);
+        return;
+    }
+
+    let email = Email {
+        to: email,
+        subject: "Reset your Pika Chat password".into(),
+        body: format!(
+            "Hi {},\n\nOpen this link to choose a new password:\n\n{}/{}\n\n\
+            The link works once and expires in {} minutes. If you did not ask to reset your password, \
+            ignore this email; your password stays the same.\n",
+            user.username,
+            PASSWORD_RESET_URL.trim_end_matches('/'),
+            token,
+            PASSWORD_RESET_TTL.num_minutes()
+        ),
+    };
+    match mailer.send(email).await {
+        Ok(()) => info!("Sent password reset link to user {}", user.user_id),
+        Err(e) => error!("Failed to send password reset link to user {}: {}", user.user_id, e),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/password/reset",
+    request_body = ResetPasswordRequest,
+    responses(
+        (status = 200, description = "Password reset; every other reset link of the account stops working, and every session is revoked, so the user logs in again with the new password", body = MessageResponse),
+        (status = 400, description = "The new password is empty", body = ErrorResponse),
+        (status = 401, description = "The reset token is unknown, expired or already used", body = ErrorResponse),
+        (status = 500, description = "Failed to update password", body = ErrorResponse)
+    )
+)]
+pub async fn reset_password(
+    pool: web::Data<SqlitePool>,
+    room_server: web::Data<Addr<RoomServer>>,
+    body: web::Json<ResetPasswordRequest>,
+) -> impl Responder {
+    let password_hash = match hash_new_password(&body.new_password) {
+        Ok(password_hash) => password_hash,
+        Err(response) => return response,
+    };
+    let token_hash = hash_token(&body.token);
+
+    // Marking the token used only succeeds once, so the same link cannot reset the password twice
+    let result = async {
+        let mut tx = pool.begin().await?;
+        let user_id = sqlx::query_scalar!(
+            "UPDATE password_reset_tokens SET used_at = datetime('now') \
+            WHERE token_hash = ? AND used_at IS NULL AND expires_at > datetime('now') \
+            AND user_id IN (SELECT user_id FROM users WHERE deleted_at IS NULL) \
+            RETURNING user_id as `user_id!: i64`",
+            token_hash
+        )
+        .fetch_optional(&mut tx)
+        .await?;
+        let user_id = match user_id {
+            Some(user_id) => user_id,
+            None => return Ok(None),
+        };
+
+        sqlx::query!("UPDATE users SET password_hash = ? WHERE user_id = ?", password_hash, user_id)
+            .execute(&mut tx)
+            .await?;
+        // The other links sent to the account are no longer needed
+        sqlx::query!(
+            "UPDATE password_reset_tokens SET used_at = datetime('now') WHERE user_id = ? AND used_at IS NULL",
+            user_id
+        )
+        .execute(&mut tx)
+        .await?;
+        // Whoever used the forgotten password, or may have guessed it, is logged out
+        let revoked = user_session_ids(&mut tx, user_id, None).await?;
+        for session_id in &revoked {
+            revoke_session(&mut tx, session_id).await?;
+        }
+        tx.commit().await?;
+        Ok::<Option<(i64, Vec<String>)>, sqlx::Error>(Some((user_id, revoked)))
+    }
+    .await;
+
+    match result {
+        Ok(Some((user_id, revoked))) => {
+            info!("User {} reset their password; {} sessions revoked", user_id, revoked.len());
+            end_sessions(room_server.get_ref(), revoked);
+            HttpResponse::Ok().json(MessageResponse {
+                message: "Password reset; log in with the new password".into(),
+            })
+        }
+        Ok(None) => HttpResponse::Unauthorized().json(ErrorResponse {
+            error: "Unauthorized: Invalid or expired reset link".into(),
+        }),
+        Err(e) => {
+            error!("Failed to reset password: {}", e);
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to update password".into(),
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mail::memory::InMemoryMailer;
+    use crate::middleware::revocation::is_session_revoked;
+    use crate::routes::test_support::test_pool;
+    use actix::Actor;
+    use actix_web::http::StatusCode;
+    use actix_web::{test, App};
+    use serde_json::json;
+    use std::net::SocketAddr;
+    use std::time::Duration;
+
+    const EMAIL: &str = "reset@example.com";
+
+    // Adds a user with the test address and one logged-in session, returning the session ID
+    async fn add_user(pool: &SqlitePool) -> String {
+        let password_hash = hash("old password", 4).unwrap();
+        let user_id = sqlx::query_scalar::<_, i64>(
+            "INSERT INTO users (username, password_hash, email) VALUES ('resetter', ?, ?) RETURNING user_id",
+        )
+        .bind(password_hash)
+        .bind(EMAIL)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        let session_id = "reset-test-session".to_string();
+        sqlx::query("INSERT INTO sessions (session_id, user_id, expires_at) VALUES (?, ?, datetime('now', '+1 day'))")
+            .bind(&session_id)
+            .bind(user_id)
+            .execute(pool)
+            .await
+            .unwrap();
+        sqlx::query(
+            "INSERT INTO refresh_tokens (token_hash, family_id, user_id, expires_at) \
+            VALUES ('refresh-hash', ?, ?, datetime('now', '+1 day'))",
+        )
+        .bind(&session_id)
+        .bind(user_id)
+        .execute(pool)
+        .await
+        .unwrap();
+        session_id
+    }
+
+    fn forgot_request(email: &str, peer: &str) -> test::TestRequest {
+        test::TestRequest::post()
+            .uri("/api/password/forgot")
+            .peer_addr(peer.parse::<SocketAddr>().unwrap())
+            .set_json(json!({ "email": email }))
+    }
+
+    fn reset_request(token: &str) -> test::TestRequest {
+        test::TestRequest::post()
+            .uri("/api/password/reset")
+            .set_json(json!({ "token": token, "new_password": "new password" }))
+    }
+
+    // Waits for the reset link sent in the background and returns the token at its end
+    async fn next_reset_token(mailer: &InMemoryMailer, sent_before: usize) -> String {
+        for _ in 0..100 {
+            let sent = mailer.sent();
+            if sent.len() > sent_before {
+                let email = sent.last().unwrap();
+                assert_eq!(email.to, EMAIL);
+                let link = email.body.lines().find(|line| line.starts_with(PASSWORD_RESET_URL.as_str())).unwrap();
+                return link.rsplit('/').next().unwrap().to_string();
+            }
+            actix_web::rt::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("no reset link was sent");
+    }
+
+    // Gives a link that would have been sent in the background the time to arrive
+    async fn assert_nothing_sent(mailer: &InMemoryMailer, sent_before: usize) {
+        actix_web::rt::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(mailer.sent().len(), sent_before);
+    }
+
+    #[actix_web::test]
+    async fn reset_tokens_are_single_use_expire_and_revoke_sessions() {
+        let pool = test_pool().await;
+        let session_id = add_user(&pool).await;
+        let mailer = Arc::new(InMemoryMailer::default());
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool.clone()))
+                .app_data(web::Data::new(RoomServer::new().start()))
+                .app_data(web::Data::from(mailer.clone() as Arc<dyn Mailer>))
+                .route("/api/password/forgot", web::post().to(forgot_password))
+                .route("/api/password/reset", web::post().to(reset_password)),
+        )
+        .await;
+
+        let response = test::call_service(&app, forgot_request(EMAIL, "10.0.0.1:4000").to_request()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let token = next_reset_token(&mailer, 0).await;
+
+        assert_eq!(test::call_service(&app, reset_request(&token).to_request()).await.status(), StatusCode::OK);
+        let password_hash = sqlx::query_scalar::<_, String>("SELECT password_hash FROM users")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert!(verify("new password", &password_hash).unwrap());
+
+        // The reset logged out the session that existed before it
+        let revoked_at = sqlx::query_scalar::<_, Option<String>>("SELECT revoked_at FROM sessions WHERE session_id = ?")
+            .bind(&session_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert!(revoked_at.is_some());
+        let live_refresh_tokens = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM refresh_tokens WHERE revoked_at IS NULL")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(live_refresh_tokens, 0);
+        assert!(is_session_revoked(&session_id));
+
+        // The same link does not work twice
+        assert_eq!(test::call_service(&app, reset_request(&token).to_request()).await.status(), StatusCode::UNAUTHORIZED);
+
+        // Nor does a link once it has expired
+        let response = test::call_service(&app, forgot_request(EMAIL, "10.0.0.1:4000").to_request()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let expired = next_reset_token(&mailer, 1).await;
+        sqlx::query("UPDATE password_reset_tokens SET expires_at = datetime('now', '-1 minute') WHERE used_at IS NULL")
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert_eq!(test::call_service(&app, reset_request(&expired).to_request()).await.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn requests_over_the_limit_leave_earlier_links_working() {
+        let pool = test_pool().await;
+        add_user(&pool).await;
+        let mailer = Arc::new(InMemoryMailer::default());
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool.clone()))
+                .app_data(web::Data::new(RoomServer::new().start()))
+                .app_data(web::Data::from(mailer.clone() as Arc<dyn Mailer>))
+                .route("/api/password/forgot", web::post().to(forgot_password))
+                .route("/api/password/reset", web::post().to(reset_password)),
+        )
+        .await;
+
+        // Every request within the limit sends a link, without voiding the ones before it
+        let mut tokens = Vec::new();
+        for sent in 0..MAX_RESET_REQUESTS_PER_EMAIL as usize {
+            let peer = format!("10.0.0.{}:4000", sent + 1);
+            let response = test::call_service(&app, forgot_request(EMAIL, &peer).to_request()).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            tokens.push(next_reset_token(&mailer, sent).await);
+        }
+
+        // The next request gets the same answer, but nothing is sent
+        let response = test::call_service(&app, forgot_request(EMAIL, "10.0.0.9:4000").to_request()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_nothing_sent(&mailer, tokens.len()).await;
+
+        // The first link still works, and using it voids the others
+        assert_eq!(test::call_service(&app, reset_request(&tokens[0]).to_request()).await.status(), StatusCode::OK);
+        assert_eq!(test::call_service(&app, reset_request(&tokens[1]).to_request()).await.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn client_limit_ignores_forwarding_headers() {
+        let pool = test_pool().await;
+        add_user(&pool).await;
+        let mailer = Arc::new(InMemoryMailer::default());
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool.clone()))
+                .app_data(web::Data::new(RoomServer::new().start()))
+                .app_data(web::Data::from(mailer.clone() as Arc<dyn Mailer>))
+                .route("/api/password/forgot", web::post().to(forgot_password)),
+        )
+        .await;
+
+        for n in 0..MAX_RESET_REQUESTS_PER_IP {
+            let unknown = format!("nobody{}@example.com", n);
+            let response = test::call_service(&app, forgot_request(&unknown, "10.0.0.1:4000").to_request()).await;
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+
+        // Claiming to be forwarded from somewhere else does not get around the limit
+        let spoofed = forgot_request(EMAIL, "10.0.0.1:4001")
+            .insert_header(("X-Forwarded-For", "192.0.2.7"))
+            .to_request();
+        assert_eq!(test::call_service(&app, spoofed).await.status(), StatusCode::OK);
+        assert_nothing_sent(&mailer, 0).await;
+
+        // Another client is not held back by it
+        let response = test::call_service(&app, forgot_request(EMAIL, "10.0.0.2:4000").to_request()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        next_reset_token(&mailer, 0).await;
+    }
+}
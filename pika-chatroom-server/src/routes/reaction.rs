@@ -0,0 +1,317 @@
+use crate::models::reaction::{ReactionRow, ReactionSummary};
+use crate::models::response::ErrorResponse;
+use crate::models::role::Permission;
+use crate::routes::message::fetch_message;
+use crate::routes::moderation::{mute_remaining, muted_response};
+use crate::routes::role::require_permission;
+use crate::websockets::chat_session::{BroadcastMessage, RoomServer};
+use crate::websockets::protocol::ServerEvent;
+use actix::Addr;
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse, Responder};
+use log::{error, info};
+use sqlx::SqlitePool;
+
+// Longest accepted emoji, in characters; sequences joined with ZWJ use several code points
+const MAX_EMOJI_LENGTH: usize = 16;
+
+// Rejects empty values, plain text and whitespace while allowing multi-code-point emoji
+fn is_valid_emoji(emoji: &str) -> bool {
+    let length = emoji.chars().count();
+    length > 0
+        && length <= MAX_EMOJI_LENGTH
+        && !emoji.chars().any(|c| c.is_whitespace() || c.is_control())
+        && !emoji.is_ascii()
+}
+
+// Loads the reactions to the messages of a room whose IDs fall within a range
+pub(crate) async fn fetch_reactions(
+    pool: &SqlitePool,
+    room_id: i64,
+    first_message_id: i64,
+    last_message_id: i64,
+) -> Result<Vec<ReactionSummary>, sqlx::Error> {
+    let rows = sqlx::query_as!(
+        ReactionRow,
+        "SELECT r.message_id, r.emoji, r.user_id, u.username FROM reactions r \
+        INNER JOIN messages m ON m.message_id = r.message_id \
+        INNER JOIN users u ON u.user_id = r.user_id \
+        WHERE m.room_id = ? AND r.message_id BETWEEN ? AND ? \
+        ORDER BY r.message_id, r.rowid",
+        room_id,
+        first_message_id,
+        last_message_id
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(ReactionSummary::from_rows(rows))
+}
+
+// Answers a reaction request with the message's current reactions
+async fn reactions_response(pool: &SqlitePool, room_id: i64, message_id: i64) -> HttpResponse {
+    match fetch_reactions(pool, room_id, message_id, message_id).await {
+        Ok(reactions) => HttpResponse::Ok().json(reactions),
+        Err(e) => {
+            error!("Failed to retrieve reactions of message {}: {}", message_id, e);
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to retrieve reactions".into(),
+            })
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/rooms/{room_id}/messages/{message_id}/reactions",
+    params(
+        ("room_id" = i64, Path, description = "ID of the room"),
+        ("message_id" = i64, Path, description = "ID of the message"),
+        ("Authorization" = String, Header, description = "Bearer <JWT Token>")
+    ),
+    responses(
+        (status = 200, description = "Reactions to the message grouped by emoji", body = [ReactionSummary]),
+        (status = 401, description = "User ID missing in token", body = ErrorResponse),
+        (status = 403, description = "User is not a member of the room", body = ErrorResponse),
+        (status = 404, description = "Message not found", body = ErrorResponse),
+        (status = 500, description = "Failed to retrieve reactions", body = ErrorResponse)
+    )
+)]
+pub async fn get_message_reactions(
+    pool: web::Data<SqlitePool>,
+    path: web::Path<(i64, i64)>,
+    req: HttpRequest,
+) -> impl Responder {
+    let (room_id, message_id) = path.into_inner();
+    let user_id = match req.extensions().get::<i64>().copied() {
+        Some(user_id) => user_id,
+        None => {
+            return HttpResponse::Unauthorized().json(ErrorResponse {
+                error: "User ID missing in token".into(),
+            })
+        }
+    };
+
+    if let Err(e) = require_permission(pool.get_ref(), room_id, user_id, Permission::ViewRoom).await {
+        return e.to_response();
+    }
+
+    match fetch_message(pool.get_ref(), room_id, message_id).await {
+        Ok(Some(_)) => reactions_response(pool.get_ref(), room_id, message_id).await,
+        Ok(None) => HttpResponse::NotFound().json(ErrorResponse {
+            error: "Message not found".into(),
+        }),
+        Err(e) => {
+            error!("Failed to load message {}: {}", message_id, e);
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to retrieve reactions".into(),
+            })
+        }
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/rooms/{room_id}/messages/{message_id}/reactions/{emoji}",
+    params(
+        ("room_id" = i64, Path, description = "ID of the room"),
+        ("message_id" = i64, Path, description = "ID of the message"),
+        ("emoji" = String, Path, description = "The emoji, URL-encoded"),
+        ("Authorization" = String, Header, description = "Bearer <JWT Token>")
+    ),
+    responses(
+        (status = 200, description = "Reaction added (or already present); returns the message's reactions", body = [ReactionSummary]),
+        (status = 400, description = "Not a valid emoji", body = ErrorResponse),
+        (status = 401, description = "User ID missing in token", body = ErrorResponse),
+        (status = 403, description = "User is not a member of the room, is muted in it, or the room is archived", body = ErrorResponse),
+        (status = 404, description = "Message not found or deleted", body = ErrorResponse),
+        (status = 500, description = "Failed to add reaction", body = ErrorResponse)
+    )
+)]
+pub async fn add_reaction(
+    pool: web::Data<SqlitePool>,
+    room_server: web::Data<Addr<RoomServer>>,
+    path: web::Path<(i64, i64, String)>,
+    req: HttpRequest,
+) -> impl Responder {
+    let (room_id, message_id, emoji) = path.into_inner();
+    let user_id = match req.extensions().get::<i64>().copied() {
+        Some(user_id) => user_id,
+        None => {
+            return HttpResponse::Unauthorized().json(ErrorResponse {
+                error: "User ID missing in token".into(),
+            })
+        }
+    };
+
+    if !is_valid_emoji(&emoji) {
+        return HttpResponse::BadRequest().json(ErrorResponse {
+            error: "Reactions must be a single emoji".into(),
+        });
+    }
+    if let Err(e) = require_permission(pool.get_ref(), room_id, user_id, Permission::PostMessages).await {
+        return e.to_response();
+    }
+    match mute_remaining(pool.get_ref(), room_id, user_id).await {
+        Ok(None) => {}
+        Ok(Some(remaining)) => return muted_response(remaining),
+        Err(e) => {
+            error!("Failed to check mute of user {} in room {}: {}", user_id, room_id, e);
+            return HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to add reaction".into(),
+            });
+        }
+    }
+
+    match fetch_message(pool.get_ref(), room_id, message_id).await {
+        Ok(Some(message)) if message.deleted_at.is_none() => {}
+        Ok(_) => {
+            return HttpResponse::NotFound().json(ErrorResponse {
+                error: "Message not found".into(),
+            })
+        }
+        Err(e) => {
+            error!("Failed to load message {}: {}", message_id, e);
+            return HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to add reaction".into(),
+            });
+        }
+    }
+
+    let username = match sqlx::query_scalar!("SELECT username FROM users WHERE user_id = ?", user_id)
+        .fetch_one(pool.get_ref())
+        .await
+    {
+        Ok(username) => username,
+        Err(e) => {
+            error!("Failed to load username of user {}: {}", user_id, e);
+            return HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to add reaction".into(),
+            });
+        }
+    };
+
+    let inserted = sqlx::query!(
+        "INSERT OR IGNORE INTO reactions (message_id, user_id, emoji) VALUES (?, ?, ?)",
+        message_id,
+        user_id,
+        emoji
+    )
+    .execute(pool.get_ref())
+    .await;
+
+    match inserted {
+        // Only tell the room when the reaction is new, so repeated requests stay silent
+        Ok(result) if result.rows_affected() > 0 => {
+            info!("User {} reacted to message {} with {}", user_id, message_id, emoji);
+            room_server.do_send(BroadcastMessage {
+                room_id,
+                event: ServerEvent::ReactionAdded {
+                    room_id,
+                    message_id,
+                    user_id,
+                    username,
+                    emoji,
+                },
+            });
+        }
+        Ok(_) => {}
+        Err(e) => {
+            error!("Failed to add reaction to message {}: {}", message_id, e);
+            return HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to add reaction".into(),
+            });
+        }
+    }
+
+    reactions_response(pool.get_ref(), room_id, message_id).await
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/rooms/{room_id}/messages/{message_id}/reactions/{emoji}",
+    params(
+        ("room_id" = i64, Path, description = "ID of the room"),
+        ("message_id" = i64, Path, description = "ID of the message"),
+        ("emoji" = String, Path, description = "The emoji, URL-encoded"),
+        ("Authorization" = String, Header, description = "Bearer <JWT Token>")
+    ),
+    responses(
+        (status = 200, description = "The user's reaction removed (if present); returns the message's reactions", body = [ReactionSummary]),
+        (status = 401, description = "User ID missing in token", body = ErrorResponse),
+        (status = 403, description = "User is not a member of the room, or the room is archived", body = ErrorResponse),
+        (status = 404, description = "Message not found", body = ErrorResponse),
+        (status = 500, description = "Failed to remove reaction", body = ErrorResponse)
+    )
+)]
+pub async fn remove_reaction(
+    pool: web::Data<SqlitePool>,
+    room_server: web::Data<Addr<RoomServer>>,
+    path: web::Path<(i64, i64, String)>,
+    req: HttpRequest,
+) -> impl Responder {
+    let (room_id, message_id, emoji) = path.into_inner();
+    let user_id = match req.extensions().get::<i64>().copied() {
+        Some(user_id) => user_id,
+        None => {
+            return HttpResponse::Unauthorized().json(ErrorResponse {
+                error: "User ID missing in token".into(),
+            })
+        }
+    };
+
+    if let Err(e) = require_permission(pool.get_ref(), room_id, user_id, Permission::PostMessages).await {
+        return e.to_response();
+    }
+
+    match fetch_message(pool.get_ref(), room_id, message_id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            return HttpResponse::NotFound().json(ErrorResponse {
+                error: "Message not found".into(),
+            })
+        }
+        Err(e) => {
+            error!("Failed to load message {}: {}", message_id, e);
+            return HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to remove reaction".into(),
+            });
+        }
+    }
+
+    let deleted = sqlx::query!(
+        "DELETE FROM reactions WHERE message_id = ? AND user_id = ? AND emoji = ?",
+        message_id,
+        user_id,
+        emoji
+    )
+    .execute(pool.get_ref())
+    .await;
+
+    match deleted {
+        Ok(result) if result.rows_affected() > 0 => {
+            let username = sqlx::query_scalar!("SELECT username FROM users WHERE user_id = ?", user_id)
+                .fetch_one(pool.get_ref())
+                .await
+                .unwrap_or_default();
+            info!("User {} removed reaction {} from message {}", user_id, emoji, message_id);
+            room_server.do_send(BroadcastMessage {
+                room_id,
+                event: ServerEvent::ReactionRemoved {
+                    room_id,
+                    message_id,
+                    user_id,
+                    username,
+                    emoji,
+                },
+            });
+        }
+        Ok(_) => {}
+        Err(e) => {
+            error!("Failed to remove reaction from message {}: {}", message_id, e);
+            return HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to remove reaction".into(),
+            });
+        }
+    }
+
+    reactions_response(pool.get_ref(), room_id, message_id).await
+}
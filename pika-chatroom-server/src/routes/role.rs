@@ -0,0 +1,346 @@
+use crate::models::response::ErrorResponse;
+use crate::models::role::{Permission, Role};
+use crate::routes::room::{fetch_room, Room, RoomMember};
+use crate::websockets::chat_session::{BroadcastMessage, RoomServer};
+use crate::websockets::protocol::ServerEvent;
+use actix::Addr;
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse, Responder};
+use log::{error, info};
+use serde::Deserialize;
+use sqlx::{Sqlite, SqlitePool, Transaction};
+use utoipa::ToSchema;
+
+// Why a user may not perform an action in a room
+pub(crate) enum AccessError {
+    NotMember,
+    Forbidden,              // The user's role lacks the permission
+    Archived,               // The room is archived and the action would change its content
+    Database(sqlx::Error),
+}
+
+impl From<sqlx::Error> for AccessError {
+    fn from(e: sqlx::Error) -> Self {
+        AccessError::Database(e)
+    }
+}
+
+impl AccessError {
+    // The HTTP response for a failed check
+    pub(crate) fn to_response(&self) -> HttpResponse {
+        match self {
+            AccessError::NotMember => HttpResponse::Forbidden().json(ErrorResponse {
+                error: "You are not a member of this room".into(),
+            }),
+            AccessError::Forbidden => HttpResponse::Forbidden().json(ErrorResponse {
+                error: "Your role in this room does not allow this".into(),
+            }),
+            AccessError::Archived => HttpResponse::Forbidden().json(ErrorResponse {
+                error: "This room is archived and read-only".into(),
+            }),
+            AccessError::Database(e) => {
+                error!("Failed to check room permissions: {}", e);
+                HttpResponse::InternalServerError().json(ErrorResponse {
+                    error: "Failed to check room permissions".into(),
+                })
+            }
+        }
+    }
+}
+
+// Loads the user's role in a room; `None` when they are not a member
+pub(crate) async fn member_role(pool: &SqlitePool, room_id: i64, user_id: i64) -> Result<Option<Role>, sqlx::Error> {
+    sqlx::query_scalar!(
+        "SELECT role as `role: Role` FROM user_rooms WHERE room_id = ? AND user_id = ?",
+        room_id,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+// The check run before every action in a room: the user must be a member whose role grants
+// the permission, and archived rooms refuse anything that changes their content. Returns the
+// role so callers can apply further rules, such as rank.
+pub(crate) async fn require_permission(
+    pool: &SqlitePool,
+    room_id: i64,
+    user_id: i64,
+    permission: Permission,
+) -> Result<Role, AccessError> {
+    let member = sqlx::query!(
+        "SELECT ur.role as `role: Role`, r.archived_at IS NOT NULL as `archived!: bool` FROM user_rooms ur \
+        INNER JOIN rooms r ON r.room_id = ur.room_id \
+        WHERE ur.room_id = ? AND ur.user_id = ?",
+        room_id,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    match member {
+        Some(member) if !member.role.can(permission) => Err(AccessError::Forbidden),
+        Some(member) if member.archived && permission.changes_content() => Err(AccessError::Archived),
+        Some(member) => Ok(member.role),
+        None => Err(AccessError::NotMember),
+    }
+}
+
+// Makes a member the owner of a room. The previous owner, if still a member, becomes an admin.
+pub(crate) async fn set_owner(
+    tx: &mut Transaction<'_, Sqlite>,
+    room_id: i64,
+    new_owner_id: i64,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE user_rooms SET role = 'admin' WHERE room_id = ? AND role = 'owner'",
+        room_id
+    )
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query!(
+        "UPDATE user_rooms SET role = 'owner' WHERE room_id = ? AND user_id = ?",
+        room_id,
+        new_owner_id
+    )
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query!("UPDATE rooms SET user_id = ? WHERE room_id = ?", new_owner_id, room_id)
+        .execute(&mut *tx)
+        .await?;
+    Ok(())
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct RoleRequest {
+    pub role: Role,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct TransferOwnershipRequest {
+    pub user_id: i64, // The member who becomes the owner
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/rooms/{room_id}/members/{user_id}/role",
+    request_body = RoleRequest,
+    params(
+        ("room_id" = i64, Path, description = "ID of the room"),
+        ("user_id" = i64, Path, description = "ID of the member to promote or demote"),
+        ("Authorization" = String, Header, description = "Bearer <JWT Token>")
+    ),
+    responses(
+        (status = 200, description = "Role changed; the room receives a role_changed event", body = RoomMember),
+        (status = 400, description = "The owner role cannot be assigned, or users cannot change their own role", body = ErrorResponse),
+        (status = 401, description = "User ID missing in token", body = ErrorResponse),
+        (status = 403, description = "Not allowed to manage roles, or the member or role ranks too high", body = ErrorResponse),
+        (status = 404, description = "Member not found", body = ErrorResponse),
+        (status = 500, description = "Failed to change role", body = ErrorResponse)
+    )
+)]
+pub async fn set_member_role(
+    pool: web::Data<SqlitePool>,
+    room_server: web::Data<Addr<RoomServer>>,
+    path: web::Path<(i64, i64)>,
+    body: web::Json<RoleRequest>,
+    req: HttpRequest,
+) -> impl Responder {
+    let (room_id, member_id) = path.into_inner();
+    let user_id = match req.extensions().get::<i64>().copied() {
+        Some(user_id) => user_id,
+        None => {
+            return HttpResponse::Unauthorized().json(ErrorResponse {
+                error: "User ID missing in token".into(),
+            })
+        }
+    };
+    let role = body.into_inner().role;
+
+    if role == Role::Owner {
+        return HttpResponse::BadRequest().json(ErrorResponse {
+            error: "The owner role cannot be assigned".into(),
+        });
+    }
+    if member_id == user_id {
+        return HttpResponse::BadRequest().json(ErrorResponse {
+            error: "You cannot change your own role".into(),
+        });
+    }
+
+    let actor_role = match require_permission(pool.get_ref(), room_id, user_id, Permission::ManageRoles).await {
+        Ok(role) => role,
+        Err(e) => return e.to_response(),
+    };
+
+    let member = match sqlx::query_as!(
+        RoomMember,
+        "SELECT u.user_id, u.username, u.avatar_url, ur.role as `role: Role`, ur.joined_at FROM users u \
+        INNER JOIN user_rooms ur ON u.user_id = ur.user_id \
+        WHERE ur.room_id = ? AND ur.user_id = ?",
+        room_id,
+        member_id
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    {
+        Ok(Some(member)) => member,
+        Ok(None) => {
+            return HttpResponse::NotFound().json(ErrorResponse {
+                error: "Member not found".into(),
+            })
+        }
+        Err(e) => {
+            error!("Failed to load member {} of room {}: {}", member_id, room_id, e);
+            return HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to change role".into(),
+            });
+        }
+    };
+
+    // Members can only be moved around below one's own rank, e.g. admins manage moderators and members
+    if !actor_role.outranks(member.role) || !actor_role.outranks(role) {
+        return HttpResponse::Forbidden().json(ErrorResponse {
+            error: "You can only assign roles below your own to members ranked below you".into(),
+        });
+    }
+
+    if let Err(e) = sqlx::query!(
+        "UPDATE user_rooms SET role = ? WHERE room_id = ? AND user_id = ?",
+        role,
+        room_id,
+        member_id
+    )
+    .execute(pool.get_ref())
+    .await
+    {
+        error!("Failed to change role of user {} in room {}: {}", member_id, room_id, e);
+        return HttpResponse::InternalServerError().json(ErrorResponse {
+            error: "Failed to change role".into(),
+        });
+    }
+
+    info!("User {} made user {} a {:?} of room {}", user_id, member_id, role, room_id);
+    room_server.do_send(BroadcastMessage {
+        room_id,
+        event: ServerEvent::RoleChanged {
+            room_id,
+            user_id: member_id,
+            username: member.username.clone(),
+            role,
+        },
+    });
+    HttpResponse::Ok().json(RoomMember { role, ..member })
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/rooms/{room_id}/owner",
+    request_body = TransferOwnershipRequest,
+    params(
+        ("room_id" = i64, Path, description = "ID of the room"),
+        ("Authorization" = String, Header, description = "Bearer <JWT Token>")
+    ),
+    responses(
+        (status = 200, description = "Ownership transferred and the previous owner made an admin; the room receives a role_changed event for each", body = Room),
+        (status = 400, description = "The owner tried to transfer the room to themselves", body = ErrorResponse),
+        (status = 401, description = "User ID missing in token", body = ErrorResponse),
+        (status = 403, description = "Only the owner can transfer the room", body = ErrorResponse),
+        (status = 404, description = "Member not found", body = ErrorResponse),
+        (status = 500, description = "Failed to transfer ownership", body = ErrorResponse)
+    )
+)]
+pub async fn transfer_ownership(
+    pool: web::Data<SqlitePool>,
+    room_server: web::Data<Addr<RoomServer>>,
+    path: web::Path<i64>,
+    body: web::Json<TransferOwnershipRequest>,
+    req: HttpRequest,
+) -> impl Responder {
+    let room_id = path.into_inner();
+    let user_id = match req.extensions().get::<i64>().copied() {
+        Some(user_id) => user_id,
+        None => {
+            return HttpResponse::Unauthorized().json(ErrorResponse {
+                error: "User ID missing in token".into(),
+            })
+        }
+    };
+    let new_owner_id = body.user_id;
+
+    if new_owner_id == user_id {
+        return HttpResponse::BadRequest().json(ErrorResponse {
+            error: "You already own this room".into(),
+        });
+    }
+    if let Err(e) = require_permission(pool.get_ref(), room_id, user_id, Permission::TransferOwnership).await {
+        return e.to_response();
+    }
+
+    let members = match sqlx::query!(
+        "SELECT u.user_id as `user_id!: i64`, u.username FROM users u \
+        INNER JOIN user_rooms ur ON u.user_id = ur.user_id \
+        WHERE ur.room_id = ? AND ur.user_id IN (?, ?)",
+        room_id,
+        user_id,
+        new_owner_id
+    )
+    .fetch_all(pool.get_ref())
+    .await
+    {
+        Ok(members) => members,
+        Err(e) => {
+            error!("Failed to load members of room {}: {}", room_id, e);
+            return HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to transfer ownership".into(),
+            });
+        }
+    };
+    let username_of = |id: i64| members.iter().find(|member| member.user_id == id).map(|member| member.username.clone());
+    let (previous_owner, new_owner) = match (username_of(user_id), username_of(new_owner_id)) {
+        (Some(previous_owner), Some(new_owner)) => (previous_owner, new_owner),
+        _ => {
+            return HttpResponse::NotFound().json(ErrorResponse {
+                error: "Member not found".into(),
+            })
+        }
+    };
+
+    let result: Result<Option<Room>, sqlx::Error> = async {
+        let mut tx = pool.begin().await?;
+        set_owner(&mut tx, room_id, new_owner_id).await?;
+        tx.commit().await?;
+        fetch_room(pool.get_ref(), room_id).await
+    }
+    .await;
+    let room = match result {
+        Ok(Some(room)) => room,
+        Ok(None) => {
+            return HttpResponse::NotFound().json(ErrorResponse {
+                error: "Room not found".into(),
+            })
+        }
+        Err(e) => {
+            error!("Failed to transfer room {} to user {}: {}", room_id, new_owner_id, e);
+            return HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to transfer ownership".into(),
+            });
+        }
+    };
+
+    info!("User {} transferred room {} to user {}", user_id, room_id, new_owner_id);
+    for (member_id, username, role) in [
+        (user_id, previous_owner, Role::Admin),
+        (new_owner_id, new_owner, Role::Owner),
+    ] {
+        room_server.do_send(BroadcastMessage {
+            room_id,
+            event: ServerEvent::RoleChanged {
+                room_id,
+                user_id: member_id,
+                username,
+                role,
+            },
+        });
+    }
+    HttpResponse::Ok().json(room)
+}
@@ -1,9 +1,17 @@
-use crate::models::presence::{GetRoomPresence, UserPresence};
+use crate::middleware::auth_middleware::{authenticate_token, bearer_token};
+use crate::models::presence::{GetOnlineCounts, GetRoomPresence, GetUsersPresence, UserPresence};
 use crate::models::response::{ErrorResponse, MessageResponse};
-use crate::websockets::chat_session::{ChatSession, RoomServer};
+use crate::models::role::{Permission, Role};
+use crate::models::room::{RoomKind, RoomVisibility};
+use crate::routes::message::TIMESTAMP_FORMAT;
+use crate::routes::moderation::is_banned;
+use crate::routes::role::{member_role, require_permission};
+use crate::websockets::chat_session::{BroadcastMessage, ChatSession, Disconnect, RoomServer};
+use crate::websockets::protocol::ServerEvent;
 use actix::Addr;
 use actix_web::{web, HttpMessage, HttpRequest, HttpResponse, Responder};
 use actix_web_actors::ws;
+use chrono::Utc;
 use log::{error, info};
 use serde::{Deserialize, Serialize};
 use sqlx::SqlitePool;
@@ -12,6 +20,8 @@ use utoipa::ToSchema;
 #[derive(Deserialize, ToSchema)]
 pub struct RoomInfo {
     pub room_name: String,
+    #[serde(default)]
+    pub visibility: RoomVisibility, // Public unless specified
 }
 
 #[derive(Serialize, ToSchema)]
@@ -19,12 +29,82 @@ pub struct Room {
     pub room_id: i64,
     pub room_name: String,
     pub user_id: i64, // The owner's user ID
+    pub visibility: RoomVisibility,
+    pub kind: RoomKind,
+    pub topic: Option<String>,
+    pub description: Option<String>,
+    pub archived_at: Option<String>, // Set while the room is archived and read-only
+    pub slow_mode_secs: i64,         // Seconds each member must wait between messages; 0 when slow mode is off
+}
+
+/// A room as listed in the room directory
+#[derive(Serialize, ToSchema)]
+pub struct RoomListing {
+    #[serde(flatten)]
+    pub room: Room,
+    pub member_count: i64,
+    pub online_count: i64,        // Users connected to the room right now
+    pub joined: bool,             // Whether the requesting user is a member
+    pub last_activity_at: String, // The latest message, or when the room was created if it has none (UTC)
 }
 
 #[derive(Serialize, ToSchema)]
 pub struct RoomsResponse {
-    pub req_user_id: i64, // The ID of the current user making the request
-    pub rooms: Vec<Room>, // List of rooms with each room's owner's user_id
+    pub req_user_id: i64,            // The ID of the current user making the request
+    pub rooms: Vec<RoomListing>,     // List of rooms with each room's owner's user_id
+    pub next_cursor: Option<String>, // Pass as `cursor` to get the next page; absent on the last page
+}
+
+/// Order of the room directory
+#[derive(Deserialize, Debug, Clone, Copy, Default, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RoomSort {
+    #[default]
+    Created,  // Newest rooms first
+    Activity, // Rooms with the most recent messages first
+    Members,  // Rooms with the most members first
+}
+
+impl RoomSort {
+    fn as_str(self) -> &'static str {
+        match self {
+            RoomSort::Created => "created",
+            RoomSort::Activity => "activity",
+            RoomSort::Members => "members",
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct RoomsQuery {
+    pub q: Option<String>,      // Only return rooms whose name contains this, ignoring case
+    pub sort: Option<RoomSort>,
+    pub cursor: Option<String>, // The `next_cursor` of the previous page
+    pub limit: Option<i64>,
+}
+
+// Number of rooms returned when the client does not ask for a specific page size
+const DEFAULT_ROOMS_PAGE_SIZE: i64 = 50;
+// Upper bound on the room page size
+const MAX_ROOMS_PAGE_SIZE: i64 = 100;
+
+// Direct messages are named after their participants with this prefix, which channels cannot use
+pub(crate) const DM_NAME_PREFIX: &str = "dm:";
+
+// Longest room topic and description accepted, in characters
+const MAX_TOPIC_LENGTH: usize = 200;
+const MAX_DESCRIPTION_LENGTH: usize = 2000;
+
+// Loads a room by ID
+pub(crate) async fn fetch_room(pool: &SqlitePool, room_id: i64) -> Result<Option<Room>, sqlx::Error> {
+    sqlx::query_as!(
+        Room,
+        "SELECT room_id as `room_id!: i64`, room_name, user_id as `user_id: i64`, visibility as `visibility: RoomVisibility`, \
+        kind as `kind: RoomKind`, topic, description, archived_at, slow_mode_secs FROM rooms WHERE room_id = ?",
+        room_id
+    )
+    .fetch_optional(pool)
+    .await
 }
 
 #[derive(Serialize, ToSchema)]
@@ -32,55 +112,204 @@ pub struct RoomMember {
     pub user_id: i64,
     pub username: String,
     pub avatar_url: Option<String>, // Include avatar URL
+    pub role: Role,
+    pub joined_at: Option<String>,  // UTC
+}
+
+// Number of members returned when the client does not ask for a specific page size
+const DEFAULT_MEMBERS_PAGE_SIZE: i64 = 50;
+// Upper bound on the member page size
+const MAX_MEMBERS_PAGE_SIZE: i64 = 100;
+
+#[derive(Deserialize)]
+pub struct RoomMembersQuery {
+    pub after: Option<String>, // Only return members whose username sorts after this one
+    pub q: Option<String>,     // Only return members whose username contains this, ignoring case
+    pub limit: Option<i64>,
+}
+
+/// A member as listed by `GET /api/rooms/{room_id}/members`, with their current presence
+#[derive(Serialize, ToSchema)]
+pub struct MemberListing {
+    #[serde(flatten)]
+    pub member: RoomMember,
+    pub is_online: bool, // Has at least one open connection anywhere
+    pub in_room: bool,   // Has at least one open connection to this room
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct RoomMembersResponse {
+    pub members: Vec<MemberListing>, // Ordered by username
+    pub next_cursor: Option<String>, // Pass as `after` to get the next page; absent on the last page
 }
 
 #[utoipa::path(
     get,
     path = "/api/rooms",
     responses(
-        (status = 200, description = "List of rooms with user ID", body = RoomsResponse),
+        (status = 200, description = "A page of the room directory: public rooms, plus the unlisted and private rooms the user belongs to or is invited to, with member and online counts; direct messages are listed by GET /api/dms instead", body = RoomsResponse),
+        (status = 400, description = "The cursor is not one returned by an earlier page", body = ErrorResponse),
         (status = 401, description = "User ID missing in token", body = ErrorResponse),
         (status = 500, description = "Failed to retrieve rooms", body = ErrorResponse)
     ),
     params(
+        ("q" = Option<String>, Query, description = "Only return rooms whose name contains this text, ignoring case"),
+        ("sort" = Option<RoomSort>, Query, description = "Newest rooms (created, the default), most recently active rooms (activity) or largest rooms (members) first"),
+        ("cursor" = Option<String>, Query, description = "Continue after the page that returned this next_cursor, with the same q and sort"),
+        ("limit" = Option<i64>, Query, description = "Maximum number of rooms to return (default 50, max 100)"),
         ("Authorization" = String, Header, description = "Bearer <JWT Token>")
     )
 )]
-pub async fn get_rooms(pool: web::Data<SqlitePool>, req: HttpRequest) -> impl Responder {
-    if let Some(user_id) = req.extensions().get::<i64>() {
-        match sqlx::query_as!(
-            Room,
-            "SELECT room_id as `room_id: i64`, room_name, user_id as `user_id: i64` FROM rooms"
-        )
-        .fetch_all(pool.get_ref())
+pub async fn get_rooms(
+    pool: web::Data<SqlitePool>,
+    room_server: web::Data<Addr<RoomServer>>,
+    query: web::Query<RoomsQuery>,
+    req: HttpRequest,
+) -> impl Responder {
+    let user_id = match req.extensions().get::<i64>().copied() {
+        Some(user_id) => user_id,
+        None => {
+            return HttpResponse::Unauthorized().json(ErrorResponse {
+                error: "User ID missing in token".into(),
+            })
+        }
+    };
+
+    let limit = query.limit.unwrap_or(DEFAULT_ROOMS_PAGE_SIZE).clamp(1, MAX_ROOMS_PAGE_SIZE);
+    // Fetch one extra row to find out whether another page exists
+    let fetch_limit = limit + 1;
+    let filter = query.q.as_deref().map(str::trim).filter(|q| !q.is_empty());
+    let sort = query.sort.unwrap_or_default().as_str();
+
+    // Cursors are the sort key and room ID of the last room on the previous page
+    let (cursor_key, cursor_room_id) = match query.cursor.as_deref().map(|cursor| cursor.rsplit_once('|')) {
+        None => (None, None),
+        Some(Some((key, room_id))) => match room_id.parse::<i64>() {
+            Ok(room_id) => (Some(key), Some(room_id)),
+            Err(_) => return invalid_cursor(),
+        },
+        Some(None) => return invalid_cursor(),
+    };
+
+    // Unlisted and private rooms stay out of the listing for everyone else. Every sort order is
+    // descending on a text key, with member counts zero-padded so that they compare as numbers,
+    // and ties broken by room ID. Unlike the other queries, this one is built at runtime and is not
+    // checked at compile time; the tests at the end of this file run it against RoomRow.
+    let rows = match sqlx::query_as::<_, RoomRow>(
+        "WITH listed AS ( \
+            SELECT r.room_id, r.room_name, r.user_id, r.visibility, r.kind, r.topic, r.description, r.archived_at, \
+            r.slow_mode_secs, COALESCE(r.created_at, '') as created_at, \
+            (SELECT COUNT(*) FROM user_rooms ur WHERE ur.room_id = r.room_id) as member_count, \
+            COALESCE((SELECT MAX(m.created_at) FROM messages m WHERE m.room_id = r.room_id), r.created_at, '') as last_activity_at, \
+            EXISTS (SELECT 1 FROM user_rooms ur WHERE ur.room_id = r.room_id AND ur.user_id = ?1) as joined \
+            FROM rooms r \
+            WHERE r.kind = 'channel' AND (r.visibility = 'public' \
+                OR EXISTS (SELECT 1 FROM user_rooms ur WHERE ur.room_id = r.room_id AND ur.user_id = ?1) \
+                OR EXISTS (SELECT 1 FROM room_invitations ri WHERE ri.room_id = r.room_id AND ri.user_id = ?1)) \
+            AND (?2 IS NULL OR instr(lower(r.room_name), lower(?2)) > 0) \
+        ), \
+        keyed AS ( \
+            SELECT *, CASE ?3 WHEN 'members' THEN printf('%010d', member_count) WHEN 'activity' THEN last_activity_at \
+            ELSE created_at END as sort_key FROM listed \
+        ) \
+        SELECT room_id, room_name, user_id, visibility, kind, topic, description, archived_at, slow_mode_secs, \
+        member_count, last_activity_at, joined, sort_key FROM keyed \
+        WHERE ?4 IS NULL OR sort_key < ?4 OR (sort_key = ?4 AND room_id < ?5) \
+        ORDER BY sort_key DESC, room_id DESC LIMIT ?6",
+    )
+    .bind(user_id)
+    .bind(filter)
+    .bind(sort)
+    .bind(cursor_key)
+    .bind(cursor_room_id)
+    .bind(fetch_limit)
+    .fetch_all(pool.get_ref())
+    .await
+    {
+        Ok(rows) => rows,
+        Err(e) => {
+            error!("Failed to retrieve rooms: {}", e);
+            return HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to retrieve rooms".into(),
+            });
+        }
+    };
+
+    let has_more = rows.len() as i64 > limit;
+    let rows = &rows[..rows.len().min(limit as usize)];
+    let next_cursor = rows
+        .last()
+        .filter(|_| has_more)
+        .map(|row| format!("{}|{}", row.sort_key, row.room_id));
+
+    // Online counts live in the RoomServer, not the database
+    let online_counts = match room_server
+        .send(GetOnlineCounts {
+            room_ids: rows.iter().map(|row| row.room_id).collect(),
+        })
         .await
-        {
-            Ok(rooms) => {
-                info!("Retrieved {} rooms from the database", rooms.len());
-                for room in &rooms {
-                    info!(
-                        "Room ID: {}, Room Name: {}, User ID: {}",
-                        room.room_id, room.room_name, room.user_id
-                    );
-                }
-                HttpResponse::Ok().json(RoomsResponse {
-                    req_user_id: *user_id,
-                    rooms,
-                })
-            }
-            Err(e) => {
-                info!("Failed to retrieve rooms: {}", e);
-                // A failure to retrieve rooms typically indicates a server-side problem, such as a database connectivity issue.
-                HttpResponse::InternalServerError().json(ErrorResponse {
-                    error: "Failed to retrieve rooms".into(),
-                })
-            }
+    {
+        Ok(online_counts) => online_counts,
+        Err(e) => {
+            error!("Failed to retrieve online counts: {}", e);
+            return HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to retrieve rooms".into(),
+            });
         }
-    } else {
-        HttpResponse::Unauthorized().json(ErrorResponse {
-            error: "User ID missing in token".into(),
+    };
+
+    let rooms: Vec<RoomListing> = rows
+        .iter()
+        .zip(online_counts)
+        .map(|(row, online_count)| RoomListing {
+            room: Room {
+                room_id: row.room_id,
+                room_name: row.room_name.clone(),
+                user_id: row.user_id,
+                visibility: row.visibility,
+                kind: row.kind,
+                topic: row.topic.clone(),
+                description: row.description.clone(),
+                archived_at: row.archived_at.clone(),
+                slow_mode_secs: row.slow_mode_secs,
+            },
+            member_count: row.member_count,
+            online_count: online_count as i64,
+            joined: row.joined,
+            last_activity_at: row.last_activity_at.clone(),
         })
-    }
+        .collect();
+    info!("Retrieved {} rooms for user {}", rooms.len(), user_id);
+    HttpResponse::Ok().json(RoomsResponse {
+        req_user_id: user_id,
+        rooms,
+        next_cursor,
+    })
+}
+
+// A row of the room directory query in get_rooms
+#[derive(sqlx::FromRow)]
+struct RoomRow {
+    room_id: i64,
+    room_name: String,
+    user_id: i64,
+    visibility: RoomVisibility,
+    kind: RoomKind,
+    topic: Option<String>,
+    description: Option<String>,
+    archived_at: Option<String>,
+    slow_mode_secs: i64,
+    member_count: i64,
+    last_activity_at: String,
+    joined: bool,
+    sort_key: String,
+}
+
+// The response for a cursor that no page could have returned
+fn invalid_cursor() -> HttpResponse {
+    HttpResponse::BadRequest().json(ErrorResponse {
+        error: "Invalid cursor".into(),
+    })
 }
 
 #[utoipa::path(
@@ -89,7 +318,7 @@ pub async fn get_rooms(pool: web::Data<SqlitePool>, req: HttpRequest) -> impl Re
     request_body = RoomInfo,
     responses(
         (status = 201, description = "Room created successfully", body = Room),
-        (status = 400, description = "Error creating room", body = ErrorResponse),
+        (status = 400, description = "Error creating room, or the name is reserved for direct messages", body = ErrorResponse),
         (status = 401, description = "User ID missing in token", body = ErrorResponse)
     ),
     params(
@@ -102,24 +331,53 @@ pub async fn create_room(
     req: HttpRequest,
 ) -> impl Responder {
     info!("Before Starting create_room function");
-    if let Some(user_id) = req.extensions().get::<i64>() {
-        match sqlx::query!(
-            "INSERT INTO rooms (room_name, user_id) VALUES (?, ?)",
-            room_info.room_name,
-            user_id
-        )
-        .execute(pool.get_ref())
-        .await
-        {
-            Ok(result) => {
+    if room_info.room_name.starts_with(DM_NAME_PREFIX) {
+        return HttpResponse::BadRequest().json(ErrorResponse {
+            error: format!("Room names cannot start with '{}'", DM_NAME_PREFIX),
+        });
+    }
+    let user_id = req.extensions().get::<i64>().copied();
+    if let Some(user_id) = user_id {
+        // The creator joins their new room as its owner
+        let result: Result<i64, sqlx::Error> = async {
+            let mut tx = pool.begin().await?;
+            let room_id = sqlx::query!(
+                "INSERT INTO rooms (room_name, user_id, visibility) VALUES (?, ?, ?)",
+                room_info.room_name,
+                user_id,
+                room_info.visibility
+            )
+            .execute(&mut tx)
+            .await?
+            .last_insert_rowid();
+            sqlx::query!(
+                "INSERT INTO user_rooms (user_id, room_id, role) VALUES (?, ?, 'owner')",
+                user_id,
+                room_id
+            )
+            .execute(&mut tx)
+            .await?;
+            tx.commit().await?;
+            Ok(room_id)
+        }
+        .await;
+
+        match result {
+            Ok(room_id) => {
                 info!(
                     "Room '{}' created successfully by user '{}'",
                     room_info.room_name, user_id
                 );
                 HttpResponse::Created().json(Room {
-                    room_id: result.last_insert_rowid(),
+                    room_id,
                     room_name: room_info.room_name.clone(),
-                    user_id: *user_id,
+                    user_id,
+                    visibility: room_info.visibility,
+                    kind: RoomKind::Channel,
+                    topic: None,
+                    description: None,
+                    archived_at: None,
+                    slow_mode_secs: 0,
                 })
             }
             Err(e) => {
@@ -141,57 +399,186 @@ pub async fn create_room(
     path = "/api/rooms/{room_id}/members",
     params(
         ("room_id" = i64, Path, description = "ID of the room"),
+        ("after" = Option<String>, Query, description = "Return members whose username sorts after this one, as given by next_cursor"),
+        ("q" = Option<String>, Query, description = "Only return members whose username contains this text, ignoring case"),
+        ("limit" = Option<i64>, Query, description = "Maximum number of members to return (default 50, max 100)"),
         ("Authorization" = String, Header, description = "Bearer <JWT Token>")
     ),
     responses(
-        (status = 200, description = "List of room members", body = [RoomMember]),
-        (status = 404, description = "Room not found", body = ErrorResponse),
+        (status = 200, description = "A page of the room's members, ordered by username, with their roles, join dates and presence", body = RoomMembersResponse),
+        (status = 401, description = "User ID missing in token", body = ErrorResponse),
+        (status = 403, description = "User is not a member of the room, or the room does not exist", body = ErrorResponse),
         (status = 500, description = "Failed to retrieve room members", body = ErrorResponse)
     )
 )]
-
 pub async fn get_room_members(
     pool: web::Data<SqlitePool>,
+    room_server: web::Data<Addr<RoomServer>>,
     path: web::Path<i64>,
-    _req: HttpRequest,
+    query: web::Query<RoomMembersQuery>,
+    req: HttpRequest,
 ) -> impl Responder {
     let room_id = path.into_inner();
+    let user_id = match req.extensions().get::<i64>().copied() {
+        Some(user_id) => user_id,
+        None => {
+            return HttpResponse::Unauthorized().json(ErrorResponse {
+                error: "User ID missing in token".into(),
+            })
+        }
+    };
 
-    // Check if the room exists
-    let room_exists = sqlx::query!(
-        "SELECT COUNT(*) as count FROM rooms WHERE room_id = ?",
-        room_id
-    )
-    .fetch_one(pool.get_ref())
-    .await
-    .map(|row| row.count > 0)
-    .unwrap_or(false);
-
-    if !room_exists {
-        return HttpResponse::NotFound().json(ErrorResponse {
-            error: "Room not found".into(),
-        });
+    // Only members may list a room's members. Checking this first keeps private rooms from
+    // revealing that they exist.
+    if let Err(e) = require_permission(pool.get_ref(), room_id, user_id, Permission::ViewRoom).await {
+        return e.to_response();
     }
 
-    // Fetch members of the room with avatar_url
-    match sqlx::query_as!(
+    let limit = query.limit.unwrap_or(DEFAULT_MEMBERS_PAGE_SIZE).clamp(1, MAX_MEMBERS_PAGE_SIZE);
+    // Fetch one extra row to find out whether another page exists
+    let fetch_limit = limit + 1;
+    let filter = query.q.as_deref().map(str::trim).filter(|q| !q.is_empty());
+
+    let mut members = match sqlx::query_as!(
         RoomMember,
-        "SELECT u.user_id, u.username, u.avatar_url FROM users u \
+        "SELECT u.user_id as `user_id!: i64`, u.username as `username!`, u.avatar_url, ur.role as `role!: Role`, \
+        ur.joined_at FROM users u \
         INNER JOIN user_rooms ur ON u.user_id = ur.user_id \
-        WHERE ur.room_id = ?",
-        room_id
+        WHERE ur.room_id = ? AND (? IS NULL OR u.username > ?) \
+        AND (? IS NULL OR instr(lower(u.username), lower(?)) > 0) \
+        ORDER BY u.username LIMIT ?",
+        room_id,
+        query.after,
+        query.after,
+        filter,
+        filter,
+        fetch_limit
     )
     .fetch_all(pool.get_ref())
     .await
     {
-        Ok(members) => HttpResponse::Ok().json(members),
+        Ok(members) => members,
         Err(e) => {
             error!("Failed to retrieve room members: {}", e);
-            HttpResponse::InternalServerError().json(ErrorResponse { error: "Failed to retrieve room members".into() })
+            return HttpResponse::InternalServerError().json(ErrorResponse { error: "Failed to retrieve room members".into() });
+        }
+    };
+
+    let next_cursor = if members.len() as i64 > limit {
+        members.truncate(limit as usize);
+        members.last().map(|member| member.username.clone())
+    } else {
+        None
+    };
+
+    // Online status lives in the RoomServer, not the database
+    let presence = match room_server
+        .send(GetUsersPresence {
+            room_id,
+            user_ids: members.iter().map(|member| member.user_id).collect(),
+        })
+        .await
+    {
+        Ok(presence) => presence,
+        Err(e) => {
+            error!("Failed to retrieve presence of room {}: {}", room_id, e);
+            return HttpResponse::InternalServerError().json(ErrorResponse { error: "Failed to retrieve room members".into() });
+        }
+    };
+
+    let members = members
+        .into_iter()
+        .zip(presence)
+        .map(|(member, presence)| MemberListing {
+            member,
+            is_online: presence.is_online,
+            in_room: presence.in_room,
+        })
+        .collect();
+    HttpResponse::Ok().json(RoomMembersResponse { members, next_cursor })
+}
+
+// Why a user could not join a room
+pub(crate) enum JoinError {
+    RoomNotFound,
+    NotInvited,             // The room is private and the user has no invitation
+    Banned,                 // The user is banned from the room
+    Database(sqlx::Error),
+}
+
+impl From<sqlx::Error> for JoinError {
+    fn from(e: sqlx::Error) -> Self {
+        JoinError::Database(e)
+    }
+}
+
+impl JoinError {
+    pub(crate) fn to_response(&self) -> HttpResponse {
+        match self {
+            JoinError::RoomNotFound => HttpResponse::NotFound().json(ErrorResponse {
+                error: "Room does not exist".into(),
+            }),
+            JoinError::NotInvited => HttpResponse::Forbidden().json(ErrorResponse {
+                error: "This room is private; you need an invitation to join".into(),
+            }),
+            JoinError::Banned => HttpResponse::Forbidden().json(ErrorResponse {
+                error: "You are banned from this room".into(),
+            }),
+            JoinError::Database(e) => {
+                error!("Failed to add user to room: {}", e);
+                HttpResponse::InternalServerError().json(ErrorResponse {
+                    error: "Error adding user to room".into(),
+                })
+            }
         }
     }
 }
 
+// Makes the user a member of the room if the room admits them: public and unlisted rooms admit
+// everyone, private rooms only users with an invitation, and no room admits users it has banned.
+// Joining uses up the invitation.
+// Returns whether the user was added, or `false` if they were already a member.
+pub(crate) async fn join_room(pool: &SqlitePool, room_id: i64, user_id: i64) -> Result<bool, JoinError> {
+    let visibility = sqlx::query_scalar!(
+        "SELECT visibility as `visibility: RoomVisibility` FROM rooms WHERE room_id = ?",
+        room_id
+    )
+    .fetch_optional(pool)
+    .await?
+    .ok_or(JoinError::RoomNotFound)?;
+
+    if member_role(pool, room_id, user_id).await?.is_some() {
+        return Ok(false);
+    }
+    if is_banned(pool, room_id, user_id).await? {
+        return Err(JoinError::Banned);
+    }
+
+    let mut tx = pool.begin().await?;
+    let invited = sqlx::query!(
+        "DELETE FROM room_invitations WHERE room_id = ? AND user_id = ?",
+        room_id,
+        user_id
+    )
+    .execute(&mut tx)
+    .await?
+    .rows_affected()
+        > 0;
+    if visibility == RoomVisibility::Private && !invited {
+        return Err(JoinError::NotInvited);
+    }
+
+    sqlx::query!(
+        "INSERT INTO user_rooms (user_id, room_id) VALUES (?, ?)",
+        user_id,
+        room_id
+    )
+    .execute(&mut tx)
+    .await?;
+    tx.commit().await?;
+    Ok(true)
+}
+
 #[utoipa::path(
     post,
     path = "/api/rooms/{room_id}/members",
@@ -201,7 +588,8 @@ pub async fn get_room_members(
     ),
     responses(
         (status = 200, description = "User added to the room successfully", body = MessageResponse),
-        (status = 400, description = "Bad request: Error adding user to room", body = ErrorResponse),
+        (status = 400, description = "Bad request: User is already a member", body = ErrorResponse),
+        (status = 403, description = "Forbidden: The room is private and the user has not been invited, or the user is banned", body = ErrorResponse),
         (status = 404, description = "Not Found: Room does not exist", body = ErrorResponse),
         (status = 401, description = "Unauthorized: User ID missing in token", body = ErrorResponse)
     )
@@ -213,42 +601,19 @@ pub async fn add_room_member(
 ) -> impl Responder {
     let room_id = path.into_inner();
 
-    if let Some(user_id) = req.extensions().get::<i64>() {
-        let room_exists = sqlx::query!(
-            "SELECT 1 AS exists_flag FROM rooms WHERE room_id = ?",
-            room_id
-        )
-        .fetch_optional(pool.get_ref())
-        .await
-        .map(|row| row.is_some())
-        .unwrap_or(false);
-
-        if !room_exists {
-            return HttpResponse::NotFound().json(ErrorResponse {
-                error: "Room does not exist".into(),
-            });
-        }
-
-        match sqlx::query!(
-            "INSERT INTO user_rooms (user_id, room_id) VALUES (?, ?)",
-            user_id,
-            room_id
-        )
-        .execute(pool.get_ref())
-        .await
-        {
-            Ok(_) => {
+    let user_id = req.extensions().get::<i64>().copied();
+    if let Some(user_id) = user_id {
+        match join_room(pool.get_ref(), room_id, user_id).await {
+            Ok(true) => {
                 info!("User '{}' added to room '{}'", user_id, room_id);
                 HttpResponse::Ok().json(MessageResponse {
                     message: "User added to the room successfully".into(),
                 })
             }
-            Err(e) => {
-                info!("Failed to add user to room: {}", e);
-                HttpResponse::BadRequest().json(ErrorResponse {
-                    error: "Error adding user to room".into(),
-                })
-            }
+            Ok(false) => HttpResponse::BadRequest().json(ErrorResponse {
+                error: "You are already a member of this room".into(),
+            }),
+            Err(e) => e.to_response(),
         }
     } else {
         HttpResponse::Unauthorized().json(ErrorResponse {
@@ -257,9 +622,385 @@ pub async fn add_room_member(
     }
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/rooms/{room_id}",
+    params(
+        ("room_id" = i64, Path, description = "ID of the room"),
+        ("Authorization" = String, Header, description = "Bearer <JWT Token>")
+    ),
+    responses(
+        (status = 200, description = "The room with its topic, description and archived state", body = Room),
+        (status = 401, description = "User ID missing in token", body = ErrorResponse),
+        (status = 403, description = "User is not a member of the room", body = ErrorResponse),
+        (status = 500, description = "Failed to retrieve room", body = ErrorResponse)
+    )
+)]
+pub async fn get_room(
+    pool: web::Data<SqlitePool>,
+    path: web::Path<i64>,
+    req: HttpRequest,
+) -> impl Responder {
+    let room_id = path.into_inner();
+    let user_id = match req.extensions().get::<i64>().copied() {
+        Some(user_id) => user_id,
+        None => {
+            return HttpResponse::Unauthorized().json(ErrorResponse {
+                error: "User ID missing in token".into(),
+            })
+        }
+    };
+
+    if let Err(e) = require_permission(pool.get_ref(), room_id, user_id, Permission::ViewRoom).await {
+        return e.to_response();
+    }
+
+    match fetch_room(pool.get_ref(), room_id).await {
+        Ok(Some(room)) => HttpResponse::Ok().json(room),
+        Ok(None) => HttpResponse::NotFound().json(ErrorResponse {
+            error: "Room not found".into(),
+        }),
+        Err(e) => {
+            error!("Failed to retrieve room {}: {}", room_id, e);
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to retrieve room".into(),
+            })
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct UpdateRoomRequest {
+    pub room_name: Option<String>,
+    pub topic: Option<String>,       // An empty topic clears it
+    pub description: Option<String>, // An empty description clears it
+    pub archived: Option<bool>,      // Archived rooms are read-only until unarchived
+}
+
+// Whether a query failed on a UNIQUE constraint, such as the one on `rooms.room_name`
+pub(crate) fn is_unique_violation(e: &sqlx::Error) -> bool {
+    matches!(e, sqlx::Error::Database(db) if db.message().contains("UNIQUE constraint failed"))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/api/rooms/{room_id}",
+    request_body = UpdateRoomRequest,
+    params(
+        ("room_id" = i64, Path, description = "ID of the room"),
+        ("Authorization" = String, Header, description = "Bearer <JWT Token>")
+    ),
+    responses(
+        (status = 200, description = "Room updated; the room receives a room_updated event. Omitted fields are left unchanged", body = Room),
+        (status = 400, description = "The name is empty, taken or reserved for direct messages, or the topic or description is too long", body = ErrorResponse),
+        (status = 401, description = "User ID missing in token", body = ErrorResponse),
+        (status = 403, description = "Only admins and the owner can update the room", body = ErrorResponse),
+        (status = 404, description = "Room not found", body = ErrorResponse),
+        (status = 500, description = "Failed to update room", body = ErrorResponse)
+    )
+)]
+pub async fn update_room(
+    pool: web::Data<SqlitePool>,
+    room_server: web::Data<Addr<RoomServer>>,
+    path: web::Path<i64>,
+    body: web::Json<UpdateRoomRequest>,
+    req: HttpRequest,
+) -> impl Responder {
+    let room_id = path.into_inner();
+    let user_id = match req.extensions().get::<i64>().copied() {
+        Some(user_id) => user_id,
+        None => {
+            return HttpResponse::Unauthorized().json(ErrorResponse {
+                error: "User ID missing in token".into(),
+            })
+        }
+    };
+    let body = body.into_inner();
+
+    if body.room_name.as_ref().is_some_and(|name| name.trim().is_empty()) {
+        return HttpResponse::BadRequest().json(ErrorResponse {
+            error: "Room name cannot be empty".into(),
+        });
+    }
+    if body.room_name.as_ref().is_some_and(|name| name.trim().starts_with(DM_NAME_PREFIX)) {
+        return HttpResponse::BadRequest().json(ErrorResponse {
+            error: format!("Room names cannot start with '{}'", DM_NAME_PREFIX),
+        });
+    }
+    if body.topic.as_ref().is_some_and(|topic| topic.chars().count() > MAX_TOPIC_LENGTH) {
+        return HttpResponse::BadRequest().json(ErrorResponse {
+            error: format!("Topics can be at most {} characters long", MAX_TOPIC_LENGTH),
+        });
+    }
+    if body.description.as_ref().is_some_and(|description| description.chars().count() > MAX_DESCRIPTION_LENGTH) {
+        return HttpResponse::BadRequest().json(ErrorResponse {
+            error: format!("Descriptions can be at most {} characters long", MAX_DESCRIPTION_LENGTH),
+        });
+    }
+
+    if let Err(e) = require_permission(pool.get_ref(), room_id, user_id, Permission::ManageRoom).await {
+        return e.to_response();
+    }
+
+    let room = match fetch_room(pool.get_ref(), room_id).await {
+        Ok(Some(room)) => room,
+        Ok(None) => {
+            return HttpResponse::NotFound().json(ErrorResponse {
+                error: "Room not found".into(),
+            })
+        }
+        Err(e) => {
+            error!("Failed to load room {}: {}", room_id, e);
+            return HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to update room".into(),
+            });
+        }
+    };
+
+    // Apply the fields that were sent; empty topics and descriptions are stored as NULL
+    let non_empty = |value: String| Some(value.trim().to_string()).filter(|value| !value.is_empty());
+    let room = Room {
+        room_name: body.room_name.map(|name| name.trim().to_string()).unwrap_or(room.room_name),
+        topic: body.topic.map_or(room.topic, non_empty),
+        description: body.description.map_or(room.description, non_empty),
+        archived_at: match body.archived {
+            Some(true) => room.archived_at.or_else(|| Some(Utc::now().format(TIMESTAMP_FORMAT).to_string())),
+            Some(false) => None,
+            None => room.archived_at,
+        },
+        ..room
+    };
+
+    if let Err(e) = sqlx::query!(
+        "UPDATE rooms SET room_name = ?, topic = ?, description = ?, archived_at = ? WHERE room_id = ?",
+        room.room_name,
+        room.topic,
+        room.description,
+        room.archived_at,
+        room_id
+    )
+    .execute(pool.get_ref())
+    .await
+    {
+        if is_unique_violation(&e) {
+            return HttpResponse::BadRequest().json(ErrorResponse {
+                error: "A room with this name already exists".into(),
+            });
+        }
+        error!("Failed to update room {}: {}", room_id, e);
+        return HttpResponse::InternalServerError().json(ErrorResponse {
+            error: "Failed to update room".into(),
+        });
+    }
+
+    info!("User {} updated room {}", user_id, room_id);
+    room_server.do_send(BroadcastMessage {
+        room_id,
+        event: ServerEvent::RoomUpdated {
+            room_id,
+            room_name: room.room_name.clone(),
+            topic: room.topic.clone(),
+            description: room.description.clone(),
+            archived: room.archived_at.is_some(),
+        },
+    });
+    HttpResponse::Ok().json(room)
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/rooms/{room_id}",
+    params(
+        ("room_id" = i64, Path, description = "ID of the room"),
+        ("Authorization" = String, Header, description = "Bearer <JWT Token>")
+    ),
+    responses(
+        (status = 200, description = "Room deleted with its members, messages, reactions, invitations, bans and mutes; connected clients receive a room_deleted event and are disconnected", body = MessageResponse),
+        (status = 401, description = "User ID missing in token", body = ErrorResponse),
+        (status = 403, description = "Only the owner can delete the room", body = ErrorResponse),
+        (status = 500, description = "Failed to delete room", body = ErrorResponse)
+    )
+)]
+pub async fn delete_room(
+    pool: web::Data<SqlitePool>,
+    room_server: web::Data<Addr<RoomServer>>,
+    path: web::Path<i64>,
+    req: HttpRequest,
+) -> impl Responder {
+    let room_id = path.into_inner();
+    let user_id = match req.extensions().get::<i64>().copied() {
+        Some(user_id) => user_id,
+        None => {
+            return HttpResponse::Unauthorized().json(ErrorResponse {
+                error: "User ID missing in token".into(),
+            })
+        }
+    };
+
+    if let Err(e) = require_permission(pool.get_ref(), room_id, user_id, Permission::DeleteRoom).await {
+        return e.to_response();
+    }
+
+    // The foreign keys do not cascade, so everything that refers to the room goes first
+    let result: Result<(), sqlx::Error> = async {
+        let mut tx = pool.begin().await?;
+        sqlx::query!(
+            "DELETE FROM reactions WHERE message_id IN (SELECT message_id FROM messages WHERE room_id = ?)",
+            room_id
+        )
+        .execute(&mut tx)
+        .await?;
+        sqlx::query!(
+            "DELETE FROM message_revisions WHERE message_id IN (SELECT message_id FROM messages WHERE room_id = ?)",
+            room_id
+        )
+        .execute(&mut tx)
+        .await?;
+        sqlx::query!("DELETE FROM messages WHERE room_id = ?", room_id)
+            .execute(&mut tx)
+            .await?;
+        sqlx::query!("DELETE FROM room_invitations WHERE room_id = ?", room_id)
+            .execute(&mut tx)
+            .await?;
+        sqlx::query!("DELETE FROM invite_links WHERE room_id = ?", room_id)
+            .execute(&mut tx)
+            .await?;
+        sqlx::query!("DELETE FROM room_bans WHERE room_id = ?", room_id)
+            .execute(&mut tx)
+            .await?;
+        sqlx::query!("DELETE FROM room_mutes WHERE room_id = ?", room_id)
+            .execute(&mut tx)
+            .await?;
+        sqlx::query!("DELETE FROM user_rooms WHERE room_id = ?", room_id)
+            .execute(&mut tx)
+            .await?;
+        sqlx::query!("DELETE FROM rooms WHERE room_id = ?", room_id)
+            .execute(&mut tx)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+    .await;
+
+    match result {
+        Ok(()) => {
+            info!("User {} deleted room {}", user_id, room_id);
+            room_server.do_send(Disconnect {
+                room_id,
+                user_id: None,
+                event: ServerEvent::RoomDeleted { room_id },
+            });
+            HttpResponse::Ok().json(MessageResponse {
+                message: "Room deleted".into(),
+            })
+        }
+        Err(e) => {
+            error!("Failed to delete room {}: {}", room_id, e);
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to delete room".into(),
+            })
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct VisibilityRequest {
+    pub visibility: RoomVisibility,
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/rooms/{room_id}/visibility",
+    request_body = VisibilityRequest,
+    params(
+        ("room_id" = i64, Path, description = "ID of the room"),
+        ("Authorization" = String, Header, description = "Bearer <JWT Token>")
+    ),
+    responses(
+        (status = 200, description = "Visibility changed", body = Room),
+        (status = 401, description = "User ID missing in token", body = ErrorResponse),
+        (status = 403, description = "Only admins and the owner can change the visibility", body = ErrorResponse),
+        (status = 404, description = "Room not found", body = ErrorResponse),
+        (status = 500, description = "Failed to change visibility", body = ErrorResponse)
+    )
+)]
+pub async fn set_room_visibility(
+    pool: web::Data<SqlitePool>,
+    path: web::Path<i64>,
+    body: web::Json<VisibilityRequest>,
+    req: HttpRequest,
+) -> impl Responder {
+    let room_id = path.into_inner();
+    let user_id = match req.extensions().get::<i64>().copied() {
+        Some(user_id) => user_id,
+        None => {
+            return HttpResponse::Unauthorized().json(ErrorResponse {
+                error: "User ID missing in token".into(),
+            })
+        }
+    };
+    let visibility = body.into_inner().visibility;
+
+    if let Err(e) = require_permission(pool.get_ref(), room_id, user_id, Permission::ManageRoom).await {
+        return e.to_response();
+    }
+
+    match sqlx::query_as!(
+        Room,
+        "UPDATE rooms SET visibility = ? WHERE room_id = ? \
+        RETURNING room_id as `room_id!: i64`, room_name as `room_name!`, user_id as `user_id!: i64`, visibility as `visibility!: RoomVisibility`, \
+        kind as `kind!: RoomKind`, topic, description, archived_at, slow_mode_secs as `slow_mode_secs!: i64`",
+        visibility,
+        room_id
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    {
+        Ok(Some(room)) => {
+            info!("User {} made room {} {:?}", user_id, room_id, visibility);
+            HttpResponse::Ok().json(room)
+        }
+        Ok(None) => HttpResponse::NotFound().json(ErrorResponse {
+            error: "Room not found".into(),
+        }),
+        Err(e) => {
+            error!("Failed to change visibility of room {}: {}", room_id, e);
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to change visibility".into(),
+            })
+        }
+    }
+}
+
 #[derive(Deserialize)]
 struct QueryParams {
-    user_id: i64,
+    token: Option<String>,
+}
+
+// Sub-protocol a browser offers alongside its token: `new WebSocket(url, ["bearer", token])`
+const WS_TOKEN_PROTOCOL: &str = "bearer";
+
+// Finds the JWT for a WebSocket upgrade. Browsers cannot set an Authorization header on
+// WebSocket requests, so the token may also come from the `token` query parameter or
+// from the Sec-WebSocket-Protocol header.
+fn ws_token(req: &HttpRequest) -> Option<String> {
+    if let Some(token) = bearer_token(req) {
+        return Some(token.to_string());
+    }
+
+    if let Ok(params) = web::Query::<QueryParams>::from_query(req.query_string()) {
+        if let Some(token) = params.into_inner().token {
+            return Some(token);
+        }
+    }
+
+    // The protocol list looks like "bearer, <token>"
+    let protocols = req.headers().get("Sec-WebSocket-Protocol")?.to_str().ok()?;
+    let mut protocols = protocols.split(',').map(str::trim);
+    if protocols.any(|protocol| protocol == WS_TOKEN_PROTOCOL) {
+        return protocols.next().map(str::to_string);
+    }
+    None
 }
 
 #[utoipa::path(
@@ -267,12 +1008,15 @@ struct QueryParams {
     path = "/ws/rooms/{room_id}",
     params(
         ("room_id" = i64, Path, description = "Room ID to join via WebSocket"),
-        ("user_id" = i64, Query, description = "User ID for authentication")
+        ("token" = Option<String>, Query, description = "JWT, for clients that cannot send an Authorization header"),
+        ("Sec-WebSocket-Protocol" = Option<String>, Header, description = "Alternative to the token query parameter: `bearer, <JWT Token>`"),
+        ("Authorization" = Option<String>, Header, description = "Bearer <JWT Token>")
     ),
     responses(
         (status = 101, description = "Switching Protocols to WebSocket"),
-        (status = 401, description = "Unauthorized: User ID missing or invalid", body = ErrorResponse),
-        (status = 404, description = "Not Found: Room does not exist or user is not a member", body = ErrorResponse),
+        (status = 401, description = "Unauthorized: Token missing or invalid", body = ErrorResponse),
+        (status = 403, description = "Forbidden: The room is private and the user has not been invited, or the user is banned", body = ErrorResponse),
+        (status = 404, description = "Not Found: Room does not exist", body = ErrorResponse),
         (status = 500, description = "Internal Server Error", body = ErrorResponse),
     )
 )]
@@ -286,67 +1030,31 @@ pub async fn join_room_ws(
     let room_id = room_id.into_inner();
     info!("Attempting to join room with ID: {}", room_id);
 
-    // Extract user_id from query parameters
-    let query_params = match web::Query::<QueryParams>::from_query(req.query_string()) {
-        Ok(params) => params,
-        Err(_) => {
-            error!("user_id query parameter is missing or invalid");
+    // Authenticate the upgrade with the same checks AuthMiddleware applies to HTTP routes
+    let token = match ws_token(&req) {
+        Some(token) => token,
+        None => {
+            error!("WebSocket upgrade without a token");
             return Ok(HttpResponse::Unauthorized().json(ErrorResponse {
-                error: "Missing or invalid user_id".to_string(),
+                error: "Missing token".to_string(),
             }));
         }
     };
-    let user_id = query_params.user_id;
-
-    // Check if the room exists
-    let room_exists = sqlx::query!(
-        "SELECT 1 AS exists_flag FROM rooms WHERE room_id = ?",
-        room_id
-    )
-    .fetch_optional(pool.get_ref())
-    .await
-    .map(|row| row.is_some())
-    .unwrap_or(false);
-
-    if !room_exists {
-        return Ok(HttpResponse::NotFound().json(ErrorResponse {
-            error: "Room does not exist".to_string(),
-        }));
-    }
-
-    // Check if the user is already in the room
-    let user_in_room = sqlx::query!(
-        "SELECT 1 AS exists_flag FROM user_rooms WHERE user_id = ? AND room_id = ?",
-        user_id,
-        room_id
-    )
-    .fetch_optional(pool.get_ref())
-    .await
-    .map(|row| row.is_some())
-    .unwrap_or(false);
-
-    if user_in_room {
-        info!("User '{}' is already in room '{}'", user_id, room_id);
-    } else {
-        // Attempt to add the user to the room
-        match sqlx::query!(
-            "INSERT INTO user_rooms (user_id, room_id) VALUES (?, ?)",
-            user_id,
-            room_id
-        )
-        .execute(pool.get_ref())
-        .await
-        {
-            Ok(_) => {
-                info!("User '{}' added to room '{}'", user_id, room_id);
-            }
-            Err(e) => {
-                error!("Failed to add user to room '{}': {}", room_id, e);
-                return Ok(HttpResponse::BadRequest().json(ErrorResponse {
-                    error: "Error adding user to room".to_string(),
-                }));
-            }
+    let (user_id, session_id) = match authenticate_token(pool.get_ref(), &token).await {
+        Ok(authenticated) => authenticated,
+        Err(auth_error) => {
+            error!("WebSocket upgrade rejected: {:?}", auth_error);
+            return Ok(HttpResponse::Unauthorized().json(ErrorResponse {
+                error: auth_error.message().to_string(),
+            }));
         }
+    };
+
+    // Joining over the WebSocket makes the user a member, as long as the room admits them
+    match join_room(pool.get_ref(), room_id, user_id).await {
+        Ok(true) => info!("User '{}' added to room '{}'", user_id, room_id),
+        Ok(false) => info!("User '{}' is already in room '{}'", user_id, room_id),
+        Err(e) => return Ok(e.to_response()),
     }
 
     // Fetch the username for WebSocket session initialization
@@ -371,15 +1079,26 @@ pub async fn join_room_ws(
         "Starting WebSocket session for userid {} username {} in room {}",
         user_id, username, room_id
     );
-    let session = ChatSession::new(room_id, user_id, username.clone(), room_server.get_ref().clone());
-    ws::start(session, &req, stream)
+    let session = ChatSession::new(
+        room_id,
+        user_id,
+        session_id.0,
+        username.clone(),
+        room_server.get_ref().clone(),
+        pool.get_ref().clone(),
+    );
+    ws::WsResponseBuilder::new(session, &req, stream)
+        .protocols(&[WS_TOKEN_PROTOCOL]) // Echo the sub-protocol back to browsers that sent one
+        .start()
 }
 
 #[utoipa::path(
     get,
-    path = "/users/presence",
+    path = "/api/users/presence/{room_id}",
     responses(
-        (status = 200, description = "Returns user presence statuses in the room", body = [UserPresence]),
+        (status = 200, description = "Returns user presence statuses in the room. Clients use this for the first load and then follow presence events on the WebSocket", body = [UserPresence]),
+        (status = 401, description = "User ID missing in token", body = ErrorResponse),
+        (status = 403, description = "User is not a member of the room", body = ErrorResponse),
         (status = 404, description = "Room not found or no users present", body = ErrorResponse),
         (status = 500, description = "Internal server error", body = ErrorResponse)
     ),
@@ -391,7 +1110,21 @@ pub async fn join_room_ws(
 pub async fn get_user_presence(
     room_id: web::Path<i64>, // Directly use i64 instead of RoomId
     room_server: web::Data<Addr<RoomServer>>,
+    pool: web::Data<SqlitePool>,
+    req: HttpRequest,
 ) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = match req.extensions().get::<i64>().copied() {
+        Some(user_id) => user_id,
+        None => {
+            return Ok(HttpResponse::Unauthorized().json(ErrorResponse {
+                error: "User ID missing in token".to_string(),
+            }))
+        }
+    };
+    if let Err(e) = require_permission(pool.get_ref(), *room_id, user_id, Permission::ViewRoom).await {
+        return Ok(e.to_response());
+    }
+
     // Send the GetRoomPresence message to the RoomServer actor to fetch presence data.
     match room_server
         .send(GetRoomPresence { room_id: *room_id })
@@ -414,3 +1147,77 @@ pub async fn get_user_presence(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routes::test_support::test_pool;
+    use actix::Actor;
+    use actix_web::http::StatusCode;
+    use actix_web::{test, App};
+    use serde_json::Value;
+
+    // Alice owns a public channel with a message, is a member of Bob's private one, and has a direct
+    // message with him; Bob also has a private channel she is not in
+    async fn add_rooms(pool: &SqlitePool) {
+        sqlx::query(
+            "INSERT INTO users (user_id, username, password_hash) VALUES (1, 'alice', ''), (2, 'bob', ''); \
+            INSERT INTO rooms (room_id, room_name, user_id, visibility, created_at) VALUES \
+                (1, 'general', 1, 'public', '2024-01-01 00:00:00'), \
+                (2, 'team', 2, 'private', '2024-01-02 00:00:00'), \
+                (3, 'secret', 2, 'private', '2024-01-03 00:00:00'); \
+            INSERT INTO rooms (room_id, room_name, user_id, visibility, kind) VALUES (4, 'dm:1:2', 1, 'private', 'dm'); \
+            INSERT INTO user_rooms (user_id, room_id, role) VALUES \
+                (1, 1, 'owner'), (2, 1, 'member'), (1, 2, 'member'), (2, 2, 'owner'), (2, 3, 'owner'), (1, 4, 'member'), (2, 4, 'member'); \
+            INSERT INTO messages (room_id, user_id, content, created_at) VALUES (1, 2, 'hello', '2024-02-01 00:00:00');",
+        )
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+
+    // Gets a page of the room directory as Alice
+    async fn rooms_page(pool: &SqlitePool, query: &str) -> Value {
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool.clone()))
+                .app_data(web::Data::new(RoomServer::new().start()))
+                .route("/api/rooms", web::get().to(get_rooms)),
+        )
+        .await;
+        let req = test::TestRequest::get().uri(&format!("/api/rooms?{}", query)).to_request();
+        req.extensions_mut().insert(1i64);
+        let response = test::call_service(&app, req).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        test::read_body_json(response).await
+    }
+
+    fn room_names(page: &Value) -> Vec<&str> {
+        page["rooms"].as_array().unwrap().iter().map(|room| room["room_name"].as_str().unwrap()).collect()
+    }
+
+    #[actix_web::test]
+    async fn directory_lists_visible_channels_page_by_page() {
+        let pool = test_pool().await;
+        add_rooms(&pool).await;
+
+        // Newest first, one room per page; neither Bob's other private channel nor the direct message shows
+        let first = rooms_page(&pool, "limit=1").await;
+        assert_eq!(room_names(&first), ["team"]);
+        assert_eq!(first["rooms"][0]["joined"], true);
+        assert_eq!(first["rooms"][0]["visibility"], "private");
+        assert_eq!(first["rooms"][0]["kind"], "channel");
+        let cursor = first["next_cursor"].as_str().unwrap().to_string();
+        let second = rooms_page(&pool, &format!("limit=1&cursor={}", cursor.replace(' ', "%20").replace('|', "%7C"))).await;
+        assert_eq!(room_names(&second), ["general"]);
+        assert_eq!(second["rooms"][0]["member_count"], 2);
+        assert_eq!(second["rooms"][0]["online_count"], 0);
+        assert_eq!(second["rooms"][0]["last_activity_at"], "2024-02-01 00:00:00");
+        assert!(second["next_cursor"].is_null());
+
+        let by_activity = rooms_page(&pool, "sort=activity").await;
+        assert_eq!(room_names(&by_activity), ["general", "team"]);
+        let filtered = rooms_page(&pool, "q=GEN&sort=members").await;
+        assert_eq!(room_names(&filtered), ["general"]);
+    }
+}
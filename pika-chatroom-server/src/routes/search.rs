@@ -0,0 +1,270 @@
+use crate::models::message::StoredMessage;
+use crate::models::response::ErrorResponse;
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse, Responder};
+use chrono::NaiveDate;
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use utoipa::ToSchema;
+
+// Number of results returned when the client does not ask for a specific page size
+const DEFAULT_PAGE_SIZE: i64 = 20;
+// Upper bound on the page size; every result also loads its surrounding messages
+const MAX_PAGE_SIZE: i64 = 50;
+// Characters FTS5 wraps around matched terms in snippets. They come from the Unicode private
+// use area so they cannot be confused with anything a user typed.
+const HIGHLIGHT_START: char = '\u{E000}';
+const HIGHLIGHT_END: char = '\u{E001}';
+
+#[derive(Deserialize)]
+pub struct SearchQuery {
+    pub q: String,
+    pub room_id: Option<i64>,  // Only search this room
+    pub author: Option<String>, // Only match messages from this username
+    pub from: Option<String>,  // Earliest day to include, YYYY-MM-DD
+    pub to: Option<String>,    // Latest day to include, YYYY-MM-DD
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+/// A piece of a snippet; `highlight` marks the text that matched the query
+#[derive(Serialize, ToSchema)]
+pub struct SnippetPart {
+    pub text: String,
+    pub highlight: bool,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct SearchResult {
+    pub message: StoredMessage,
+    pub room_name: String,
+    pub snippet: Vec<SnippetPart>,
+    pub context_before: Option<StoredMessage>, // Previous message in the same timeline or thread
+    pub context_after: Option<StoredMessage>,  // Next message in the same timeline or thread
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct SearchResponse {
+    pub results: Vec<SearchResult>, // Best matches first
+    pub has_more: bool,
+}
+
+// Turns free text into an FTS5 query: every word must appear, and the last one may be a prefix.
+// Quoting each word keeps FTS5 operators and punctuation in the input from being interpreted.
+fn fts_query(q: &str) -> Option<String> {
+    let terms: Vec<String> = q
+        .split_whitespace()
+        .map(|term| format!("\"{}\"", term.replace('"', "\"\"")))
+        .collect();
+    if terms.is_empty() {
+        return None;
+    }
+    Some(format!("{}*", terms.join(" ")))
+}
+
+// Splits a snippet produced with the highlight markers into plain and highlighted parts
+fn snippet_parts(snippet: &str) -> Vec<SnippetPart> {
+    let mut parts = Vec::new();
+    for (index, segment) in snippet.split(HIGHLIGHT_START).enumerate() {
+        // Every segment after the first starts with highlighted text up to the end marker
+        let (highlighted, plain) = match segment.split_once(HIGHLIGHT_END) {
+            Some((highlighted, plain)) if index > 0 => (highlighted, plain),
+            _ => ("", segment),
+        };
+        if !highlighted.is_empty() {
+            parts.push(SnippetPart { text: highlighted.to_string(), highlight: true });
+        }
+        if !plain.is_empty() {
+            parts.push(SnippetPart { text: plain.to_string(), highlight: false });
+        }
+    }
+    parts
+}
+
+// Checks an optional YYYY-MM-DD filter
+fn valid_date(date: &Option<String>) -> bool {
+    match date {
+        Some(date) => NaiveDate::parse_from_str(date, "%Y-%m-%d").is_ok(),
+        None => true,
+    }
+}
+
+// Loads the message next to a search hit in the same timeline (for root messages) or thread (for replies)
+async fn neighbour(
+    pool: &SqlitePool,
+    message: &StoredMessage,
+    newer: bool,
+) -> Result<Option<StoredMessage>, sqlx::Error> {
+    if newer {
+        sqlx::query_as!(
+            StoredMessage,
+            "SELECT m.message_id as `message_id!`, m.room_id, m.user_id, u.username, m.content, m.created_at as `created_at!`, m.edited_at, m.deleted_at, m.parent_id, \
+                (SELECT COUNT(*) FROM messages r WHERE r.parent_id = m.message_id AND r.deleted_at IS NULL) as `reply_count!: i64` \
+            FROM messages m INNER JOIN users u ON u.user_id = m.user_id \
+            WHERE m.room_id = ? AND m.parent_id IS ? AND m.message_id > ? \
+            ORDER BY m.message_id ASC LIMIT 1",
+            message.room_id,
+            message.parent_id,
+            message.message_id
+        )
+        .fetch_optional(pool)
+        .await
+    } else {
+        sqlx::query_as!(
+            StoredMessage,
+            "SELECT m.message_id as `message_id!`, m.room_id, m.user_id, u.username, m.content, m.created_at as `created_at!`, m.edited_at, m.deleted_at, m.parent_id, \
+                (SELECT COUNT(*) FROM messages r WHERE r.parent_id = m.message_id AND r.deleted_at IS NULL) as `reply_count!: i64` \
+            FROM messages m INNER JOIN users u ON u.user_id = m.user_id \
+            WHERE m.room_id = ? AND m.parent_id IS ? AND m.message_id < ? \
+            ORDER BY m.message_id DESC LIMIT 1",
+            message.room_id,
+            message.parent_id,
+            message.message_id
+        )
+        .fetch_optional(pool)
+        .await
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/search/messages",
+    params(
+        ("q" = String, Query, description = "Words to search for; the last word also matches as a prefix"),
+        ("room_id" = Option<i64>, Query, description = "Only search this room"),
+        ("author" = Option<String>, Query, description = "Only match messages sent by this username"),
+        ("from" = Option<String>, Query, description = "Earliest day to include (YYYY-MM-DD)"),
+        ("to" = Option<String>, Query, description = "Latest day to include (YYYY-MM-DD)"),
+        ("limit" = Option<i64>, Query, description = "Maximum number of results (default 20, max 50)"),
+        ("offset" = Option<i64>, Query, description = "Number of results to skip"),
+        ("Authorization" = String, Header, description = "Bearer <JWT Token>")
+    ),
+    responses(
+        (status = 200, description = "Matching messages from rooms the user is a member of, best matches first", body = SearchResponse),
+        (status = 400, description = "Empty query or invalid date", body = ErrorResponse),
+        (status = 401, description = "User ID missing in token", body = ErrorResponse),
+        (status = 500, description = "Failed to search messages", body = ErrorResponse)
+    )
+)]
+pub async fn search_messages(
+    pool: web::Data<SqlitePool>,
+    query: web::Query<SearchQuery>,
+    req: HttpRequest,
+) -> impl Responder {
+    let user_id = match req.extensions().get::<i64>().copied() {
+        Some(user_id) => user_id,
+        None => {
+            return HttpResponse::Unauthorized().json(ErrorResponse {
+                error: "User ID missing in token".into(),
+            })
+        }
+    };
+
+    let match_query = match fts_query(&query.q) {
+        Some(match_query) => match_query,
+        None => {
+            return HttpResponse::BadRequest().json(ErrorResponse {
+                error: "Search query must not be empty".into(),
+            })
+        }
+    };
+    if !valid_date(&query.from) || !valid_date(&query.to) {
+        return HttpResponse::BadRequest().json(ErrorResponse {
+            error: "Dates must use the YYYY-MM-DD format".into(),
+        });
+    }
+
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    let offset = query.offset.unwrap_or(0).max(0);
+    // Fetch one extra row to find out whether another page exists
+    let fetch_limit = limit + 1;
+
+    // Joining user_rooms on the caller keeps rooms they are not a member of out of the results.
+    // SQLx cannot infer nullability through the FTS5 table, hence the `!` overrides.
+    let rows = sqlx::query!(
+        "SELECT m.message_id as `message_id!`, m.room_id as `room_id!`, m.user_id as `user_id!`, u.username as `username!`, m.content as `content!`, \
+            m.created_at as `created_at!`, m.edited_at, m.deleted_at, m.parent_id, \
+            (SELECT COUNT(*) FROM messages r WHERE r.parent_id = m.message_id AND r.deleted_at IS NULL) as `reply_count!: i64`, \
+            rm.room_name as `room_name!`, \
+            snippet(messages_fts, 0, char(57344), char(57345), '…', 16) as `snippet!: String` \
+        FROM messages_fts \
+        INNER JOIN messages m ON m.message_id = messages_fts.rowid \
+        INNER JOIN users u ON u.user_id = m.user_id \
+        INNER JOIN rooms rm ON rm.room_id = m.room_id \
+        INNER JOIN user_rooms ur ON ur.room_id = m.room_id AND ur.user_id = ? \
+        WHERE messages_fts MATCH ? AND m.deleted_at IS NULL \
+            AND (? IS NULL OR m.room_id = ?) \
+            AND (? IS NULL OR u.username = ?) \
+            AND (? IS NULL OR date(m.created_at) >= ?) \
+            AND (? IS NULL OR date(m.created_at) <= ?) \
+        ORDER BY messages_fts.rank, m.message_id DESC \
+        LIMIT ? OFFSET ?",
+        user_id,
+        match_query,
+        query.room_id,
+        query.room_id,
+        query.author,
+        query.author,
+        query.from,
+        query.from,
+        query.to,
+        query.to,
+        fetch_limit,
+        offset
+    )
+    .fetch_all(pool.get_ref())
+    .await;
+
+    let mut rows = match rows {
+        Ok(rows) => rows,
+        Err(e) => {
+            error!("Failed to search messages for user {}: {}", user_id, e);
+            return HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to search messages".into(),
+            });
+        }
+    };
+    let has_more = rows.len() as i64 > limit;
+    rows.truncate(limit as usize);
+
+    let mut results = Vec::with_capacity(rows.len());
+    for row in rows {
+        let message = StoredMessage {
+            message_id: row.message_id,
+            room_id: row.room_id,
+            user_id: row.user_id,
+            username: row.username,
+            content: row.content,
+            created_at: row.created_at,
+            edited_at: row.edited_at,
+            deleted_at: row.deleted_at,
+            parent_id: row.parent_id,
+            reply_count: row.reply_count,
+        };
+
+        let context = match neighbour(pool.get_ref(), &message, false).await {
+            Ok(before) => neighbour(pool.get_ref(), &message, true).await.map(|after| (before, after)),
+            Err(e) => Err(e),
+        };
+        let (context_before, context_after) = match context {
+            Ok(context) => context,
+            Err(e) => {
+                error!("Failed to load context of message {}: {}", message.message_id, e);
+                return HttpResponse::InternalServerError().json(ErrorResponse {
+                    error: "Failed to search messages".into(),
+                });
+            }
+        };
+
+        results.push(SearchResult {
+            snippet: snippet_parts(&row.snippet),
+            room_name: row.room_name,
+            message,
+            context_before,
+            context_after,
+        });
+    }
+
+    info!("User {} searched messages and got {} results", user_id, results.len());
+    HttpResponse::Ok().json(SearchResponse { results, has_more })
+}
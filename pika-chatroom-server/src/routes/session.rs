@@ -0,0 +1,308 @@
+use crate::config::settings::REFRESH_TOKEN_TTL;
+use crate::middleware::auth_middleware::SessionId;
+use crate::middleware::revocation::revoke_session_tokens;
+use crate::models::response::{ErrorResponse, MessageResponse};
+use crate::routes::message::TIMESTAMP_FORMAT;
+use crate::websockets::chat_session::{EndSessions, RoomServer};
+use actix::Addr;
+use actix_web::http::header::USER_AGENT;
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse, Responder};
+use chrono::Utc;
+use log::{error, info};
+use serde::Serialize;
+use sqlx::{Executor, Sqlite, SqlitePool, Transaction};
+use utoipa::ToSchema;
+
+/// A login session: one login on one device, kept alive by refreshing its tokens
+#[derive(Serialize, ToSchema)]
+pub struct SessionInfo {
+    pub session_id: String,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: String,   // UTC
+    pub last_seen_at: String, // UTC; the latest login or token refresh
+    pub current: bool,        // Whether this is the session the request was made in
+}
+
+// The device details recorded with a session at login and on every refresh
+pub(crate) struct ClientInfo {
+    user_agent: Option<String>,
+    ip_address: Option<String>,
+}
+
+impl ClientInfo {
+    pub(crate) fn from_request(req: &HttpRequest) -> Self {
+        ClientInfo {
+            user_agent: req
+                .headers()
+                .get(USER_AGENT)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string),
+            ip_address: req.connection_info().realip_remote_addr().map(str::to_string),
+        }
+    }
+}
+
+// Records a new session for a login
+pub(crate) async fn start_session<'e, E: Executor<'e, Database = Sqlite>>(
+    executor: E,
+    session_id: &str,
+    user_id: i64,
+    client: &ClientInfo,
+) -> Result<(), sqlx::Error> {
+    let expires_at = (Utc::now() + *REFRESH_TOKEN_TTL).format(TIMESTAMP_FORMAT).to_string();
+    sqlx::query!(
+        "INSERT INTO sessions (session_id, user_id, user_agent, ip_address, expires_at) VALUES (?, ?, ?, ?, ?)",
+        session_id,
+        user_id,
+        client.user_agent,
+        client.ip_address,
+        expires_at
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+// Records that a session refreshed its tokens, which keeps it alive for another REFRESH_TOKEN_TTL
+pub(crate) async fn touch_session<'e, E: Executor<'e, Database = Sqlite>>(
+    executor: E,
+    session_id: &str,
+    client: &ClientInfo,
+) -> Result<(), sqlx::Error> {
+    let expires_at = (Utc::now() + *REFRESH_TOKEN_TTL).format(TIMESTAMP_FORMAT).to_string();
+    sqlx::query!(
+        "UPDATE sessions SET user_agent = ?, ip_address = ?, last_seen_at = datetime('now'), expires_at = ? \
+        WHERE session_id = ?",
+        client.user_agent,
+        client.ip_address,
+        expires_at,
+        session_id
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+// Revokes a session and every refresh token it was issued. Once the transaction is committed,
+// end_sessions makes the revocation take effect on the session's access tokens and WebSockets.
+pub(crate) async fn revoke_session(tx: &mut Transaction<'_, Sqlite>, session_id: &str) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE sessions SET revoked_at = datetime('now') WHERE session_id = ? AND revoked_at IS NULL",
+        session_id
+    )
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query!(
+        "UPDATE refresh_tokens SET revoked_at = datetime('now') WHERE family_id = ? AND revoked_at IS NULL",
+        session_id
+    )
+    .execute(&mut *tx)
+    .await?;
+    Ok(())
+}
+
+// Refuses the access tokens of revoked sessions from now on and closes their WebSockets
+pub(crate) fn end_sessions(room_server: &Addr<RoomServer>, session_ids: Vec<String>) {
+    if session_ids.is_empty() {
+        return;
+    }
+    revoke_session_tokens(&session_ids);
+    room_server.do_send(EndSessions { session_ids });
+}
+
+// IDs of the user's sessions that have not been revoked, but `except`
+pub(crate) async fn user_session_ids<'e, E: Executor<'e, Database = Sqlite>>(
+    executor: E,
+    user_id: i64,
+    except: Option<&str>,
+) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query_scalar!(
+        "SELECT session_id as `session_id!: String` FROM sessions \
+        WHERE user_id = ? AND revoked_at IS NULL AND (? IS NULL OR session_id != ?)",
+        user_id,
+        except,
+        except
+    )
+    .fetch_all(executor)
+    .await
+}
+
+// Revokes sessions of a user in one transaction, then ends them
+async fn revoke_sessions(
+    pool: &SqlitePool,
+    room_server: &Addr<RoomServer>,
+    session_ids: Vec<String>,
+) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    for session_id in &session_ids {
+        revoke_session(&mut tx, session_id).await?;
+    }
+    tx.commit().await?;
+    end_sessions(room_server, session_ids);
+    Ok(())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/sessions",
+    params(
+        ("Authorization" = String, Header, description = "Bearer <JWT Token>")
+    ),
+    responses(
+        (status = 200, description = "The user's live sessions, most recently seen first; revoked and expired sessions are left out", body = [SessionInfo]),
+        (status = 401, description = "User ID missing in token", body = ErrorResponse),
+        (status = 500, description = "Failed to retrieve sessions", body = ErrorResponse)
+    )
+)]
+pub async fn get_sessions(pool: web::Data<SqlitePool>, req: HttpRequest) -> impl Responder {
+    let (user_id, current) = match (req.extensions().get::<i64>().copied(), req.extensions().get::<SessionId>().cloned()) {
+        (Some(user_id), Some(session_id)) => (user_id, session_id.0),
+        _ => {
+            return HttpResponse::Unauthorized().json(ErrorResponse {
+                error: "User ID missing in token".into(),
+            })
+        }
+    };
+
+    match sqlx::query_as!(
+        SessionInfo,
+        "SELECT session_id as `session_id!: String`, user_agent, ip_address, created_at, last_seen_at, \
+        session_id = ? as `current!: bool` \
+        FROM sessions \
+        WHERE user_id = ? AND revoked_at IS NULL AND expires_at > datetime('now') \
+        ORDER BY last_seen_at DESC, created_at DESC",
+        current,
+        user_id
+    )
+    .fetch_all(pool.get_ref())
+    .await
+    {
+        Ok(sessions) => HttpResponse::Ok().json(sessions),
+        Err(e) => {
+            error!("Failed to retrieve sessions of user {}: {}", user_id, e);
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to retrieve sessions".into(),
+            })
+        }
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/sessions/{session_id}",
+    params(
+        ("session_id" = String, Path, description = "ID of the session to revoke"),
+        ("Authorization" = String, Header, description = "Bearer <JWT Token>")
+    ),
+    responses(
+        (status = 200, description = "Session revoked: its tokens are refused and its WebSockets closed. Revoking the current session logs out", body = MessageResponse),
+        (status = 401, description = "User ID missing in token", body = ErrorResponse),
+        (status = 404, description = "The user has no live session with this ID", body = ErrorResponse),
+        (status = 500, description = "Failed to revoke session", body = ErrorResponse)
+    )
+)]
+pub async fn delete_session(
+    pool: web::Data<SqlitePool>,
+    room_server: web::Data<Addr<RoomServer>>,
+    path: web::Path<String>,
+    req: HttpRequest,
+) -> impl Responder {
+    let session_id = path.into_inner();
+    let user_id = match req.extensions().get::<i64>().copied() {
+        Some(user_id) => user_id,
+        None => {
+            return HttpResponse::Unauthorized().json(ErrorResponse {
+                error: "User ID missing in token".into(),
+            })
+        }
+    };
+
+    // Sessions of other users are reported as missing, not forbidden, so that their IDs do not leak
+    match sqlx::query_scalar!(
+        "SELECT 1 as `found!: i64` FROM sessions WHERE session_id = ? AND user_id = ? AND revoked_at IS NULL",
+        session_id,
+        user_id
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            return HttpResponse::NotFound().json(ErrorResponse {
+                error: "Session not found".into(),
+            })
+        }
+        Err(e) => {
+            error!("Failed to look up session {}: {}", session_id, e);
+            return HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to revoke session".into(),
+            });
+        }
+    }
+
+    match revoke_sessions(pool.get_ref(), room_server.get_ref(), vec![session_id.clone()]).await {
+        Ok(()) => {
+            info!("User {} revoked session {}", user_id, session_id);
+            HttpResponse::Ok().json(MessageResponse {
+                message: "Session revoked".into(),
+            })
+        }
+        Err(e) => {
+            error!("Failed to revoke session {}: {}", session_id, e);
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to revoke session".into(),
+            })
+        }
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/sessions",
+    params(
+        ("Authorization" = String, Header, description = "Bearer <JWT Token>")
+    ),
+    responses(
+        (status = 200, description = "Every session but the current one revoked, logging the user out on all other devices", body = MessageResponse),
+        (status = 401, description = "User ID missing in token", body = ErrorResponse),
+        (status = 500, description = "Failed to revoke sessions", body = ErrorResponse)
+    )
+)]
+pub async fn delete_other_sessions(
+    pool: web::Data<SqlitePool>,
+    room_server: web::Data<Addr<RoomServer>>,
+    req: HttpRequest,
+) -> impl Responder {
+    let (user_id, current) = match (req.extensions().get::<i64>().copied(), req.extensions().get::<SessionId>().cloned()) {
+        (Some(user_id), Some(session_id)) => (user_id, session_id.0),
+        _ => {
+            return HttpResponse::Unauthorized().json(ErrorResponse {
+                error: "User ID missing in token".into(),
+            })
+        }
+    };
+
+    let result = async {
+        let session_ids = user_session_ids(pool.get_ref(), user_id, Some(&current)).await?;
+        let revoked = session_ids.len();
+        revoke_sessions(pool.get_ref(), room_server.get_ref(), session_ids).await?;
+        Ok::<usize, sqlx::Error>(revoked)
+    }
+    .await;
+
+    match result {
+        Ok(revoked) => {
+            info!("User {} revoked {} other sessions", user_id, revoked);
+            HttpResponse::Ok().json(MessageResponse {
+                message: format!("Logged out of {} other sessions", revoked),
+            })
+        }
+        Err(e) => {
+            error!("Failed to revoke other sessions of user {}: {}", user_id, e);
+            HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Failed to revoke sessions".into(),
+            })
+        }
+    }
+}
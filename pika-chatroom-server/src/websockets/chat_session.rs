@@ -1,30 +1,42 @@
-use actix::{Actor, StreamHandler, Context, Addr, Message, Handler, AsyncContext};
+use actix::{Actor, ActorContext, ActorFutureExt, StreamHandler, Context, Addr, Message, Handler, AsyncContext, WrapFuture};
 use actix_web_actors::ws;
+use chrono::Utc;
 use std::collections::{HashMap, HashSet};
-use crate::models::presence::{GetRoomPresence, UserPresence};
-use serde::Serialize;
-
-// Define RoomId and UserId types for better readability
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use crate::models::message::StoredMessage;
+use crate::models::presence::{GetOnlineCounts, GetRoomPresence, GetUsersPresence, PresenceChange, UserPresence};
+use crate::middleware::revocation::is_session_revoked;
+use crate::models::role::Permission;
+use crate::routes::moderation::{mute_remaining, slow_mode_remaining};
+use crate::routes::role::{require_permission, AccessError};
+use crate::websockets::protocol::{ClientCommand, ClientFrame, ErrorCode, ServerEvent, PROTOCOL_VERSION};
+use sqlx::SqlitePool;
+
+// Define RoomId, UserId and ConnectionId types for better readability
 pub type RoomId = i64;
 pub type UserId = i64;
+pub type ConnectionId = u64; // Identifies one WebSocket connection; a user may have several
+
+// Source of connection IDs, unique for the lifetime of the process
+static NEXT_CONNECTION_ID: AtomicU64 = AtomicU64::new(1);
 
-// Message type for broadcasting a message within a room.
-#[derive(Serialize)]
+// Message type for broadcasting a protocol event to every session within a room.
 pub struct BroadcastMessage {
     pub room_id: RoomId,
-    pub message: String,
-    pub is_system: bool,
-    pub username: String,
+    pub event: ServerEvent,
 }
 
 impl Message for BroadcastMessage {
     type Result = ();
 }
 
-// Message type for adding a user with session address to a room
+// Message type for adding a connection with its session address to a room
 pub struct AddUser {
+    pub connection_id: ConnectionId,
     pub room_id: RoomId,
     pub user_id: UserId,
+    pub session_id: String,
     pub username: String,
     pub addr: Addr<ChatSession>,
 }
@@ -33,134 +45,327 @@ impl Message for AddUser {
     type Result = ();
 }
 
-// Message type for removing a user from a room
+// Message type for removing a connection from its room
 pub struct RemoveUser {
+    pub connection_id: ConnectionId,
+}
+
+impl Message for RemoveUser {
+    type Result = ();
+}
+
+// Message type for a user starting or stopping to type in a room
+pub struct SetTyping {
     pub room_id: RoomId,
     pub user_id: UserId,
+    pub is_typing: bool,
 }
 
-impl Message for RemoveUser {
+impl Message for SetTyping {
     type Result = ();
 }
 
+// Message type for closing live connections to a room, such as when the room is deleted.
+// Each connection receives `event` before it closes.
+pub struct Disconnect {
+    pub room_id: RoomId,
+    pub user_id: Option<UserId>, // Only this user's connections, or everyone's when `None`
+    pub event: ServerEvent,
+}
+
+impl Message for Disconnect {
+    type Result = ();
+}
+
+// Message type for taking a member who left or was removed out of a room: the rest of the room
+// receives `event`, and the member's own connections receive it and close.
+pub struct RemoveMember {
+    pub room_id: RoomId,
+    pub user_id: UserId,
+    pub event: ServerEvent,
+}
+
+impl Message for RemoveMember {
+    type Result = ();
+}
+
+// Message type for closing every connection opened with one of these login sessions, after
+// the sessions were revoked
+pub struct EndSessions {
+    pub session_ids: Vec<String>,
+}
+
+impl Message for EndSessions {
+    type Result = ();
+}
+
+// How long a typing signal lasts unless the client renews it
+const TYPING_TIMEOUT: Duration = Duration::from_secs(5);
+// Minimum time between two `typing` announcements of the same user in a room
+const TYPING_RATE_LIMIT: Duration = Duration::from_secs(1);
+// How often expired typing signals are swept
+const TYPING_SWEEP_INTERVAL: Duration = Duration::from_secs(1);
+
+// A live WebSocket connection tracked by the RoomServer
+struct Connection {
+    user_id: UserId,
+    room_id: RoomId,
+    session_id: String, // The login session whose token opened the connection
+    addr: Addr<ChatSession>,
+}
+
 // RoomServer is an Actix actor responsible for managing chat rooms and users within them.
 pub struct RoomServer {
-    rooms: HashMap<RoomId, HashSet<UserId>>,               // Tracks user IDs in each room
-    user_sessions: HashMap<UserId, Addr<ChatSession>>,      // Tracks active sessions by user ID
-    user_presence: HashMap<UserId, bool>,                   // Tracks online/offline status of each user
-    user_names: HashMap<UserId, String>,                   // Maps user IDs to usernames
+    connections: HashMap<ConnectionId, Connection>,               // Every live connection
+    rooms: HashMap<RoomId, HashSet<ConnectionId>>,                // Tracks connection IDs in each room
+    user_connections: HashMap<UserId, HashSet<ConnectionId>>,     // A user is online while this set is non-empty
+    room_users: HashMap<RoomId, HashSet<UserId>>,                 // Users who have connected to each room since it was last empty, online or not
+    user_names: HashMap<UserId, String>,                          // Maps user IDs to usernames
+    typing: HashMap<(RoomId, UserId), Instant>,                   // Users currently typing in each room, with when their signal expires
+    typing_announced: HashMap<(RoomId, UserId), Instant>,         // Last `typing` start sent per user and room, for rate limiting
 }
 
 impl RoomServer {
     // Constructor to create a new RoomServer instance.
     pub fn new() -> Self {
         RoomServer {
+            connections: HashMap::new(),
             rooms: HashMap::new(),
-            user_sessions: HashMap::new(),
-            user_presence: HashMap::new(),
+            user_connections: HashMap::new(),
+            room_users: HashMap::new(),
             user_names: HashMap::new(),
+            typing: HashMap::new(),
+            typing_announced: HashMap::new(),
         }
     }
 
-    // Adds a user to a specified room.
-    pub fn add_user(&mut self, room_id: RoomId, user_id: UserId, addr: Addr<ChatSession>) {
-        // self.rooms.entry(room_id).or_insert_with(HashSet::new).insert(user_id);
-        // self.user_sessions.insert(user_id, addr);
-        // self.user_presence.insert(user_id, true);
-        if self.rooms.contains_key(&room_id) && self.rooms[&room_id].contains(&user_id) {
-            log::warn!(
-                "User {} is already in room {}. Skipping re-addition.",
-                user_id,
-                room_id
-            );
-        } else {
-            self.rooms
-                .entry(room_id)
-                .or_insert_with(HashSet::new)
-                .insert(user_id);
-            self.user_sessions.insert(user_id, addr);
-            self.user_presence.insert(user_id, true); // Set user as online
-    
-            log::info!(
-                "User {} added to room {}. Current users in room: {:?}",
-                user_id,
-                room_id,
-                self.rooms.get(&room_id)
-            );
+    // Adds a connection to its room.
+    pub fn add_connection(
+        &mut self,
+        connection_id: ConnectionId,
+        room_id: RoomId,
+        user_id: UserId,
+        session_id: String,
+        addr: Addr<ChatSession>,
+    ) {
+        if self.connections.contains_key(&connection_id) {
+            log::warn!("Connection {} is already tracked. Skipping re-addition.", connection_id);
+            return;
         }
+
+        self.connections.insert(connection_id, Connection { user_id, room_id, session_id, addr });
+        self.rooms.entry(room_id).or_default().insert(connection_id);
+        self.user_connections.entry(user_id).or_default().insert(connection_id);
+        self.room_users.entry(room_id).or_default().insert(user_id);
+
+        log::info!(
+            "Connection {} of user {} added to room {}. Current connections in room: {:?}",
+            connection_id,
+            user_id,
+            room_id,
+            self.rooms.get(&room_id)
+        );
     }
 
-    // Removes a user from a specified room, marking them as offline
-    pub fn remove_user(&mut self, room_id: RoomId, user_id: UserId) {
-        if let Some(users) = self.rooms.get_mut(&room_id) {
-            users.remove(&user_id);                   // Remove user from room set
-            self.user_presence.insert(user_id, false); // Mark user as offline
-            if users.is_empty() {                     // If no users left, remove the room
-                self.rooms.remove(&room_id);
+    // Removes a connection and returns it, dropping rooms and users that have no connections left
+    fn remove_connection(&mut self, connection_id: ConnectionId) -> Option<Connection> {
+        let connection = self.connections.remove(&connection_id)?;
+
+        if let Some(connection_ids) = self.rooms.get_mut(&connection.room_id) {
+            connection_ids.remove(&connection_id);
+            if connection_ids.is_empty() {            // If no connections left, remove the room
+                self.rooms.remove(&connection.room_id);
+                // Nobody is left to see who was there, so the room is forgotten altogether
+                self.room_users.remove(&connection.room_id);
+            }
+        }
+        if let Some(connection_ids) = self.user_connections.get_mut(&connection.user_id) {
+            connection_ids.remove(&connection_id);
+            if connection_ids.is_empty() {            // The user's last connection closed
+                self.user_connections.remove(&connection.user_id);
             }
         }
-        self.user_sessions.remove(&user_id);          // Remove user's active session
-    }    
-    
-    fn broadcast_to_room(&self, room_id: RoomId, message: &str, is_system: bool, sender_username: String) {
-        if let Some(user_ids) = self.rooms.get(&room_id) {
-            for &user_id in user_ids {
-                if let Some(addr) = self.user_sessions.get(&user_id) {
-                    // Create a BroadcastMessage and serialize it
-                    let broadcast_message = BroadcastMessage {
-                        room_id,
-                        message: message.to_string(),
-                        is_system,
-                        username: sender_username.clone(), // Sender's username
-                    };
-    
-                    if let Ok(serialized_message) = serde_json::to_string(&broadcast_message) {
-                        addr.do_send(ChatMessage {
-                            message: serialized_message,
-                        });
-                    } else {
-                        eprintln!("Failed to serialize BroadcastMessage for room {}", room_id);
+        Some(connection)
+    }
+
+    // Whether the user has at least one open connection
+    pub fn is_online(&self, user_id: UserId) -> bool {
+        self.user_connections.contains_key(&user_id)
+    }
+
+    // Number of open connections the user has in a room
+    fn connections_in_room(&self, room_id: RoomId, user_id: UserId) -> usize {
+        self.rooms.get(&room_id).map_or(0, |connection_ids| {
+            connection_ids
+                .iter()
+                .filter(|id| self.connections.get(id).is_some_and(|c| c.user_id == user_id))
+                .count()
+        })
+    }
+
+    fn broadcast_to_room(&self, room_id: RoomId, event: &ServerEvent) {
+        self.broadcast_to_room_except(room_id, event, None);
+    }
+
+    // Sends an event to a room, skipping every connection of `except_user` when it is set
+    fn broadcast_to_room_except(&self, room_id: RoomId, event: &ServerEvent, except_user: Option<UserId>) {
+        if let Some(connection_ids) = self.rooms.get(&room_id) {
+            // Serialize once and send the same payload to every session in the room
+            let serialized_message = match serde_json::to_string(event) {
+                Ok(serialized_message) => serialized_message,
+                Err(e) => {
+                    log::error!("Failed to serialize event for room {}: {}", room_id, e);
+                    return;
+                }
+            };
+
+            for connection_id in connection_ids {
+                if let Some(connection) = self.connections.get(connection_id) {
+                    if Some(connection.user_id) == except_user {
+                        continue;
                     }
+                    connection.addr.do_send(ChatMessage {
+                        message: serialized_message.clone(),
+                    });
                 }
             }
         }
-    }    
+    }
 
-    // Sets a user's presence to online.
-    pub fn set_user_online(&mut self, user_id: UserId) {
-        self.user_presence.insert(user_id, true);
+    // Sends a presence change for a user to one room
+    fn broadcast_presence(&self, room_id: RoomId, user_id: UserId, change: PresenceChange) {
+        let username = self.user_names.get(&user_id).cloned().unwrap_or_default();
+        self.broadcast_to_room(room_id, &ServerEvent::Presence {
+            room_id,
+            user_id,
+            username,
+            change,
+        });
     }
 
-    // Sets a user's presence to offline.
-    pub fn set_user_offline(&mut self, user_id: UserId) {
-        self.user_presence.insert(user_id, false);
+    // Tells every room the user has been seen in that the user came online or went offline
+    fn broadcast_online_change(&self, user_id: UserId, change: PresenceChange) {
+        for (&room_id, user_ids) in &self.room_users {
+            if user_ids.contains(&user_id) {
+                self.broadcast_presence(room_id, user_id, change);
+            }
+        }
     }
 
-    // Retrieves the presence status of all users in a room, including offline users
-    pub fn get_room_presence(&self, room_id: RoomId) -> Vec<UserPresence> {
-        let mut presence_list = Vec::new();
-    
-        if let Some(user_ids) = self.rooms.get(&room_id) {
-            // Track presence of users who are currently in the room (online)
-            for &user_id in user_ids {
-                let is_online = *self.user_presence.get(&user_id).unwrap_or(&false);
-                presence_list.push(UserPresence::new(user_id, is_online));
+    // Tells the other users in a room that a user started or stopped typing
+    fn broadcast_typing(&self, room_id: RoomId, user_id: UserId, is_typing: bool) {
+        let username = self.user_names.get(&user_id).cloned().unwrap_or_default();
+        self.broadcast_to_room_except(
+            room_id,
+            &ServerEvent::Typing {
+                room_id,
+                user_id,
+                username,
+                is_typing,
+            },
+            Some(user_id),
+        );
+    }
+
+    // Records a typing signal and announces it when the user's typing state changes.
+    // Renewals only push back the expiry, and a user flapping between start and stop
+    // is announced as typing at most once per TYPING_RATE_LIMIT.
+    pub fn set_typing(&mut self, room_id: RoomId, user_id: UserId, is_typing: bool) {
+        let key = (room_id, user_id);
+        let now = Instant::now();
+
+        if !is_typing {
+            if self.typing.remove(&key).is_some() {
+                self.broadcast_typing(room_id, user_id, false);
             }
+            return;
+        }
+
+        if let Some(expires_at) = self.typing.get_mut(&key) {
+            *expires_at = now + TYPING_TIMEOUT;
+            return;
+        }
+        if matches!(self.typing_announced.get(&key), Some(&last) if now.duration_since(last) < TYPING_RATE_LIMIT) {
+            return;
+        }
+
+        self.typing.insert(key, now + TYPING_TIMEOUT);
+        self.typing_announced.insert(key, now);
+        self.broadcast_typing(room_id, user_id, true);
+    }
+
+    // Announces that users whose typing signal was not renewed in time stopped typing
+    fn expire_typing(&mut self) {
+        let now = Instant::now();
+        let expired: Vec<(RoomId, UserId)> = self
+            .typing
+            .iter()
+            .filter(|(_, &expires_at)| expires_at <= now)
+            .map(|(&key, _)| key)
+            .collect();
+
+        for (room_id, user_id) in expired {
+            self.typing.remove(&(room_id, user_id));
+            self.broadcast_typing(room_id, user_id, false);
         }
-    
-        // Add users who were in the room but are now offline, based on user_presence
-        for (&user_id, &is_online) in &self.user_presence {
-            if !is_online && self.user_presence.contains_key(&user_id) {
-                presence_list.push(UserPresence::new(user_id, is_online));
+        // Rate limit entries are only needed until the limit has passed
+        self.typing_announced
+            .retain(|_, announced_at| now.duration_since(*announced_at) < TYPING_RATE_LIMIT);
+    }
+
+    // Sends `event` to the matching connections in a room, then closes them; `user_id` limits this
+    // to one user's connections. The sessions remove themselves from the room as they stop.
+    fn close_connections(&self, room_id: RoomId, user_id: Option<UserId>, event: &ServerEvent) {
+        let message = match serde_json::to_string(event) {
+            Ok(message) => message,
+            Err(e) => {
+                log::error!("Failed to serialize event for room {}: {}", room_id, e);
+                return;
+            }
+        };
+
+        for connection_id in self.rooms.get(&room_id).into_iter().flatten() {
+            if let Some(connection) = self.connections.get(connection_id) {
+                if user_id.is_none() || user_id == Some(connection.user_id) {
+                    connection.addr.do_send(CloseSession {
+                        message: message.clone(),
+                    });
+                }
             }
         }
-    
+    }
+
+    // Retrieves the presence status of all users seen in a room, including offline users
+    pub fn get_room_presence(&self, room_id: RoomId) -> Vec<UserPresence> {
+        let mut presence_list: Vec<UserPresence> = self
+            .room_users
+            .get(&room_id)
+            .map(|user_ids| {
+                user_ids
+                    .iter()
+                    .map(|&user_id| {
+                        UserPresence::new(
+                            user_id,
+                            self.is_online(user_id),
+                            self.connections_in_room(room_id, user_id) > 0,
+                        )
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        presence_list.sort_by_key(|presence| presence.user_id);
         presence_list
-    }    
+    }
 }
 
 impl Actor for RoomServer {
     type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        // Clients may vanish without sending a typing stop, so signals expire on the server
+        ctx.run_interval(TYPING_SWEEP_INTERVAL, |act, _| act.expire_typing());
+    }
 }
 
 // Handler for BroadcastMessage to send a message to all users in a room.
@@ -168,35 +373,135 @@ impl Handler<BroadcastMessage> for RoomServer {
     type Result = ();
 
     fn handle(&mut self, msg: BroadcastMessage, _: &mut Self::Context) {
-        println!(
-            "Broadcasting to room {}: {} (is_system: {}, username: {})",
-            msg.room_id, msg.message, msg.is_system, msg.username
-        );
-        
-        // Pass all necessary parameters to the updated broadcast_to_room method
-        self.broadcast_to_room(msg.room_id, &msg.message, msg.is_system, msg.username.clone());
+        log::debug!("Broadcasting to room {}", msg.room_id);
+
+        self.broadcast_to_room(msg.room_id, &msg.event);
     }
 }
 
-// Handler for AddUser to add a user to a room.
+// Handler for AddUser to add a connection to a room.
 impl Handler<AddUser> for RoomServer {
     type Result = ();
 
     fn handle(&mut self, msg: AddUser, _: &mut Self::Context) {
-        self.add_user(msg.room_id, msg.user_id, msg.addr.clone());
+        let was_online = self.is_online(msg.user_id);
         self.user_names.insert(msg.user_id, msg.username.clone());
-        self.set_user_online(msg.user_id);
+        self.add_connection(msg.connection_id, msg.room_id, msg.user_id, msg.session_id, msg.addr.clone());
+
+        // Only the user's first connection brings them online
+        if !was_online {
+            self.broadcast_online_change(msg.user_id, PresenceChange::Online);
+        }
+
+        // Announce the user once per room, not once per tab
+        if self.connections_in_room(msg.room_id, msg.user_id) == 1 {
+            self.broadcast_presence(msg.room_id, msg.user_id, PresenceChange::Joined);
+            self.broadcast_to_room(msg.room_id, &ServerEvent::System {
+                room_id: msg.room_id,
+                message: format!("⚡ Pika Pi! Welcome to the chat, {}!", msg.username),
+            });
+        }
+
+        // Give the new connection the full picture; later changes arrive as deltas
+        let snapshot = ServerEvent::PresenceSnapshot {
+            room_id: msg.room_id,
+            users: self.get_room_presence(msg.room_id),
+        };
+        match serde_json::to_string(&snapshot) {
+            Ok(message) => msg.addr.do_send(ChatMessage { message }),
+            Err(e) => log::error!("Failed to serialize presence snapshot for room {}: {}", msg.room_id, e),
+        }
     }
 }
 
-// Handler for RemoveUser to remove a user from a room.
+// Handler for RemoveUser to remove a connection from its room.
 impl Handler<RemoveUser> for RoomServer {
     type Result = ();
 
     fn handle(&mut self, msg: RemoveUser, _: &mut Self::Context) {
-        self.remove_user(msg.room_id, msg.user_id);
-        self.set_user_offline(msg.user_id); // Set user as offline when removed
-        println!("User {} removed from room {}", msg.user_id, msg.room_id);
+        let connection = match self.remove_connection(msg.connection_id) {
+            Some(connection) => connection,
+            None => return,
+        };
+        log::info!(
+            "Connection {} of user {} removed from room {}",
+            msg.connection_id, connection.user_id, connection.room_id
+        );
+
+        // Say goodbye once the user's last connection to this room closes
+        if self.connections_in_room(connection.room_id, connection.user_id) == 0 {
+            self.set_typing(connection.room_id, connection.user_id, false);
+            self.broadcast_presence(connection.room_id, connection.user_id, PresenceChange::Left);
+            let username = self.user_names.get(&connection.user_id).cloned().unwrap_or_default();
+            self.broadcast_to_room(connection.room_id, &ServerEvent::System {
+                room_id: connection.room_id,
+                message: format!("Pika-pika... Goodbye, {}!", username),
+            });
+        }
+
+        // The user only goes offline when their last connection anywhere closes
+        if !self.is_online(connection.user_id) {
+            self.broadcast_online_change(connection.user_id, PresenceChange::Offline);
+        }
+    }
+}
+
+// Handler for Disconnect to close connections to a room.
+impl Handler<Disconnect> for RoomServer {
+    type Result = ();
+
+    fn handle(&mut self, msg: Disconnect, _: &mut Self::Context) {
+        self.close_connections(msg.room_id, msg.user_id, &msg.event);
+    }
+}
+
+// Handler for RemoveMember to tell a room about a member who is gone and close their connections.
+impl Handler<RemoveMember> for RoomServer {
+    type Result = ();
+
+    fn handle(&mut self, msg: RemoveMember, _: &mut Self::Context) {
+        self.broadcast_to_room_except(msg.room_id, &msg.event, Some(msg.user_id));
+        self.close_connections(msg.room_id, Some(msg.user_id), &msg.event);
+        // No longer listed in the room's presence once their connections are gone
+        if let Some(user_ids) = self.room_users.get_mut(&msg.room_id) {
+            user_ids.remove(&msg.user_id);
+            if user_ids.is_empty() {
+                self.room_users.remove(&msg.room_id);
+            }
+        }
+    }
+}
+
+// Handler for EndSessions to close the connections of revoked sessions. Each connection removes
+// itself as it stops, which tells its room the user left or went offline.
+impl Handler<EndSessions> for RoomServer {
+    type Result = ();
+
+    fn handle(&mut self, msg: EndSessions, _: &mut Self::Context) {
+        let message = match serde_json::to_string(&ServerEvent::SessionRevoked) {
+            Ok(message) => message,
+            Err(e) => {
+                log::error!("Failed to serialize session revocation: {}", e);
+                return;
+            }
+        };
+        let session_ids: HashSet<&String> = msg.session_ids.iter().collect();
+        for connection in self.connections.values() {
+            if session_ids.contains(&connection.session_id) {
+                connection.addr.do_send(CloseSession {
+                    message: message.clone(),
+                });
+            }
+        }
+    }
+}
+
+// Handler for SetTyping to record and announce a user's typing state.
+impl Handler<SetTyping> for RoomServer {
+    type Result = ();
+
+    fn handle(&mut self, msg: SetTyping, _: &mut Self::Context) {
+        self.set_typing(msg.room_id, msg.user_id, msg.is_typing);
     }
 }
 
@@ -209,6 +514,44 @@ impl Handler<GetRoomPresence> for RoomServer {
     }
 }
 
+impl Handler<GetOnlineCounts> for RoomServer {
+    type Result = Vec<usize>;
+
+    fn handle(&mut self, msg: GetOnlineCounts, _: &mut Self::Context) -> Self::Result {
+        msg.room_ids
+            .into_iter()
+            .map(|room_id| {
+                // A user with several connections to the room counts once
+                self.rooms
+                    .get(&room_id)
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|connection_id| self.connections.get(connection_id))
+                    .map(|connection| connection.user_id)
+                    .collect::<HashSet<_>>()
+                    .len()
+            })
+            .collect()
+    }
+}
+
+impl Handler<GetUsersPresence> for RoomServer {
+    type Result = Vec<UserPresence>;
+
+    fn handle(&mut self, msg: GetUsersPresence, _: &mut Self::Context) -> Self::Result {
+        msg.user_ids
+            .into_iter()
+            .map(|user_id| {
+                UserPresence::new(
+                    user_id,
+                    self.is_online(user_id),
+                    self.connections_in_room(msg.room_id, user_id) > 0,
+                )
+            })
+            .collect()
+    }
+}
+
 // ChatMessage represents a message sent from RoomServer to a ChatSession.
 pub struct ChatMessage {
     pub message: String,
@@ -218,48 +561,391 @@ impl Message for ChatMessage {
     type Result = ();
 }
 
+// Sent from RoomServer to a ChatSession to deliver a last event and close the connection
+pub struct CloseSession {
+    pub message: String,
+}
+
+impl Message for CloseSession {
+    type Result = ();
+}
+
+// How long a new connection has to complete the `hello` handshake
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+// Longest chat message accepted, in characters
+pub const MAX_MESSAGE_LENGTH: usize = 2000;
+
+// Why a message could not be stored
+enum StoreError {
+    NotAllowed,              // The user is no longer a member, or their role does not allow posting
+    Archived,                // The room is archived and read-only
+    Muted(i64),              // The user is muted for this many more seconds
+    SlowMode(i64),           // Slow mode lets the user post again in this many seconds
+    ParentNotFound,          // The message being replied to is not in this room or was deleted
+    Database(sqlx::Error),
+}
+
+impl From<sqlx::Error> for StoreError {
+    fn from(e: sqlx::Error) -> Self {
+        StoreError::Database(e)
+    }
+}
+
+impl From<AccessError> for StoreError {
+    fn from(e: AccessError) -> Self {
+        match e {
+            AccessError::Database(e) => StoreError::Database(e),
+            AccessError::NotMember | AccessError::Forbidden => StoreError::NotAllowed,
+            AccessError::Archived => StoreError::Archived,
+        }
+    }
+}
+
 // ChatSession represents an individual WebSocket connection for a user in a room.
 pub struct ChatSession {
+    pub connection_id: ConnectionId,
     pub room_id: RoomId,
     pub user_id: UserId,
+    pub session_id: String,                 // The login session whose token opened the connection
     pub username: String,
     pub room_server: Addr<RoomServer>,
+    pub pool: SqlitePool,                   // Used to persist messages sent in this session
+    pub joined: bool,                       // Set once the `hello` handshake succeeds
+    pub last_acked_message_id: Option<i64>, // Latest message the client confirmed receiving
 }
 
 impl ChatSession {
-    pub fn new(room_id: RoomId, user_id: UserId, username: String, room_server: Addr<RoomServer>) -> Self {
-        ChatSession { room_id, user_id, username, room_server }
+    pub fn new(
+        room_id: RoomId,
+        user_id: UserId,
+        session_id: String,
+        username: String,
+        room_server: Addr<RoomServer>,
+        pool: SqlitePool,
+    ) -> Self {
+        ChatSession {
+            connection_id: NEXT_CONNECTION_ID.fetch_add(1, Ordering::Relaxed),
+            room_id,
+            user_id,
+            session_id,
+            username,
+            room_server,
+            pool,
+            joined: false,
+            last_acked_message_id: None,
+        }
     }
-}
 
-impl Actor for ChatSession {
-    type Context = ws::WebsocketContext<Self>;
+    // Builds a system announcement for this session's room
+    fn system_message(&self, message: String) -> BroadcastMessage {
+        BroadcastMessage {
+            room_id: self.room_id,
+            event: ServerEvent::System {
+                room_id: self.room_id,
+                message,
+            },
+        }
+    }
 
-    fn started(&mut self, ctx: &mut Self::Context) {
-        // Send AddUser message to RoomServer to track this user
+    // Sends an event to this session's client only
+    fn send_event(&self, event: &ServerEvent, ctx: &mut ws::WebsocketContext<Self>) {
+        match serde_json::to_string(event) {
+            Ok(serialized_event) => ctx.text(serialized_event),
+            Err(e) => log::error!("Failed to serialize event for user {}: {}", self.user_id, e),
+        }
+    }
+
+    // Parses a text frame and dispatches the command it carries
+    fn handle_frame(&mut self, text: &str, ctx: &mut ws::WebsocketContext<Self>) {
+        let ClientFrame { id, command } = match serde_json::from_str::<ClientFrame>(text) {
+            Ok(frame) => frame,
+            Err(e) => {
+                log::warn!("Malformed frame from user {}: {}", self.user_id, e);
+                // Echo the correlation id back if the frame was at least a JSON object carrying one
+                let id = serde_json::from_str::<serde_json::Value>(text)
+                    .ok()
+                    .and_then(|value| value.get("id")?.as_str().map(str::to_string));
+                self.send_event(&ServerEvent::error(id, ErrorCode::MalformedFrame, e.to_string()), ctx);
+                return;
+            }
+        };
+
+        match command {
+            ClientCommand::Hello { version } => self.handle_hello(id, version, ctx),
+            // Every other command needs a completed handshake
+            _ if !self.joined => self.send_event(
+                &ServerEvent::error(Some(id), ErrorCode::HandshakeRequired, "Send hello before any other command"),
+                ctx,
+            ),
+            ClientCommand::Send { content, parent_id } => self.handle_send(id, content, parent_id, ctx),
+            ClientCommand::Typing { is_typing } => {
+                // The RoomServer decides whether the room needs to hear about it
+                self.room_server.do_send(SetTyping {
+                    room_id: self.room_id,
+                    user_id: self.user_id,
+                    is_typing,
+                });
+                self.send_event(&ServerEvent::Ack { id, message_id: None }, ctx);
+            }
+            ClientCommand::Ack { message_id } => {
+                if !matches!(self.last_acked_message_id, Some(last) if last >= message_id) {
+                    self.last_acked_message_id = Some(message_id);
+                }
+            }
+            ClientCommand::Ping => self.send_event(&ServerEvent::Ack { id, message_id: None }, ctx),
+        }
+    }
+
+    // Completes the handshake and joins the room
+    fn handle_hello(&mut self, id: String, version: u32, ctx: &mut ws::WebsocketContext<Self>) {
+        if self.joined {
+            self.send_event(
+                &ServerEvent::error(Some(id), ErrorCode::InvalidCommand, "Handshake already completed"),
+                ctx,
+            );
+            return;
+        }
+
+        if version != PROTOCOL_VERSION {
+            self.send_event(
+                &ServerEvent::error(
+                    Some(id),
+                    ErrorCode::UnsupportedVersion,
+                    format!("Server speaks protocol version {}", PROTOCOL_VERSION),
+                ),
+                ctx,
+            );
+            ctx.close(Some(ws::CloseCode::Protocol.into()));
+            ctx.stop();
+            return;
+        }
+
+        // The session may have been revoked since the connection was opened
+        if is_session_revoked(&self.session_id) {
+            self.send_event(&ServerEvent::SessionRevoked, ctx);
+            ctx.close(Some(ws::CloseCode::Policy.into()));
+            ctx.stop();
+            return;
+        }
+
+        self.joined = true;
+
+        // Send AddUser message to RoomServer to track this connection; it announces the user to the room
         self.room_server.do_send(AddUser {
+            connection_id: self.connection_id,
             room_id: self.room_id,
             user_id: self.user_id,
+            session_id: self.session_id.clone(),
             username: self.username.clone(),
             addr: ctx.address(),
         });
 
-        // Announce that the user has joined the room
-        self.room_server.do_send(BroadcastMessage {
-            room_id: self.room_id,
-            message: format!("⚡ Pika Pi! Welcome to the chat, {}!", self.username),
-            is_system: true,
-            username: self.username.clone(),
-        });
+        self.send_event(
+            &ServerEvent::Welcome {
+                version: PROTOCOL_VERSION,
+                room_id: self.room_id,
+                user_id: self.user_id,
+                username: self.username.clone(),
+            },
+            ctx,
+        );
 
         log::info!(
-            "Welcome message sent for user_id: {}, username: {}, room_id: {}",
+            "Handshake completed for connection: {}, user_id: {}, username: {}, room_id: {}",
+            self.connection_id,
             self.user_id,
             self.username,
             self.room_id
         );
     }
 
+    // Stores a chat message or thread reply, acknowledges it to the sender and broadcasts it to the room
+    fn handle_send(&mut self, id: String, content: String, parent_id: Option<i64>, ctx: &mut ws::WebsocketContext<Self>) {
+        if content.trim().is_empty() || content.chars().count() > MAX_MESSAGE_LENGTH {
+            self.send_event(
+                &ServerEvent::error(
+                    Some(id),
+                    ErrorCode::InvalidCommand,
+                    format!("Messages must contain between 1 and {} characters", MAX_MESSAGE_LENGTH),
+                ),
+                ctx,
+            );
+            return;
+        }
+
+        let created_at = Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        let pool = self.pool.clone();
+        let (room_id, user_id) = (self.room_id, self.user_id);
+
+        // Store the message before broadcasting it so that every client receives its ID
+        let insert = {
+            let content = content.clone();
+            let created_at = created_at.clone();
+            async move {
+                // Membership and roles can change while the connection is open, so check on every message
+                let role = require_permission(&pool, room_id, user_id, Permission::PostMessages).await?;
+                if let Some(remaining) = mute_remaining(&pool, room_id, user_id).await? {
+                    return Err(StoreError::Muted(remaining));
+                }
+                // Moderators and above are exempt from slow mode
+                if !role.can(Permission::ModerateChat) {
+                    if let Some(remaining) = slow_mode_remaining(&pool, room_id, user_id).await? {
+                        return Err(StoreError::SlowMode(remaining));
+                    }
+                }
+
+                // Threads are one level deep: a reply to a reply joins the same root
+                let parent_id = match parent_id {
+                    Some(parent_id) => Some(
+                        sqlx::query_scalar!(
+                            "SELECT COALESCE(parent_id, message_id) as `root_id!: i64` FROM messages \
+                            WHERE message_id = ? AND room_id = ? AND deleted_at IS NULL",
+                            parent_id,
+                            room_id
+                        )
+                        .fetch_optional(&pool)
+                        .await?
+                        .ok_or(StoreError::ParentNotFound)?,
+                    ),
+                    None => None,
+                };
+
+                let message_id = sqlx::query!(
+                    "INSERT INTO messages (room_id, user_id, content, created_at, parent_id) VALUES (?, ?, ?, ?, ?)",
+                    room_id,
+                    user_id,
+                    content,
+                    created_at,
+                    parent_id
+                )
+                .execute(&pool)
+                .await?
+                .last_insert_rowid();
+                Ok::<_, StoreError>((message_id, parent_id))
+            }
+        };
+
+        // `wait` holds back further frames from this session until the insert finishes,
+        // which keeps this user's messages in the order they were sent
+        ctx.wait(insert.into_actor(self).map(move |result, act, ctx| {
+            let (message_id, parent_id) = match result {
+                Ok(stored) => stored,
+                Err(StoreError::NotAllowed) => {
+                    act.send_event(
+                        &ServerEvent::error(Some(id), ErrorCode::Forbidden, "You are not allowed to post in this room"),
+                        ctx,
+                    );
+                    return;
+                }
+                Err(StoreError::Archived) => {
+                    act.send_event(
+                        &ServerEvent::error(Some(id), ErrorCode::Forbidden, "This room is archived and read-only"),
+                        ctx,
+                    );
+                    return;
+                }
+                Err(StoreError::Muted(remaining)) => {
+                    act.send_event(
+                        &ServerEvent::cooldown(
+                            Some(id),
+                            ErrorCode::Muted,
+                            format!("You are muted in this room for {} more seconds", remaining),
+                            remaining,
+                        ),
+                        ctx,
+                    );
+                    return;
+                }
+                Err(StoreError::SlowMode(remaining)) => {
+                    act.send_event(
+                        &ServerEvent::cooldown(
+                            Some(id),
+                            ErrorCode::SlowMode,
+                            format!("Slow mode is on; you can send another message in {} seconds", remaining),
+                            remaining,
+                        ),
+                        ctx,
+                    );
+                    return;
+                }
+                Err(StoreError::ParentNotFound) => {
+                    act.send_event(
+                        &ServerEvent::error(Some(id), ErrorCode::InvalidCommand, "Parent message not found"),
+                        ctx,
+                    );
+                    return;
+                }
+                Err(StoreError::Database(e)) => {
+                    log::error!("Failed to store message from user {} in room {}: {}", act.user_id, act.room_id, e);
+                    act.send_event(
+                        &ServerEvent::error(Some(id), ErrorCode::InternalError, "Failed to store message"),
+                        ctx,
+                    );
+                    return;
+                }
+            };
+
+            act.send_event(&ServerEvent::Ack { id, message_id: Some(message_id) }, ctx);
+
+            // Sending a message ends the typing signal that preceded it
+            act.room_server.do_send(SetTyping {
+                room_id: act.room_id,
+                user_id: act.user_id,
+                is_typing: false,
+            });
+
+            // Send the stored message to the RoomServer for broadcasting
+            act.room_server.do_send(BroadcastMessage {
+                room_id: act.room_id,
+                event: ServerEvent::Message {
+                    message: StoredMessage {
+                        message_id,
+                        room_id: act.room_id,
+                        user_id: act.user_id,
+                        username: act.username.clone(),
+                        content: content.clone(),
+                        created_at,
+                        edited_at: None,
+                        deleted_at: None,
+                        parent_id,
+                        reply_count: 0,
+                    },
+                },
+            });
+
+            // Celebrate a great message with Easter egg
+            let lower_text = content.to_lowercase();
+            if lower_text.contains("great")
+                || lower_text.contains("awesome")
+                || lower_text.contains("amazing")
+                || lower_text.contains("ginny")
+            {
+                act.room_server.do_send(
+                    act.system_message(format!("⚡ Pikachuuu~! Great message from {}!", act.username)),
+                );
+            }
+        }));
+    }
+}
+
+impl Actor for ChatSession {
+    type Context = ws::WebsocketContext<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        // The session only joins the room after the `hello` handshake; drop connections that never send it
+        ctx.run_later(HANDSHAKE_TIMEOUT, |act, ctx| {
+            if !act.joined {
+                log::info!("Closing session for user_id {}: no hello received", act.user_id);
+                act.send_event(
+                    &ServerEvent::error(None, ErrorCode::HandshakeRequired, "Handshake timed out"),
+                    ctx,
+                );
+                ctx.close(Some(ws::CloseCode::Policy.into()));
+                ctx.stop();
+            }
+        });
+    }
+
     fn stopped(&mut self, _ctx: &mut Self::Context) {
         log::info!(
             "ChatSession stopped for user_id: {}, username: {}, room_id: {}",
@@ -267,25 +953,16 @@ impl Actor for ChatSession {
             self.username,
             self.room_id
         );
-        // Send RemoveUser message to RoomServer to stop tracking this user
-        self.room_server.do_send(RemoveUser {
-            room_id: self.room_id,
-            user_id: self.user_id,
-        });
+        // Sessions that never completed the handshake were never added to the room
+        if !self.joined {
+            return;
+        }
 
-        // Announce that the user has left the room
-        self.room_server.do_send(BroadcastMessage {
-            room_id: self.room_id,
-            message: format!("Pika-pika... Goodbye, {}!", self.username),
-            is_system: true,
-            username: self.username.clone(),
+        // Send RemoveUser message to RoomServer to stop tracking this connection; it says goodbye
+        // to the room once the user's last connection there closes
+        self.room_server.do_send(RemoveUser {
+            connection_id: self.connection_id,
         });
-        log::info!(
-            "Goodbye message sent for user_id: {}, username: {}, room_id: {}",
-            self.user_id,
-            self.username,
-            self.room_id
-        );
     }
 }
 
@@ -298,32 +975,36 @@ impl Handler<ChatMessage> for ChatSession {
     }
 }
 
+// ChatSession handler for CloseSession: sends the last event, then closes the connection normally
+impl Handler<CloseSession> for ChatSession {
+    type Result = ();
+
+    fn handle(&mut self, msg: CloseSession, ctx: &mut Self::Context) {
+        ctx.text(msg.message);
+        ctx.close(Some(ws::CloseCode::Normal.into()));
+        ctx.stop();
+    }
+}
+
 // Implement StreamHandler to handle incoming WebSocket messages from the client.
 impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for ChatSession {
-    fn handle(&mut self, msg: Result<ws::Message, ws::ProtocolError>, _ctx: &mut Self::Context) {
-        // Handle text messages received over the WebSocket connection
-        if let Ok(ws::Message::Text(text)) = msg {
-            // Send the received message to the RoomServer for broadcasting
-            self.room_server.do_send(BroadcastMessage {
-                room_id: self.room_id,
-                message: format!("{}", text),
-                is_system: false,
-                username: self.username.clone(),
-            });
-
-            // Celebrate a great message with Easter egg
-            let lower_text = text.to_lowercase();
-            if lower_text.contains("great") 
-                || lower_text.contains("awesome") 
-                || lower_text.contains("amazing") 
-                || lower_text.contains("ginny") 
-            {
-                self.room_server.do_send(BroadcastMessage {
-                    room_id: self.room_id,
-                    message: format!("⚡ Pikachuuu~! Great message from {}!", self.username),
-                    is_system: true,
-                    username: self.username.clone(),
-                });
+    fn handle(&mut self, msg: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
+        match msg {
+            // Text frames carry protocol commands
+            Ok(ws::Message::Text(text)) => self.handle_frame(&text, ctx),
+            Ok(ws::Message::Binary(_)) => self.send_event(
+                &ServerEvent::error(None, ErrorCode::MalformedFrame, "Binary frames are not supported"),
+                ctx,
+            ),
+            Ok(ws::Message::Ping(bytes)) => ctx.pong(&bytes),
+            Ok(ws::Message::Close(reason)) => {
+                ctx.close(reason);
+                ctx.stop();
+            }
+            Ok(_) => {}
+            Err(e) => {
+                log::warn!("WebSocket protocol error for user {}: {}", self.user_id, e);
+                ctx.stop();
             }
         }
     }
@@ -1 +1,2 @@
 pub mod chat_session;
+pub mod protocol;
@@ -0,0 +1,204 @@
+//! JSON protocol spoken over `/ws/rooms/{room_id}`.
+//!
+//! Every frame is a JSON object tagged by its `type` field. Clients send [`ClientFrame`]s, each with a
+//! client-chosen correlation `id` that the server echoes in the matching `ack` or `error` event.
+//! The first frame of a connection must be `hello` with the protocol version the client speaks.
+//! The frontend keeps a copy of these types in `pika-chat-frontend/src/services/protocol.rs`.
+
+use crate::models::message::StoredMessage;
+use crate::models::presence::{PresenceChange, UserPresence};
+use crate::models::role::Role;
+use crate::websockets::chat_session::{RoomId, UserId};
+use serde::{Deserialize, Serialize};
+
+/// Version of the protocol implemented by this server
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// A command sent by the client, wrapped with its correlation id
+#[derive(Deserialize, Debug)]
+pub struct ClientFrame {
+    pub id: String,
+    #[serde(flatten)]
+    pub command: ClientCommand,
+}
+
+/// Commands a client can send to the server
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientCommand {
+    /// Handshake; must be the first frame of every connection
+    Hello { version: u32 },
+    /// Post a chat message to the room, or a reply to the thread of `parent_id`
+    Send {
+        content: String,
+        #[serde(default)]
+        parent_id: Option<i64>,
+    },
+    /// Tell the room that the user started or stopped typing
+    Typing { is_typing: bool },
+    /// Acknowledge that a message has been received and displayed
+    Ack { message_id: i64 },
+    /// Keep-alive; answered with an `ack`
+    Ping,
+}
+
+/// Events the server pushes to clients
+#[derive(Serialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerEvent {
+    /// Reply to a successful `hello`
+    Welcome {
+        version: u32,
+        room_id: RoomId,
+        user_id: UserId,
+        username: String,
+    },
+    /// A chat message posted to the room; replies carry their thread's root ID in `parent_id`
+    Message { message: StoredMessage },
+    /// A message's content was changed; carries the updated message
+    MessageEdited { message: StoredMessage },
+    /// A message was replaced by a tombstone; `parent_id` is set when it was a thread reply
+    MessageDeleted {
+        room_id: RoomId,
+        message_id: i64,
+        parent_id: Option<i64>,
+        deleted_at: String,
+    },
+    /// A user reacted to a message with an emoji
+    ReactionAdded {
+        room_id: RoomId,
+        message_id: i64,
+        user_id: UserId,
+        username: String,
+        emoji: String,
+    },
+    /// A user took back their reaction to a message
+    ReactionRemoved {
+        room_id: RoomId,
+        message_id: i64,
+        user_id: UserId,
+        username: String,
+        emoji: String,
+    },
+    /// An announcement generated by the server
+    System { room_id: RoomId, message: String },
+    /// A user's presence changed; sent to every room the change affects
+    Presence {
+        room_id: RoomId,
+        user_id: UserId,
+        username: String,
+        change: PresenceChange,
+    },
+    /// A member was promoted or demoted
+    RoleChanged {
+        room_id: RoomId,
+        user_id: UserId,
+        username: String,
+        role: Role,
+    },
+    /// The room's name, topic, description or archived state changed
+    RoomUpdated {
+        room_id: RoomId,
+        room_name: String,
+        topic: Option<String>,
+        description: Option<String>,
+        archived: bool,
+    },
+    /// The room was deleted; the server closes the connection right after this event
+    RoomDeleted { room_id: RoomId },
+    /// A member left or was removed from the room; the server closes that member's connections
+    /// right after this event
+    MemberRemoved {
+        room_id: RoomId,
+        user_id: UserId,
+        username: String,
+        cause: RemovalCause,
+    },
+    /// The login session the connection was opened with was revoked, e.g. from another device;
+    /// the server closes the connection right after this event
+    SessionRevoked,
+    /// A member was muted until `muted_until` (UTC), or unmuted when it is absent
+    MemberMuted {
+        room_id: RoomId,
+        user_id: UserId,
+        username: String,
+        muted_until: Option<String>,
+    },
+    /// Slow mode was set to `seconds` between messages per member, or turned off at 0
+    SlowModeChanged { room_id: RoomId, seconds: i64 },
+    /// Presence of every user seen in the room; sent once when the connection joins
+    PresenceSnapshot {
+        room_id: RoomId,
+        users: Vec<UserPresence>,
+    },
+    /// A user started or stopped typing
+    Typing {
+        room_id: RoomId,
+        user_id: UserId,
+        username: String,
+        is_typing: bool,
+    },
+    /// A command was accepted; `message_id` is set when it created a message
+    Ack { id: String, message_id: Option<i64> },
+    /// A command or frame was rejected; `id` is absent when the frame could not be parsed.
+    /// `retry_after_secs` is set when the command may succeed once a mute or slow mode cooldown ends
+    Error {
+        id: Option<String>,
+        code: ErrorCode,
+        message: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        retry_after_secs: Option<i64>,
+    },
+}
+
+/// Why a member is no longer in a room
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RemovalCause {
+    Left,
+    Kicked,
+    Banned,
+    AccountDeleted, // The member deleted their account
+}
+
+/// Machine-readable reasons carried by `error` events
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// The frame was not valid JSON or not a known command
+    MalformedFrame,
+    /// The client asked for a protocol version the server does not speak
+    UnsupportedVersion,
+    /// A command other than `hello` arrived before the handshake
+    HandshakeRequired,
+    /// The command was well-formed but its content was rejected
+    InvalidCommand,
+    /// The user's membership or role in the room does not allow the command
+    Forbidden,
+    /// The user is muted in the room; `retry_after_secs` tells when the mute ends
+    Muted,
+    /// The room is in slow mode; `retry_after_secs` tells when the user may post again
+    SlowMode,
+    /// The server failed to process the command
+    InternalError,
+}
+
+impl ServerEvent {
+    pub fn error(id: Option<String>, code: ErrorCode, message: impl Into<String>) -> Self {
+        ServerEvent::Error {
+            id,
+            code,
+            message: message.into(),
+            retry_after_secs: None,
+        }
+    }
+
+    pub fn cooldown(id: Option<String>, code: ErrorCode, message: impl Into<String>, retry_after_secs: i64) -> Self {
+        ServerEvent::Error {
+            id,
+            code,
+            message: message.into(),
+            retry_after_secs: Some(retry_after_secs),
+        }
+    }
+}